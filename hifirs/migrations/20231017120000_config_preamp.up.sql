@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "preamp" REAL NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "relogin_interval" INTEGER NOT NULL DEFAULT 0;
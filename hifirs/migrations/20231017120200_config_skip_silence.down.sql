@@ -0,0 +1,2 @@
+ALTER TABLE "config" DROP COLUMN "skip_silence";
+ALTER TABLE "config" DROP COLUMN "skip_silence_threshold";
@@ -0,0 +1,2 @@
+ALTER TABLE "config" ADD COLUMN "skip_silence" INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE "config" ADD COLUMN "skip_silence_threshold" INTEGER NOT NULL DEFAULT -60;
@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "default_screen" INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "resume_position" INTEGER NOT NULL DEFAULT 1;
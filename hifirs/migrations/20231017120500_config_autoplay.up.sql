@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "autoplay" INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "position_interval" INTEGER NOT NULL DEFAULT 250;
@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "level_meter" INTEGER NOT NULL DEFAULT 0;
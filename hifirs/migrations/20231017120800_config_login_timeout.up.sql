@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "login_timeout" INTEGER NOT NULL DEFAULT 15;
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS "search_sort" (
+  "result_type" TEXT NOT NULL PRIMARY KEY,
+  "sort_order" TEXT NOT NULL
+);
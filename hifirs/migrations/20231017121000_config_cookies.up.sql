@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "cookies" TEXT NOT NULL DEFAULT 'session';
@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "idle_timeout" INTEGER NOT NULL DEFAULT 0;
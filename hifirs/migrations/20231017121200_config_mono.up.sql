@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "mono" INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "marquee" INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE "config" DROP COLUMN "playlist_page_size";
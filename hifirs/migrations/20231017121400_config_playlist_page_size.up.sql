@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "playlist_page_size" INTEGER NOT NULL DEFAULT 50;
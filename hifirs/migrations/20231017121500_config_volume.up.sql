@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "volume" REAL NOT NULL DEFAULT 1;
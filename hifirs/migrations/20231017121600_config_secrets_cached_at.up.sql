@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "secrets_cached_at" INTEGER;
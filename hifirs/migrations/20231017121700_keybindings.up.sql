@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS "keybindings" (
+  "action" TEXT NOT NULL PRIMARY KEY,
+  "key" TEXT NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "normalization" TEXT NOT NULL DEFAULT 'off';
@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "library_path" TEXT;
@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "jump_seconds" INTEGER NOT NULL DEFAULT 10;
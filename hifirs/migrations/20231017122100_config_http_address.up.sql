@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "http_address" TEXT;
@@ -0,0 +1 @@
+ALTER TABLE "config" DROP COLUMN "discord_app_id";
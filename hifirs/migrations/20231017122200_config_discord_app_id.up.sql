@@ -0,0 +1 @@
+ALTER TABLE "config" ADD COLUMN "discord_app_id" TEXT;
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS "search_history" (
+  "term" TEXT NOT NULL PRIMARY KEY COLLATE NOCASE,
+  "searched_at" INTEGER NOT NULL
+);
@@ -1,18 +1,35 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, str::FromStr};
 
+#[cfg(feature = "discord")]
+use crate::discord_rpc;
+#[cfg(feature = "http-api")]
+use crate::http;
 #[cfg(target_os = "linux")]
 use crate::mpris;
 use crate::{
-    cursive::{self, CursiveUI},
-    player::{self},
-    qobuz::{self},
+    cursive::{
+        self,
+        keys::{self, KeyAction},
+        CursiveUI, Screen,
+    },
+    player::{
+        self,
+        controls::{parse_qobuz_uri, NormalizationMode},
+        queue::export::{csv_field, ExportFormat},
+    },
+    qobuz::{
+        self,
+        import::{self, ImportTarget},
+    },
     sql::db::{self},
-    wait, websocket,
+    wait, websocket, POSITION_INTERVAL_RANGE,
 };
+#[cfg(feature = "http-api")]
+use axum::http::StatusCode;
 use clap::{Parser, Subcommand};
 use comfy_table::{presets::UTF8_FULL, Table};
 use dialoguer::{Confirm, Input, Password};
-use hifirs_qobuz_api::client::{api::OutputFormat, AudioQuality};
+use hifirs_qobuz_api::client::{album::FeaturedType, api::OutputFormat, AudioQuality};
 use snafu::prelude::*;
 use tokio::task::JoinHandle;
 use tracing_subscriber::EnvFilter;
@@ -33,6 +50,10 @@ struct Cli {
     /// Quit after done playing
     pub quit_when_done: bool,
 
+    #[clap(long, default_value_t = false)]
+    /// Start playing the saved queue as soon as the player opens.
+    pub autoplay: bool,
+
     #[clap(short, long, default_value_t = false)]
     /// Disable the TUI interface.
     pub disable_tui: bool,
@@ -53,10 +74,15 @@ struct Cli {
 enum Commands {
     /// Open the player
     Open {},
-    /// Play an Qobuz entity using the url.
+    /// Play a Qobuz album, track or playlist from its link, or from
+    /// `album:<id>`, `track:<id>` or `playlist:<id>`. Goes to the player
+    /// that is already running when the HTTP control API is on.
     Play {
-        #[clap(long, short)]
-        url: String,
+        #[clap(value_parser, required_unless_present = "url")]
+        target: Option<String>,
+        /// The same as the target, for older scripts.
+        #[clap(long, short, hide = true, conflicts_with = "target")]
+        url: Option<String>,
     },
     /// Stream an individual track by its ID.
     StreamTrack {
@@ -73,6 +99,40 @@ enum Commands {
         #[clap(subcommand)]
         command: ApiCommands,
     },
+    /// Export a playlist as text, M3U8, JSON or CSV.
+    ExportPlaylist {
+        #[clap(value_parser)]
+        playlist_id: i64,
+        #[clap(short, long, value_enum, default_value_t = ExportFormat::M3u8)]
+        format: ExportFormat,
+        /// Write to this file instead of stdout.
+        #[clap(short, long)]
+        output: Option<String>,
+    },
+    /// Export the queue from the last session as text, M3U8, JSON or CSV.
+    /// M3U8 entries point at downloaded files where there are any.
+    ExportQueue {
+        #[clap(short, long, value_enum, default_value_t = ExportFormat::M3u8)]
+        format: ExportFormat,
+        /// Write to this file instead of stdout.
+        #[clap(short, long)]
+        output: Option<String>,
+    },
+    /// Add the tracks listed in an M3U or text file to a Qobuz playlist.
+    /// Lines can be Qobuz track links, ISRCs or `Artist - Title`.
+    ImportPlaylist {
+        #[clap(value_parser)]
+        path: String,
+        /// Add to this playlist instead of creating a new one.
+        #[clap(long, conflicts_with = "name")]
+        playlist_id: Option<i64>,
+        /// Name of the new playlist. Defaults to the file name.
+        #[clap(short, long)]
+        name: Option<String>,
+        /// Only report the matches, without changing any playlist.
+        #[clap(long, default_value_t = false)]
+        dry_run: bool,
+    },
     /// Reset the player state
     Reset,
     /// Set configuration options
@@ -90,6 +150,9 @@ pub enum ApiCommands {
         query: String,
         #[clap(long, short)]
         limit: Option<i32>,
+        /// Skip this many results in each category.
+        #[clap(long)]
+        offset: Option<i32>,
         #[clap(short, long = "output", value_enum)]
         output_format: Option<OutputFormat>,
     },
@@ -111,6 +174,30 @@ pub enum ApiCommands {
         #[clap(short, long = "output", value_enum)]
         output_format: Option<OutputFormat>,
     },
+    /// Search for tracks in the Qobuz database
+    SearchTracks {
+        #[clap(value_parser)]
+        query: String,
+        #[clap(long, short)]
+        limit: Option<i32>,
+        /// Skip this many results.
+        #[clap(long)]
+        offset: Option<i32>,
+        #[clap(short, long = "output", value_enum)]
+        output_format: Option<OutputFormat>,
+    },
+    /// Search for playlists in the Qobuz database
+    SearchPlaylists {
+        #[clap(value_parser)]
+        query: String,
+        #[clap(long, short)]
+        limit: Option<i32>,
+        /// Skip this many results.
+        #[clap(long)]
+        offset: Option<i32>,
+        #[clap(short, long = "output", value_enum)]
+        output_format: Option<OutputFormat>,
+    },
     Album {
         #[clap(value_parser)]
         id: String,
@@ -129,6 +216,20 @@ pub enum ApiCommands {
         #[clap(short, long = "output", value_enum)]
         output_format: Option<OutputFormat>,
     },
+    /// Show the logged in user, their subscription and the best quality it streams
+    Me {
+        #[clap(short, long = "output", value_enum)]
+        output_format: Option<OutputFormat>,
+    },
+    /// Browse new releases, best sellers and other curated album lists
+    Featured {
+        #[clap(short = 't', long = "type", value_enum, default_value_t = FeaturedType::NewReleases)]
+        featured_type: FeaturedType,
+        #[clap(long, short)]
+        limit: Option<i32>,
+        #[clap(short, long = "output", value_enum)]
+        output_format: Option<OutputFormat>,
+    },
     /// Retreive information about a specific playlist.
     Playlist {
         #[clap(value_parser)]
@@ -150,9 +251,121 @@ pub enum ConfigCommands {
     Clear {},
     /// Target this quality when playing audio.
     DefaultQuality {
-        #[clap(value_enum)]
+        /// mp3, cd, hifi96 or hifi192. Aliases like "hires" and format ids like 27 also work.
+        #[clap(value_parser = AudioQuality::from_str)]
         quality: AudioQuality,
     },
+    /// Screen shown when the player opens.
+    DefaultScreen {
+        #[clap(value_enum)]
+        screen: Screen,
+    },
+    /// Resume from the saved position within the last track when the player opens.
+    ResumePosition {
+        #[clap(value_parser)]
+        enabled: bool,
+    },
+    /// Start playing the saved queue as soon as the player opens.
+    Autoplay {
+        #[clap(value_parser)]
+        enabled: bool,
+    },
+    /// Show a live peak/RMS level meter in the player. Adds a little pipeline overhead.
+    LevelMeter {
+        #[clap(value_parser)]
+        enabled: bool,
+    },
+    /// Slowly scroll track and album titles that don't fit the player.
+    Marquee {
+        #[clap(value_parser)]
+        enabled: bool,
+    },
+    /// Pre-amp gain in dB (-12 to 12), applied independently of the volume.
+    Preamp {
+        #[clap(value_parser, allow_hyphen_values = true)]
+        gain: f64,
+    },
+    /// Skip long stretches of silence quieter than the threshold, in dB.
+    SkipSilence {
+        #[clap(value_parser)]
+        enabled: bool,
+        #[clap(long, short, default_value_t = -60, allow_hyphen_values = true)]
+        threshold: i32,
+    },
+    /// Bind a TUI action to a different key. Leave out the key to restore the default.
+    Keybinding {
+        #[clap(value_enum)]
+        action: KeyAction,
+        /// A single character, or "space".
+        #[clap(value_parser = parse_key_arg)]
+        key: Option<char>,
+    },
+    /// Sum both channels to mono, for a single speaker or hearing in one ear.
+    Mono {
+        #[clap(value_parser)]
+        enabled: bool,
+    },
+    /// Seconds to jump forward or backward in a track.
+    JumpSeconds {
+        #[clap(value_parser)]
+        seconds: u32,
+    },
+    /// Folder downloaded tracks are saved to.
+    LibraryPath {
+        #[clap(value_parser)]
+        path: String,
+    },
+    /// Even out loudness between tracks with ReplayGain.
+    Normalization {
+        #[clap(value_enum)]
+        mode: NormalizationMode,
+    },
+    /// Seconds to wait for Qobuz at startup before opening in offline mode.
+    LoginTimeout {
+        #[clap(value_parser)]
+        seconds: u32,
+    },
+    /// Re-validate the session every N hours and log in again if needed. (0 disables)
+    ReloginInterval {
+        #[clap(value_parser)]
+        hours: u32,
+    },
+    /// How the api client handles cookies. Persisted cookies are saved to disk in plain text.
+    Cookies {
+        #[clap(value_enum)]
+        mode: qobuz::CookieMode,
+    },
+    /// Release the audio device after being paused or stopped for N minutes. (0 disables)
+    IdleTimeout {
+        #[clap(value_parser)]
+        minutes: u32,
+    },
+    /// Tracks to load before a playlist starts playing, the rest load in the background. (0 loads everything first)
+    PlaylistPageSize {
+        #[clap(value_parser)]
+        tracks: u32,
+    },
+    /// How often, in milliseconds (100 to 1000), the playback position is updated.
+    PositionInterval {
+        #[clap(value_parser)]
+        millis: u64,
+    },
+    /// Address the REST control API listens on, e.g. 0.0.0.0:9889. Leave it out to turn the API off.
+    /// (requires the http-api feature)
+    HttpAddress {
+        #[clap(value_parser)]
+        address: Option<SocketAddr>,
+    },
+    /// Show the playing track on Discord as this application. Leave out the id to turn it off.
+    /// (requires the discord feature)
+    DiscordAppId {
+        #[clap(value_parser)]
+        app_id: Option<String>,
+    },
+}
+
+fn parse_key_arg(text: &str) -> Result<char, String> {
+    keys::parse_key(text).ok_or_else(|| format!("{text} is not a single key"))
 }
 
 #[derive(Debug, Snafu)]
@@ -184,6 +397,7 @@ impl From<player::error::Error> for Error {
 async fn setup_player(
     quit_when_done: bool,
     resume: bool,
+    autoplay: bool,
     web: bool,
     interface: SocketAddr,
     username: Option<&str>,
@@ -195,7 +409,7 @@ async fn setup_player(
 
     if resume {
         handles.push(tokio::spawn(async move {
-            match player::resume(false).await {
+            match player::resume(autoplay).await {
                 Ok(_) => debug!("resume success"),
                 Err(error) => debug!("resume error {error}"),
             }
@@ -218,6 +432,22 @@ async fn setup_player(
         ));
     }
 
+    #[cfg(feature = "discord")]
+    {
+        if let Some(app_id) = db::get_discord_app_id().await {
+            handles.push(tokio::spawn(async move {
+                discord_rpc::receive_notifications(app_id).await;
+            }));
+        }
+    }
+
+    #[cfg(feature = "http-api")]
+    {
+        if let Some(address) = db::get_http_address().await {
+            handles.push(tokio::spawn(async move { http::init(address).await }));
+        }
+    }
+
     handles.push(tokio::spawn(async {
         match player::player_loop().await {
             Ok(_) => debug!("player loop exited successfully"),
@@ -248,9 +478,12 @@ pub async fn run() -> Result<(), Error> {
     // CLI COMMANDS
     match cli.command {
         Commands::Open {} => {
+            let autoplay = cli.autoplay || db::get_autoplay().await;
+
             let mut handles = setup_player(
                 cli.quit_when_done,
                 true,
+                autoplay,
                 cli.web,
                 cli.interface,
                 cli.username.as_deref(),
@@ -262,10 +495,31 @@ pub async fn run() -> Result<(), Error> {
 
             Ok(())
         }
-        Commands::Play { url } => {
+        Commands::Play { target, url } => {
+            let target = target.or(url).unwrap_or_default();
+            let action = parse_qobuz_uri(&target).ok_or_else(|| Error::ClientError {
+                error: format!("{target} isn't a Qobuz album, track or playlist link"),
+            })?;
+
+            #[cfg(feature = "http-api")]
+            {
+                if let Some(address) = db::get_http_address().await {
+                    match http::forward(address, &action).await {
+                        Some(StatusCode::ACCEPTED) => return Ok(()),
+                        Some(status) => {
+                            return Err(Error::PlayerError {
+                                error: format!("the running player refused it: {status}"),
+                            })
+                        }
+                        None => debug!("no running player, starting one"),
+                    }
+                }
+            }
+
             let mut handles = setup_player(
                 cli.quit_when_done,
                 false,
+                false,
                 cli.web,
                 cli.interface,
                 cli.username.as_deref(),
@@ -273,7 +527,7 @@ pub async fn run() -> Result<(), Error> {
             )
             .await?;
 
-            player::play_uri(url).await?;
+            player::handle_action(action).await?;
 
             wait!(mut handles, cli.disable_tui);
 
@@ -283,6 +537,7 @@ pub async fn run() -> Result<(), Error> {
             let mut handles = setup_player(
                 cli.quit_when_done,
                 false,
+                false,
                 cli.web,
                 cli.interface,
                 cli.username.as_deref(),
@@ -290,7 +545,7 @@ pub async fn run() -> Result<(), Error> {
             )
             .await?;
 
-            player::play_track(track_id).await?;
+            player::play_track(track_id, None).await?;
 
             wait!(mut handles, cli.disable_tui);
 
@@ -300,6 +555,7 @@ pub async fn run() -> Result<(), Error> {
             let mut handles = setup_player(
                 cli.quit_when_done,
                 false,
+                false,
                 cli.web,
                 cli.interface,
                 cli.username.as_deref(),
@@ -317,11 +573,14 @@ pub async fn run() -> Result<(), Error> {
             ApiCommands::Search {
                 query,
                 limit,
+                offset,
                 output_format,
             } => {
                 let client =
                     qobuz::make_client(cli.username.as_deref(), cli.password.as_deref()).await?;
-                let results = client.search_all(query, limit.unwrap_or_default()).await?;
+                let results = client
+                    .search_all(query, limit.unwrap_or_default(), offset.unwrap_or_default())
+                    .await?;
 
                 output!(results, output_format);
 
@@ -353,6 +612,34 @@ pub async fn run() -> Result<(), Error> {
 
                 Ok(())
             }
+            ApiCommands::SearchTracks {
+                query,
+                limit,
+                offset,
+                output_format,
+            } => {
+                let client =
+                    qobuz::make_client(cli.username.as_deref(), cli.password.as_deref()).await?;
+                let results = client.search_tracks(query, limit, offset).await?;
+
+                output!(results, output_format);
+
+                Ok(())
+            }
+            ApiCommands::SearchPlaylists {
+                query,
+                limit,
+                offset,
+                output_format,
+            } => {
+                let client =
+                    qobuz::make_client(cli.username.as_deref(), cli.password.as_deref()).await?;
+                let results = client.search_playlists(query, limit, offset).await?;
+
+                output!(results, output_format);
+
+                Ok(())
+            }
             ApiCommands::Playlist { id, output_format } => {
                 let client =
                     qobuz::make_client(cli.username.as_deref(), cli.password.as_deref()).await?;
@@ -377,6 +664,20 @@ pub async fn run() -> Result<(), Error> {
                 output!(results, output_format);
                 Ok(())
             }
+            ApiCommands::Featured {
+                featured_type,
+                limit,
+                output_format,
+            } => {
+                let client =
+                    qobuz::make_client(cli.username.as_deref(), cli.password.as_deref()).await?;
+
+                let results = client
+                    .featured_albums(Some(featured_type), limit, None)
+                    .await?;
+                output!(results, output_format);
+                Ok(())
+            }
             ApiCommands::Track { id, output_format } => {
                 let client =
                     qobuz::make_client(cli.username.as_deref(), cli.password.as_deref()).await?;
@@ -385,7 +686,107 @@ pub async fn run() -> Result<(), Error> {
                 output!(results, output_format);
                 Ok(())
             }
+            ApiCommands::Me { output_format } => {
+                let client =
+                    qobuz::make_client(cli.username.as_deref(), cli.password.as_deref()).await?;
+
+                let results = client.me().await?;
+                output!(results, output_format);
+                Ok(())
+            }
         },
+        Commands::ExportPlaylist {
+            playlist_id,
+            format,
+            output,
+        } => {
+            player::init(cli.username.as_deref(), cli.password.as_deref(), false).await?;
+
+            if let Some(path) = output {
+                let mut file = std::fs::File::create(path).map_err(|error| Error::PlayerError {
+                    error: error.to_string(),
+                })?;
+
+                player::export_playlist(playlist_id, format, &mut file).await?;
+            } else {
+                player::export_playlist(playlist_id, format, &mut std::io::stdout()).await?;
+            }
+
+            Ok(())
+        }
+        Commands::ExportQueue { format, output } => {
+            player::init(cli.username.as_deref(), cli.password.as_deref(), false).await?;
+
+            if let Some(path) = output {
+                let mut file = std::fs::File::create(path).map_err(|error| Error::PlayerError {
+                    error: error.to_string(),
+                })?;
+
+                player::export_saved_queue(format, &mut file).await?;
+            } else {
+                player::export_saved_queue(format, &mut std::io::stdout()).await?;
+            }
+
+            Ok(())
+        }
+        Commands::ImportPlaylist {
+            path,
+            playlist_id,
+            name,
+            dry_run,
+        } => {
+            let contents = std::fs::read_to_string(&path).map_err(|error| Error::ClientError {
+                error: format!("unable to read {path}: {error}"),
+            })?;
+
+            let target = if let Some(id) = playlist_id {
+                ImportTarget::Existing(id)
+            } else {
+                let name = name.unwrap_or_else(|| {
+                    std::path::Path::new(&path)
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().to_string())
+                        .unwrap_or_else(|| "Imported".to_string())
+                });
+
+                ImportTarget::New(name)
+            };
+
+            let client =
+                qobuz::make_client(cli.username.as_deref(), cli.password.as_deref()).await?;
+            let report = import::import_playlist(&client, &contents, target, dry_run).await?;
+
+            for (line, track) in &report.matched {
+                let performer = track
+                    .performer
+                    .as_ref()
+                    .map(|p| p.name.as_str())
+                    .unwrap_or("Unknown Artist");
+
+                println!(
+                    "found: {line} -> {performer} - {} ({})",
+                    track.title, track.id
+                );
+            }
+
+            for line in &report.unresolved {
+                println!("not found: {line}");
+            }
+
+            println!(
+                "{} matched, {} not found.",
+                report.matched.len(),
+                report.unresolved.len()
+            );
+
+            if let Some(id) = report.playlist_id {
+                println!("Tracks added to playlist {id}.");
+            } else if dry_run {
+                println!("Dry run, no playlist was changed.");
+            }
+
+            Ok(())
+        }
         Commands::Reset => {
             db::clear_state().await;
             Ok(())
@@ -424,6 +825,191 @@ pub async fn run() -> Result<(), Error> {
 
                 Ok(())
             }
+            ConfigCommands::DefaultScreen { screen } => {
+                db::set_default_screen(screen as i64).await;
+
+                println!("Default screen saved.");
+
+                Ok(())
+            }
+            ConfigCommands::ResumePosition { enabled } => {
+                db::set_resume_position(enabled).await;
+
+                println!("Resume position setting saved.");
+
+                Ok(())
+            }
+            ConfigCommands::Autoplay { enabled } => {
+                db::set_autoplay(enabled).await;
+
+                println!("Autoplay setting saved.");
+
+                Ok(())
+            }
+            ConfigCommands::LevelMeter { enabled } => {
+                db::set_level_meter(enabled).await;
+
+                println!("Level meter setting saved.");
+
+                Ok(())
+            }
+            ConfigCommands::Marquee { enabled } => {
+                db::set_marquee(enabled).await;
+
+                println!("Marquee setting saved.");
+
+                Ok(())
+            }
+            ConfigCommands::Preamp { gain } => {
+                let clamped = gain.clamp(-player::PREAMP_RANGE, player::PREAMP_RANGE);
+
+                if clamped != gain {
+                    println!("Pre-amp gain clamped to {clamped} dB.");
+                }
+
+                db::set_preamp(clamped).await;
+
+                println!("Pre-amp gain saved.");
+
+                Ok(())
+            }
+            ConfigCommands::SkipSilence { enabled, threshold } => {
+                db::set_skip_silence(enabled, threshold).await;
+
+                println!("Skip silence setting saved.");
+
+                Ok(())
+            }
+            ConfigCommands::Keybinding { action, key } => {
+                if let Some(key) = key {
+                    db::set_keybinding(&action.name(), &keys::key_label(key)).await;
+
+                    println!("Keybinding saved.");
+                } else {
+                    db::clear_keybinding(&action.name()).await;
+
+                    println!(
+                        "Keybinding reset to {}.",
+                        keys::key_label(action.default_key())
+                    );
+                }
+
+                Ok(())
+            }
+            ConfigCommands::Mono { enabled } => {
+                db::set_mono(enabled).await;
+
+                println!("Mono setting saved.");
+
+                Ok(())
+            }
+            ConfigCommands::JumpSeconds { seconds } => {
+                db::set_jump_seconds(seconds.max(1) as i64).await;
+
+                println!("Jump setting saved.");
+
+                Ok(())
+            }
+            ConfigCommands::LibraryPath { path } => {
+                db::set_library_path(path).await;
+
+                println!("Library path saved.");
+
+                Ok(())
+            }
+            ConfigCommands::Normalization { mode } => {
+                db::set_normalization(mode).await;
+
+                println!("Normalization setting saved.");
+
+                Ok(())
+            }
+            ConfigCommands::PlaylistPageSize { tracks } => {
+                db::set_playlist_page_size(tracks as i64).await;
+
+                println!("Playlist page size saved.");
+
+                Ok(())
+            }
+            ConfigCommands::PositionInterval { millis } => {
+                let (min, max) = POSITION_INTERVAL_RANGE;
+                let clamped = millis.clamp(min, max);
+
+                if clamped != millis {
+                    println!("Position interval clamped to {clamped} ms.");
+                }
+
+                db::set_position_interval(clamped as i64).await;
+
+                println!("Position interval saved.");
+
+                Ok(())
+            }
+            ConfigCommands::HttpAddress { address } => {
+                db::set_http_address(address.map(|a| a.to_string())).await;
+
+                match address {
+                    Some(address) => println!("HTTP API will listen on {address}."),
+                    None => println!("HTTP API turned off."),
+                }
+
+                Ok(())
+            }
+            ConfigCommands::DiscordAppId { app_id } => {
+                let enabled = app_id.is_some();
+                db::set_discord_app_id(app_id).await;
+
+                if enabled {
+                    println!("Discord presence turned on.");
+                } else {
+                    println!("Discord presence turned off.");
+                }
+
+                Ok(())
+            }
+            ConfigCommands::Cookies { mode } => {
+                db::set_cookie_mode(mode).await;
+
+                if mode == qobuz::CookieMode::Persist {
+                    println!(
+                        "Cookies will be saved to {}.",
+                        db::cookie_jar_path().display()
+                    );
+                } else {
+                    println!("Cookie setting saved.");
+                }
+
+                Ok(())
+            }
+            ConfigCommands::LoginTimeout { seconds } => {
+                db::set_login_timeout(seconds.max(1) as i64).await;
+
+                println!("Login timeout saved.");
+
+                Ok(())
+            }
+            ConfigCommands::IdleTimeout { minutes } => {
+                db::set_idle_timeout(minutes as i64).await;
+
+                if minutes == 0 {
+                    println!("Idle timeout disabled.");
+                } else {
+                    println!("Idle timeout saved.");
+                }
+
+                Ok(())
+            }
+            ConfigCommands::ReloginInterval { hours } => {
+                db::set_relogin_interval(hours as i64).await;
+
+                if hours == 0 {
+                    println!("Automatic re-login disabled.");
+                } else {
+                    println!("Re-login interval saved.");
+                }
+
+                Ok(())
+            }
             ConfigCommands::Clear {} => {
                 if let Ok(ok) = Confirm::new()
                     .with_prompt("This will clear the configuration in the database.\nDo you want to continue?")
@@ -450,6 +1036,8 @@ macro_rules! wait {
                 cursive::receive_notifications().await
             }));
 
+            $handles.push(tokio::spawn(async { cursive::marquee().await }));
+
             tui.run().await;
 
             debug!("tui exited, quitting");
@@ -491,29 +1079,35 @@ macro_rules! output {
                 print!("{}", json);
             }
             Some(OutputFormat::Tsv) => {
-                // let formatted_results: Vec<Vec<String>> = $results.into();
+                let rows: Vec<Vec<String>> = $results.into();
 
-                // let rows = formatted_results
-                //     .iter()
-                //     .map(|row| {
-                //         let tabbed = row.join("\t");
+                for row in rows {
+                    println!("{}", row.join("\t"));
+                }
+            }
+            Some(OutputFormat::Csv) => {
+                let rows: Vec<Vec<String>> = $results.into();
 
-                //         tabbed
-                //     })
-                //     .collect::<Vec<String>>();
+                for row in rows {
+                    println!("{}", csv_row(&row));
+                }
+            }
+            Some(OutputFormat::Yaml) => {
+                let yaml =
+                    serde_yaml::to_string(&$results).expect("failed to convert results to string");
 
-                print!("");
+                print!("{}", yaml);
             }
             None => {
                 let mut table = Table::new();
                 table.load_preset(UTF8_FULL);
                 table.set_content_arrangement(comfy_table::ContentArrangement::Dynamic);
 
-                //let table_rows: Vec<Vec<String>> = $results.into();
+                let table_rows: Vec<Vec<String>> = $results.into();
 
-                // for row in table_rows {
-                //     table.add_row(row);
-                // }
+                for row in table_rows {
+                    table.add_row(row);
+                }
 
                 print!("{}", table);
             }
@@ -521,4 +1115,12 @@ macro_rules! output {
     };
 }
 
+/// Joins fields with commas, quoting the ones that contain a delimiter.
+fn csv_row(row: &[String]) -> String {
+    row.iter()
+        .map(|field| csv_field(field))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
 pub(crate) use output;
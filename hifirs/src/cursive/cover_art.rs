@@ -0,0 +1,357 @@
+use base64::{engine::general_purpose, Engine as _};
+use cursive::{Printer, Vec2, View};
+use image::{imageops::FilterType, DynamicImage, ImageOutputFormat, RgbImage};
+use once_cell::sync::Lazy;
+use std::{
+    collections::{BTreeSet, HashMap},
+    env,
+    io::{Cursor, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Columns the cover takes up in the player panel.
+pub const COVER_COLUMNS: usize = 12;
+/// Rows the cover takes up in the player panel.
+pub const COVER_ROWS: usize = 6;
+/// Pixels a cell is assumed to be, to size the image before sending it.
+const CELL_PIXELS: (u32, u32) = (8, 16);
+/// Covers kept in memory before the cache starts over.
+const CACHE_LIMIT: usize = 32;
+
+static PROTOCOL: Lazy<Option<Protocol>> = Lazy::new(Protocol::detect);
+/// Covers ready to write to the terminal, by url.
+static CACHE: Lazy<Mutex<HashMap<String, Arc<String>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+/// Bumped whenever cursive may have drawn over the cover.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Draw the cover again on the next frame, even if it hasn't moved. Cursive
+/// doesn't know about the cover, so it paints over it when the screen is
+/// cleared or a layer above it closes.
+pub fn invalidate() {
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Terminal graphics protocols a cover can be drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Kitty,
+    Iterm,
+    Sixel,
+}
+
+impl Protocol {
+    /// The protocol the terminal says it supports through its environment.
+    /// None when it can't be told, so nothing is drawn.
+    pub fn detect() -> Option<Protocol> {
+        let term = env::var("TERM").unwrap_or_default();
+        let program = env::var("TERM_PROGRAM").unwrap_or_default();
+
+        if env::var_os("KITTY_WINDOW_ID").is_some() || term.contains("kitty") {
+            Some(Protocol::Kitty)
+        } else if matches!(program.as_str(), "iTerm.app" | "WezTerm") {
+            Some(Protocol::Iterm)
+        } else if term.contains("sixel")
+            || matches!(term.as_str(), "foot" | "foot-extra" | "mlterm")
+        {
+            Some(Protocol::Sixel)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether covers can be drawn in this terminal.
+pub fn enabled() -> bool {
+    PROTOCOL.is_some()
+}
+
+/// Fetch a cover and encode it for the terminal, from the cache when it was
+/// loaded before.
+pub async fn load(url: &str) -> Option<Arc<String>> {
+    let protocol = (*PROTOCOL)?;
+
+    if let Some(cover) = CACHE.lock().unwrap().get(url) {
+        return Some(cover.clone());
+    }
+
+    let bytes = match reqwest::get(url).await.and_then(|r| r.error_for_status()) {
+        Ok(response) => response.bytes().await.ok()?,
+        Err(error) => {
+            debug!("failed to fetch cover art: {error}");
+            return None;
+        }
+    };
+
+    let image = match image::load_from_memory(&bytes) {
+        Ok(image) => image,
+        Err(error) => {
+            debug!("failed to decode cover art: {error}");
+            return None;
+        }
+    };
+
+    let cover = Arc::new(encode(protocol, &image)?);
+
+    let mut cache = CACHE.lock().unwrap();
+    if cache.len() >= CACHE_LIMIT {
+        cache.clear();
+    }
+    cache.insert(url.to_string(), cover.clone());
+
+    Some(cover)
+}
+
+fn encode(protocol: Protocol, image: &DynamicImage) -> Option<String> {
+    let image = image.resize(
+        COVER_COLUMNS as u32 * CELL_PIXELS.0,
+        COVER_ROWS as u32 * CELL_PIXELS.1,
+        FilterType::Triangle,
+    );
+
+    match protocol {
+        Protocol::Kitty => Some(kitty(&png(&image)?)),
+        Protocol::Iterm => Some(iterm(&png(&image)?)),
+        Protocol::Sixel => Some(sixel(&image.to_rgb8())),
+    }
+}
+
+fn png(image: &DynamicImage) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    match image.write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png) {
+        Ok(()) => Some(bytes),
+        Err(error) => {
+            debug!("failed to encode cover art: {error}");
+            None
+        }
+    }
+}
+
+/// Replaces any cover drawn before. Placed under the text, so popups stay
+/// readable, and sent in chunks as the protocol requires.
+fn kitty(png: &[u8]) -> String {
+    let data = general_purpose::STANDARD.encode(png);
+    let chunks = data.as_bytes().chunks(4096).collect::<Vec<&[u8]>>();
+    let mut escape = String::from(KITTY_CLEAR);
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(index + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+
+        if index == 0 {
+            escape.push_str(&format!(
+                "\x1b_Ga=T,f=100,q=2,z=-1,C=1,c={COVER_COLUMNS},r={COVER_ROWS},m={more};{chunk}\x1b\\"
+            ));
+        } else {
+            escape.push_str(&format!("\x1b_Gm={more};{chunk}\x1b\\"));
+        }
+    }
+
+    escape
+}
+
+const KITTY_CLEAR: &str = "\x1b_Ga=d,d=a,q=2\x1b\\";
+
+fn iterm(png: &[u8]) -> String {
+    format!(
+        "\x1b]1337;File=inline=1;width={COVER_COLUMNS};height={COVER_ROWS};preserveAspectRatio=1:{}\x07",
+        general_purpose::STANDARD.encode(png)
+    )
+}
+
+/// Sixel with a 6x6x6 color cube, plenty for a thumbnail.
+fn sixel(image: &RgbImage) -> String {
+    let (width, height) = image.dimensions();
+    let level = |c: u8| (c as u32 * 5 + 127) / 255;
+    let color = |x: u32, y: u32| {
+        let pixel = image.get_pixel(x, y);
+        level(pixel[0]) * 36 + level(pixel[1]) * 6 + level(pixel[2])
+    };
+
+    let mut escape = format!("\x1bPq\"1;1;{width};{height}");
+
+    for index in 0..216 {
+        let percent = |level: u32| level * 100 / 5;
+        escape.push_str(&format!(
+            "#{index};2;{};{};{}",
+            percent(index / 36),
+            percent(index / 6 % 6),
+            percent(index % 6)
+        ));
+    }
+
+    for top in (0..height).step_by(6) {
+        let rows = (height - top).min(6);
+        let used = (0..width)
+            .flat_map(|x| (top..top + rows).map(move |y| (x, y)))
+            .map(|(x, y)| color(x, y))
+            .collect::<BTreeSet<u32>>();
+
+        for index in used {
+            escape.push_str(&format!("#{index}"));
+
+            let sixels = (0..width).map(|x| {
+                let bits = (0..rows)
+                    .filter(|dy| color(x, top + dy) == index)
+                    .fold(0u8, |bits, dy| bits | 1 << dy);
+
+                (63 + bits) as char
+            });
+
+            push_run_length(&mut escape, sixels);
+            escape.push('$');
+        }
+
+        escape.push('-');
+    }
+
+    escape.push_str("\x1b\\");
+    escape
+}
+
+/// Writes repeated sixels as `!<count><sixel>`.
+fn push_run_length(escape: &mut String, sixels: impl Iterator<Item = char>) {
+    let mut run: Option<(char, usize)> = None;
+
+    for sixel in sixels {
+        run = match run {
+            Some((last, count)) if last == sixel => Some((last, count + 1)),
+            other => {
+                push_run(escape, other);
+                Some((sixel, 1))
+            }
+        };
+    }
+
+    push_run(escape, run);
+}
+
+fn push_run(escape: &mut String, run: Option<(char, usize)>) {
+    match run {
+        Some((sixel, count)) if count > 3 => escape.push_str(&format!("!{count}{sixel}")),
+        Some((sixel, count)) => escape.extend(std::iter::repeat(sixel).take(count)),
+        None => {}
+    }
+}
+
+/// Where and when a cover was last written to the terminal.
+struct Drawn {
+    cover: Arc<String>,
+    offset: Vec2,
+    size: Vec2,
+    generation: u64,
+}
+
+/// Holds the space for the cover in the layout. The cover itself is written
+/// straight to the terminal, around cursive, whenever it, its place or the
+/// screen around it changes.
+pub struct CoverArt {
+    url: Option<String>,
+    cover: Option<Arc<String>>,
+    size: Vec2,
+    drawn: Mutex<Option<Drawn>>,
+}
+
+impl CoverArt {
+    pub fn new() -> Self {
+        Self {
+            url: None,
+            cover: None,
+            size: Vec2::zero(),
+            drawn: Mutex::new(None),
+        }
+    }
+
+    /// Start showing the cover at `url`, once it is loaded. Clears the
+    /// current one.
+    pub fn set_url(&mut self, url: Option<String>) {
+        if self.url != url {
+            self.url = url;
+            self.cover = None;
+        }
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// A loaded cover, ignored when another url was set in the meantime.
+    pub fn set_cover(&mut self, url: &str, cover: Arc<String>) {
+        if self.url.as_deref() == Some(url) {
+            self.cover = Some(cover);
+        }
+    }
+}
+
+impl Default for CoverArt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl View for CoverArt {
+    fn draw(&self, printer: &Printer) {
+        let mut drawn = self.drawn.lock().unwrap();
+        let offset = printer.offset;
+
+        let escape = match (&self.cover, drawn.as_ref()) {
+            (Some(cover), Some(last))
+                if Arc::ptr_eq(cover, &last.cover)
+                    && last.offset == offset
+                    && last.size == self.size
+                    && last.generation == GENERATION.load(Ordering::Relaxed) =>
+            {
+                return;
+            }
+            (Some(cover), _) => {
+                *drawn = Some(Drawn {
+                    cover: cover.clone(),
+                    offset,
+                    size: self.size,
+                    generation: GENERATION.load(Ordering::Relaxed),
+                });
+                // Cursive owns the cursor, so put it back afterwards.
+                format!("\x1b7\x1b[{};{}H{cover}\x1b8", offset.y + 1, offset.x + 1)
+            }
+            (None, Some(_)) if *PROTOCOL == Some(Protocol::Kitty) => {
+                *drawn = None;
+                KITTY_CLEAR.to_string()
+            }
+            (None, _) => {
+                *drawn = None;
+                return;
+            }
+        };
+
+        let mut stdout = std::io::stdout().lock();
+        let written = stdout.write_all(escape.as_bytes());
+
+        if let Err(error) = written.and_then(|_| stdout.flush()) {
+            debug!("failed to draw cover art: {error}");
+        }
+    }
+
+    fn layout(&mut self, size: Vec2) {
+        self.size = size;
+    }
+
+    fn required_size(&mut self, _constraint: Vec2) -> Vec2 {
+        Vec2::new(COVER_COLUMNS, COVER_ROWS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sixel_runs_are_compressed() {
+        let mut escape = String::new();
+        push_run_length(&mut escape, "??????AB".chars());
+
+        assert_eq!(escape, "!6?AB");
+    }
+}
@@ -0,0 +1,282 @@
+use clap::ValueEnum;
+use std::collections::HashMap;
+
+/// Groups of keys on the help screen, in the order they are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KeyCategory {
+    Playback,
+    Navigation,
+    Queue,
+    Other,
+}
+
+impl KeyCategory {
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeyCategory::Playback => "playback",
+            KeyCategory::Navigation => "navigation",
+            KeyCategory::Queue => "queue",
+            KeyCategory::Other => "other",
+        }
+    }
+}
+
+/// Everything a global key can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub enum KeyAction {
+    PlayPause,
+    Next,
+    Previous,
+    JumpForward,
+    JumpBackward,
+    JumpForwardLarge,
+    JumpBackwardLarge,
+    VolumeUp,
+    VolumeDown,
+    SeekTo,
+    CycleRepeat,
+    ToggleMono,
+    SleepTimer,
+    Favorite,
+    AddToPlaylist,
+    Quality,
+    NowPlaying,
+    MyPlaylists,
+    Search,
+    EnterUrl,
+    Purchases,
+    Favorites,
+    RefreshFavorites,
+    Editorial,
+    TrackDetails,
+    Lyrics,
+    ToggleTrackList,
+    CopyQueue,
+    ExportQueue,
+    TrackRadio,
+    ToggleRadio,
+    AddToQueue,
+    PlayNext,
+    RemoveFromQueue,
+    MoveTrackUp,
+    MoveTrackDown,
+    Download,
+    DumpState,
+    Help,
+}
+
+impl KeyAction {
+    pub fn name(&self) -> String {
+        self.to_possible_value()
+            .map(|v| v.get_name().to_string())
+            .unwrap_or_default()
+    }
+
+    pub fn default_key(&self) -> char {
+        match self {
+            KeyAction::PlayPause => ' ',
+            KeyAction::Next => 'N',
+            KeyAction::Previous => 'P',
+            KeyAction::JumpForward => 'l',
+            KeyAction::JumpBackward => 'h',
+            KeyAction::JumpForwardLarge => 'L',
+            KeyAction::JumpBackwardLarge => 'H',
+            KeyAction::VolumeUp => '+',
+            KeyAction::VolumeDown => '-',
+            KeyAction::SeekTo => 'g',
+            KeyAction::CycleRepeat => 'r',
+            KeyAction::ToggleMono => 'M',
+            KeyAction::SleepTimer => 'z',
+            KeyAction::Favorite => 'f',
+            KeyAction::AddToPlaylist => 'a',
+            KeyAction::Quality => 'b',
+            KeyAction::NowPlaying => '1',
+            KeyAction::MyPlaylists => '2',
+            KeyAction::Search => '3',
+            KeyAction::EnterUrl => '4',
+            KeyAction::Purchases => '5',
+            KeyAction::Favorites => '6',
+            KeyAction::RefreshFavorites => 'F',
+            KeyAction::Editorial => 'e',
+            KeyAction::TrackDetails => 'i',
+            KeyAction::Lyrics => 't',
+            KeyAction::ToggleTrackList => 'q',
+            KeyAction::CopyQueue => 'y',
+            KeyAction::ExportQueue => 'Y',
+            KeyAction::TrackRadio => 'R',
+            KeyAction::ToggleRadio => 'E',
+            KeyAction::AddToQueue => 'Q',
+            KeyAction::PlayNext => 'n',
+            KeyAction::RemoveFromQueue => 'x',
+            KeyAction::MoveTrackUp => 'K',
+            KeyAction::MoveTrackDown => 'J',
+            KeyAction::Download => 'd',
+            KeyAction::DumpState => 'D',
+            KeyAction::Help => '?',
+        }
+    }
+
+    pub fn category(&self) -> KeyCategory {
+        match self {
+            KeyAction::PlayPause
+            | KeyAction::Next
+            | KeyAction::Previous
+            | KeyAction::JumpForward
+            | KeyAction::JumpBackward
+            | KeyAction::JumpForwardLarge
+            | KeyAction::JumpBackwardLarge
+            | KeyAction::VolumeUp
+            | KeyAction::VolumeDown
+            | KeyAction::SeekTo
+            | KeyAction::CycleRepeat
+            | KeyAction::ToggleMono
+            | KeyAction::SleepTimer
+            | KeyAction::Favorite
+            | KeyAction::AddToPlaylist
+            | KeyAction::Quality => KeyCategory::Playback,
+            KeyAction::NowPlaying
+            | KeyAction::MyPlaylists
+            | KeyAction::Search
+            | KeyAction::EnterUrl
+            | KeyAction::Purchases
+            | KeyAction::Favorites
+            | KeyAction::RefreshFavorites
+            | KeyAction::Editorial
+            | KeyAction::TrackDetails
+            | KeyAction::Lyrics => KeyCategory::Navigation,
+            KeyAction::ToggleTrackList
+            | KeyAction::CopyQueue
+            | KeyAction::ExportQueue
+            | KeyAction::TrackRadio
+            | KeyAction::ToggleRadio
+            | KeyAction::AddToQueue
+            | KeyAction::PlayNext
+            | KeyAction::RemoveFromQueue
+            | KeyAction::MoveTrackUp
+            | KeyAction::MoveTrackDown => KeyCategory::Queue,
+            KeyAction::Download | KeyAction::DumpState | KeyAction::Help => KeyCategory::Other,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            KeyAction::PlayPause => "Play/pause",
+            KeyAction::Next => "Next track",
+            KeyAction::Previous => "Previous track",
+            KeyAction::JumpForward => "Jump forward",
+            KeyAction::JumpBackward => "Jump backward",
+            KeyAction::JumpForwardLarge => "Jump forward further",
+            KeyAction::JumpBackwardLarge => "Jump backward further",
+            KeyAction::VolumeUp => "Volume up",
+            KeyAction::VolumeDown => "Volume down",
+            KeyAction::SeekTo => "Seek to position",
+            KeyAction::CycleRepeat => "Cycle repeat mode",
+            KeyAction::ToggleMono => "Toggle mono",
+            KeyAction::SleepTimer => "Set sleep timer",
+            KeyAction::Favorite => "Favorite current track",
+            KeyAction::AddToPlaylist => "Add track to playlist",
+            KeyAction::Quality => "Change stream quality",
+            KeyAction::NowPlaying => "Now playing",
+            KeyAction::MyPlaylists => "My playlists",
+            KeyAction::Search => "Search",
+            KeyAction::EnterUrl => "Enter URL",
+            KeyAction::Purchases => "Purchases",
+            KeyAction::Favorites => "Favorites",
+            KeyAction::RefreshFavorites => "Refresh favorites",
+            KeyAction::Editorial => "Editorial",
+            KeyAction::TrackDetails => "Track details",
+            KeyAction::Lyrics => "Track lyrics",
+            KeyAction::ToggleTrackList => "Toggle track list",
+            KeyAction::CopyQueue => "Copy queue as text",
+            KeyAction::ExportQueue => "Export queue to file",
+            KeyAction::TrackRadio => "Start track radio",
+            KeyAction::ToggleRadio => "Toggle endless radio",
+            KeyAction::AddToQueue => "Add track to queue",
+            KeyAction::PlayNext => "Play track next",
+            KeyAction::RemoveFromQueue => "Remove track from queue",
+            KeyAction::MoveTrackUp => "Move track up",
+            KeyAction::MoveTrackDown => "Move track down",
+            KeyAction::Download => "Download track or album",
+            KeyAction::DumpState => "Log player state",
+            KeyAction::Help => "Show this help",
+        }
+    }
+}
+
+/// Reads a key as written in the config: a single character, or `space`.
+pub fn parse_key(text: &str) -> Option<char> {
+    if text.eq_ignore_ascii_case("space") {
+        return Some(' ');
+    }
+
+    let mut chars = text.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(key), None) => Some(key),
+        _ => None,
+    }
+}
+
+pub fn key_label(key: char) -> String {
+    if key == ' ' {
+        "space".to_string()
+    } else {
+        key.to_string()
+    }
+}
+
+/// The key for every action: the saved ones where they are valid, the
+/// defaults for the rest. Keys bound to more than one action are logged.
+pub fn resolve(saved: Vec<(String, String)>) -> HashMap<KeyAction, char> {
+    let mut keys = KeyAction::value_variants()
+        .iter()
+        .map(|a| (*a, a.default_key()))
+        .collect::<HashMap<KeyAction, char>>();
+
+    for (action, key) in saved {
+        match (KeyAction::from_str(&action, true), parse_key(&key)) {
+            (Ok(action), Some(key)) => {
+                keys.insert(action, key);
+            }
+            _ => warn!("ignoring keybinding {action} = {key:?}"),
+        }
+    }
+
+    let mut seen: HashMap<char, KeyAction> = HashMap::new();
+
+    for action in KeyAction::value_variants() {
+        let key = keys[action];
+
+        if let Some(other) = seen.insert(key, *action) {
+            warn!(
+                "{} is bound to both {} and {}",
+                key_label(key),
+                other.name(),
+                action.name()
+            );
+        }
+    }
+
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_keys_override_defaults() {
+        let keys = resolve(vec![
+            ("next".to_string(), "j".to_string()),
+            ("play_pause".to_string(), "space".to_string()),
+            ("previous".to_string(), "too long".to_string()),
+            ("not_an_action".to_string(), "x".to_string()),
+        ]);
+
+        assert_eq!(keys[&KeyAction::Next], 'j');
+        assert_eq!(keys[&KeyAction::PlayPause], ' ');
+        assert_eq!(keys[&KeyAction::Previous], 'P');
+        assert_eq!(keys.len(), KeyAction::value_variants().len());
+    }
+}
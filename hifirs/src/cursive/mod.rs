@@ -1,35 +1,59 @@
 use std::{
+    collections::HashMap,
     rc::Rc,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
+    time::{Duration, Instant},
 };
 
 use crate::{
-    player::{self, controls::Controls, notification::Notification, queue::TrackListType},
-    service::{SearchResults, Track, TrackStatus},
+    cursive::keys::{KeyAction, KeyCategory},
+    player::{
+        self,
+        controls::{parse_qobuz_uri, Action, Controls, RepeatMode},
+        notification::{Notification, StopReason},
+        queue::{TrackListType, TrackListValue},
+    },
+    service::{
+        sort_results, Account, Album, ArticleLink, Favorites, Playlist, SearchResults, SortOrder,
+        Track, TrackStatus,
+    },
+    sql::db,
 };
+use clap::ValueEnum;
 use cursive::{
     align::HAlign,
     direction::Orientation,
-    event::{Event, Key},
+    event::{Event, EventResult, EventTrigger, Key, MouseButton, MouseEvent},
     reexports::crossbeam_channel::Sender,
-    theme::{BorderStyle, ColorStyle, Effect, Palette, Style},
+    theme::{BaseColor, Color, ColorStyle, Effect, Style},
     utils::{markup::StyledString, Counter},
     view::{Nameable, Position, Resizable, Scrollable, SizeConstraint},
     views::{
-        Button, Dialog, EditView, HideableView, Layer, LinearLayout, MenuPopup, NamedView,
-        OnEventView, PaddedView, Panel, ProgressBar, ResizedView, ScreensView, ScrollView,
-        SelectView, TextView,
+        Button, Checkbox, Dialog, EditView, HideableView, LastSizeView, Layer, LinearLayout,
+        MenuPopup, NamedView, OnEventView, PaddedView, Panel, ProgressBar, ResizedView,
+        ScreensView, ScrollView, SelectView, TextView,
     },
     CbSink, Cursive, CursiveRunnable, With,
 };
 use futures::executor::block_on;
 use gstreamer::{ClockTime, State as GstState};
+use hifirs_qobuz_api::client::AudioQuality;
 use once_cell::sync::{Lazy, OnceCell};
 use tokio::select;
 use tokio_stream::StreamExt;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+#[cfg(feature = "cover-art")]
+pub mod cover_art;
+pub mod keys;
+mod search_history;
+mod theme;
+
+use search_history::{SearchHistory, HISTORY_LIMIT};
 
 type CursiveSender = Sender<Box<dyn FnOnce(&mut Cursive) + Send>>;
 
@@ -38,9 +62,89 @@ static CONTROLS: Lazy<Controls> = Lazy::new(player::controls);
 
 static UNSTREAMABLE: &str = "UNSTREAMABLE";
 static ENTER_URL_OPEN: AtomicBool = AtomicBool::new(false);
+/// Whether the player title says a link from the URL box is loading.
+static LOADING_URL: AtomicBool = AtomicBool::new(false);
+/// Whether the progress bar shows buffering instead of the position.
+static BUFFERING: AtomicBool = AtomicBool::new(false);
+static TRACK_LIST_VISIBLE: AtomicBool = AtomicBool::new(true);
+/// Fetched the first time the favorites screen is opened.
+static FAVORITES: Lazy<Mutex<Option<Favorites>>> = Lazy::new(|| Mutex::new(None));
+/// Fetched once when the TUI starts.
+static ACCOUNT: Lazy<Mutex<Option<Account>>> = Lazy::new(|| Mutex::new(None));
+
+/// Quietest level, in dB, shown on the level meter.
+const LEVEL_FLOOR: f64 = -60.0;
+/// Peaks at or above this level, in dB, are flagged as clipping.
+const CLIPPING: f64 = -0.1;
+const LEVEL_METER_WIDTH: usize = 40;
+const LEVEL_REFRESH: Duration = Duration::from_millis(100);
+/// Columns available to the track number fields of the player panel.
+const TRACK_NUMBER_WIDTH: usize = 3;
+/// Columns available to the status fields of the player panel.
+const STATUS_WIDTH: usize = 8;
+/// How often a title that doesn't fit moves by one column.
+const MARQUEE_STEP: Duration = Duration::from_millis(300);
+/// Steps a title rests at either end before moving again.
+const MARQUEE_PAUSE: usize = 10;
+/// Titles panned by the marquee.
+const MARQUEE_VIEWS: [&str; 2] = ["current_track_title_scroll", "entity_title_scroll"];
+
+type TitleScroll = ScrollView<NamedView<TextView>>;
+
+#[derive(Debug)]
+struct KeyBinding {
+    category: KeyCategory,
+    key: String,
+    action: &'static str,
+}
+
+/// How long after the track list is scrolled by hand it stops following the
+/// current track.
+const MANUAL_SCROLL_GRACE: Duration = Duration::from_secs(10);
+static LAST_MANUAL_SCROLL: Lazy<Mutex<Option<Instant>>> = Lazy::new(|| Mutex::new(None));
+/// Where a track was just moved to, so it stays selected once the list is redrawn.
+static MOVED_TRACK: Lazy<Mutex<Option<usize>>> = Lazy::new(|| Mutex::new(None));
+static SEARCH_HISTORY: Lazy<Mutex<SearchHistory>> =
+    Lazy::new(|| Mutex::new(SearchHistory::default()));
+/// Rows of recent searches shown under the search box.
+const RECENT_SEARCH_ROWS: usize = 5;
+
+const TOAST_DURATION: Duration = Duration::from_secs(2);
+/// How many steps the larger jumps cover.
+const LARGE_JUMP_STEPS: u64 = 6;
+/// Value of the disc headers in the track list, where tracks have their
+/// position, which starts at one.
+const DISC_HEADER: usize = 0;
+
+/// Every global key, filled in as the callbacks are registered.
+static KEY_BINDINGS: Lazy<Mutex<Vec<KeyBinding>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Screens of the TUI, in the order they are added.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Screen {
+    #[default]
+    NowPlaying = 0,
+    MyPlaylists = 1,
+    Search = 2,
+    Purchases = 3,
+    Favorites = 4,
+}
+
+impl From<i64> for Screen {
+    fn from(screen_id: i64) -> Self {
+        match screen_id {
+            1 => Self::MyPlaylists,
+            2 => Self::Search,
+            3 => Self::Purchases,
+            4 => Self::Favorites,
+            _ => Self::NowPlaying,
+        }
+    }
+}
 
 pub struct CursiveUI {
     root: CursiveRunnable,
+    keys: HashMap<KeyAction, char>,
 }
 
 impl CursiveUI {
@@ -49,40 +153,12 @@ impl CursiveUI {
 
         SINK.set(siv.cb_sink().clone()).expect("error setting sink");
 
-        siv.set_theme(cursive::theme::Theme {
-            shadow: false,
-            borders: BorderStyle::Simple,
-            palette: Palette::terminal_default().with(|palette| {
-                use cursive::theme::BaseColor::*;
-
-                {
-                    use cursive::theme::Color::TerminalDefault;
-                    use cursive::theme::PaletteColor::*;
-
-                    palette[Background] = TerminalDefault;
-                    palette[View] = TerminalDefault;
-                    palette[Primary] = White.dark();
-                    palette[Highlight] = Cyan.dark();
-                    palette[HighlightInactive] = Black.dark();
-                    palette[HighlightText] = Black.dark();
-                }
-
-                {
-                    use cursive::theme::Color::TerminalDefault;
-                    use cursive::theme::Effect::*;
-                    use cursive::theme::PaletteStyle::*;
-
-                    palette[Highlight] = Style::from(Cyan.dark())
-                        .combine(Underline)
-                        .combine(Reverse)
-                        .combine(Bold);
-                    palette[HighlightInactive] = Style::from(TerminalDefault).combine(Reverse);
-                    palette[TitlePrimary] = Style::from(Cyan.dark()).combine(Bold);
-                }
-            }),
-        });
+        siv.set_theme(theme::load());
 
-        Self { root: siv }
+        Self {
+            root: siv,
+            keys: HashMap::new(),
+        }
     }
 
     pub fn player(&self) -> LinearLayout {
@@ -101,7 +177,8 @@ impl CursiveUI {
                         .with_name("current_track_title")
                         .scrollable()
                         .show_scrollbars(false)
-                        .scroll_x(true),
+                        .scroll_x(true)
+                        .with_name("current_track_title_scroll"),
                 )
                 .child(TextView::new("").with_name("artist_name"))
                 .child(
@@ -109,7 +186,13 @@ impl CursiveUI {
                         .with_name("entity_title")
                         .scrollable()
                         .show_scrollbars(false)
-                        .scroll_x(true),
+                        .scroll_x(true)
+                        .with_name("entity_title_scroll"),
+                )
+                .child(
+                    TextView::new("")
+                        .style(Style::from(Effect::Dim))
+                        .with_name("queue_time"),
                 ),
         )
         .resized(SizeConstraint::Full, SizeConstraint::Free);
@@ -126,14 +209,19 @@ impl CursiveUI {
                     .h_align(HAlign::Left)
                     .with_name("total_tracks"),
             )
-            .fixed_width(3);
+            .fixed_width(TRACK_NUMBER_WIDTH);
 
         let player_status = LinearLayout::new(Orientation::Vertical)
             .child(
-                TextView::new(format!(" {}", '\u{23f9}'))
+                TextView::new(get_state_icon(GstState::Null))
                     .h_align(HAlign::Center)
                     .with_name("player_status"),
             )
+            .child(
+                TextView::new(volume_label(player::volume()))
+                    .h_align(HAlign::Right)
+                    .with_name("volume"),
+            )
             .child(
                 TextView::new("16 bits")
                     .h_align(HAlign::Right)
@@ -144,12 +232,32 @@ impl CursiveUI {
                     .h_align(HAlign::Right)
                     .with_name("sample_rate"),
             )
-            .fixed_width(8);
+            .child(
+                TextView::new(favorite_icon(false))
+                    .h_align(HAlign::Right)
+                    .with_name("favorite"),
+            )
+            .child(
+                TextView::new(repeat_label(RepeatMode::Off))
+                    .h_align(HAlign::Right)
+                    .with_name("repeat_mode"),
+            )
+            .child(
+                TextView::new(sleep_timer_label(player::sleep_timer_remaining()))
+                    .h_align(HAlign::Right)
+                    .with_name("sleep_timer"),
+            )
+            .child(TextView::new(jump_label(player::jump_seconds())).h_align(HAlign::Right))
+            .fixed_width(STATUS_WIDTH);
 
         let counter = Counter::new(0);
         let progress = ProgressBar::new()
             .with_value(counter)
             .with_label(|value, (_, max)| {
+                if BUFFERING.load(Ordering::Relaxed) {
+                    return format!("buffering {value}%");
+                }
+
                 let position =
                     ClockTime::from_seconds(value as u64).to_string().as_str()[2..7].to_string();
                 let duration =
@@ -159,16 +267,70 @@ impl CursiveUI {
             })
             .with_name("progress");
 
+        #[cfg(feature = "cover-art")]
+        if cover_art::enabled() {
+            track_info.add_child(cover_art::CoverArt::new().with_name("cover_art"));
+        }
+
         track_info.add_child(track_num);
         track_info.add_child(meta);
         track_info.add_child(player_status);
 
+        // Clicking the bar seeks to the same fraction of the track.
+        let progress = OnEventView::new(LastSizeView::new(progress)).on_event_inner(
+            EventTrigger::mouse(),
+            |view: &mut LastSizeView<NamedView<ProgressBar>>, event| {
+                if let Event::Mouse {
+                    offset,
+                    position,
+                    event: MouseEvent::Press(MouseButton::Left),
+                } = *event
+                {
+                    let x = position.checked_sub(offset)?.x;
+                    let width = view.size.x.max(1);
+                    let fraction = x as f64 / width as f64;
+
+                    tokio::spawn(async move {
+                        if let Some(track) = player::current_track().await {
+                            let seconds = (track.duration_seconds as f64 * fraction) as u64;
+                            CONTROLS.seek_to(seconds).await;
+                        }
+                    });
+
+                    Some(EventResult::Consumed(None))
+                } else {
+                    None
+                }
+            },
+        );
+
         container.add_child(track_info);
         container.add_child(progress);
 
+        if player::level_meter_enabled() {
+            container.add_child(TextView::new("").with_name("level_meter"));
+        }
+
+        container.add_child(
+            TextView::new("")
+                .style(Style::from(Color::Dark(BaseColor::Red)))
+                .with_name("error_line"),
+        );
+
+        container.add_child(
+            TextView::new("")
+                .h_align(HAlign::Right)
+                .style(Style::from(Effect::Dim))
+                .with_name("account"),
+        );
+
         let mut track_list: SelectView<usize> = SelectView::new();
 
         track_list.set_on_submit(move |_s, item| {
+            if *item == DISC_HEADER {
+                return;
+            }
+
             let i = item.to_owned();
             tokio::spawn(async move { CONTROLS.skip_to(i as u32).await });
         });
@@ -179,15 +341,24 @@ impl CursiveUI {
                 .with_name("player_panel"),
         );
 
+        // Note when the list is scrolled by hand, so following the current
+        // track doesn't fight the user.
+        let track_list = OnEventView::new(
+            track_list
+                .scrollable()
+                .scroll_y(true)
+                .scroll_x(true)
+                .with_name("current_track_list"),
+        )
+        .on_pre_event_inner(EventTrigger::from_fn(is_scroll_event), |_, _| {
+            *LAST_MANUAL_SCROLL.lock().unwrap() = Some(Instant::now());
+            None
+        });
+
         layout.add_child(Panel::new(
-            HideableView::new(
-                track_list
-                    .scrollable()
-                    .scroll_y(true)
-                    .scroll_x(true)
-                    .with_name("current_track_list"),
-            )
-            .visible(true),
+            HideableView::new(track_list)
+                .visible(TRACK_LIST_VISIBLE.load(Ordering::Relaxed))
+                .with_name("current_track_list_panel"),
         ));
 
         layout
@@ -196,6 +367,19 @@ impl CursiveUI {
     pub fn global_events(&mut self) {
         self.root.clear_global_callbacks(Event::CtrlChar('c'));
 
+        // Resizing, switching screens and closing dialogs all start with an
+        // event, and all of them may paint over the cover.
+        #[cfg(feature = "cover-art")]
+        self.root
+            .set_on_pre_event_inner(EventTrigger::any(), |event| {
+                if *event != Event::Refresh {
+                    cover_art::invalidate();
+                }
+
+                None
+            });
+
+        self.describe(KeyCategory::Other, "ctrl+c", "Quit");
         self.root.set_on_pre_event(Event::CtrlChar('c'), move |s| {
             let dialog = Dialog::text("Do you want to quit?")
                 .button("Yes", move |s: &mut Cursive| {
@@ -206,36 +390,276 @@ impl CursiveUI {
             s.add_layer(dialog);
         });
 
-        self.root.add_global_callback('1', move |s| {
-            s.set_screen(0);
+        self.bind(KeyAction::PlayPause, move |_| {
+            block_on(async { CONTROLS.play_pause().await });
         });
 
-        self.root.add_global_callback('2', move |s| {
-            s.set_screen(1);
+        self.bind(KeyAction::Next, move |_| {
+            block_on(async { CONTROLS.next().await });
         });
 
-        self.root.add_global_callback('3', move |s| {
-            s.set_screen(2);
+        self.bind(KeyAction::Previous, move |_| {
+            block_on(async { CONTROLS.previous().await });
         });
 
-        self.root.add_global_callback(' ', move |_| {
-            block_on(async { CONTROLS.play_pause().await });
+        self.bind(KeyAction::JumpForward, move |_| {
+            block_on(async { CONTROLS.jump_forward(None).await });
         });
 
-        self.root.add_global_callback('N', move |_| {
-            block_on(async { CONTROLS.next().await });
+        self.bind(KeyAction::JumpBackward, move |_| {
+            block_on(async { CONTROLS.jump_backward(None).await });
         });
 
-        self.root.add_global_callback('P', move |_| {
-            block_on(async { CONTROLS.previous().await });
+        self.bind(KeyAction::JumpForwardLarge, move |_| {
+            let seconds = player::jump_seconds() * LARGE_JUMP_STEPS;
+            block_on(async { CONTROLS.jump_forward(Some(seconds)).await });
+        });
+
+        self.bind(KeyAction::JumpBackwardLarge, move |_| {
+            let seconds = player::jump_seconds() * LARGE_JUMP_STEPS;
+            block_on(async { CONTROLS.jump_backward(Some(seconds)).await });
+        });
+
+        #[cfg(feature = "clipboard")]
+        self.bind(KeyAction::CopyQueue, move |s| {
+            let text = block_on(async { player::current_tracklist().await }).to_text();
+
+            let message = match arboard::Clipboard::new().and_then(|mut c| c.set_text(text)) {
+                Ok(()) => "Queue copied to clipboard.".to_string(),
+                Err(error) => format!("Failed to copy queue: {error}"),
+            };
+
+            s.add_layer(Dialog::info(message));
+        });
+
+        self.bind(KeyAction::ExportQueue, move |s| {
+            let input = EditView::new()
+                .on_submit(move |s, path| {
+                    let path = path.to_string();
+                    tokio::spawn(async move { CONTROLS.export_queue(path, None).await });
+                    s.pop_layer();
+                })
+                .fixed_width(40);
+
+            s.add_layer(
+                Dialog::around(input)
+                    .title("Export queue (.txt, .m3u8, .json or .csv)")
+                    .dismiss_button("Cancel"),
+            );
+        });
+
+        self.describe(KeyCategory::Queue, "esc", "Cancel loading");
+        self.root
+            .add_global_callback(Event::Key(Key::Esc), move |_| {
+                if player::is_loading() {
+                    block_on(async { CONTROLS.cancel_load().await });
+                }
+            });
+
+        self.bind(KeyAction::Editorial, show_editorial);
+
+        self.bind(KeyAction::Favorite, move |_| {
+            block_on(async { CONTROLS.toggle_favorite_current().await });
+        });
+
+        self.bind(KeyAction::AddToPlaylist, move |s| {
+            let track =
+                selected_track(s).or_else(|| block_on(async { player::current_track().await }));
+
+            if let Some(track) = track {
+                add_to_playlist(s, track);
+            }
+        });
+
+        self.bind(KeyAction::Quality, choose_default_quality);
+
+        self.bind(KeyAction::AddToQueue, move |s| {
+            if let Some(track) = selected_track(s) {
+                tokio::spawn(async move { CONTROLS.add_to_queue(track.id as i32).await });
+                show_toast(s, format!("Added {} to the queue.", track.title.trim()));
+            }
+        });
+
+        self.bind(KeyAction::PlayNext, move |s| {
+            if let Some(track) = selected_track(s) {
+                tokio::spawn(async move { CONTROLS.play_next(track.id as i32).await });
+                show_toast(s, format!("{} plays next.", track.title.trim()));
+            }
+        });
+
+        self.bind(KeyAction::RemoveFromQueue, move |s| {
+            if s.active_screen() != 0 {
+                return;
+            }
+
+            let position = s
+                .find_name::<ScrollView<SelectView<usize>>>("current_track_list")
+                .and_then(|list| list.get_inner().selection().map(|p| *p))
+                .filter(|p| *p != DISC_HEADER);
+
+            if let Some(position) = position {
+                let position = position as u32;
+                tokio::spawn(async move { CONTROLS.remove_from_queue(position).await });
+            }
+        });
+
+        self.bind(KeyAction::MoveTrackUp, move |s| move_selected_track(s, -1));
+        self.bind(KeyAction::MoveTrackDown, move |s| move_selected_track(s, 1));
+
+        self.bind(KeyAction::SleepTimer, move |s| {
+            let input = EditView::new()
+                .on_submit(move |s, text| {
+                    match text.trim().parse::<u32>() {
+                        Ok(0) => block_on(async { CONTROLS.cancel_sleep_timer().await }),
+                        Ok(minutes) => block_on(async { CONTROLS.set_sleep_timer(minutes).await }),
+                        Err(_) => return,
+                    }
+
+                    s.pop_layer();
+                })
+                .fixed_width(10);
+
+            s.add_layer(
+                Dialog::around(input)
+                    .title("Sleep timer (minutes, 0 to cancel)")
+                    .dismiss_button("Cancel"),
+            );
+        });
+
+        self.bind(KeyAction::Download, move |s| {
+            s.add_layer(
+                Dialog::text("Download the current track, or the whole album?")
+                    .title("Download")
+                    .button("Track", |s| {
+                        block_on(async { CONTROLS.download(false).await });
+                        s.pop_layer();
+                    })
+                    .button("Album", |s| {
+                        block_on(async { CONTROLS.download(true).await });
+                        s.pop_layer();
+                    })
+                    .dismiss_button("Cancel"),
+            );
         });
 
-        self.root.add_global_callback('l', move |_| {
-            block_on(async { CONTROLS.jump_forward().await });
+        self.bind(KeyAction::VolumeUp, move |_| {
+            block_on(async { CONTROLS.volume_up().await });
         });
 
-        self.root.add_global_callback('h', move |_| {
-            block_on(async { CONTROLS.jump_backward().await });
+        self.bind(KeyAction::VolumeDown, move |_| {
+            block_on(async { CONTROLS.volume_down().await });
+        });
+
+        self.bind(KeyAction::SeekTo, move |s| {
+            let input = EditView::new()
+                .on_submit(move |s, text| {
+                    let text = text.to_string();
+
+                    tokio::spawn(async move {
+                        if let Some(track) = player::current_track().await {
+                            if let Some(seconds) = parse_seek(&text, track.duration_seconds) {
+                                CONTROLS.seek_to(seconds).await;
+                            }
+                        }
+                    });
+
+                    s.pop_layer();
+                })
+                .fixed_width(20);
+
+            s.add_layer(
+                Dialog::around(input)
+                    .title("Seek to (mm:ss or %)")
+                    .dismiss_button("Cancel"),
+            );
+        });
+
+        self.bind(KeyAction::CycleRepeat, move |_| {
+            block_on(async { CONTROLS.cycle_repeat().await });
+        });
+
+        self.bind(KeyAction::TrackRadio, move |_| {
+            let current = block_on(async { player::current_track().await });
+
+            if let Some(track) = current {
+                block_on(async { CONTROLS.start_radio(track.id as i32).await });
+            }
+        });
+
+        self.bind(KeyAction::ToggleRadio, move |_| {
+            let enabled = !player::is_radio();
+            block_on(async { CONTROLS.set_radio(enabled).await });
+        });
+
+        self.bind(KeyAction::Lyrics, move |s| {
+            let current = block_on(async { player::current_track().await });
+
+            if let Some(track) = current {
+                show_lyrics(s, &track);
+            }
+        });
+
+        self.bind(KeyAction::ToggleMono, move |_| {
+            let enabled = !player::is_mono();
+            block_on(async { CONTROLS.set_mono(enabled).await });
+        });
+
+        self.bind(KeyAction::DumpState, move |_| {
+            block_on(async { CONTROLS.dump_state().await });
+        });
+
+        self.bind(KeyAction::TrackDetails, move |s| {
+            if let Some(track) = selected_track(s) {
+                show_track_details(s, &track);
+            }
+        });
+
+        self.bind(KeyAction::ToggleTrackList, move |s| {
+            let visible = !TRACK_LIST_VISIBLE.load(Ordering::Relaxed);
+            TRACK_LIST_VISIBLE.store(visible, Ordering::Relaxed);
+
+            s.call_on_name(
+                "current_track_list_panel",
+                |view: &mut HideableView<OnEventView<NamedView<ScrollView<SelectView<usize>>>>>| {
+                    view.set_visible(visible);
+                },
+            );
+        });
+
+        let help_key = self.key(KeyAction::Help);
+        self.bind(KeyAction::Help, move |s| show_help(s, help_key));
+    }
+
+    /// The key bound to the action, as configured or by default.
+    fn key(&self, action: KeyAction) -> char {
+        self.keys
+            .get(&action)
+            .copied()
+            .unwrap_or_else(|| action.default_key())
+    }
+
+    /// Registers a global callback for the action's key and lists it on the
+    /// help screen.
+    fn bind<F>(&mut self, action: KeyAction, callback: F)
+    where
+        F: FnMut(&mut Cursive) + 'static,
+    {
+        let key = self.key(action);
+
+        self.describe(
+            action.category(),
+            &keys::key_label(key),
+            action.description(),
+        );
+        self.root.add_global_callback(key, callback);
+    }
+
+    /// Lists a key handled outside of `bind` on the help screen.
+    fn describe(&mut self, category: KeyCategory, key: &str, action: &'static str) {
+        KEY_BINDINGS.lock().unwrap().push(KeyBinding {
+            category,
+            key: key.to_string(),
+            action,
         });
     }
 
@@ -259,7 +683,7 @@ impl CursiveUI {
                 return;
             }
 
-            let layout = submit_playlist(s, *item).wrap_with(Panel::new);
+            let layout = submit_playlist(s, *item, false).wrap_with(Panel::new);
 
             s.call_on_name("user_playlist_layout", |l: &mut LinearLayout| {
                 l.remove_child(1);
@@ -273,11 +697,15 @@ impl CursiveUI {
 
         list_layout.add_child(
             Panel::new(
-                user_playlists
-                    .with_name("user_playlists")
-                    .scrollable()
-                    .scroll_y(true)
-                    .resized(SizeConstraint::Full, SizeConstraint::Free),
+                LinearLayout::horizontal()
+                    .child(
+                        user_playlists
+                            .with_name("user_playlists")
+                            .scrollable()
+                            .scroll_y(true)
+                            .resized(SizeConstraint::Full, SizeConstraint::Free),
+                    )
+                    .child(Button::new("New playlist", new_playlist)),
             )
             .title("my playlists"),
         );
@@ -285,6 +713,95 @@ impl CursiveUI {
         list_layout.with_name("user_playlist_layout")
     }
 
+    pub async fn purchases(&self) -> LinearLayout {
+        let mut layout = LinearLayout::new(Orientation::Vertical);
+
+        let purchases = player::user_purchases().await;
+
+        if purchases.albums.is_empty() && purchases.tracks.is_empty() {
+            layout.add_child(Panel::new(TextView::new("No purchases found.")).title("purchases"));
+
+            return layout;
+        }
+
+        if !purchases.albums.is_empty() {
+            let mut albums: SelectView<String> = SelectView::new();
+
+            for a in &purchases.albums {
+                albums.add_item(a.list_item(), a.id.clone());
+            }
+
+            albums.set_on_submit(move |s: &mut Cursive, item: &String| {
+                let id = item.clone();
+                tokio::spawn(async move { CONTROLS.play_album(id).await });
+                show_player(s);
+            });
+
+            layout.add_child(
+                Panel::new(
+                    albums
+                        .scrollable()
+                        .scroll_y(true)
+                        .resized(SizeConstraint::Full, SizeConstraint::Full),
+                )
+                .title("purchased albums"),
+            );
+        }
+
+        if !purchases.tracks.is_empty() {
+            let mut tracks: SelectView<i32> = SelectView::new();
+
+            for t in &purchases.tracks {
+                tracks.add_item(t.list_item(), t.id as i32);
+            }
+
+            tracks.set_on_submit(move |s: &mut Cursive, item: &i32| {
+                submit_track(s, (*item, None));
+            });
+
+            layout.add_child(
+                Panel::new(
+                    tracks
+                        .scrollable()
+                        .scroll_y(true)
+                        .resized(SizeConstraint::Full, SizeConstraint::Full),
+                )
+                .title("purchased tracks"),
+            );
+        }
+
+        layout
+    }
+
+    fn favorites(&self) -> LinearLayout {
+        let favorites_type = SelectView::new()
+            .item_str("Albums")
+            .item_str("Tracks")
+            .item_str("Artists")
+            .on_submit(move |s: &mut Cursive, item: &String| {
+                load_favorites(item, s);
+            })
+            .popup()
+            .with_name("favorites_type")
+            .wrap_with(Panel::new)
+            .full_width();
+
+        let favorites_results: SelectView<String> = SelectView::new();
+
+        LinearLayout::new(Orientation::Vertical)
+            .child(favorites_type)
+            .child(
+                Panel::new(
+                    favorites_results
+                        .with_name("favorites_results")
+                        .scrollable()
+                        .scroll_y(true)
+                        .resized(SizeConstraint::Full, SizeConstraint::Full),
+                )
+                .title("favorites"),
+            )
+    }
+
     fn search(&mut self) -> LinearLayout {
         let mut layout = LinearLayout::new(Orientation::Vertical);
 
@@ -300,35 +817,88 @@ impl CursiveUI {
             .on_submit(on_submit)
             .popup()
             .with_name("search_type")
-            .wrap_with(Panel::new);
+            .wrap_with(Panel::new)
+            .full_width();
+
+        let mut search_sort: SelectView<SortOrder> = SelectView::new().popup();
 
-        let search_form = EditView::new()
-            .on_submit_mut(move |_, item| {
-                let item = item.to_string();
+        for order in SortOrder::ALL {
+            search_sort.add_item(order.as_str(), order);
+        }
 
-                tokio::spawn(async move {
-                    let results = player::search(&item).await;
+        search_sort.set_on_submit(move |s: &mut Cursive, order: &SortOrder| {
+            let result_type = s
+                .find_name::<SelectView>("search_type")
+                .and_then(|view| view.selection());
 
-                    SINK.get()
-                        .unwrap()
-                        .send(Box::new(move |s| {
-                            s.set_user_data(results);
+            if let Some(result_type) = result_type {
+                let order = *order;
+                block_on(async { db::set_search_sort(&result_type, order).await });
 
-                            if let Some(view) = s.find_name::<SelectView>("search_type") {
-                                if let Some(value) = view.selection() {
-                                    load_search_results(&value, s);
-                                }
-                            }
-                        }))
-                        .expect("failed to send update");
-                });
-            })
-            .wrap_with(Panel::new);
+                load_search_results(&result_type, s);
+            }
+        });
+
+        let search_sort = search_sort
+            .with_name("search_sort")
+            .wrap_with(Panel::new)
+            .title("sort")
+            .full_width();
+
+        *SEARCH_HISTORY.lock().unwrap() = SearchHistory::new(block_on(async {
+            db::get_search_history(HISTORY_LIMIT as i64).await
+        }));
+
+        let search_form = OnEventView::new(
+            EditView::new()
+                .on_submit_mut(search_for)
+                .with_name("search_input"),
+        )
+        .on_pre_event_inner(Key::Up, |view, _| {
+            let term = SEARCH_HISTORY.lock().unwrap().older().map(String::from)?;
+            view.get_mut().set_content(term);
+
+            Some(EventResult::Consumed(None))
+        })
+        .on_pre_event_inner(Key::Down, |view, _| {
+            let mut history = SEARCH_HISTORY.lock().unwrap();
+
+            // Not going through the history, so move on to the next field as usual.
+            if !history.is_recalling() {
+                return None;
+            }
+
+            let term = history.newer().map(String::from).unwrap_or_default();
+            view.get_mut().set_content(term);
+
+            Some(EventResult::Consumed(None))
+        })
+        .wrap_with(Panel::new);
+
+        let mut recent_searches: SelectView<String> = SelectView::new();
+        recent_searches.add_all_str(SEARCH_HISTORY.lock().unwrap().terms().to_vec());
+        recent_searches.set_on_submit(|s: &mut Cursive, term: &String| {
+            set_search_input(s, term);
+            search_for(s, term);
+        });
+
+        let recent_searches = Panel::new(
+            recent_searches
+                .with_name("recent_searches")
+                .scrollable()
+                .max_height(RECENT_SEARCH_ROWS),
+        )
+        .title("recent");
 
         let search_results: SelectView<String> = SelectView::new();
 
         layout.add_child(search_form.title("search"));
-        layout.add_child(search_type);
+        layout.add_child(recent_searches);
+        layout.add_child(
+            LinearLayout::horizontal()
+                .child(search_type)
+                .child(search_sort),
+        );
 
         layout.add_child(
             Panel::new(
@@ -355,15 +925,25 @@ impl CursiveUI {
         panel
     }
 
-    fn enter_url<F>(callback: F) -> NamedView<OnEventView<ResizedView<Panel<EditView>>>>
+    fn enter_url<F>(callback: F) -> NamedView<OnEventView<ResizedView<Panel<LinearLayout>>>>
     where
         F: Fn(&mut Cursive, &str) + 'static,
     {
         let mut input = EditView::new();
 
         input.set_on_submit(callback);
+        // A new attempt clears the complaint about the last one.
+        input.set_on_edit(|s, _, _| {
+            s.call_on_name("url_feedback", |view: &mut TextView| view.set_content(""));
+        });
+
+        let layout = LinearLayout::new(Orientation::Vertical).child(input).child(
+            TextView::new("")
+                .style(Style::from(Color::Dark(BaseColor::Red)))
+                .with_name("url_feedback"),
+        );
 
-        let panel = OnEventView::new(Panel::new(input).title("Enter URL").full_width());
+        let panel = OnEventView::new(Panel::new(layout).title("Enter URL").full_width());
 
         panel.with_name("event_url")
     }
@@ -373,15 +953,48 @@ impl CursiveUI {
 
         let open = Arc::new(move |s: &mut Cursive| {
             let mut panel = CursiveUI::enter_url(move |s, url| {
-                let u = url.to_string();
-                tokio::spawn(async move { CONTROLS.play_uri(u).await });
-                s.pop_layer();
-                ENTER_URL_OPEN.store(false, Ordering::Relaxed);
-            });
+                let (action, loading) = match parse_qobuz_uri(url) {
+                    Some(action) => {
+                        let loading = match &action {
+                            Action::PlayAlbum { album_id } => format!("album {album_id}"),
+                            Action::PlayTrack { track_id, .. } => format!("track {track_id}"),
+                            Action::PlayPlaylist { playlist_id } => {
+                                format!("playlist {playlist_id}")
+                            }
+                            _ => "link".to_string(),
+                        };
 
-            panel
-                .get_mut()
-                .set_on_pre_event(Event::Key(Key::Esc), move |s| {
+                        (action, loading)
+                    }
+                    None => {
+                        let message = if url.trim().is_empty() {
+                            "Paste a Qobuz album, track or playlist link."
+                        } else {
+                            "Not a Qobuz album, track or playlist link."
+                        };
+
+                        s.call_on_name("url_feedback", |view: &mut TextView| {
+                            view.set_content(message);
+                        });
+                        return;
+                    }
+                };
+
+                tokio::spawn(async move { CONTROLS.send(action).await });
+                s.pop_layer();
+                ENTER_URL_OPEN.store(false, Ordering::Relaxed);
+
+                // Playback starting, or failing, puts the plain title back.
+                s.call_on_name("player_panel", |panel: &mut Panel<LinearLayout>| {
+                    panel.set_title(format!("player - loading {loading}"));
+                });
+                LOADING_URL.store(true, Ordering::Relaxed);
+                s.set_screen(0);
+            });
+
+            panel
+                .get_mut()
+                .set_on_pre_event(Event::Key(Key::Esc), move |s| {
                     s.pop_layer();
                     ENTER_URL_OPEN.store(false, Ordering::Relaxed);
                 });
@@ -392,7 +1005,7 @@ impl CursiveUI {
                     2,
                     2,
                     2,
-                    panel.resized(SizeConstraint::Full, SizeConstraint::Fixed(3)),
+                    panel.resized(SizeConstraint::Full, SizeConstraint::Fixed(4)),
                 )
                 .full_width(),
                 ColorStyle::highlight_inactive(),
@@ -434,6 +1047,26 @@ impl CursiveUI {
                 s.set_screen(2);
             })
             .add_delimiter()
+            .add_leaf("Purchases", move |s| {
+                if ENTER_URL_OPEN.load(Ordering::Relaxed) {
+                    s.pop_layer();
+                    ENTER_URL_OPEN.store(false, Ordering::Relaxed);
+                }
+
+                s.set_screen(3);
+            })
+            .add_delimiter()
+            .add_leaf("Favorites", move |s| {
+                if ENTER_URL_OPEN.load(Ordering::Relaxed) {
+                    s.pop_layer();
+                    ENTER_URL_OPEN.store(false, Ordering::Relaxed);
+                }
+
+                show_favorites(s);
+            })
+            .add_delimiter()
+            .add_leaf("Editorial", show_editorial)
+            .add_delimiter()
             .add_leaf("Enter URL", move |s| {
                 if !ENTER_URL_OPEN.load(Ordering::Relaxed) {
                     o(s);
@@ -441,11 +1074,11 @@ impl CursiveUI {
             });
 
         let o = open.clone();
-        self.root.add_global_callback('4', move |s| {
+        self.bind(KeyAction::EnterUrl, move |s| {
             o(s);
         });
 
-        self.root.add_global_callback('1', move |s| {
+        self.bind(KeyAction::NowPlaying, move |s| {
             if ENTER_URL_OPEN.load(Ordering::Relaxed) {
                 s.pop_layer();
                 ENTER_URL_OPEN.store(false, Ordering::Relaxed);
@@ -454,7 +1087,7 @@ impl CursiveUI {
             s.set_screen(0);
         });
 
-        self.root.add_global_callback('2', move |s| {
+        self.bind(KeyAction::MyPlaylists, move |s| {
             if ENTER_URL_OPEN.load(Ordering::Relaxed) {
                 s.pop_layer();
                 ENTER_URL_OPEN.store(false, Ordering::Relaxed);
@@ -463,7 +1096,7 @@ impl CursiveUI {
             s.set_screen(1);
         });
 
-        self.root.add_global_callback('3', move |s| {
+        self.bind(KeyAction::Search, move |s| {
             if ENTER_URL_OPEN.load(Ordering::Relaxed) {
                 s.pop_layer();
                 ENTER_URL_OPEN.store(false, Ordering::Relaxed);
@@ -471,12 +1104,34 @@ impl CursiveUI {
 
             s.set_screen(2);
         });
+
+        self.bind(KeyAction::Purchases, move |s| {
+            if ENTER_URL_OPEN.load(Ordering::Relaxed) {
+                s.pop_layer();
+                ENTER_URL_OPEN.store(false, Ordering::Relaxed);
+            }
+
+            s.set_screen(3);
+        });
+
+        self.bind(KeyAction::Favorites, move |s| {
+            if ENTER_URL_OPEN.load(Ordering::Relaxed) {
+                s.pop_layer();
+                ENTER_URL_OPEN.store(false, Ordering::Relaxed);
+            }
+
+            show_favorites(s);
+        });
+
+        self.bind(KeyAction::RefreshFavorites, refresh_favorites);
     }
 
     pub async fn run(&mut self) {
         let player = self.player();
         let search = self.search();
         let my_playlists = self.my_playlists().await;
+        let purchases = self.purchases().await;
+        let favorites = self.favorites();
 
         self.root
             .screen_mut()
@@ -510,7 +1165,46 @@ impl CursiveUI {
                 search.resized(SizeConstraint::Full, SizeConstraint::Free),
             ));
 
-        self.root.set_screen(0);
+        self.root.add_active_screen();
+        self.root
+            .screen_mut()
+            .add_fullscreen_layer(PaddedView::lrtb(
+                0,
+                0,
+                1,
+                0,
+                purchases.resized(SizeConstraint::Full, SizeConstraint::Free),
+            ));
+
+        self.root.add_active_screen();
+        self.root
+            .screen_mut()
+            .add_fullscreen_layer(PaddedView::lrtb(
+                0,
+                0,
+                1,
+                0,
+                favorites.resized(SizeConstraint::Full, SizeConstraint::Free),
+            ));
+
+        let default_screen = Screen::from(db::get_default_screen().await);
+        self.root.set_screen(default_screen as usize);
+
+        if default_screen == Screen::Favorites {
+            refresh_favorites(&mut self.root);
+        }
+
+        if player::is_offline().await {
+            self.root
+                .call_on_name("player_panel", |panel: &mut Panel<LinearLayout>| {
+                    panel.set_title("player - offline");
+                });
+            self.root.add_layer(offline_dialog());
+        } else {
+            tokio::spawn(show_account());
+        }
+
+        self.keys = keys::resolve(db::get_keybindings().await);
 
         self.menubar();
         self.global_events();
@@ -530,14 +1224,245 @@ impl Default for CursiveUI {
 
 type ResultsPanel = ScrollView<NamedView<SelectView<(i32, Option<String>)>>>;
 
+fn show_favorites(s: &mut Cursive) {
+    s.set_screen(Screen::Favorites as usize);
+
+    if FAVORITES.lock().unwrap().is_none() {
+        refresh_favorites(s);
+    }
+}
+
+fn new_playlist(s: &mut Cursive) {
+    let form = LinearLayout::vertical()
+        .child(TextView::new("Name"))
+        .child(
+            EditView::new()
+                .with_name("new_playlist_name")
+                .fixed_width(40),
+        )
+        .child(TextView::new("Description (optional)"))
+        .child(
+            EditView::new()
+                .with_name("new_playlist_description")
+                .fixed_width(40),
+        )
+        .child(
+            LinearLayout::horizontal()
+                .child(Checkbox::new().with_name("new_playlist_public"))
+                .child(TextView::new(" Public")),
+        );
+
+    let dialog = Dialog::around(form)
+        .title("New playlist")
+        .button("Create", create_playlist)
+        .dismiss_button("Cancel");
+
+    s.add_layer(dialog);
+}
+
+fn create_playlist(s: &mut Cursive) {
+    let name = s
+        .call_on_name("new_playlist_name", |view: &mut EditView| {
+            view.get_content()
+        })
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default();
+
+    if name.is_empty() {
+        s.add_layer(Dialog::info("Enter a name for the playlist."));
+        return;
+    }
+
+    let description = s
+        .call_on_name("new_playlist_description", |view: &mut EditView| {
+            view.get_content()
+        })
+        .map(|description| description.trim().to_string())
+        .filter(|description| !description.is_empty());
+
+    let public = s
+        .call_on_name("new_playlist_public", |view: &mut Checkbox| {
+            view.is_checked()
+        })
+        .unwrap_or(false);
+
+    s.pop_layer();
+
+    match block_on(async { player::create_playlist(name.clone(), public, description).await }) {
+        Some(playlist) => {
+            let playlists = block_on(async { player::user_playlists().await });
+
+            s.call_on_name("user_playlists", |view: &mut SelectView<u32>| {
+                view.clear();
+                view.add_item("Select Playlist", 0);
+
+                for p in &playlists {
+                    view.add_item(p.title.clone(), p.id);
+                }
+
+                if let Some(index) = playlists.iter().position(|p| p.id == playlist.id) {
+                    view.set_selection(index + 1);
+                }
+            });
+        }
+        None => {
+            s.add_layer(Dialog::info(format!("Could not create playlist {name}.")));
+        }
+    }
+}
+
+fn add_to_playlist(s: &mut Cursive, track: Track) {
+    let playlists = block_on(async { player::user_playlists().await });
+
+    if playlists.is_empty() {
+        s.add_layer(Dialog::info("You don't have any playlists yet."));
+        return;
+    }
+
+    let mut list: SelectView<u32> = SelectView::new();
+
+    for p in &playlists {
+        list.add_item(p.title.clone(), p.id);
+    }
+
+    list.set_on_submit(move |s: &mut Cursive, playlist_id: &u32| {
+        s.pop_layer();
+
+        let playlist = playlists
+            .iter()
+            .find(|p| p.id == *playlist_id)
+            .map(|p| p.title.clone())
+            .unwrap_or_default();
+
+        match block_on(async { player::add_track_to_playlist(*playlist_id, track.id).await }) {
+            Some(true) => show_toast(s, format!("Added {} to {playlist}.", track.title)),
+            Some(false) => s.add_layer(Dialog::info(format!(
+                "{} is already in {playlist}.",
+                track.title
+            ))),
+            None => s.add_layer(Dialog::info(format!(
+                "Could not add {} to {playlist}.",
+                track.title
+            ))),
+        }
+    });
+
+    s.add_layer(
+        Dialog::around(list.scrollable().max_height(20))
+            .title(format!("Add {} to playlist", track.title))
+            .dismiss_button("Cancel"),
+    );
+}
+
+/// A message that goes away on its own after a couple of seconds.
+fn show_toast(s: &mut Cursive, message: String) {
+    s.add_layer(Dialog::text(message).with_name("toast"));
+
+    tokio::spawn(async {
+        tokio::time::sleep(TOAST_DURATION).await;
+
+        SINK.get()
+            .unwrap()
+            .send(Box::new(|s| {
+                if let Some(position) = s.screen_mut().find_layer_from_name("toast") {
+                    s.screen_mut().remove_layer(position);
+                }
+            }))
+            .expect("failed to send update");
+    });
+}
+
+fn refresh_favorites(s: &mut Cursive) {
+    let favorites = block_on(async { player::favorites().await });
+    *FAVORITES.lock().unwrap() = Some(favorites);
+
+    let item = s
+        .find_name::<SelectView>("favorites_type")
+        .and_then(|view| view.selection());
+
+    if let Some(item) = item {
+        load_favorites(&item, s);
+    }
+}
+
+fn load_favorites(item: &str, s: &mut Cursive) {
+    let favorites = FAVORITES.lock().unwrap().clone().unwrap_or_default();
+
+    if let Some(mut results) = s.find_name::<SelectView>("favorites_results") {
+        results.clear();
+
+        match item {
+            "Albums" => {
+                for a in &favorites.albums {
+                    let id = if a.available {
+                        a.id.clone()
+                    } else {
+                        UNSTREAMABLE.to_string()
+                    };
+
+                    results.add_item(a.list_item(), id);
+                }
+
+                results.set_on_submit(move |s: &mut Cursive, item: &String| {
+                    if item != UNSTREAMABLE {
+                        let id = item.clone();
+                        tokio::spawn(async move { CONTROLS.play_album(id).await });
+                        show_player(s);
+                    }
+                });
+            }
+            "Tracks" => {
+                for t in &favorites.tracks {
+                    let id = if t.available {
+                        t.id.to_string()
+                    } else {
+                        UNSTREAMABLE.to_string()
+                    };
+
+                    results.add_item(t.list_item(), id);
+                }
+
+                results.set_on_submit(move |s: &mut Cursive, item: &String| {
+                    if item != UNSTREAMABLE {
+                        let id = item.parse::<i32>().expect("failed to parse string");
+                        tokio::spawn(async move { CONTROLS.play_track(id).await });
+                        show_player(s);
+                    }
+                });
+            }
+            "Artists" => {
+                for a in &favorites.artists {
+                    results.add_item(a.name.clone(), a.id.to_string());
+                }
+
+                results.set_on_submit(move |s: &mut Cursive, item: &String| {
+                    submit_artist(s, item.parse::<i32>().expect("failed to parse string"));
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
 fn load_search_results(item: &str, s: &mut Cursive) {
+    let order = block_on(async { db::get_search_sort(item).await });
+
+    s.call_on_name("search_sort", |view: &mut SelectView<SortOrder>| {
+        if let Some(index) = SortOrder::ALL.iter().position(|o| *o == order) {
+            view.set_selection(index);
+        }
+    });
+
     if let Some(mut search_results) = s.find_name::<SelectView>("search_results") {
         search_results.clear();
 
         if let Some(data) = s.user_data::<SearchResults>() {
             match item {
                 "Albums" => {
-                    for a in &data.albums {
+                    let mut albums = data.albums.clone();
+                    sort_results(&mut albums, order);
+
+                    for a in &albums {
                         let id = if a.available {
                             a.id.clone()
                         } else {
@@ -555,7 +1480,10 @@ fn load_search_results(item: &str, s: &mut Cursive) {
                     });
                 }
                 "Artists" => {
-                    for a in &data.artists {
+                    let mut artists = data.artists.clone();
+                    sort_results(&mut artists, order);
+
+                    for a in &artists {
                         search_results.add_item(a.name.clone(), a.id.to_string());
                     }
 
@@ -564,7 +1492,10 @@ fn load_search_results(item: &str, s: &mut Cursive) {
                     });
                 }
                 "Tracks" => {
-                    for t in &data.tracks {
+                    let mut tracks = data.tracks.clone();
+                    sort_results(&mut tracks, order);
+
+                    for t in &tracks {
                         let id = if t.available {
                             t.id.to_string()
                         } else {
@@ -584,7 +1515,10 @@ fn load_search_results(item: &str, s: &mut Cursive) {
                     });
                 }
                 "Playlists" => {
-                    for p in &data.playlists {
+                    let mut playlists = data.playlists.clone();
+                    sort_results(&mut playlists, order);
+
+                    for p in &playlists {
                         search_results.add_item(p.title.clone(), p.id.to_string())
                     }
 
@@ -592,6 +1526,7 @@ fn load_search_results(item: &str, s: &mut Cursive) {
                         let layout = submit_playlist(
                             s,
                             item.parse::<u32>().expect("failed to parse string"),
+                            true,
                         );
 
                         let event_panel =
@@ -608,7 +1543,9 @@ fn load_search_results(item: &str, s: &mut Cursive) {
     }
 }
 
-fn submit_playlist(_s: &mut Cursive, item: u32) -> LinearLayout {
+/// The tracks of a playlist with a button to play it, and one to follow it
+/// when `followable`.
+fn submit_playlist(_s: &mut Cursive, item: u32, followable: bool) -> LinearLayout {
     let mut layout = LinearLayout::vertical();
 
     let playlist_tracks = block_on(async { player::playlist_tracks(item as i64).await });
@@ -642,15 +1579,19 @@ fn submit_playlist(_s: &mut Cursive, item: u32) -> LinearLayout {
         submit_track(s, item.clone());
     });
 
-    let meta = LinearLayout::horizontal()
-        .child(Button::new("play", move |_s| {
-            tokio::spawn(async move { CONTROLS.play_playlist(item as i64).await });
-        }))
-        .child(
-            TextView::new(format!("total tracks: {}", playlist_tracks.len()))
-                .h_align(HAlign::Right)
-                .full_width(),
-        );
+    let mut meta = LinearLayout::horizontal().child(Button::new("play", move |_s| {
+        tokio::spawn(async move { CONTROLS.play_playlist(item as i64).await });
+    }));
+
+    if followable {
+        meta.add_child(follow_button(item));
+    }
+
+    meta.add_child(
+        TextView::new(format!("total tracks: {}", playlist_tracks.len()))
+            .h_align(HAlign::Right)
+            .full_width(),
+    );
 
     layout.add_child(meta);
     layout.add_child(list);
@@ -658,159 +1599,1001 @@ fn submit_playlist(_s: &mut Cursive, item: u32) -> LinearLayout {
     layout
 }
 
-fn submit_artist(s: &mut Cursive, item: i32) {
-    let artist_albums = block_on(async { player::artist_albums(item).await });
-
-    if !artist_albums.is_empty() {
-        let mut tree = cursive::menu::Tree::new();
+/// Follows a playlist into my playlists, or stops following it.
+fn follow_button(playlist_id: u32) -> NamedView<Button> {
+    let followed = block_on(async { player::user_playlists().await })
+        .iter()
+        .any(|p| p.id == playlist_id);
+    let label = follow_label(followed);
+    let followed = Arc::new(AtomicBool::new(followed));
 
-        for a in artist_albums {
-            if !a.available {
-                continue;
-            }
+    Button::new(label, move |s| {
+        let follow = !followed.load(Ordering::Relaxed);
 
-            tree.add_leaf(a.list_item(), move |s: &mut Cursive| {
-                let id = a.id.clone();
-                tokio::spawn(async move { CONTROLS.play_album(id).await });
+        if block_on(async { player::set_playlist_followed(playlist_id, follow).await }) {
+            followed.store(follow, Ordering::Relaxed);
 
-                s.call_on_name(
-                    "screens",
-                    |screens: &mut ScreensView<ResizedView<LinearLayout>>| {
-                        screens.set_active_screen(0);
-                    },
-                );
+            s.call_on_name("follow_button", |button: &mut Button| {
+                button.set_label(follow_label(follow));
             });
+
+            refresh_user_playlists(s);
+        } else {
+            s.add_layer(Dialog::info("Could not update the playlist."));
         }
+    })
+    .with_name("follow_button")
+}
+
+fn follow_label(followed: bool) -> &'static str {
+    if followed {
+        "unfollow"
+    } else {
+        "follow"
+    }
+}
 
-        let album_list: MenuPopup = MenuPopup::new(Rc::new(tree));
+/// Reload my playlists, after one was added or taken away elsewhere.
+fn refresh_user_playlists(s: &mut Cursive) {
+    let playlists = block_on(async { player::user_playlists().await });
 
-        let events = album_list
-            .scrollable()
-            .resized(SizeConstraint::Full, SizeConstraint::Free);
+    s.call_on_name("user_playlists", |view: &mut SelectView<u32>| {
+        view.clear();
+        view.add_item("Select Playlist", 0);
 
-        s.screen_mut().add_layer(events);
-    }
+        for p in &playlists {
+            view.add_item(p.title.clone(), p.id);
+        }
+    });
 }
 
-fn submit_track(s: &mut Cursive, item: (i32, Option<String>)) {
-    if item.0 == -1 {
-        return;
-    }
+fn submit_artist(s: &mut Cursive, item: i32) {
+    let (artist_albums, artist_playlists) = block_on(async {
+        (
+            player::artist_albums(item).await,
+            player::artist_playlists(item).await.unwrap_or_default(),
+        )
+    });
 
-    if item.1.is_none() {
-        tokio::spawn(async move { CONTROLS.play_track(item.0).await });
+    // Albums credited to someone else are ones the artist only appears on.
+    let (albums, appears_on): (Vec<Album>, Vec<Album>) = artist_albums
+        .into_iter()
+        .filter(|a| a.available)
+        .partition(|a| a.artist.id == item as u32);
+
+    let catalog = Rc::new(ArtistCatalog {
+        artist_id: item,
+        albums,
+        appears_on,
+        playlists: artist_playlists,
+    });
 
-        s.call_on_name(
-            "screens",
-            |screens: &mut ScreensView<ResizedView<LinearLayout>>| {
-                screens.set_active_screen(0);
-            },
-        );
+    let tree = catalog.tree("");
+
+    if tree.is_empty() {
         return;
     }
 
-    let track = move |s: &mut Cursive| {
-        s.screen_mut().pop_layer();
+    // Qobuz search is global, so the artist's catalog is filtered locally.
+    let filter = EditView::new()
+        .on_edit(move |s, text, _| {
+            let tree = catalog.tree(text);
 
-        tokio::spawn(async move { CONTROLS.play_track(item.0).await });
+            s.call_on_name("artist_catalog", |view: &mut MenuPopup| {
+                *view = MenuPopup::new(Rc::new(tree));
+            });
+        })
+        .wrap_with(Panel::new)
+        .title("filter");
 
-        s.call_on_name(
-            "screens",
-            |screens: &mut ScreensView<ResizedView<LinearLayout>>| {
-                screens.set_active_screen(0);
-            },
-        );
-    };
+    let album_list = MenuPopup::new(Rc::new(tree))
+        .with_name("artist_catalog")
+        .scrollable();
 
-    let album = move |s: &mut Cursive| {
-        s.screen_mut().pop_layer();
+    let layout = LinearLayout::vertical()
+        .child(filter)
+        .child(album_list)
+        .resized(SizeConstraint::Full, SizeConstraint::Free);
 
-        if let Some(album_id) = &item.1 {
-            let a = album_id.clone();
-            tokio::spawn(async move { CONTROLS.play_album(a).await });
+    s.screen_mut().add_layer(layout);
+}
 
-            s.call_on_name(
-                "screens",
-                |screens: &mut ScreensView<ResizedView<LinearLayout>>| {
-                    screens.set_active_screen(0);
-                },
-            );
+/// Everything fetched for an artist, so it can be filtered without another request.
+struct ArtistCatalog {
+    artist_id: i32,
+    albums: Vec<Album>,
+    appears_on: Vec<Album>,
+    playlists: Vec<Playlist>,
+}
+
+impl ArtistCatalog {
+    /// Builds the artist menu, keeping only entries whose title contains `filter`.
+    fn tree(&self, filter: &str) -> cursive::menu::Tree {
+        let filter = filter.trim().to_lowercase();
+        let matches = |title: &str| filter.is_empty() || title.to_lowercase().contains(&filter);
+
+        let mut tree = cursive::menu::Tree::new();
+
+        for a in self.albums.iter().filter(|a| matches(&a.title)) {
+            tree.add_leaf(a.list_item(), album_leaf(a.id.clone()));
         }
-    };
 
-    let mut album_or_track = Dialog::text("Track or album?")
-        .button("Track", track)
-        .button("Album", album)
-        .dismiss_button("Cancel")
-        .wrap_with(OnEventView::new);
+        let mut subtree = cursive::menu::Tree::new();
 
-    album_or_track.set_on_pre_event(Event::Key(Key::Esc), |s| {
-        s.screen_mut().pop_layer();
-    });
+        for a in self.appears_on.iter().filter(|a| matches(&a.title)) {
+            subtree.add_leaf(a.list_item(), album_leaf(a.id.clone()));
+        }
 
-    s.screen_mut().add_layer(album_or_track);
-}
+        if !subtree.is_empty() {
+            tree.add_subtree("Appears on", subtree);
+        }
 
-fn set_current_track(s: &mut Cursive, track: &Track, lt: &TrackListType) {
-    if let (Some(mut track_num), Some(mut track_title), Some(mut progress)) = (
-        s.find_name::<TextView>("current_track_number"),
-        s.find_name::<TextView>("current_track_title"),
-        s.find_name::<ProgressBar>("progress"),
-    ) {
-        match lt {
-            TrackListType::Album => {
-                track_num.set_content(format!("{:03}", track.number));
-            }
-            TrackListType::Playlist => {
-                track_num.set_content(format!("{:03}", track.position));
-            }
-            TrackListType::Track => {
-                track_num.set_content(format!("{:03}", track.number));
-            }
-            TrackListType::Unknown => {
-                track_num.set_content(format!("{:03}", track.position));
-            }
-        };
+        let mut subtree = cursive::menu::Tree::new();
 
-        track_title.set_content(track.title.trim());
-        progress.set_max(track.duration_seconds as usize);
-    }
+        for p in self.playlists.iter().filter(|p| matches(&p.title)) {
+            let id = p.id as i64;
 
-    if let Some(artist) = &track.artist {
-        s.call_on_name("artist_name", |view: &mut TextView| {
-            view.set_content(artist.name.clone());
+            subtree.add_leaf(p.title.clone(), move |s: &mut Cursive| {
+                tokio::spawn(async move { CONTROLS.play_playlist(id).await });
+                show_player(s);
+            });
+        }
+
+        if !subtree.is_empty() {
+            tree.add_subtree("Playlists", subtree);
+        }
+
+        let artist_id = self.artist_id;
+        tree.add_leaf("Similar artists", move |s: &mut Cursive| {
+            show_similar_artists(s, artist_id);
+        });
+
+        tree
+    }
+}
+
+fn show_similar_artists(s: &mut Cursive, artist_id: i32) {
+    let artists = block_on(async { player::similar_artists(artist_id).await });
+
+    if artists.is_empty() {
+        s.add_layer(Dialog::info("No similar artists found."));
+        return;
+    }
+
+    let mut tree = cursive::menu::Tree::new();
+
+    for a in artists {
+        let id = a.id as i32;
+
+        tree.add_leaf(a.name, move |s: &mut Cursive| {
+            submit_artist(s, id);
         });
     }
 
-    if let (Some(mut bit_depth), Some(mut sample_rate)) = (
-        s.find_name::<TextView>("bit_depth"),
-        s.find_name::<TextView>("sample_rate"),
-    ) {
-        bit_depth.set_content(format!("{} bits", track.bit_depth));
-        sample_rate.set_content(format!("{} kHz", track.sampling_rate));
+    let artist_list = MenuPopup::new(Rc::new(tree))
+        .scrollable()
+        .resized(SizeConstraint::Full, SizeConstraint::Free);
+
+    s.screen_mut().add_layer(artist_list);
+}
+
+fn show_help(s: &mut Cursive, help_key: char) {
+    let mut help = StyledString::new();
+    let mut bindings = KEY_BINDINGS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|b| (b.category, b.key.clone(), b.action))
+        .collect::<Vec<(KeyCategory, String, &str)>>();
+    bindings.sort_by_key(|(category, _, _)| *category);
+
+    let mut current = None;
+
+    for (category, key, action) in bindings {
+        if current != Some(category) {
+            if current.is_some() {
+                help.append_plain("\n");
+            }
+
+            help.append_styled(format!("{}\n", category.label()), Effect::Bold);
+            current = Some(category);
+        }
+
+        help.append_styled(format!("{key:>8}  "), Effect::Dim);
+        help.append_plain(format!("{action}\n"));
+    }
+
+    let dialog = Dialog::around(TextView::new(help).scrollable())
+        .title("keys")
+        .dismiss_button("Close")
+        .max_height(30);
+
+    s.add_layer(
+        OnEventView::new(dialog)
+            .on_pre_event(Event::Key(Key::Esc), |s| {
+                s.pop_layer();
+            })
+            .on_pre_event(help_key, |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn show_lyrics(s: &mut Cursive, track: &Track) {
+    let lyrics = block_on(async { player::track_lyrics(track.id as i32).await });
+
+    let text = lyrics
+        .filter(|lyrics| !lyrics.is_empty())
+        .unwrap_or_else(|| "No lyrics available for this track.".to_string());
+
+    s.add_layer(
+        Dialog::around(
+            TextView::new(text)
+                .scrollable()
+                .max_height(30)
+                .max_width(80),
+        )
+        .title(track.title.trim())
+        .dismiss_button("Close"),
+    );
+}
+
+fn offline_dialog() -> Dialog {
+    Dialog::text("Unable to reach Qobuz. Playback and browsing won't work until you log in.")
+        .title("offline")
+        .button("Retry login", move |s: &mut Cursive| {
+            s.pop_layer();
+            tokio::spawn(async { CONTROLS.retry_login().await });
+        })
+        .dismiss_button("Continue offline")
+}
+
+fn show_editorial(s: &mut Cursive) {
+    let articles = block_on(async { player::editorial_content().await }).unwrap_or_default();
+
+    if articles.is_empty() {
+        s.add_layer(Dialog::info("No editorial content available."));
+        return;
+    }
+
+    let mut list: SelectView<Option<ArticleLink>> = SelectView::new();
+
+    for a in articles {
+        // Articles without an album or playlist to play are shown dimmed.
+        let style = if a.link.is_some() {
+            Style::none()
+        } else {
+            Style::from(Effect::Dim)
+        };
+
+        let mut row = StyledString::styled(a.title, style.combine(Effect::Bold));
+
+        if let Some(description) = a.description {
+            row.append_styled(format!(" - {description}"), style.combine(Effect::Dim));
+        }
+
+        list.add_item(row, a.link);
+    }
+
+    list.set_on_submit(
+        move |s: &mut Cursive, item: &Option<ArticleLink>| match item.clone() {
+            Some(ArticleLink::Album(id)) => {
+                tokio::spawn(async move { CONTROLS.play_album(id).await });
+                s.pop_layer();
+                show_player(s);
+            }
+            Some(ArticleLink::Playlist(id)) => {
+                tokio::spawn(async move { CONTROLS.play_playlist(id).await });
+                s.pop_layer();
+                show_player(s);
+            }
+            None => {}
+        },
+    );
+
+    let events = OnEventView::new(list.scrollable().scroll_y(true)).on_event(
+        Event::Key(Key::Esc),
+        move |s| {
+            s.pop_layer();
+        },
+    );
+
+    s.add_layer(
+        Panel::new(events)
+            .title("editorial")
+            .resized(SizeConstraint::Full, SizeConstraint::Full),
+    );
+}
+
+fn album_leaf(id: String) -> impl Fn(&mut Cursive) + 'static {
+    move |s: &mut Cursive| {
+        let id = id.clone();
+        tokio::spawn(async move { CONTROLS.play_album(id).await });
+        show_player(s);
+    }
+}
+
+fn show_player(s: &mut Cursive) {
+    s.call_on_name(
+        "screens",
+        |screens: &mut ScreensView<ResizedView<LinearLayout>>| {
+            screens.set_active_screen(0);
+        },
+    );
+}
+
+fn submit_track(s: &mut Cursive, item: (i32, Option<String>)) {
+    if item.0 == -1 {
+        return;
+    }
+
+    if item.1.is_none() {
+        tokio::spawn(async move { CONTROLS.play_track(item.0).await });
+
+        s.call_on_name(
+            "screens",
+            |screens: &mut ScreensView<ResizedView<LinearLayout>>| {
+                screens.set_active_screen(0);
+            },
+        );
+        return;
+    }
+
+    let track = move |s: &mut Cursive| {
+        s.screen_mut().pop_layer();
+
+        tokio::spawn(async move { CONTROLS.play_track(item.0).await });
+
+        s.call_on_name(
+            "screens",
+            |screens: &mut ScreensView<ResizedView<LinearLayout>>| {
+                screens.set_active_screen(0);
+            },
+        );
+    };
+
+    let radio = move |s: &mut Cursive| {
+        s.screen_mut().pop_layer();
+
+        tokio::spawn(async move { CONTROLS.start_radio(item.0).await });
+
+        s.call_on_name(
+            "screens",
+            |screens: &mut ScreensView<ResizedView<LinearLayout>>| {
+                screens.set_active_screen(0);
+            },
+        );
+    };
+
+    let from_here = item.clone();
+
+    let album = move |s: &mut Cursive| {
+        s.screen_mut().pop_layer();
+
+        if let Some(album_id) = &item.1 {
+            let a = album_id.clone();
+            tokio::spawn(async move { CONTROLS.play_album(a).await });
+
+            s.call_on_name(
+                "screens",
+                |screens: &mut ScreensView<ResizedView<LinearLayout>>| {
+                    screens.set_active_screen(0);
+                },
+            );
+        }
+    };
+
+    let album_from_here = move |s: &mut Cursive| {
+        s.screen_mut().pop_layer();
+
+        if let Some(album_id) = &from_here.1 {
+            let (a, track_id) = (album_id.clone(), from_here.0);
+            tokio::spawn(async move { CONTROLS.play_album_from(a, track_id).await });
+
+            show_player(s);
+        }
+    };
+
+    let mut album_or_track = Dialog::text("Track, radio or album?")
+        .button("Track", track)
+        .button("Radio", radio)
+        .button("Album", album)
+        .button("Album from here", album_from_here)
+        .dismiss_button("Cancel")
+        .wrap_with(OnEventView::new);
+
+    album_or_track.set_on_pre_event(Event::Key(Key::Esc), |s| {
+        s.screen_mut().pop_layer();
+    });
+
+    s.screen_mut().add_layer(album_or_track);
+}
+
+fn is_scroll_event(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Key(Key::Up | Key::Down | Key::PageUp | Key::PageDown | Key::Home | Key::End)
+            | Event::Mouse {
+                event: MouseEvent::WheelUp | MouseEvent::WheelDown | MouseEvent::Hold(_),
+                ..
+            }
+    )
+}
+
+/// Selects the playing track and centers the track list on it, unless the
+/// list was scrolled by hand a moment ago.
+fn follow_current_track(s: &mut Cursive, list: &TrackListValue) {
+    let moved = MOVED_TRACK.lock().unwrap().take();
+
+    if let Some(position) = moved {
+        s.call_on_name(
+            "current_track_list",
+            |view: &mut ScrollView<SelectView<usize>>| {
+                let index = view.get_inner().iter().position(|(_, p)| *p == position);
+
+                if let Some(index) = index {
+                    view.get_inner_mut().set_selection(index);
+                    view.scroll_to_important_area();
+                }
+            },
+        );
+
+        return;
+    }
+
+    let scrolled_recently = LAST_MANUAL_SCROLL
+        .lock()
+        .unwrap()
+        .map(|at| at.elapsed() < MANUAL_SCROLL_GRACE)
+        .unwrap_or(false);
+
+    if scrolled_recently {
+        return;
+    }
+
+    let playing = list
+        .queue
+        .values()
+        .find(|t| t.status == TrackStatus::Playing)
+        .map(|t| t.position as usize);
+
+    if let Some(position) = playing {
+        s.call_on_name(
+            "current_track_list",
+            |view: &mut ScrollView<SelectView<usize>>| {
+                let index = view.get_inner().iter().position(|(_, p)| *p == position);
+
+                if let Some(index) = index {
+                    view.get_inner_mut().set_selection(index);
+
+                    let height = view.content_viewport().height();
+                    let x = view.content_viewport().left();
+                    view.set_offset((x, index.saturating_sub(height / 2)));
+                }
+            },
+        );
+    }
+}
+
+/// Search for `term`, remembering it for next time.
+fn search_for(s: &mut Cursive, term: &str) {
+    let term = term.trim().to_string();
+
+    if SEARCH_HISTORY.lock().unwrap().remember(&term) {
+        let saved = term.clone();
+        tokio::spawn(async move { db::add_search_term(&saved, HISTORY_LIMIT as i64).await });
+    }
+
+    show_recent_searches(s);
+
+    tokio::spawn(async move {
+        let results = player::search(&term).await;
+
+        SINK.get()
+            .unwrap()
+            .send(Box::new(move |s| {
+                s.set_user_data(results);
+
+                if let Some(view) = s.find_name::<SelectView>("search_type") {
+                    if let Some(value) = view.selection() {
+                        load_search_results(&value, s);
+                    }
+                }
+            }))
+            .expect("failed to send update");
+    });
+}
+
+fn set_search_input(s: &mut Cursive, term: &str) {
+    s.call_on_name("search_input", |view: &mut EditView| {
+        view.set_content(term);
+    });
+}
+
+fn show_recent_searches(s: &mut Cursive) {
+    let terms = SEARCH_HISTORY.lock().unwrap().terms().to_vec();
+
+    s.call_on_name("recent_searches", |view: &mut SelectView<String>| {
+        view.clear();
+        view.add_all_str(terms);
+    });
+}
+
+/// Move the selected track in the current track list `step` places.
+fn move_selected_track(s: &mut Cursive, step: i64) {
+    if s.active_screen() != 0 {
+        return;
+    }
+
+    let position = s
+        .find_name::<ScrollView<SelectView<usize>>>("current_track_list")
+        .and_then(|list| list.get_inner().selection().map(|p| *p))
+        .filter(|p| *p != DISC_HEADER);
+
+    if let Some(position) = position {
+        let from = position as u32;
+        let to = (position as i64 + step).max(1) as u32;
+
+        if from != to {
+            *MOVED_TRACK.lock().unwrap() = Some(to as usize);
+            tokio::spawn(async move { CONTROLS.move_track(from, to).await });
+        }
+    }
+}
+
+/// The track selected in the list of the active screen, if there is one.
+fn selected_track(s: &mut Cursive) -> Option<Track> {
+    match s.active_screen() {
+        0 => {
+            let position = s
+                .find_name::<ScrollView<SelectView<usize>>>("current_track_list")
+                .and_then(|list| list.get_inner().selection())?;
+
+            block_on(async { player::current_tracklist().await })
+                .find_track_by_index(*position as u32)
+                .cloned()
+        }
+        1 => {
+            let (track_id, _) = s
+                .find_name::<SelectView<(i32, Option<String>)>>("playlist_items")
+                .and_then(|list| list.selection())?
+                .as_ref()
+                .clone();
+
+            block_on(async { player::track(track_id).await })
+        }
+        2 => {
+            let is_tracks = s
+                .find_name::<SelectView>("search_type")
+                .and_then(|view| view.selection())
+                .map(|selection| selection.as_str() == "Tracks")
+                .unwrap_or(false);
+
+            if !is_tracks {
+                return None;
+            }
+
+            let track_id = s
+                .find_name::<SelectView>("search_results")
+                .and_then(|list| list.selection())?
+                .parse::<i32>()
+                .ok()?;
+
+            block_on(async { player::track(track_id).await })
+        }
+        _ => None,
+    }
+}
+
+/// Load the cover of the track's album into the player panel.
+#[cfg(feature = "cover-art")]
+fn show_cover_art(s: &mut Cursive, track: &Track) {
+    let url = track
+        .cover_art
+        .clone()
+        .or_else(|| track.album.as_ref().and_then(|a| a.cover_art.clone()));
+
+    let changed = s
+        .call_on_name("cover_art", |view: &mut cover_art::CoverArt| {
+            let changed = view.url() != url.as_deref();
+            view.set_url(url.clone());
+            changed
+        })
+        .unwrap_or(false);
+
+    if let (true, Some(url)) = (changed, url) {
+        tokio::spawn(async move {
+            if let Some(cover) = cover_art::load(&url).await {
+                SINK.get()
+                    .unwrap()
+                    .send(Box::new(move |s| {
+                        s.call_on_name("cover_art", |view: &mut cover_art::CoverArt| {
+                            view.set_cover(&url, cover);
+                        });
+                    }))
+                    .expect("failed to send update");
+            }
+        });
+    }
+}
+
+fn show_track_details(s: &mut Cursive, track: &Track) {
+    let mut details = StyledString::new();
+
+    let mut row = |label: &str, value: String| {
+        details.append_styled(format!("{label:>16}  "), Effect::Dim);
+        details.append_plain(value);
+        details.append_plain("\n");
+    };
+
+    row("title", track.title.trim().to_string());
+
+    if let Some(artist) = &track.artist {
+        row("artist", artist.name.clone());
+    }
+
+    if let Some(album) = &track.album {
+        row(
+            "album",
+            format!("{} ({})", album.title.trim(), album.release_year),
+        );
+    }
+
+    row(
+        "duration",
+        ClockTime::from_seconds(track.duration_seconds as u64).to_string()[2..7].to_string(),
+    );
+    row("bit depth", format!("{} bits", track.bit_depth));
+    row("sampling rate", format!("{} kHz", track.sampling_rate));
+    row(
+        "isrc",
+        track.isrc.clone().unwrap_or_else(|| "-".to_string()),
+    );
+    row(
+        "genre",
+        track.genre.clone().unwrap_or_else(|| "-".to_string()),
+    );
+    row(
+        "composer",
+        track.composer.clone().unwrap_or_else(|| "-".to_string()),
+    );
+    row(
+        "explicit",
+        if track.explicit { "yes" } else { "no" }.to_string(),
+    );
+    row(
+        "streamable",
+        if track.available { "yes" } else { "no" }.to_string(),
+    );
+
+    for (index, credit) in track.credits.iter().enumerate() {
+        row(if index == 0 { "credits" } else { "" }, credit.to_string());
+    }
+
+    let track_id = track.id as i32;
+    let available = track.available;
+
+    let mut dialog = Dialog::around(TextView::new(details))
+        .title("track details")
+        .button("Play at quality", move |s| {
+            if available {
+                choose_quality(s, track_id);
+            }
+        })
+        .dismiss_button("Close")
+        .wrap_with(OnEventView::new);
+
+    dialog.set_on_pre_event(Event::Key(Key::Esc), |s| {
+        s.pop_layer();
+    });
+
+    s.add_layer(dialog);
+}
+
+/// Fill the progress bar with how much is buffered, or put the track
+/// position back when `percent` is None and it was showing buffering.
+fn show_buffering(s: &mut Cursive, percent: Option<u32>, duration: u32, elapsed: u64) {
+    let was_buffering = BUFFERING.swap(percent.is_some(), Ordering::Relaxed);
+
+    s.call_on_name("progress", |progress: &mut ProgressBar| match percent {
+        Some(percent) => {
+            progress.set_max(100);
+            progress.set_value(percent.min(100) as usize);
+        }
+        None if was_buffering => {
+            progress.set_max(duration as usize);
+            progress.set_value(elapsed as usize);
+        }
+        None => {}
+    });
+}
+
+fn set_current_track(s: &mut Cursive, track: &Track, lt: &TrackListType) {
+    s.call_on_name("error_line", |view: &mut TextView| view.set_content(""));
+
+    #[cfg(feature = "cover-art")]
+    show_cover_art(s, track);
+
+    if let (Some(mut track_num), Some(mut track_title), Some(mut progress)) = (
+        s.find_name::<TextView>("current_track_number"),
+        s.find_name::<TextView>("current_track_title"),
+        s.find_name::<ProgressBar>("progress"),
+    ) {
+        let number = match lt {
+            TrackListType::Album => track.number,
+            TrackListType::Playlist => track.position,
+            TrackListType::Track => track.number,
+            TrackListType::Unknown => track.position,
+        };
+
+        track_num.set_content(track_number(number));
+
+        track_title.set_content(track.title.trim());
+        progress.set_max(track.duration_seconds as usize);
+    }
+
+    if let Some(artist) = &track.artist {
+        s.call_on_name("artist_name", |view: &mut TextView| {
+            view.set_content(artist.name.clone());
+        });
+    }
+
+    if let (Some(mut bit_depth), Some(mut sample_rate)) = (
+        s.find_name::<TextView>("bit_depth"),
+        s.find_name::<TextView>("sample_rate"),
+    ) {
+        bit_depth.set_content(truncate_width(
+            &format!("{} bits", track.bit_depth),
+            STATUS_WIDTH,
+        ));
+        sample_rate.set_content(truncate_width(
+            &format!("{} kHz", track.sampling_rate),
+            STATUS_WIDTH,
+        ));
+    }
+
+    let track_id = track.id;
+    tokio::spawn(async move {
+        let favorited = player::is_favorite_track(track_id).await;
+
+        SINK.get()
+            .unwrap()
+            .send(Box::new(move |s| {
+                s.call_on_name("favorite", |view: &mut TextView| {
+                    view.set_content(favorite_icon(favorited));
+                });
+            }))
+            .expect("failed to send update");
+    });
+}
+
+/// Play a track at a chosen quality once, leaving the default quality alone.
+fn choose_quality(s: &mut Cursive, track_id: i32) {
+    let mut qualities: SelectView<AudioQuality> = SelectView::new();
+
+    for quality in &allowed_qualities() {
+        let name = quality
+            .to_possible_value()
+            .map(|v| v.get_name().to_string())
+            .unwrap_or_else(|| quality.to_string());
+
+        qualities.add_item(name, quality.clone());
+    }
+
+    qualities.set_on_submit(move |s: &mut Cursive, quality: &AudioQuality| {
+        let quality = quality.clone();
+        tokio::spawn(async move { CONTROLS.play_track_with_quality(track_id, quality).await });
+
+        s.pop_layer();
+        s.pop_layer();
+        show_player(s);
+    });
+
+    s.add_layer(
+        Dialog::around(qualities)
+            .title("play at quality")
+            .dismiss_button("Cancel"),
+    );
+}
+
+/// Switch the quality every track streams at from the next one on.
+fn choose_default_quality(s: &mut Cursive) {
+    let current = block_on(async { player::default_quality().await });
+    let allowed = allowed_qualities();
+    let mut qualities: SelectView<AudioQuality> = SelectView::new();
+
+    for quality in &allowed {
+        qualities.add_item(quality_label(quality), quality.clone());
+    }
+
+    if let Some(index) = allowed.iter().position(|q| *q == current) {
+        qualities.set_selection(index);
+    }
+
+    qualities.set_on_submit(move |s: &mut Cursive, quality: &AudioQuality| {
+        let quality = quality.clone();
+        let message = format!(
+            "Streaming at {} from the next track.",
+            quality_label(&quality)
+        );
+        tokio::spawn(async move { CONTROLS.set_quality(quality).await });
+
+        s.pop_layer();
+        show_toast(s, message);
+    });
+
+    s.add_layer(
+        Dialog::around(qualities)
+            .title("stream quality")
+            .dismiss_button("Cancel"),
+    );
+}
+
+/// The qualities the account may stream, every one of them until the
+/// account is known.
+fn allowed_qualities() -> Vec<AudioQuality> {
+    let max_quality = ACCOUNT
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|account| account.max_quality.clone() as u32);
+
+    AudioQuality::value_variants()
+        .iter()
+        .filter(|q| max_quality.map_or(true, |max| (*q).clone() as u32 <= max))
+        .cloned()
+        .collect()
+}
+
+/// Show who is logged in, and keep the account to limit the qualities offered.
+async fn show_account() {
+    let account = match player::account().await {
+        Some(account) => account,
+        None => return,
+    };
+
+    let label = match &account.subscription {
+        Some(subscription) => format!("{} \u{b7} {subscription}", account.name),
+        None => account.name.clone(),
+    };
+
+    *ACCOUNT.lock().unwrap() = Some(account);
+
+    SINK.get()
+        .unwrap()
+        .send(Box::new(move |s| {
+            s.call_on_name("account", |view: &mut TextView| view.set_content(label));
+        }))
+        .expect("failed to send update");
+}
+
+fn quality_label(quality: &AudioQuality) -> &'static str {
+    match quality {
+        AudioQuality::Mp3 => "MP3 320",
+        AudioQuality::CD => "CD 16/44.1",
+        AudioQuality::HIFI96 => "Hi-Res 24/96",
+        AudioQuality::HIFI192 => "Hi-Res 24/192",
+    }
+}
+
+fn disc_header(disc: u32) -> StyledString {
+    StyledString::styled(format!("Disc {disc}"), Effect::Bold)
+}
+
+fn favorite_icon(favorited: bool) -> String {
+    if favorited {
+        '\u{2665}'.to_string()
+    } else {
+        '\u{2661}'.to_string()
+    }
+}
+
+/// Reads a seek target as `mm:ss`, plain seconds or a percentage of `duration`.
+fn parse_seek(text: &str, duration: u32) -> Option<u64> {
+    let text = text.trim();
+
+    if let Some(percent) = text.strip_suffix('%') {
+        let percent = percent.trim().parse::<f64>().ok()?.clamp(0.0, 100.0);
+        return Some((duration as f64 * percent / 100.0) as u64);
+    }
+
+    if let Some((minutes, seconds)) = text.split_once(':') {
+        return minutes
+            .parse::<u64>()
+            .ok()?
+            .checked_mul(60)?
+            .checked_add(seconds.parse::<u64>().ok()?);
+    }
+
+    text.parse::<u64>().ok()
+}
+
+/// Volume as a percentage that fits the status column.
+fn volume_label(level: f64) -> String {
+    truncate_width(&format!("vol {:.0}%", level * 100.0), STATUS_WIDTH)
+}
+
+/// Short repeat mode label that fits the status column. Empty when off.
+fn repeat_label(mode: RepeatMode) -> String {
+    let label = match mode {
+        RepeatMode::Off => "",
+        RepeatMode::All => "rpt all",
+        RepeatMode::One => "rpt one",
+    };
+
+    truncate_width(label, STATUS_WIDTH)
+}
+
+/// Minutes left on the sleep timer, for the status column. Empty when off.
+fn sleep_timer_label(remaining: Option<Duration>) -> String {
+    match remaining {
+        Some(remaining) => truncate_width(
+            &format!("zz {}m", (remaining.as_secs() + 59) / 60),
+            STATUS_WIDTH,
+        ),
+        None => String::new(),
     }
 }
 
+/// Step of the jump keys, for the status column.
+fn jump_label(seconds: u64) -> String {
+    truncate_width(&format!("\u{b1}{seconds}s"), STATUS_WIDTH)
+}
+
 fn get_state_icon(state: GstState) -> String {
-    match state {
-        GstState::Playing => {
-            format!(" {}", '\u{23f5}')
-        }
-        GstState::Paused => {
-            format!(" {}", '\u{23f8}')
-        }
-        GstState::Ready => {
-            format!(" {}", '\u{23f9}')
-        }
-        GstState::Null => {
-            format!(" {}", '\u{23f9}')
-        }
-        _ => format!(" {}", '\u{23f9}'),
+    let icon = match state {
+        GstState::Playing => '\u{23f5}',
+        GstState::Paused => '\u{23f8}',
+        GstState::Ready => '\u{23f9}',
+        GstState::Null => '\u{23f9}',
+        _ => '\u{23f9}',
+    };
+
+    status_icon(icon)
+}
+
+/// Some terminals draw the media control glyphs as emoji, two columns wide. Asking for
+/// the text presentation keeps them to a single column and inside the status field.
+fn status_icon(icon: char) -> String {
+    truncate_width(&format!(" {icon}\u{fe0e}"), STATUS_WIDTH)
+}
+
+/// Length of the queue and how much of it is left. Empty for an empty queue.
+fn queue_time_label(list: &TrackListValue, elapsed: u64) -> String {
+    if list.queue.is_empty() {
+        return String::new();
+    }
+
+    format!(
+        "{} queued, {} left",
+        queue_duration(list.duration_seconds()),
+        queue_duration(list.remaining_seconds(elapsed))
+    )
+}
+
+/// Hours and minutes, or minutes and seconds for less than an hour.
+fn queue_duration(seconds: u64) -> String {
+    let hours = seconds / 3600;
+    let minutes = (seconds % 3600) / 60;
+
+    if hours > 0 {
+        format!("{hours}h {minutes:02}m")
+    } else {
+        format!("{minutes}m {:02}s", seconds % 60)
     }
 }
 
+/// Zero padded track number that always fits the track number column.
+fn track_number(number: u32) -> String {
+    truncate_width(&format!("{number:03}"), TRACK_NUMBER_WIDTH)
+}
+
 pub async fn receive_notifications() {
-    let mut receiver = player::notify_receiver();
+    let mut receiver = player::notify_receiver().merge(player::level_receiver());
+    let mut last_level = Instant::now();
+    // Kept for the queue time, which changes with both the list and the position.
+    let mut queue = TrackListValue::default();
+    let mut elapsed = 0;
 
     loop {
         select! {
@@ -824,7 +2607,7 @@ pub async fn receive_notifications() {
                         SINK.get().unwrap().send(Box::new(move |s| {
                                 if let Some(mut view) = s.find_name::<TextView>("player_status") {
                                     if is_loading {
-                                        view.set_content(format!(" {}", '\u{2B71}'));
+                                        view.set_content(status_icon('\u{2B71}'));
                                     } else {
                                         view.set_content(get_state_icon(target_state));
                                     }
@@ -832,18 +2615,31 @@ pub async fn receive_notifications() {
                         })).expect("failed to send update");
                     }
                     Notification::Status { status } => {
+                        let duration = queue
+                            .current_track()
+                            .map(|t| t.duration_seconds)
+                            .unwrap_or_default();
+
                         SINK.get()
                             .unwrap()
                             .send(Box::new(move |s| {
                                 if let Some(mut view) = s.find_name::<TextView>("player_status") {
                                     view.set_content(get_state_icon(status));
                                     match status {
+                                        GstState::Playing => {
+                                            LOADING_URL.store(false, Ordering::Relaxed);
+                                            s.call_on_name("player_panel", |panel: &mut Panel<LinearLayout>| {
+                                                panel.set_title("player");
+                                            });
+                                        }
                                         GstState::Ready => {
+                                            show_buffering(s, None, duration, 0);
                                             s.call_on_name("progress", |progress: &mut ProgressBar| {
                                                 progress.set_value(0);
                                             });
                                         }
                                         GstState::Null => {
+                                            show_buffering(s, None, duration, 0);
                                             s.call_on_name("progress", |progress: &mut ProgressBar| {
                                                 progress.set_value(0);
                                             });
@@ -855,16 +2651,41 @@ pub async fn receive_notifications() {
                             .expect("failed to send update");
                     }
                     Notification::Position { clock } => {
+                        elapsed = clock.seconds();
+                        let queue_time = queue_time_label(&queue, elapsed);
+
                         SINK.get()
                             .unwrap()
                             .send(Box::new(move |s| {
                                 if let Some(mut progress) = s.find_name::<ProgressBar>("progress") {
-                                    progress.set_value(clock.seconds() as usize);
+                                    if !BUFFERING.load(Ordering::Relaxed) {
+                                        progress.set_value(clock.seconds() as usize);
+                                    }
                                 }
+
+                                s.call_on_name("queue_time", |view: &mut TextView| {
+                                    view.set_content(queue_time);
+                                });
+
+                                s.call_on_name("sleep_timer", |view: &mut TextView| {
+                                    view.set_content(sleep_timer_label(player::sleep_timer_remaining()));
+                                });
                             }))
                             .expect("failed to send update");
                     }
                     Notification::CurrentTrackList { list } => {
+                        queue = list.clone();
+                        let queue_time = queue_time_label(&queue, elapsed);
+
+                        SINK.get()
+                            .unwrap()
+                            .send(Box::new(move |s| {
+                                s.call_on_name("queue_time", |view: &mut TextView| {
+                                    view.set_content(queue_time);
+                                });
+                            }))
+                            .expect("failed to send update");
+
                         match list.list_type() {
                             TrackListType::Album => {
                                 SINK.get()
@@ -877,19 +2698,28 @@ pub async fn receive_notifications() {
                                         {
                                             list_view.get_inner_mut().clear();
 
-                                            list.unplayed_tracks().iter().for_each(|i| {
-                                                list_view.get_inner_mut().add_item(
-                                                    i.track_list_item(list.list_type(), false),
-                                                    i.position as usize,
-                                                );
-                                            });
-
-                                            list.played_tracks().iter().for_each(|i| {
-                                                list_view.get_inner_mut().add_item(
-                                                    i.track_list_item(list.list_type(), true),
-                                                    i.position as usize,
-                                                );
-                                            });
+                                            let multi_disc = !list.disc_starts().is_empty();
+
+                                            for (tracks, inactive) in
+                                                [(list.unplayed_tracks(), false), (list.played_tracks(), true)]
+                                            {
+                                                let mut disc = None;
+
+                                                for i in tracks {
+                                                    if multi_disc && disc != Some(i.media_number) {
+                                                        disc = Some(i.media_number);
+                                                        list_view.get_inner_mut().add_item(
+                                                            disc_header(i.media_number),
+                                                            DISC_HEADER,
+                                                        );
+                                                    }
+
+                                                    list_view.get_inner_mut().add_item(
+                                                        i.track_list_item(list.list_type(), inactive),
+                                                        i.position as usize,
+                                                    );
+                                                }
+                                            }
                                         }
                                         if let (
                                             Some(album),
@@ -908,8 +2738,7 @@ pub async fn receive_notifications() {
                                             );
 
                                             entity_title.set_content(title);
-                                            total_tracks
-                                                .set_content(format!("{:03}", album.total_tracks));
+                                            total_tracks.set_content(track_number(list.total()));
                                         }
 
                                         for t in list.queue.values() {
@@ -918,6 +2747,8 @@ pub async fn receive_notifications() {
                                                 break;
                                             }
                                         }
+
+                                        follow_current_track(s, &list);
                                     }))
                                     .expect("failed to send update");
                             }
@@ -960,7 +2791,7 @@ pub async fn receive_notifications() {
                                             }
 
                                             entity_title.set_content(playlist.title.clone());
-                                            total_tracks.set_content(format!("{:03}", list.total()));
+                                            total_tracks.set_content(track_number(list.total()));
                                         }
 
                                         for t in list.queue.values() {
@@ -969,6 +2800,8 @@ pub async fn receive_notifications() {
                                                 break;
                                             }
                                         }
+
+                                        follow_current_track(s, &list);
                                     }))
                                     .expect("failed to send update");
                             }
@@ -982,6 +2815,23 @@ pub async fn receive_notifications() {
                                             )
                                         {
                                             list_view.get_inner_mut().clear();
+
+                                            // A single track needs no list, queued ones do.
+                                            if list.queue.len() > 1 {
+                                                list.unplayed_tracks().iter().for_each(|i| {
+                                                    list_view.get_inner_mut().add_item(
+                                                        i.track_list_item(list.list_type(), false),
+                                                        i.position as usize,
+                                                    );
+                                                });
+
+                                                list.played_tracks().iter().for_each(|i| {
+                                                    list_view.get_inner_mut().add_item(
+                                                        i.track_list_item(list.list_type(), true),
+                                                        i.position as usize,
+                                                    );
+                                                });
+                                            }
                                         }
 
                                         if let (Some(album), Some(mut entity_title)) =
@@ -992,7 +2842,7 @@ pub async fn receive_notifications() {
                                         if let Some(mut total_tracks) =
                                             s.find_name::<TextView>("total_tracks")
                                         {
-                                            total_tracks.set_content("001");
+                                            total_tracks.set_content(track_number(list.total()));
                                         }
 
                                         for t in list.queue.values() {
@@ -1001,6 +2851,8 @@ pub async fn receive_notifications() {
                                                 break;
                                             }
                                         }
+
+                                        follow_current_track(s, &list);
                                     }))
                                     .expect("failed to send update");
                             }
@@ -1012,12 +2864,23 @@ pub async fn receive_notifications() {
                         target_state,
                         percent,
                     } => {
+                        let duration = queue
+                            .current_track()
+                            .map(|t| t.duration_seconds)
+                            .unwrap_or_default();
+
                         SINK.get()
                             .unwrap()
                             .send(Box::new(move |s| {
+                                let percent = Some(percent).filter(|_| is_buffering);
+                                show_buffering(s, percent, duration, elapsed);
+
                                 s.call_on_name("player_status", |view: &mut TextView| {
                                     if is_buffering {
-                                        view.set_content(format!("{}%", percent));
+                                        view.set_content(truncate_width(
+                                            &format!("{}%", percent),
+                                            STATUS_WIDTH,
+                                        ));
                                     } else {
                                         view.set_content(get_state_icon(target_state));
                                     }
@@ -1028,26 +2891,360 @@ pub async fn receive_notifications() {
                     Notification::AudioQuality { bitdepth, sampling_rate} => {
                         SINK.get().unwrap().send(Box::new(move |s| {
                             s.call_on_name("bit_depth", |view: &mut TextView| {
-                                view.set_content(format!("{bitdepth} bits"));
+                                view.set_content(truncate_width(
+                                    &format!("{bitdepth} bits"),
+                                    STATUS_WIDTH,
+                                ));
                             });
                         })).expect("failed to send update");
 
                         SINK.get().unwrap().send(Box::new(move |s| {
                             s.call_on_name("sample_rate", |view: &mut TextView| {
-                                view.set_content(format!("{} kHz", sampling_rate as f32 / 1000.));
+                                view.set_content(truncate_width(
+                                    &format!("{} kHz", sampling_rate as f32 / 1000.),
+                                    STATUS_WIDTH,
+                                ));
+                            });
+                        })).expect("failed to send update");
+                    }
+                    Notification::Error { error } => {
+                        SINK.get().unwrap().send(Box::new(move |s| {
+                            if LOADING_URL.swap(false, Ordering::Relaxed) {
+                                s.call_on_name("player_panel", |panel: &mut Panel<LinearLayout>| {
+                                    panel.set_title("player");
+                                });
+                            }
+
+                            if error.is_fatal() {
+                                s.add_layer(Dialog::info(error.to_string()).title("Error"));
+                            } else {
+                                s.call_on_name("error_line", |view: &mut TextView| {
+                                    view.set_content(error.to_string());
+                                });
+                            }
+                        })).expect("failed to send update");
+                    }
+                    Notification::Reauthenticating => {
+                        SINK.get().unwrap().send(Box::new(move |s| {
+                            s.call_on_name("player_panel", |panel: &mut Panel<LinearLayout>| {
+                                panel.set_title("player - reconnecting");
+                            });
+                        })).expect("failed to send update");
+                    }
+                    Notification::Reauthenticated { success } => {
+                        SINK.get().unwrap().send(Box::new(move |s| {
+                            if success {
+                                s.call_on_name("player_panel", |panel: &mut Panel<LinearLayout>| {
+                                    panel.set_title("player");
+                                });
+                            } else {
+                                s.call_on_name("player_panel", |panel: &mut Panel<LinearLayout>| {
+                                    panel.set_title("player - offline");
+                                });
+                                s.add_layer(offline_dialog());
+                            }
+                        })).expect("failed to send update");
+                    }
+                    Notification::SilenceSkipped => {}
+                    Notification::TrackSkipped { track_id: _, title } => {
+                        let message = format!("{} can't be streamed, skipped it.", title.trim());
+
+                        SINK.get().unwrap().send(Box::new(move |s| {
+                            s.call_on_name("error_line", |view: &mut TextView| {
+                                view.set_content(message);
+                            });
+                        })).expect("failed to send update");
+                    }
+                    Notification::FavoriteChanged { track_id: _, favorited } => {
+                        SINK.get().unwrap().send(Box::new(move |s| {
+                            s.call_on_name("favorite", |view: &mut TextView| {
+                                view.set_content(favorite_icon(favorited));
+                            });
+                        })).expect("failed to send update");
+                    }
+                    Notification::Level { peak, rms } => {
+                        // The meter doesn't need to redraw more often than the eye can follow.
+                        if last_level.elapsed() < LEVEL_REFRESH {
+                            continue;
+                        }
+
+                        last_level = Instant::now();
+
+                        SINK.get().unwrap().send(Box::new(move |s| {
+                            s.call_on_name("level_meter", |view: &mut TextView| {
+                                view.set_content(level_meter(&peak, &rms));
+                            });
+                        })).expect("failed to send update");
+                    }
+                    Notification::StateDump { snapshot } => {
+                        let message = format!(
+                            "Player state written to the log ({} tracks queued).",
+                            snapshot.queue.len()
+                        );
+
+                        SINK.get().unwrap().send(Box::new(move |s| {
+                            s.add_layer(Dialog::info(message));
+                        })).expect("failed to send update");
+                    }
+                    Notification::Volume { level } => {
+                        SINK.get().unwrap().send(Box::new(move |s| {
+                            s.call_on_name("volume", |view: &mut TextView| {
+                                view.set_content(volume_label(level));
+                            });
+                        })).expect("failed to send update");
+                    }
+                    Notification::DownloadProgress { track_id: _, title, written, total } => {
+                        let message = match total {
+                            Some(total) if written >= total => format!("downloaded {title}"),
+                            Some(total) => format!("downloading {title} {}%", written * 100 / total.max(1)),
+                            None => format!("downloading {title}"),
+                        };
+
+                        SINK.get().unwrap().send(Box::new(move |s| {
+                            s.call_on_name("player_panel", |panel: &mut Panel<LinearLayout>| {
+                                panel.set_title(format!("player - {message}"));
+                            });
+                        })).expect("failed to send update");
+                    }
+                    Notification::SleepTimer { seconds } => {
+                        SINK.get().unwrap().send(Box::new(move |s| {
+                            s.call_on_name("sleep_timer", |view: &mut TextView| {
+                                view.set_content(sleep_timer_label(seconds.map(Duration::from_secs)));
                             });
                         })).expect("failed to send update");
                     }
-                    Notification::Error { error: _ } => {}
+                    Notification::Repeat { mode } => {
+                        SINK.get().unwrap().send(Box::new(move |s| {
+                            s.call_on_name("repeat_mode", |view: &mut TextView| {
+                                view.set_content(repeat_label(mode));
+                            });
+                        })).expect("failed to send update");
+                    }
+                    Notification::Mono { enabled } => {
+                        let message = if enabled {
+                            "mono"
+                        } else {
+                            "stereo"
+                        };
+
+                        SINK.get().unwrap().send(Box::new(move |s| {
+                            s.call_on_name("player_panel", |panel: &mut Panel<LinearLayout>| {
+                                panel.set_title(format!("player - {message}"));
+                            });
+                        })).expect("failed to send update");
+                    }
+                    Notification::Radio { enabled } => {
+                        let message = if enabled {
+                            "radio on"
+                        } else {
+                            "radio off"
+                        };
+
+                        SINK.get().unwrap().send(Box::new(move |s| {
+                            s.call_on_name("player_panel", |panel: &mut Panel<LinearLayout>| {
+                                panel.set_title(format!("player - {message}"));
+                            });
+                        })).expect("failed to send update");
+                    }
+                    Notification::Stopped { reason } => {
+                        let message = match reason {
+                            StopReason::EndOfQueue => "queue finished",
+                            StopReason::UserStop => "stopped",
+                            StopReason::Error => "stopped after an error",
+                            StopReason::SleepTimer => "sleep timer ended playback",
+                            StopReason::Idle => "idle, audio device released",
+                            StopReason::NothingPlayable => "no playable tracks left",
+                        };
+
+                        SINK.get().unwrap().send(Box::new(move |s| {
+                            s.call_on_name("player_panel", |panel: &mut Panel<LinearLayout>| {
+                                panel.set_title(format!("player - {message}"));
+                            });
+                        })).expect("failed to send update");
+                    }
+                    Notification::QualityFallback { requested, granted } => {
+                        let message = format!(
+                            "{} isn't available to this account, playing {} instead.",
+                            quality_label(&requested),
+                            quality_label(&granted)
+                        );
+
+                        SINK.get().unwrap().send(Box::new(move |s| {
+                            s.call_on_name("error_line", |view: &mut TextView| {
+                                view.set_content(message);
+                            });
+                        })).expect("failed to send update");
+                    }
+                }
+            }
+            else => {
+                debug!("exiting tui notification thread");
+                return;
+            }
+        }
+    }
+}
+
+/// Pans titles too long for the player back and forth, when enabled.
+/// Starts over from the beginning whenever the track changes.
+pub async fn marquee() {
+    if !db::get_marquee().await {
+        return;
+    }
+
+    let mut receiver = player::notify_receiver();
+    let mut interval = tokio::time::interval(MARQUEE_STEP);
+    let mut step = 0;
+
+    loop {
+        select! {
+            _ = interval.tick() => {
+                SINK.get().unwrap().send(Box::new(move |s| {
+                    for name in MARQUEE_VIEWS {
+                        s.call_on_name(name, |view: &mut TitleScroll| {
+                            let overflow = view
+                                .inner_size()
+                                .x
+                                .saturating_sub(view.content_viewport().width());
+
+                            view.set_offset((marquee_offset(step, overflow), 0));
+                        });
+                    }
+                })).expect("failed to send update");
+
+                step += 1;
+            }
+            notification = receiver.next() => {
+                match notification {
+                    Some(Notification::Quit) | None => {
+                        debug!("exiting marquee thread");
+                        return;
+                    }
+                    Some(Notification::CurrentTrackList { list: _ }) => step = 0,
+                    _ => {}
                 }
             }
         }
     }
 }
 
+/// Column a title overflowing by `overflow` columns starts at, `step` steps
+/// into the animation. Titles that fit always start at the first column.
+fn marquee_offset(step: usize, overflow: usize) -> usize {
+    if overflow == 0 {
+        return 0;
+    }
+
+    let cycle = overflow + 2 * MARQUEE_PAUSE;
+
+    (step % cycle).saturating_sub(MARQUEE_PAUSE).min(overflow)
+}
+
+/// Draws one bar per channel from `LEVEL_FLOOR` to 0 dB, filled to the RMS
+/// level with a marker at the peak.
+fn level_meter(peak: &[f64], rms: &[f64]) -> StyledString {
+    let scale = |db: f64| -> usize {
+        let db = db.clamp(LEVEL_FLOOR, 0.0);
+        (((db - LEVEL_FLOOR) / -LEVEL_FLOOR) * LEVEL_METER_WIDTH as f64).round() as usize
+    };
+
+    let mut meter = StyledString::new();
+
+    for (channel, (peak, rms)) in peak.iter().zip(rms.iter()).enumerate() {
+        let label = match (peak.len(), channel) {
+            (2, 0) => "L".to_string(),
+            (2, 1) => "R".to_string(),
+            _ => (channel + 1).to_string(),
+        };
+
+        let filled = scale(*rms);
+        let peak_at = scale(*peak).max(filled);
+
+        if channel > 0 {
+            meter.append_plain("\n");
+        }
+
+        meter.append_plain(format!("{label} "));
+        meter.append_plain("\u{2588}".repeat(filled));
+        meter.append_plain(" ".repeat(peak_at - filled));
+        meter.append_plain("\u{258f}");
+        meter.append_plain(" ".repeat(LEVEL_METER_WIDTH - peak_at));
+
+        if *peak >= CLIPPING {
+            meter.append_styled(
+                " CLIP",
+                Style::from(Color::Dark(BaseColor::Red)).combine(Effect::Bold),
+            );
+        } else {
+            meter.append_plain(format!(" {peak:>5.1} dB"));
+        }
+    }
+
+    meter
+}
+
 pub trait CursiveFormat {
     fn list_item(&self) -> StyledString;
     fn track_list_item(&self, _list_type: &TrackListType, _inactive: bool) -> StyledString {
         StyledString::new()
     }
 }
+
+/// Marks explicit and hi-res items, colored to stand out from the dimmed
+/// columns. Dimmed items keep their style so they don't draw the eye.
+pub fn append_badges(item: &mut StyledString, explicit: bool, hires: bool, style: Style) {
+    let color = |color: Color| {
+        if style.effects.contains(Effect::Dim) {
+            style
+        } else {
+            style.combine(color)
+        }
+    };
+
+    if explicit {
+        item.append_styled("e", color(Color::Dark(BaseColor::Red)));
+    }
+
+    if hires {
+        item.append_styled("*", color(Color::Dark(BaseColor::Cyan)));
+    }
+}
+
+/// Pads or truncates `text` to exactly `width` terminal columns, marking truncation with an ellipsis.
+pub fn fixed_width(text: &str, width: usize) -> String {
+    let truncated = truncate_width(text, width);
+    let padding = width.saturating_sub(truncated.width());
+
+    format!("{truncated}{}", " ".repeat(padding))
+}
+
+/// Truncates `text` to at most `width` terminal columns, marking truncation with an ellipsis.
+///
+/// Wide characters like CJK and emoji are measured by their display width and text is only
+/// cut between grapheme clusters, so combined characters are never split.
+pub fn truncate_width(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+
+    if width == 0 {
+        return String::new();
+    }
+
+    let mut truncated = String::new();
+    let mut used = 0;
+
+    for grapheme in text.graphemes(true) {
+        let grapheme_width = grapheme.width();
+
+        if used + grapheme_width > width - 1 {
+            break;
+        }
+
+        truncated.push_str(grapheme);
+        used += grapheme_width;
+    }
+
+    truncated.push('\u{2026}');
+    truncated
+}
@@ -0,0 +1,103 @@
+/// How many searches are remembered.
+pub const HISTORY_LIMIT: usize = 20;
+
+/// Recent search terms, newest first, and how far back Up/Down has gone.
+#[derive(Debug, Default)]
+pub struct SearchHistory {
+    terms: Vec<String>,
+    recall: Option<usize>,
+}
+
+impl SearchHistory {
+    pub fn new(mut terms: Vec<String>) -> Self {
+        terms.truncate(HISTORY_LIMIT);
+
+        Self {
+            terms,
+            recall: None,
+        }
+    }
+
+    pub fn terms(&self) -> &[String] {
+        &self.terms
+    }
+
+    /// Whether Up has been used to go back through the history.
+    pub fn is_recalling(&self) -> bool {
+        self.recall.is_some()
+    }
+
+    /// Put a term first, dropping an earlier search for it and the oldest
+    /// beyond the limit. False when the term is blank.
+    pub fn remember(&mut self, term: &str) -> bool {
+        let term = term.trim();
+        self.recall = None;
+
+        if term.is_empty() {
+            return false;
+        }
+
+        self.terms.retain(|t| !t.eq_ignore_ascii_case(term));
+        self.terms.insert(0, term.to_string());
+        self.terms.truncate(HISTORY_LIMIT);
+
+        true
+    }
+
+    /// One search further back, staying on the oldest.
+    pub fn older(&mut self) -> Option<&str> {
+        if self.terms.is_empty() {
+            return None;
+        }
+
+        let index = self
+            .recall
+            .map_or(0, |index| (index + 1).min(self.terms.len() - 1));
+        self.recall = Some(index);
+
+        self.terms.get(index).map(String::as_str)
+    }
+
+    /// One search closer to now. None once past the newest, to start over
+    /// with an empty box.
+    pub fn newer(&mut self) -> Option<&str> {
+        match self.recall {
+            Some(index) if index > 0 => {
+                self.recall = Some(index - 1);
+                self.terms.get(index - 1).map(String::as_str)
+            }
+            _ => {
+                self.recall = None;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn searches_are_deduplicated_and_recalled_newest_first() {
+        let mut history = SearchHistory::new(vec!["radiohead".to_string()]);
+
+        assert!(history.remember("Bjork"));
+        assert!(history.remember("  Radiohead "));
+        assert!(!history.remember("   "));
+        assert_eq!(history.terms(), ["Radiohead", "Bjork"]);
+
+        assert_eq!(history.older(), Some("Radiohead"));
+        assert_eq!(history.older(), Some("Bjork"));
+        assert_eq!(history.older(), Some("Bjork"));
+        assert_eq!(history.newer(), Some("Radiohead"));
+        assert_eq!(history.newer(), None);
+        assert!(!history.is_recalling());
+
+        for index in 0..HISTORY_LIMIT + 5 {
+            history.remember(&format!("artist {index}"));
+        }
+        assert_eq!(history.terms().len(), HISTORY_LIMIT);
+        assert_eq!(history.terms()[0], format!("artist {}", HISTORY_LIMIT + 4));
+    }
+}
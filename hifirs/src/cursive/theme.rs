@@ -0,0 +1,230 @@
+use cursive::{
+    theme::{
+        BaseColor::*, BorderStyle, Color, Effect, Palette, PaletteColor, PaletteStyle, Style, Theme,
+    },
+    With,
+};
+use std::path::PathBuf;
+
+/// Built-in themes, picked with `theme = "<name>"` in the theme file.
+const THEMES: [&str; 2] = ["default", "light"];
+
+/// The colors a theme can change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Colors {
+    background: Color,
+    view: Color,
+    primary: Color,
+    secondary: Color,
+    highlight: Color,
+    highlight_inactive: Color,
+    highlight_text: Color,
+    title: Color,
+}
+
+impl Colors {
+    fn named(name: &str) -> Option<Colors> {
+        match name {
+            "default" => Some(Colors {
+                background: Color::TerminalDefault,
+                view: Color::TerminalDefault,
+                primary: White.dark(),
+                secondary: Color::TerminalDefault,
+                highlight: Cyan.dark(),
+                highlight_inactive: Black.dark(),
+                highlight_text: Black.dark(),
+                title: Cyan.dark(),
+            }),
+            // For terminals with a light background.
+            "light" => Some(Colors {
+                background: Color::TerminalDefault,
+                view: Color::TerminalDefault,
+                primary: Black.dark(),
+                secondary: Blue.dark(),
+                highlight: Blue.dark(),
+                highlight_inactive: White.dark(),
+                highlight_text: White.light(),
+                title: Blue.dark(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut Color> {
+        match key {
+            "background" => Some(&mut self.background),
+            "view" => Some(&mut self.view),
+            "primary" => Some(&mut self.primary),
+            "secondary" => Some(&mut self.secondary),
+            "highlight" => Some(&mut self.highlight),
+            "highlight_inactive" => Some(&mut self.highlight_inactive),
+            "highlight_text" => Some(&mut self.highlight_text),
+            "title" => Some(&mut self.title),
+            _ => None,
+        }
+    }
+}
+
+/// Where the theme is read from.
+fn theme_path() -> Option<PathBuf> {
+    let mut path = dirs::config_dir()?;
+    path.push("hifi-rs");
+    path.push("theme.toml");
+
+    Some(path)
+}
+
+/// The theme from the theme file, or the default one when there is none.
+/// Anything in the file that can't be used is logged and left at its default.
+pub fn load() -> Theme {
+    let text = match theme_path().map(std::fs::read_to_string) {
+        Some(Ok(text)) => text,
+        _ => return default_theme(),
+    };
+
+    let (theme, warnings) = parse(&text);
+
+    for warning in warnings {
+        warn!("theme: {warning}");
+    }
+
+    theme
+}
+
+fn default_theme() -> Theme {
+    build(&Colors::named("default").unwrap(), BorderStyle::Simple)
+}
+
+/// A theme from the text of a theme file, with what was wrong in it.
+fn parse(text: &str) -> (Theme, Vec<String>) {
+    let mut warnings = Vec::new();
+
+    let table = match text.parse::<toml::Table>() {
+        Ok(table) => table,
+        Err(error) => return (default_theme(), vec![format!("invalid file: {error}")]),
+    };
+
+    let name = table
+        .get("theme")
+        .and_then(|v| v.as_str())
+        .unwrap_or("default");
+    let mut colors = Colors::named(name).unwrap_or_else(|| {
+        warnings.push(format!(
+            "unknown theme {name}, expected one of {}",
+            THEMES.join(", ")
+        ));
+        Colors::named("default").unwrap()
+    });
+
+    let borders = match table.get("borders").and_then(|v| v.as_str()) {
+        None | Some("simple") => BorderStyle::Simple,
+        Some("outset") => BorderStyle::Outset,
+        Some("none") => BorderStyle::None,
+        Some(other) => {
+            warnings.push(format!(
+                "unknown border style {other}, expected simple, outset or none"
+            ));
+            BorderStyle::Simple
+        }
+    };
+
+    for (key, value) in &table {
+        match key.as_str() {
+            "theme" | "borders" => {}
+            "colors" => match value.as_table() {
+                Some(overrides) => {
+                    for (key, value) in overrides {
+                        let color = value.as_str().and_then(parse_color);
+
+                        match (colors.get_mut(key), color) {
+                            (Some(slot), Some(color)) => *slot = color,
+                            (Some(_), None) => {
+                                warnings.push(format!("invalid color {value} for {key}"))
+                            }
+                            (None, _) => warnings.push(format!("unknown color {key}")),
+                        }
+                    }
+                }
+                None => warnings.push("colors should be a table".to_string()),
+            },
+            other => warnings.push(format!("unknown key {other}")),
+        }
+    }
+
+    (build(&colors, borders), warnings)
+}
+
+/// A color name like "cyan" or "light red", a hex value like "#2e3440", or
+/// "default" for the terminal's own color.
+fn parse_color(value: &str) -> Option<Color> {
+    match value.trim() {
+        "default" => Some(Color::TerminalDefault),
+        other => Color::parse(other),
+    }
+}
+
+fn build(colors: &Colors, borders: BorderStyle) -> Theme {
+    Theme {
+        shadow: false,
+        borders,
+        palette: Palette::terminal_default().with(|palette| {
+            palette[PaletteColor::Background] = colors.background;
+            palette[PaletteColor::View] = colors.view;
+            palette[PaletteColor::Primary] = colors.primary;
+            palette[PaletteColor::Secondary] = colors.secondary;
+            palette[PaletteColor::Highlight] = colors.highlight;
+            palette[PaletteColor::HighlightInactive] = colors.highlight_inactive;
+            palette[PaletteColor::HighlightText] = colors.highlight_text;
+
+            palette[PaletteStyle::Highlight] = Style::from(colors.highlight)
+                .combine(Effect::Underline)
+                .combine(Effect::Reverse)
+                .combine(Effect::Bold);
+            palette[PaletteStyle::HighlightInactive] =
+                Style::from(Color::TerminalDefault).combine(Effect::Reverse);
+            palette[PaletteStyle::TitlePrimary] = Style::from(colors.title).combine(Effect::Bold);
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn theme_file_overrides_a_built_in_theme() {
+        let (theme, warnings) = parse(
+            r##"
+            theme = "light"
+            borders = "outset"
+            shadow = true
+
+            [colors]
+            highlight = "light red"
+            title = "#2e3440"
+            primary = "not a color"
+            accent = "red"
+            "##,
+        );
+
+        assert_eq!(theme.borders, BorderStyle::Outset);
+        assert_eq!(theme.palette[PaletteColor::Highlight], Red.light());
+        assert_eq!(theme.palette[PaletteColor::Primary], Black.dark());
+        assert_eq!(
+            warnings,
+            vec![
+                "unknown color accent",
+                "invalid color \"not a color\" for primary",
+                "unknown key shadow",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_theme_falls_back_to_the_default() {
+        let (theme, warnings) = parse(r#"theme = "solarized""#);
+
+        assert_eq!(theme.palette[PaletteColor::Highlight], Cyan.dark());
+        assert_eq!(warnings.len(), 1);
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{
+    player::{self, notification::Notification},
+    service::{Track, TrackStatus},
+};
+use async_broadcast::RecvError;
+use discord_rich_presence::{
+    activity::{Activity, Assets, Timestamps},
+    DiscordIpc, DiscordIpcClient,
+};
+use gstreamer::{ClockTime, State as GstState};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How long to wait before trying to reach Discord again.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(15);
+/// How far the shown elapsed time may drift before it is sent again, e.g. after a seek.
+const ALLOWED_DRIFT: i64 = 2;
+/// Discord rejects fields shorter or longer than this.
+const FIELD_LENGTH: (usize, usize) = (2, 128);
+
+/// The text and artwork shown for a track.
+#[derive(Debug, Clone, PartialEq)]
+struct PresenceText {
+    details: String,
+    state: String,
+    album: Option<String>,
+    cover_art: Option<String>,
+}
+
+impl From<&Track> for PresenceText {
+    fn from(track: &Track) -> Self {
+        let artist = track
+            .artist
+            .as_ref()
+            .map(|a| a.name.trim())
+            .unwrap_or("unknown artist");
+
+        Self {
+            details: field(track.title.trim()),
+            state: field(&format!("by {artist}")),
+            album: track.album.as_ref().map(|a| field(a.title.trim())),
+            cover_art: track.cover_art.clone(),
+        }
+    }
+}
+
+/// Pads or cuts text to a length Discord accepts.
+fn field(text: &str) -> String {
+    let (min, max) = FIELD_LENGTH;
+    let mut field = text.chars().take(max).collect::<String>();
+
+    while field.chars().count() < min {
+        field.push(' ');
+    }
+
+    field
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+struct Presence {
+    app_id: String,
+    client: Option<DiscordIpcClient>,
+    last_attempt: Option<Instant>,
+    track: Option<PresenceText>,
+    state: GstState,
+    /// When the track would have started, had it played without pausing.
+    started_at: i64,
+    /// What was last sent, to leave Discord alone when nothing changed.
+    shown: Option<(PresenceText, Option<i64>)>,
+}
+
+impl Presence {
+    fn new(app_id: String) -> Self {
+        Self {
+            app_id,
+            client: None,
+            last_attempt: None,
+            track: None,
+            state: GstState::Null,
+            started_at: unix_now(),
+            shown: None,
+        }
+    }
+
+    /// The connected client, connecting first when Discord wasn't reachable
+    /// and it has been a while since the last try.
+    fn client(&mut self) -> Option<&mut DiscordIpcClient> {
+        if self.client.is_none() {
+            let retry = self
+                .last_attempt
+                .map_or(true, |at| at.elapsed() >= RECONNECT_INTERVAL);
+
+            if !retry {
+                return None;
+            }
+
+            self.last_attempt = Some(Instant::now());
+
+            let connected = DiscordIpcClient::new(&self.app_id).and_then(|mut client| {
+                client.connect()?;
+                Ok(client)
+            });
+
+            match connected {
+                Ok(client) => {
+                    debug!("connected to discord");
+                    self.client = Some(client);
+                    self.shown = None;
+                }
+                Err(error) => debug!("unable to reach discord: {error}"),
+            }
+        }
+
+        self.client.as_mut()
+    }
+
+    /// Forget a connection that stopped working, so it is made again later.
+    fn disconnect(&mut self, error: &str) {
+        debug!("lost discord: {error}");
+
+        if let Some(mut client) = self.client.take() {
+            client.close().ok();
+        }
+    }
+
+    fn set_position(&mut self, position: ClockTime) {
+        self.started_at = unix_now() - position.seconds() as i64;
+    }
+
+    fn update(&mut self) {
+        let text = match (&self.track, self.state) {
+            (Some(text), GstState::Playing | GstState::Paused) => text.clone(),
+            _ => return self.clear(),
+        };
+
+        // Paused tracks don't count up.
+        let started_at = Some(self.started_at).filter(|_| self.state == GstState::Playing);
+
+        let unchanged = self.shown.as_ref().map_or(false, |(shown, shown_start)| {
+            *shown == text
+                && match (shown_start, started_at) {
+                    (Some(a), Some(b)) => (a - b).abs() <= ALLOWED_DRIFT,
+                    (a, b) => a.is_none() && b.is_none(),
+                }
+        });
+
+        if unchanged {
+            return;
+        }
+
+        let client = match self.client() {
+            Some(client) => client,
+            None => return,
+        };
+
+        let mut assets = Assets::new();
+        if let Some(cover_art) = &text.cover_art {
+            assets = assets.large_image(cover_art);
+        }
+        if let Some(album) = &text.album {
+            assets = assets.large_text(album);
+        }
+
+        let mut activity = Activity::new()
+            .details(&text.details)
+            .state(&text.state)
+            .assets(assets);
+        if let Some(start) = started_at {
+            activity = activity.timestamps(Timestamps::new().start(start));
+        }
+
+        let result = client.set_activity(activity).map_err(|e| e.to_string());
+
+        match result {
+            Ok(()) => self.shown = Some((text, started_at)),
+            Err(error) => self.disconnect(&error),
+        }
+    }
+
+    fn clear(&mut self) {
+        if self.shown.take().is_none() {
+            return;
+        }
+
+        if let Some(client) = self.client.as_mut() {
+            let result = client.clear_activity().map_err(|e| e.to_string());
+
+            if let Err(error) = result {
+                self.disconnect(&error);
+            }
+        }
+    }
+}
+
+/// Mirror the playing track on the Discord profile of whoever runs the
+/// Discord app on this machine. Reconnects on its own when Discord restarts.
+pub async fn receive_notifications(app_id: String) {
+    let mut receiver = player::notify_receiver();
+    let mut presence = Presence::new(app_id);
+
+    loop {
+        let notification = match receiver.recv().await {
+            Ok(notification) => notification,
+            Err(RecvError::Overflowed(skipped)) => {
+                debug!("discord missed {skipped} notifications");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        match notification {
+            Notification::CurrentTrackList { list } => {
+                let playing = list
+                    .queue
+                    .values()
+                    .find(|t| t.status == TrackStatus::Playing)
+                    .map(PresenceText::from);
+
+                if playing != presence.track {
+                    presence.track = playing;
+                    presence.started_at = unix_now();
+                }
+            }
+            Notification::Status { status } => presence.state = status,
+            Notification::Position { clock } => presence.set_position(clock),
+            Notification::Stopped { reason: _ } => {
+                presence.state = GstState::Null;
+            }
+            Notification::Quit => {
+                presence.clear();
+
+                if let Some(mut client) = presence.client.take() {
+                    client.close().ok();
+                }
+
+                return;
+            }
+            _ => continue,
+        }
+
+        presence.update();
+    }
+}
+
+#[test]
+fn presence_fields_fit_discord_limits() {
+    assert_eq!(field("A"), "A ");
+    assert_eq!(field(&"x".repeat(200)).chars().count(), 128);
+    assert_eq!(field("Acknowledgement"), "Acknowledgement");
+}
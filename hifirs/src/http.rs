@@ -0,0 +1,257 @@
+use axum::{
+    extract::{ws::WebSocketUpgrade, Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use futures::StreamExt;
+use gstreamer::State as GstState;
+use hifirs_qobuz_api::client::AudioQuality;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::RwLock;
+
+use crate::{
+    player::{
+        self,
+        controls::{Action, RepeatMode},
+        notification::Notification,
+        queue::TrackListValue,
+    },
+    service::{Track, TrackStatus},
+    websocket,
+};
+
+/// How long to wait on a running player before starting a new one.
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(2);
+
+type Reply = (StatusCode, Json<Value>);
+type SharedStatus = Arc<RwLock<Status>>;
+
+/// What the player is doing, kept up to date from its notifications.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Status {
+    state: GstState,
+    position_seconds: u64,
+    track: Option<Track>,
+    queue_length: u32,
+    #[serde(skip)]
+    list: TrackListValue,
+}
+
+impl Status {
+    fn set_list(&mut self, list: TrackListValue) {
+        self.track = list
+            .queue
+            .values()
+            .find(|t| t.status == TrackStatus::Playing)
+            .cloned();
+        self.queue_length = list.total();
+        self.list = list;
+    }
+}
+
+/// Serve the REST control API until the player quits.
+pub async fn init(binding_interface: SocketAddr) {
+    let mut status = Status {
+        state: player::current_state(),
+        position_seconds: player::position().map(|p| p.seconds()).unwrap_or_default(),
+        track: None,
+        queue_length: 0,
+        list: TrackListValue::default(),
+    };
+    status.set_list(player::current_tracklist().await);
+
+    let status = Arc::new(RwLock::new(status));
+
+    let app = Router::new()
+        .route("/status", get(get_status))
+        .route("/events", get(events))
+        .route("/play", post(play))
+        .route("/pause", post(pause))
+        .route("/next", post(next))
+        .route("/previous", post(previous))
+        .route("/skip/:position", post(skip))
+        .route("/play/album/:album_id", post(play_album))
+        .route("/play/track/:track_id", post(play_track))
+        .route("/play/playlist/:playlist_id", post(play_playlist))
+        .with_state(status.clone());
+
+    debug!("http api listening on {}", binding_interface);
+
+    let server = axum::Server::bind(&binding_interface).serve(app.into_make_service());
+
+    let graceful = server.with_graceful_shutdown(async move {
+        let mut broadcast_receiver = player::notify_receiver();
+
+        while let Some(message) = broadcast_receiver.next().await {
+            match message {
+                Notification::Quit => break,
+                Notification::Status { status: state } => status.write().await.state = state,
+                Notification::Position { clock } => {
+                    status.write().await.position_seconds = clock.seconds();
+                }
+                Notification::CurrentTrackList { list } => status.write().await.set_list(list),
+                _ => {}
+            }
+        }
+    });
+
+    if let Err(e) = graceful.await {
+        debug!(?e)
+    }
+}
+
+fn accepted() -> Reply {
+    (StatusCode::ACCEPTED, Json(json!({ "accepted": true })))
+}
+
+fn rejected(code: StatusCode, message: &str) -> Reply {
+    (code, Json(json!({ "error": message })))
+}
+
+async fn get_status(State(status): State<SharedStatus>) -> Json<Status> {
+    Json(status.read().await.clone())
+}
+
+/// Streams notifications as JSON, like the websocket API but read only.
+async fn events(ws: WebSocketUpgrade) -> impl IntoResponse {
+    ws.on_upgrade(|socket| async move {
+        let (mut sender, _) = socket.split();
+        // Nothing answers requests here, so there are no responses to send.
+        let (_, responses) = flume::bounded(1);
+
+        websocket::stream_notifications(&mut sender, responses).await;
+    })
+}
+
+async fn play(State(status): State<SharedStatus>) -> Reply {
+    if status.read().await.track.is_none() {
+        return rejected(StatusCode::CONFLICT, "nothing to play");
+    }
+
+    player::controls().play().await;
+    accepted()
+}
+
+async fn pause(State(status): State<SharedStatus>) -> Reply {
+    if status.read().await.state != GstState::Playing {
+        return rejected(StatusCode::CONFLICT, "not playing");
+    }
+
+    player::controls().pause().await;
+    accepted()
+}
+
+async fn next(State(status): State<SharedStatus>) -> Reply {
+    let repeating = player::repeat_mode().await != RepeatMode::Off;
+
+    if !repeating && status.read().await.list.unplayed_tracks().is_empty() {
+        return rejected(StatusCode::CONFLICT, "no next track");
+    }
+
+    player::controls().next().await;
+    accepted()
+}
+
+async fn previous(State(status): State<SharedStatus>) -> Reply {
+    if status.read().await.track.is_none() {
+        return rejected(StatusCode::CONFLICT, "nothing is playing");
+    }
+
+    player::controls().previous().await;
+    accepted()
+}
+
+async fn skip(State(status): State<SharedStatus>, Path(position): Path<u32>) -> Reply {
+    if status
+        .read()
+        .await
+        .list
+        .find_track_by_index(position)
+        .is_none()
+    {
+        return rejected(StatusCode::NOT_FOUND, "no track at that position");
+    }
+
+    player::controls().skip_to(position).await;
+    accepted()
+}
+
+async fn play_album(Path(album_id): Path<String>) -> Reply {
+    if album_id.is_empty() || !album_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return rejected(StatusCode::BAD_REQUEST, "invalid album id");
+    }
+
+    player::controls().play_album(album_id).await;
+    accepted()
+}
+
+/// Options for playing a track.
+#[derive(Debug, Deserialize)]
+struct PlayTrackQuery {
+    /// Play at this quality instead of the default, just this once.
+    quality: Option<String>,
+}
+
+async fn play_track(Path(track_id): Path<i32>, Query(query): Query<PlayTrackQuery>) -> Reply {
+    match query.quality.as_deref().map(str::parse::<AudioQuality>) {
+        Some(Ok(quality)) => {
+            player::controls()
+                .play_track_with_quality(track_id, quality)
+                .await
+        }
+        Some(Err(error)) => return rejected(StatusCode::BAD_REQUEST, &error.to_string()),
+        None => player::controls().play_track(track_id).await,
+    }
+
+    accepted()
+}
+
+async fn play_playlist(Path(playlist_id): Path<i64>) -> Reply {
+    player::controls().play_playlist(playlist_id).await;
+    accepted()
+}
+
+/// Hand a play action to a player already running with the API at
+/// `address`, so `hifi-rs play` doesn't start a second one. None when nothing
+/// answered, or for actions the API has no route for.
+pub async fn forward(mut address: SocketAddr, action: &Action) -> Option<StatusCode> {
+    if address.ip().is_unspecified() {
+        address.set_ip(Ipv4Addr::LOCALHOST.into());
+    }
+
+    let path = match action {
+        Action::PlayAlbum { album_id } => format!("/play/album/{album_id}"),
+        Action::PlayTrack {
+            track_id,
+            quality: Some(quality),
+        } => format!("/play/track/{track_id}?quality={}", quality.clone() as i64),
+        Action::PlayTrack { track_id, .. } => format!("/play/track/{track_id}"),
+        Action::PlayPlaylist { playlist_id } => format!("/play/playlist/{playlist_id}"),
+        _ => return None,
+    };
+
+    let client = match reqwest::Client::builder().timeout(FORWARD_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(error) => {
+            debug!("unable to make a client to forward with: {error}");
+            return None;
+        }
+    };
+
+    match client.post(format!("http://{address}{path}")).send().await {
+        Ok(response) => StatusCode::from_u16(response.status().as_u16()).ok(),
+        Err(error) => {
+            debug!("no player at {address}: {error}");
+            None
+        }
+    }
+}
@@ -9,6 +9,10 @@ mod mpris;
 #[macro_use]
 mod player;
 pub mod cursive;
+#[cfg(feature = "discord")]
+mod discord_rpc;
+#[cfg(feature = "http-api")]
+mod http;
 mod qobuz;
 pub mod service;
 #[macro_use]
@@ -16,4 +20,6 @@ pub mod sql;
 pub mod websocket;
 
 const REFRESH_RESOLUTION: u64 = 250;
+/// Allowed range, in milliseconds, for the position notification interval.
+pub const POSITION_INTERVAL_RANGE: (u64, u64) = (100, 1000);
 pub const TEST_TEMP_PATH: &str = "/tmp/hifirs_test";
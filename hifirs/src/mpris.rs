@@ -1,7 +1,13 @@
 use crate::{
-    player::{self, controls::Controls, notification::Notification, queue::TrackListValue},
+    player::{
+        self,
+        controls::{Controls, RepeatMode},
+        notification::Notification,
+        queue::TrackListValue,
+    },
     service::Track,
 };
+use async_broadcast::RecvError;
 use chrono::{DateTime, Duration, Local};
 use gstreamer::{ClockTime, State as GstState};
 use std::collections::HashMap;
@@ -62,145 +68,205 @@ pub async fn receive_notifications(conn: Connection) {
     let object_server = conn.object_server();
 
     loop {
-        if let Ok(notification) = receiver.recv().await {
-            match notification {
-                Notification::Quit => {
-                    return;
+        let notification = match receiver.recv().await {
+            Ok(notification) => notification,
+            Err(RecvError::Overflowed(skipped)) => {
+                debug!("mpris missed {skipped} notifications");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        match notification {
+            Notification::Quit => {
+                return;
+            }
+            Notification::Loading {
+                is_loading: _,
+                target_state: _,
+            } => {}
+            Notification::Buffering {
+                is_buffering: _,
+                target_state: _,
+                percent: _,
+            } => {
+                let iface_ref = object_server
+                    .interface::<_, MprisPlayer>("/org/mpris/MediaPlayer2")
+                    .await
+                    .expect("failed to get object server");
+
+                iface_ref
+                    .get_mut()
+                    .await
+                    .playback_status_changed(iface_ref.signal_context())
+                    .await
+                    .expect("failed to signal metadata change");
+            }
+            Notification::Status { status } => {
+                let iface_ref = object_server
+                    .interface::<_, MprisPlayer>("/org/mpris/MediaPlayer2")
+                    .await
+                    .expect("failed to get object server");
+
+                let mut iface = iface_ref.get_mut().await;
+                iface.status = status;
+
+                match status {
+                    GstState::Null => {
+                        iface.can_play = true;
+                        iface.can_pause = true;
+                        iface.can_stop = false;
+                    }
+                    GstState::Paused => {
+                        iface.can_play = true;
+                        iface.can_pause = false;
+                        iface.can_stop = true;
+                    }
+                    GstState::Playing => {
+                        iface.position_ts = chrono::offset::Local::now();
+                        iface.can_play = true;
+                        iface.can_pause = true;
+                        iface.can_stop = true;
+                    }
+                    _ => {
+                        iface.can_play = true;
+                        iface.can_pause = true;
+                        iface.can_stop = true;
+                    }
                 }
-                Notification::Loading {
-                    is_loading: _,
-                    target_state: _,
-                } => {}
-                Notification::Buffering {
-                    is_buffering: _,
-                    target_state: _,
-                    percent: _,
-                } => {
-                    let iface_ref = object_server
-                        .interface::<_, MprisPlayer>("/org/mpris/MediaPlayer2")
-                        .await
-                        .expect("failed to get object server");
 
-                    iface_ref
-                        .get_mut()
-                        .await
-                        .playback_status_changed(iface_ref.signal_context())
+                iface
+                    .playback_status_changed(iface_ref.signal_context())
+                    .await
+                    .expect("failed to signal metadata change");
+            }
+            Notification::Position { clock } => {
+                let iface_ref = object_server
+                    .interface::<_, MprisPlayer>("/org/mpris/MediaPlayer2")
+                    .await
+                    .expect("failed to get object server");
+
+                let mut iface = iface_ref.get_mut().await;
+                let now = chrono::offset::Local::now();
+                let diff = now.signed_duration_since(iface.position_ts);
+                let position_secs = clock.seconds();
+
+                if diff.num_seconds() != position_secs as i64 {
+                    debug!("mpris clock drift, sending new position");
+                    iface.position_ts =
+                        chrono::offset::Local::now() - Duration::seconds(position_secs as i64);
+
+                    MprisPlayer::seeked(iface_ref.signal_context(), clock.useconds() as i64)
                         .await
-                        .expect("failed to signal metadata change");
+                        .expect("failed to send seeked signal");
                 }
-                Notification::Status { status } => {
-                    let iface_ref = object_server
+            }
+            Notification::CurrentTrackList { list } => {
+                if let Some(current) = list.current_track() {
+                    let player_ref = object_server
                         .interface::<_, MprisPlayer>("/org/mpris/MediaPlayer2")
                         .await
                         .expect("failed to get object server");
 
-                    let mut iface = iface_ref.get_mut().await;
-                    iface.status = status;
-
-                    match status {
-                        GstState::Null => {
-                            iface.can_play = true;
-                            iface.can_pause = true;
-                            iface.can_stop = false;
-                        }
-                        GstState::Paused => {
-                            iface.can_play = true;
-                            iface.can_pause = false;
-                            iface.can_stop = true;
-                        }
-                        GstState::Playing => {
-                            iface.position_ts = chrono::offset::Local::now();
-                            iface.can_play = true;
-                            iface.can_pause = true;
-                            iface.can_stop = true;
-                        }
-                        _ => {
-                            iface.can_play = true;
-                            iface.can_pause = true;
-                            iface.can_stop = true;
-                        }
+                    let mut player_iface = player_ref.get_mut().await;
+
+                    player_iface.can_previous = current.position != 0;
+
+                    player_iface.can_next = !(player_iface.total_tracks != 0
+                        && current.position == player_iface.total_tracks - 1);
+
+                    if let Some(album) = &current.album {
+                        player_iface.total_tracks = album.total_tracks;
                     }
 
-                    iface
-                        .playback_status_changed(iface_ref.signal_context())
+                    player_iface.current_track = Some(current.clone());
+
+                    player_iface
+                        .metadata_changed(player_ref.signal_context())
                         .await
                         .expect("failed to signal metadata change");
-                }
-                Notification::Position { clock } => {
-                    let iface_ref = object_server
-                        .interface::<_, MprisPlayer>("/org/mpris/MediaPlayer2")
+
+                    let list_ref = object_server
+                        .interface::<_, MprisTrackList>("/org/mpris/MediaPlayer2")
                         .await
                         .expect("failed to get object server");
 
-                    let mut iface = iface_ref.get_mut().await;
-                    let now = chrono::offset::Local::now();
-                    let diff = now.signed_duration_since(iface.position_ts);
-                    let position_secs = clock.seconds();
-
-                    if diff.num_seconds() != position_secs as i64 {
-                        debug!("mpris clock drift, sending new position");
-                        iface.position_ts =
-                            chrono::offset::Local::now() - Duration::seconds(position_secs as i64);
-
-                        MprisPlayer::seeked(iface_ref.signal_context(), clock.useconds() as i64)
-                            .await
-                            .expect("failed to send seeked signal");
-                    }
-                }
-                Notification::CurrentTrackList { list } => {
-                    if let Some(current) = list.current_track() {
-                        let player_ref = object_server
-                            .interface::<_, MprisPlayer>("/org/mpris/MediaPlayer2")
-                            .await
-                            .expect("failed to get object server");
-
-                        let mut player_iface = player_ref.get_mut().await;
-
-                        player_iface.can_previous = current.position != 0;
-
-                        player_iface.can_next = !(player_iface.total_tracks != 0
-                            && current.position == player_iface.total_tracks - 1);
-
-                        if let Some(album) = &current.album {
-                            player_iface.total_tracks = album.total_tracks;
-                        }
-
-                        player_iface.current_track = Some(current.clone());
-
-                        player_iface
-                            .metadata_changed(player_ref.signal_context())
-                            .await
-                            .expect("failed to signal metadata change");
-
-                        let list_ref = object_server
-                            .interface::<_, MprisTrackList>("/org/mpris/MediaPlayer2")
-                            .await
-                            .expect("failed to get object server");
-
-                        let tracks = list
-                            .cursive_list()
-                            .iter()
-                            .map(|t| t.0.clone())
-                            .collect::<Vec<String>>();
-                        let mut list_iface = list_ref.get_mut().await;
-
-                        list_iface.track_list = list;
-
-                        MprisTrackList::track_list_replaced(
-                            list_ref.signal_context(),
-                            tracks,
-                            current.title,
-                        )
-                        .await
-                        .expect("failed to send track list replaced signal");
-                    }
+                    let tracks = list
+                        .cursive_list()
+                        .iter()
+                        .map(|t| t.0.clone())
+                        .collect::<Vec<String>>();
+                    let mut list_iface = list_ref.get_mut().await;
+
+                    list_iface.track_list = list;
+
+                    MprisTrackList::track_list_replaced(
+                        list_ref.signal_context(),
+                        tracks,
+                        current.title,
+                    )
+                    .await
+                    .expect("failed to send track list replaced signal");
                 }
-                Notification::Error { error: _ } => {}
-                Notification::AudioQuality {
-                    bitdepth: _,
-                    sampling_rate: _,
-                } => {}
             }
+            Notification::Error { error: _ } => {}
+            Notification::Reauthenticating => {}
+            Notification::Reauthenticated { success: _ } => {}
+            Notification::SilenceSkipped => {}
+            Notification::TrackSkipped {
+                track_id: _,
+                title: _,
+            } => {}
+            Notification::FavoriteChanged {
+                track_id: _,
+                favorited: _,
+            } => {}
+            Notification::Level { peak: _, rms: _ } => {}
+            Notification::Stopped { reason: _ } => {}
+            Notification::StateDump { snapshot: _ } => {}
+            Notification::Mono { enabled: _ } => {}
+            Notification::Radio { enabled: _ } => {}
+            Notification::SleepTimer { seconds: _ } => {}
+            Notification::QualityFallback {
+                requested: _,
+                granted: _,
+            } => {}
+            Notification::DownloadProgress {
+                track_id: _,
+                title: _,
+                written: _,
+                total: _,
+            } => {}
+            Notification::Repeat { mode: _ } => {
+                let iface_ref = object_server
+                    .interface::<_, MprisPlayer>("/org/mpris/MediaPlayer2")
+                    .await
+                    .expect("failed to get object server");
+
+                iface_ref
+                    .get()
+                    .await
+                    .loop_status_changed(iface_ref.signal_context())
+                    .await
+                    .expect("failed to signal loop status change");
+            }
+            Notification::Volume { level: _ } => {
+                let iface_ref = object_server
+                    .interface::<_, MprisPlayer>("/org/mpris/MediaPlayer2")
+                    .await
+                    .expect("failed to get object server");
+
+                iface_ref
+                    .get()
+                    .await
+                    .volume_changed(iface_ref.signal_context())
+                    .await
+                    .expect("failed to signal volume change");
+            }
+            Notification::AudioQuality {
+                bitdepth: _,
+                sampling_rate: _,
+            } => {}
         }
     }
 }
@@ -280,6 +346,51 @@ impl MprisPlayer {
     async fn previous(&self) {
         self.controls.previous().await;
     }
+    /// Moves by `offset` microseconds. Seeking past the end goes to the next
+    /// track and seeking before the start goes to the start, per the spec.
+    async fn seek(&self, offset: i64) {
+        let current = player::position().unwrap_or_default().useconds();
+        let position = i64::try_from(current)
+            .unwrap_or(i64::MAX)
+            .saturating_add(offset);
+
+        if let Some(track) = &self.current_track {
+            let duration = ClockTime::from_seconds(track.duration_seconds as u64).useconds() as i64;
+
+            if position > duration {
+                self.controls.next().await;
+                return;
+            }
+        }
+
+        self.controls
+            .seek(ClockTime::from_useconds(position.max(0) as u64))
+            .await;
+    }
+    async fn set_position(&self, track_id: zvariant::ObjectPath<'_>, position: i64) {
+        let current_id = self.current_track.as_ref().map(|t| t.id);
+
+        // Stale requests for a track that is no longer playing are ignored, per the spec.
+        if !is_current_track(current_id, track_id.as_str()) {
+            debug!(
+                "ignoring set position for stale track id {}",
+                track_id.as_str()
+            );
+            return;
+        }
+
+        if let Some(track) = &self.current_track {
+            let duration = ClockTime::from_seconds(track.duration_seconds as u64);
+
+            if position < 0 || position as u64 > duration.useconds() {
+                return;
+            }
+
+            self.controls
+                .seek(ClockTime::from_useconds(position as u64))
+                .await;
+        }
+    }
     #[dbus_interface(property, name = "PlaybackStatus")]
     async fn playback_status(&self) -> String {
         match self.status {
@@ -291,8 +402,22 @@ impl MprisPlayer {
         }
     }
     #[dbus_interface(property, name = "LoopStatus")]
-    fn loop_status(&self) -> &'static str {
-        "None"
+    async fn loop_status(&self) -> &'static str {
+        match player::repeat_mode().await {
+            RepeatMode::Off => "None",
+            RepeatMode::All => "Playlist",
+            RepeatMode::One => "Track",
+        }
+    }
+    #[dbus_interface(property, name = "LoopStatus")]
+    async fn set_loop_status(&mut self, status: String) {
+        let mode = match status.as_str() {
+            "Playlist" => RepeatMode::All,
+            "Track" => RepeatMode::One,
+            _ => RepeatMode::Off,
+        };
+
+        self.controls.set_repeat(mode).await;
     }
     #[dbus_interface(property, name = "Rate")]
     fn rate(&self) -> f64 {
@@ -313,7 +438,11 @@ impl MprisPlayer {
     }
     #[dbus_interface(property, name = "Volume")]
     fn volume(&self) -> f64 {
-        1.0
+        player::volume()
+    }
+    #[dbus_interface(property, name = "Volume")]
+    async fn set_volume(&mut self, level: f64) {
+        self.controls.set_volume(level).await;
     }
     #[dbus_interface(property, name = "Position")]
     async fn position(&self) -> i64 {
@@ -420,10 +549,7 @@ fn track_to_meta(playlist_track: Track) -> HashMap<&'static str, zvariant::Value
 
     meta.insert(
         "mpris:trackid",
-        zvariant::Value::new(format!(
-            "/org/hifirs/Player/TrackList/{}",
-            playlist_track.id
-        )),
+        zvariant::Value::new(track_object_path(playlist_track.id)),
     );
     meta.insert(
         "xesam:title",
@@ -449,7 +575,10 @@ fn track_to_meta(playlist_track: Track) -> HashMap<&'static str, zvariant::Value
     }
 
     if let Some(album) = playlist_track.album {
-        meta.insert("mpris:artUrl", zvariant::Value::new(album.cover_art));
+        if let Some(cover_art) = album.cover_art {
+            meta.insert("mpris:artUrl", zvariant::Value::new(cover_art));
+        }
+
         meta.insert(
             "xesam:album",
             zvariant::Value::new(album.title.trim().to_string()),
@@ -466,3 +595,31 @@ fn track_to_meta(playlist_track: Track) -> HashMap<&'static str, zvariant::Value
 
     meta
 }
+
+fn track_object_path(track_id: u32) -> String {
+    format!("/org/hifirs/Player/TrackList/{track_id}")
+}
+
+fn is_current_track(current_id: Option<u32>, track_id: &str) -> bool {
+    if let Some(id) = current_id {
+        track_object_path(id) == track_id
+    } else {
+        false
+    }
+}
+
+#[test]
+fn set_position_ignores_stale_track_id() {
+    assert!(is_current_track(
+        Some(155999429),
+        "/org/hifirs/Player/TrackList/155999429"
+    ));
+    assert!(!is_current_track(
+        Some(155999429),
+        "/org/hifirs/Player/TrackList/64868955"
+    ));
+    assert!(!is_current_track(
+        None,
+        "/org/hifirs/Player/TrackList/155999429"
+    ));
+}
@@ -1,7 +1,68 @@
-use crate::action;
+use crate::{action, player::queue::export::ExportFormat};
+use clap::ValueEnum;
 use flume::{Receiver, Sender};
+use gstreamer::ClockTime;
+use hifirs_qobuz_api::client::{parse_url, AudioQuality, UrlType};
 use serde::{Deserialize, Serialize};
 
+/// What happens when the queue, or the current track, runs out.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RepeatMode {
+    /// Stop at the end of the queue.
+    #[default]
+    Off,
+    /// Start the queue over from the first track.
+    All,
+    /// Play the current track again.
+    One,
+}
+
+impl RepeatMode {
+    /// The mode after this one, in the order off, all, one.
+    pub fn next(self) -> Self {
+        match self {
+            RepeatMode::Off => RepeatMode::All,
+            RepeatMode::All => RepeatMode::One,
+            RepeatMode::One => RepeatMode::Off,
+        }
+    }
+}
+
+/// How ReplayGain is applied to even out loudness between tracks.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum NormalizationMode {
+    /// Play tracks as they are.
+    #[default]
+    Off,
+    /// Bring every track to the same loudness.
+    Track,
+    /// Keep the differences between tracks of an album, using album gain
+    /// when the stream has it.
+    Album,
+}
+
+impl NormalizationMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NormalizationMode::Off => "off",
+            NormalizationMode::Track => "track",
+            NormalizationMode::Album => "album",
+        }
+    }
+}
+
+impl From<&str> for NormalizationMode {
+    fn from(value: &str) -> Self {
+        match value {
+            "track" => NormalizationMode::Track,
+            "album" => NormalizationMode::Album,
+            _ => NormalizationMode::Off,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum Action {
@@ -12,17 +73,173 @@ pub enum Action {
     Previous,
     Stop,
     Quit,
-    SkipTo { num: u32 },
-    JumpForward,
-    JumpBackward,
-    PlayAlbum { album_id: String },
-    PlayTrack { track_id: i32 },
-    PlayUri { uri: String },
-    PlayPlaylist { playlist_id: i64 },
-    Search { query: String },
-    FetchArtistAlbums { artist_id: i32 },
-    FetchPlaylistTracks { playlist_id: i64 },
+    SkipTo {
+        num: u32,
+    },
+    /// Jump ahead in the current track, by the configured step when no
+    /// seconds are given.
+    JumpForward {
+        seconds: Option<u64>,
+    },
+    /// Jump back in the current track, by the configured step when no
+    /// seconds are given.
+    JumpBackward {
+        seconds: Option<u64>,
+    },
+    Seek {
+        time: ClockTime,
+    },
+    /// Seek to an absolute position in the current track, clamped to its duration.
+    SeekTo {
+        seconds: u64,
+    },
+    SetPreamp {
+        db: f64,
+    },
+    /// Output volume, from 0.0 to 1.0.
+    SetVolume {
+        level: f64,
+    },
+    SetSkipSilence {
+        enabled: bool,
+        threshold: i32,
+    },
+    SetMono {
+        enabled: bool,
+    },
+    SetRepeat {
+        mode: RepeatMode,
+    },
+    SetNormalization {
+        mode: NormalizationMode,
+    },
+    /// Stream at this quality from the next track on.
+    SetQuality {
+        quality: AudioQuality,
+    },
+    PlayAlbum {
+        album_id: String,
+    },
+    /// Play an album, starting at one of its tracks.
+    PlayAlbumFrom {
+        album_id: String,
+        track_id: i32,
+    },
+    PlayTrack {
+        track_id: i32,
+        /// Play at this quality instead of the default, for this play only.
+        #[serde(default)]
+        quality: Option<AudioQuality>,
+    },
+    PlayUri {
+        uri: String,
+    },
+    PlayPlaylist {
+        playlist_id: i64,
+    },
+    /// Play a track followed by tracks suggested from it.
+    PlayTrackRadio {
+        track_id: i32,
+    },
+    /// Play a track radio that keeps going with more suggestions once the
+    /// queue runs out.
+    StartRadio {
+        track_id: i32,
+    },
+    /// Keep the queue going with suggested tracks once it runs out.
+    SetRadio {
+        enabled: bool,
+    },
+    /// Add a track to the end of the queue.
+    AddToQueue {
+        track_id: i32,
+    },
+    /// Queue a track to play right after the current one.
+    PlayNext {
+        track_id: i32,
+    },
+    /// Take the track at this queue position out of the queue.
+    RemoveFromQueue {
+        position: u32,
+    },
+    /// Move the track at one queue position to another. Saved to the
+    /// playlist too when a playlist is playing.
+    MoveTrack {
+        from: u32,
+        to: u32,
+    },
+    ExportQueue {
+        path: String,
+        /// Picked from the file extension when not given.
+        #[serde(default)]
+        format: Option<ExportFormat>,
+    },
+    CancelLoad,
+    RetryLogin,
+    ToggleFavoriteCurrent,
+    Search {
+        query: String,
+    },
+    FetchArtistAlbums {
+        artist_id: i32,
+    },
+    FetchPlaylistTracks {
+        playlist_id: i64,
+    },
     FetchUserPlaylists,
+    /// Answered with the next tracks in the queue.
+    QueuePeek {
+        count: usize,
+    },
+    /// Answered with the most recently played tracks.
+    QueuePrevious {
+        count: usize,
+    },
+    /// Log a snapshot of the player state, for bug reports.
+    DumpState,
+    /// Stop playback after this many minutes, fading out at the end.
+    SetSleepTimer {
+        minutes: u32,
+    },
+    CancelSleepTimer,
+    /// Save the current track, or the whole album it is from, to the library.
+    Download {
+        album: bool,
+    },
+}
+
+impl Action {
+    /// Actions that fetch and buffer a new track, which can be cancelled.
+    pub fn is_load(&self) -> bool {
+        matches!(
+            self,
+            Action::Next
+                | Action::Previous
+                | Action::SkipTo { .. }
+                | Action::PlayAlbum { .. }
+                | Action::PlayAlbumFrom { .. }
+                | Action::PlayTrack { .. }
+                | Action::PlayUri { .. }
+                | Action::PlayPlaylist { .. }
+                | Action::PlayTrackRadio { .. }
+                | Action::StartRadio { .. }
+        )
+    }
+}
+
+/// The action that plays what a Qobuz link points at: an
+/// `https://open.qobuz.com/album/...`, `/track/...` or `/playlist/...` link,
+/// a `qobuz://` link, or any other form `parse_url` reads. None when it isn't
+/// a link to something playable.
+pub fn parse_qobuz_uri(uri: &str) -> Option<Action> {
+    match parse_url(uri).ok()? {
+        UrlType::Album { id } => Some(Action::PlayAlbum { album_id: id }),
+        UrlType::Track { id } => Some(Action::PlayTrack {
+            track_id: id,
+            quality: None,
+        }),
+        UrlType::Playlist { id } => Some(Action::PlayPlaylist { playlist_id: id }),
+    }
 }
 
 /// Provides controls for other modules to send commands
@@ -45,6 +262,9 @@ impl Controls {
     pub fn action_receiver(&self) -> Receiver<Action> {
         self.action_rx.clone()
     }
+    pub async fn send(&self, action: Action) {
+        action!(self, action);
+    }
     pub async fn play(&self) {
         action!(self, Action::Play);
     }
@@ -69,24 +289,127 @@ impl Controls {
     pub async fn skip_to(&self, num: u32) {
         action!(self, Action::SkipTo { num });
     }
-    pub async fn jump_forward(&self) {
-        action!(self, Action::JumpForward);
+    pub async fn jump_forward(&self, seconds: Option<u64>) {
+        action!(self, Action::JumpForward { seconds });
+    }
+    pub async fn jump_backward(&self, seconds: Option<u64>) {
+        action!(self, Action::JumpBackward { seconds });
+    }
+    pub async fn seek(&self, time: ClockTime) {
+        action!(self, Action::Seek { time });
+    }
+    pub async fn seek_to(&self, seconds: u64) {
+        action!(self, Action::SeekTo { seconds });
+    }
+    pub async fn set_skip_silence(&self, enabled: bool, threshold: i32) {
+        action!(self, Action::SetSkipSilence { enabled, threshold });
+    }
+    pub async fn set_repeat(&self, mode: RepeatMode) {
+        action!(self, Action::SetRepeat { mode });
+    }
+    /// Switch to the next repeat mode.
+    pub async fn cycle_repeat(&self) {
+        let mode = super::repeat_mode().await.next();
+        self.set_repeat(mode).await;
+    }
+    pub async fn set_normalization(&self, mode: NormalizationMode) {
+        action!(self, Action::SetNormalization { mode });
+    }
+    pub async fn set_quality(&self, quality: AudioQuality) {
+        action!(self, Action::SetQuality { quality });
+    }
+    pub async fn set_mono(&self, enabled: bool) {
+        action!(self, Action::SetMono { enabled });
     }
-    pub async fn jump_backward(&self) {
-        action!(self, Action::JumpBackward);
+    pub async fn set_volume(&self, level: f64) {
+        action!(self, Action::SetVolume { level });
+    }
+    /// Raise the volume by `VOLUME_STEP`.
+    pub async fn volume_up(&self) {
+        self.set_volume(super::volume() + super::VOLUME_STEP).await;
+    }
+    /// Lower the volume by `VOLUME_STEP`.
+    pub async fn volume_down(&self) {
+        self.set_volume(super::volume() - super::VOLUME_STEP).await;
+    }
+    pub async fn set_preamp(&self, db: f64) {
+        action!(self, Action::SetPreamp { db });
     }
     pub async fn play_album(&self, album_id: String) {
         action!(self, Action::PlayAlbum { album_id });
     }
+    pub async fn play_album_from(&self, album_id: String, track_id: i32) {
+        action!(self, Action::PlayAlbumFrom { album_id, track_id });
+    }
     pub async fn play_uri(&self, uri: String) {
         action!(self, Action::PlayUri { uri });
     }
     pub async fn play_track(&self, track_id: i32) {
-        action!(self, Action::PlayTrack { track_id });
+        action!(
+            self,
+            Action::PlayTrack {
+                track_id,
+                quality: None
+            }
+        );
+    }
+    pub async fn play_track_with_quality(&self, track_id: i32, quality: AudioQuality) {
+        action!(
+            self,
+            Action::PlayTrack {
+                track_id,
+                quality: Some(quality)
+            }
+        );
     }
     pub async fn play_playlist(&self, playlist_id: i64) {
         action!(self, Action::PlayPlaylist { playlist_id })
     }
+    pub async fn play_track_radio(&self, track_id: i32) {
+        action!(self, Action::PlayTrackRadio { track_id })
+    }
+    pub async fn start_radio(&self, track_id: i32) {
+        action!(self, Action::StartRadio { track_id })
+    }
+    pub async fn set_radio(&self, enabled: bool) {
+        action!(self, Action::SetRadio { enabled })
+    }
+    pub async fn add_to_queue(&self, track_id: i32) {
+        action!(self, Action::AddToQueue { track_id })
+    }
+    pub async fn play_next(&self, track_id: i32) {
+        action!(self, Action::PlayNext { track_id })
+    }
+    pub async fn remove_from_queue(&self, position: u32) {
+        action!(self, Action::RemoveFromQueue { position })
+    }
+    pub async fn move_track(&self, from: u32, to: u32) {
+        action!(self, Action::MoveTrack { from, to })
+    }
+    pub async fn cancel_load(&self) {
+        action!(self, Action::CancelLoad)
+    }
+    pub async fn retry_login(&self) {
+        action!(self, Action::RetryLogin)
+    }
+    pub async fn toggle_favorite_current(&self) {
+        action!(self, Action::ToggleFavoriteCurrent)
+    }
+    pub async fn export_queue(&self, path: String, format: Option<ExportFormat>) {
+        action!(self, Action::ExportQueue { path, format })
+    }
+    pub async fn dump_state(&self) {
+        action!(self, Action::DumpState)
+    }
+    pub async fn set_sleep_timer(&self, minutes: u32) {
+        action!(self, Action::SetSleepTimer { minutes })
+    }
+    pub async fn cancel_sleep_timer(&self) {
+        action!(self, Action::CancelSleepTimer)
+    }
+    pub async fn download(&self, album: bool) {
+        action!(self, Action::Download { album })
+    }
 }
 
 impl Default for Controls {
@@ -94,3 +417,51 @@ impl Default for Controls {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qobuz_links_become_play_actions() {
+        match parse_qobuz_uri("https://open.qobuz.com/album/lzqw5n2ft5pmb") {
+            Some(Action::PlayAlbum { album_id }) => assert_eq!(album_id, "lzqw5n2ft5pmb"),
+            other => panic!("expected an album, got {other:?}"),
+        }
+
+        match parse_qobuz_uri("https://open.qobuz.com/track/52151402") {
+            Some(Action::PlayTrack { track_id, quality }) => {
+                assert_eq!(track_id, 52151402);
+                assert!(quality.is_none());
+            }
+            other => panic!("expected a track, got {other:?}"),
+        }
+
+        match parse_qobuz_uri("https://open.qobuz.com/playlist/1141084") {
+            Some(Action::PlayPlaylist { playlist_id }) => assert_eq!(playlist_id, 1141084),
+            other => panic!("expected a playlist, got {other:?}"),
+        }
+
+        match parse_qobuz_uri("qobuz://album/0634904032463") {
+            Some(Action::PlayAlbum { album_id }) => assert_eq!(album_id, "0634904032463"),
+            other => panic!("expected an album, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn malformed_links_are_rejected() {
+        for uri in [
+            "",
+            "open.qobuz.com/album/lzqw5n2ft5pmb",
+            "https://open.qobuz.com/",
+            "https://open.qobuz.com/album/",
+            "https://open.qobuz.com/track/abc",
+            "https://open.qobuz.com/playlist/12.5",
+            "https://open.qobuz.com/artist/36819",
+            "https://open.spotify.com/album/lzqw5n2ft5pmb",
+            "qobuz://track",
+        ] {
+            assert!(parse_qobuz_uri(uri).is_none(), "{uri}");
+        }
+    }
+}
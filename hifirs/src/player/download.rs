@@ -0,0 +1,269 @@
+use crate::{
+    player::{error::Error, notification::Notification, Result, BROADCAST_CHANNELS},
+    service::{Album, MusicService, Track},
+};
+use hifirs_qobuz_api::client::AudioQuality;
+use lofty::{Accessor, ItemKey, ItemValue, Probe, Tag, TagExt, TagItem, TaggedFileExt};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Least time between two progress notifications for the same download.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// File extension for downloads at the given quality.
+pub fn extension(quality: &AudioQuality) -> &'static str {
+    match quality {
+        AudioQuality::Mp3 => "mp3",
+        _ => "flac",
+    }
+}
+
+/// Where a track is saved: `<library>/<artist>/<album>/<NN> - <title>.<extension>`.
+/// Tracks of albums with more than one disc are prefixed with the disc,
+/// `<disc>-<NN> - <title>.<extension>`, so the discs don't overwrite each other.
+pub fn track_path(
+    library: &Path,
+    track: &Track,
+    album: Option<&Album>,
+    extension: &str,
+) -> PathBuf {
+    let multi_disc = track.media_number > 1 || album.map_or(false, |a| !a.disc_starts().is_empty());
+    let number = if multi_disc {
+        format!("{}-{:02}", track.media_number, track.number)
+    } else {
+        format!("{:02}", track.number)
+    };
+
+    let mut path = library.to_path_buf();
+    path.push(sanitize(&artist_name(track, album)));
+    path.push(sanitize(&album_title(track, album)));
+    path.push(format!("{number} - {}.{extension}", sanitize(&track.title)));
+
+    path
+}
+
+/// Save a track to the library and tag it. Unstreamable tracks are skipped
+/// and tracks already in the library are left alone. A partial file from an
+/// earlier attempt is picked up where it stopped.
+pub async fn download_track(
+    service: Arc<dyn MusicService>,
+    track: &Track,
+    album: Option<&Album>,
+    library: &Path,
+) -> Result<Option<PathBuf>> {
+    if !track.available {
+        debug!("skipping unstreamable track {}", track.id);
+        return Ok(None);
+    }
+
+    let extension = extension(&service.default_quality());
+    let path = track_path(library, track, album, extension);
+
+    if path.exists() {
+        debug!("{} is already downloaded", path.display());
+        return Ok(Some(path));
+    }
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await.map_err(io_error)?;
+    }
+
+    let track_id = track.id;
+    let title = track.title.clone();
+    let mut last_percent = None;
+    let mut last_sent: Option<Instant> = None;
+
+    let on_progress = Box::new(move |written: u64, total: Option<u64>| {
+        let percent = total.map(|total| written * 100 / total.max(1));
+        let complete = total.map_or(false, |total| written >= total);
+
+        // Only tell listeners when there is something new to show, and not
+        // more often than they can draw it. The end is always sent.
+        if percent.is_some() && percent == last_percent {
+            return;
+        }
+        if !complete && last_sent.map_or(false, |sent| sent.elapsed() < PROGRESS_INTERVAL) {
+            return;
+        }
+        last_percent = percent;
+        last_sent = Some(Instant::now());
+
+        let notification = Notification::DownloadProgress {
+            track_id,
+            title: title.clone(),
+            written,
+            total,
+        };
+
+        if let Err(error) = BROADCAST_CHANNELS.tx.try_broadcast(notification) {
+            debug!("unable to send download progress: {error}");
+        }
+    });
+
+    if !service
+        .download_track(track.id as i32, &path, on_progress)
+        .await
+    {
+        return Err(Error::Download {
+            message: format!("failed to download {}", track.title),
+        });
+    }
+
+    write_tags(&path, track, album)?;
+
+    Ok(Some(path))
+}
+
+/// Save every streamable track of an album to the library. A track that
+/// fails doesn't stop the rest, the error lists every track that failed.
+pub async fn download_album(
+    service: Arc<dyn MusicService>,
+    album: &Album,
+    library: &Path,
+) -> Result<()> {
+    let mut failed = Vec::new();
+
+    for track in album.tracks.values() {
+        if let Err(error) = download_track(service.clone(), track, Some(album), library).await {
+            warn!("failed to download {}: {error}", track.title);
+            failed.push(track.title.trim().to_string());
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Download {
+            message: format!("failed to download {}", failed.join(", ")),
+        })
+    }
+}
+
+fn write_tags(path: &Path, track: &Track, album: Option<&Album>) -> Result<()> {
+    let tag_error = |error: lofty::LoftyError| Error::Download {
+        message: format!("failed to tag {}: {error}", path.display()),
+    };
+
+    let mut file = Probe::open(path)
+        .and_then(|probe| probe.read())
+        .map_err(tag_error)?;
+
+    if file.primary_tag().is_none() {
+        file.insert_tag(Tag::new(file.primary_tag_type()));
+    }
+
+    if let Some(tag) = file.primary_tag_mut() {
+        tag.set_title(track.title.clone());
+        tag.set_artist(artist_name(track, album));
+        tag.set_album(album_title(track, album));
+        tag.set_track(track.number);
+
+        if let Some(genre) = &track.genre {
+            tag.set_genre(genre.clone());
+        }
+        if let Some(composer) = &track.composer {
+            tag.insert_text(ItemKey::Composer, composer.clone());
+        }
+        if let Some(isrc) = &track.isrc {
+            tag.insert_text(ItemKey::Isrc, isrc.clone());
+        }
+        for credit in &track.credits {
+            tag.push(TagItem::new(
+                ItemKey::Performer,
+                ItemValue::Text(credit.to_string()),
+            ));
+        }
+
+        tag.save_to_path(path).map_err(tag_error)?;
+    }
+
+    Ok(())
+}
+
+fn io_error(error: std::io::Error) -> Error {
+    Error::Download {
+        message: error.to_string(),
+    }
+}
+
+fn artist_name(track: &Track, album: Option<&Album>) -> String {
+    track
+        .artist
+        .as_ref()
+        .or(album.map(|a| &a.artist))
+        .map(|a| a.name.clone())
+        .unwrap_or_else(|| "Unknown Artist".to_string())
+}
+
+fn album_title(track: &Track, album: Option<&Album>) -> String {
+    album
+        .or(track.album.as_ref())
+        .map(|a| a.title.clone())
+        .unwrap_or_else(|| "Unknown Album".to_string())
+}
+
+/// Replace characters that are not allowed in file names on common filesystems.
+/// Names that would point at the directory itself or its parent, like `..`,
+/// are replaced as well.
+fn sanitize(name: &str) -> String {
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    match name.as_str() {
+        "" | "." | ".." => "_".repeat(name.len().max(1)),
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+use crate::service::Artist;
+
+#[cfg(test)]
+fn track() -> Track {
+    Track {
+        number: 3,
+        title: "What/If?".to_string(),
+        artist: Some(Artist {
+            id: 1,
+            name: "AC/DC".to_string(),
+            albums: None,
+        }),
+        position: 3,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn paths_are_grouped_by_artist_and_album() {
+    assert_eq!(
+        track_path(Path::new("/music"), &track(), None, "flac"),
+        PathBuf::from("/music/AC_DC/Unknown Album/03 - What_If_.flac")
+    );
+
+    let second_disc = Track {
+        media_number: 2,
+        ..track()
+    };
+    assert_eq!(
+        track_path(Path::new("/music"), &second_disc, None, "flac"),
+        PathBuf::from("/music/AC_DC/Unknown Album/2-03 - What_If_.flac")
+    );
+}
+
+#[test]
+fn names_stay_inside_the_library() {
+    assert_eq!(sanitize(".."), "__");
+    assert_eq!(sanitize(" . "), "_");
+    assert_eq!(sanitize(""), "_");
+    assert_eq!(sanitize("...And Justice for All"), "...And Justice for All");
+}
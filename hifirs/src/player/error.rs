@@ -2,7 +2,7 @@ use gstreamer::{glib, traits::GstObjectExt, StateChangeError};
 use serde::{Deserialize, Serialize};
 use snafu::prelude::*;
 
-use crate::player::notification::Notification;
+use crate::{player::notification::Notification, service::StreamError};
 
 #[derive(Snafu, Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum Error {
@@ -10,8 +10,12 @@ pub enum Error {
     FailedToPlay {
         message: String,
     },
-    #[snafu(display("failed to retrieve a track url"))]
+    #[snafu(display("failed to retrieve a track url, it may not be streamable in your region"))]
     TrackURL,
+    #[snafu(display("unable to stream the track: {message}"))]
+    Stream {
+        message: String,
+    },
     #[snafu(display("failed to seek"))]
     Seek,
     #[snafu(display("sorry, could not resume previous session"))]
@@ -24,10 +28,32 @@ pub enum Error {
     Client {
         message: String,
     },
+    #[snafu(display("failed to export: {message}"))]
+    Export {
+        message: String,
+    },
+    #[snafu(display("download failed: {message}"))]
+    Download {
+        message: String,
+    },
+    #[snafu(display("unable to change the queue: {message}"))]
+    Queue {
+        message: String,
+    },
+    #[snafu(display("unable to update favorites, are you signed in?"))]
+    Favorite,
     NotificationError,
     App,
 }
 
+impl Error {
+    /// Errors that leave the player unable to carry on, as opposed to a
+    /// single track or request failing.
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Error::Resume | Error::Client { .. } | Error::App)
+    }
+}
+
 impl From<glib::Error> for Error {
     fn from(value: glib::Error) -> Self {
         Error::GStreamer {
@@ -60,6 +86,23 @@ impl From<hifirs_qobuz_api::Error> for Error {
     }
 }
 
+impl From<StreamError> for Error {
+    fn from(value: StreamError) -> Self {
+        match value {
+            StreamError::Refused => Error::TrackURL,
+            StreamError::Unavailable { message } => Error::Stream { message },
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Error::Export {
+            message: value.to_string(),
+        }
+    }
+}
+
 impl From<flume::SendError<Notification>> for Error {
     fn from(_value: flume::SendError<Notification>) -> Self {
         Self::NotificationError
@@ -1,17 +1,23 @@
 use crate::{
     player::{
-        controls::{Action, Controls},
+        controls::{Action, Controls, NormalizationMode, RepeatMode},
         error::Error,
-        notification::{BroadcastReceiver, BroadcastSender, Notification},
+        notification::{BroadcastReceiver, BroadcastSender, Notification, StopReason},
         queue::{
-            controls::{PlayerState, SafePlayerState},
-            TrackListValue,
+            controls::{PlayerState, PrefetchedUrl, SafePlayerState},
+            export::ExportFormat,
+            TrackListType, TrackListValue,
         },
     },
-    service::{Album, Playlist, SearchResults, Track},
-    REFRESH_RESOLUTION,
+    qobuz,
+    service::{
+        Account, Album, Article, Artist, Favorites, Playlist, Purchases, SearchResults,
+        StreamError, StreamUrl, Track,
+    },
+    sql::db,
+    POSITION_INTERVAL_RANGE,
 };
-use cached::proc_macro::cached;
+use cached::{proc_macro::cached, Cached};
 use flume::{Receiver, Sender};
 use futures::prelude::*;
 use gst::{
@@ -19,20 +25,26 @@ use gst::{
     State as GstState, StateChangeSuccess, Structure,
 };
 use gstreamer as gst;
-use hifirs_qobuz_api::client::{self, UrlType};
+use hifirs_qobuz_api::client::{self, api::Reauthentication, AudioQuality, UrlType};
 use once_cell::sync::{Lazy, OnceCell};
 use std::{
+    io::Write,
     str::FromStr,
     sync::{
-        atomic::{AtomicBool, AtomicU32, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicI64, AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
     },
-    time::Duration,
+    time::{Duration, Instant},
+};
+use tokio::{
+    select,
+    sync::{broadcast::error::RecvError, RwLock, RwLockWriteGuard},
+    task::AbortHandle,
 };
-use tokio::{select, sync::RwLock};
 
 #[macro_use]
 pub mod controls;
+pub mod download;
 pub mod error;
 pub mod notification;
 #[macro_use]
@@ -42,6 +54,119 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 static VERSION: Lazy<(u32, u32, u32, u32)> = Lazy::new(gstreamer::version);
 
+/// How much `volume_up` and `volume_down` change the volume.
+pub const VOLUME_STEP: f64 = 0.05;
+
+/// Range, in dB, the pre-amp gain is clamped to.
+pub const PREAMP_RANGE: f64 = 12.0;
+
+/// How often, in seconds of playback, the position is saved for resuming.
+const SAVE_POSITION_INTERVAL: u64 = 30;
+
+/// Least time between two position notifications from the clock loop, unless
+/// the second changes. At the shortest clock interval this halves the updates
+/// the UIs redraw for, from ten a second to five.
+const POSITION_THROTTLE: Duration = Duration::from_millis(250);
+
+/// How long the sleep timer takes to fade the volume out before stopping.
+const SLEEP_FADE: Duration = Duration::from_secs(30);
+
+/// Seconds into a track before the url of the next one is prefetched.
+const PREFETCH_AFTER_SECONDS: u64 = 5;
+
+/// Suggested tracks added each time the radio runs out of queue.
+const RADIO_BATCH: usize = 10;
+
+/// Silence shorter than this is never skipped, so quiet passages are left alone.
+const MINIMUM_SILENCE: ClockTime = ClockTime::from_seconds(3);
+
+/// Elements the player can't work without, and the package that provides each.
+const REQUIRED_ELEMENTS: &[(&str, &str)] = &[
+    ("playbin3", "gst-plugins-base"),
+    ("audioconvert", "gst-plugins-base"),
+    ("volume", "gst-plugins-base"),
+    ("souphttpsrc", "gst-plugins-good"),
+    ("flacparse", "gst-plugins-good"),
+    ("flacdec", "gst-plugins-good"),
+    ("mpg123audiodec", "gst-plugins-good"),
+    ("autoaudiosink", "gst-plugins-good"),
+];
+
+/// Tracks requested per page when loading the rest of a playlist.
+const PLAYLIST_PAGE_LIMIT: u32 = 500;
+
+/// How often the player checks whether it has been idle for too long.
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Longest wait for a newly loaded track to pause, before it counts as failed.
+const PAUSE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How often the level meter posts peak and RMS values.
+const LEVEL_INTERVAL: ClockTime = ClockTime::from_mseconds(100);
+
+/// Caps that make the mono stage sum every channel into one.
+const MONO_CAPS: &str = "audio/x-raw,channels=1";
+
+/// Bin of audio elements placed in front of the playbin sink. Holds the
+/// pre-amp, which is independent of the user volume, the optional
+/// silence remover when the plugin is installed, the mono downmix stage
+/// and the level meter when it is enabled.
+///
+/// The mono stage sits after the pre-amp so the downmix sees the final
+/// gain. Its first audioconvert averages the channels, keeping the level,
+/// and the second one spreads the result back out to what the sink wants.
+static AUDIO_FILTER: Lazy<gst::Bin> = Lazy::new(|| {
+    gst::init().expect("error initializing gstreamer");
+
+    let mut description = if gst::ElementFactory::find("removesilence").is_some() {
+        "audioconvert ! removesilence name=silence remove=false silent=false ! audioconvert ! volume name=preamp".to_string()
+    } else {
+        debug!("removesilence element not available, skip silence is disabled");
+        "audioconvert ! volume name=preamp".to_string()
+    };
+
+    if gst::ElementFactory::find("rgvolume").is_some()
+        && gst::ElementFactory::find("rglimiter").is_some()
+    {
+        description.push_str(" ! rgvolume name=replaygain ! rglimiter name=rglimiter");
+    } else {
+        debug!("rgvolume or rglimiter element not available, normalization is disabled");
+    }
+
+    description.push_str(" ! audioconvert ! capsfilter name=mono ! audioconvert");
+
+    if LEVEL_METER.load(Ordering::Relaxed) {
+        description.push_str(&format!(
+            " ! level name=level post-messages=true interval={}",
+            LEVEL_INTERVAL.nseconds()
+        ));
+    }
+
+    let bin =
+        gst::parse_bin_from_description(&description, true).expect("error building audio filter");
+
+    // rgvolume only sees the stream's gain tags when they are wanted, so
+    // without them it applies `fallback-gain` instead.
+    if let Some(pad) = bin
+        .by_name("replaygain")
+        .and_then(|replaygain| replaygain.static_pad("sink"))
+    {
+        pad.add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, |_, info| {
+            match &info.data {
+                Some(gst::PadProbeData::Event(event))
+                    if event.type_() == gst::EventType::Tag
+                        && !REPLAYGAIN_TAGS.load(Ordering::Relaxed) =>
+                {
+                    gst::PadProbeReturn::Drop
+                }
+                _ => gst::PadProbeReturn::Ok,
+            }
+        });
+    }
+
+    bin
+});
+
 static PLAYBIN: Lazy<Element> = Lazy::new(|| {
     gst::init().expect("error initializing gstreamer");
 
@@ -50,6 +175,7 @@ static PLAYBIN: Lazy<Element> = Lazy::new(|| {
         .expect("error building playbin element");
 
     playbin.set_property_from_str("flags", "audio+buffering");
+    playbin.set_property("audio-filter", &*AUDIO_FILTER);
     if VERSION.1 >= 22 {
         playbin.connect("element-setup", false, |value| {
             let element = &value[1].get::<gst::Element>().unwrap();
@@ -113,6 +239,15 @@ static BROADCAST_CHANNELS: Lazy<Broadcast> = Lazy::new(|| {
 
     Broadcast { rx, tx }
 });
+/// `Notification::Level` arrives ten times a second, so it has a channel of
+/// its own where it can't push the other notifications out. Only the latest
+/// reading is kept.
+static LEVEL_CHANNELS: Lazy<Broadcast> = Lazy::new(|| {
+    let (mut tx, rx) = async_broadcast::broadcast(1);
+    tx.set_overflow(true);
+
+    Broadcast { rx, tx }
+});
 
 struct AboutToFinish {
     tx: Sender<bool>,
@@ -125,11 +260,40 @@ static ABOUT_TO_FINISH: Lazy<AboutToFinish> = Lazy::new(|| {
     AboutToFinish { tx, rx }
 });
 static QUIT_WHEN_DONE: AtomicBool = AtomicBool::new(false);
+static LEVEL_METER: AtomicBool = AtomicBool::new(false);
 static IS_BUFFERING: AtomicBool = AtomicBool::new(false);
 static IS_LIVE: AtomicBool = AtomicBool::new(false);
 static SAMPLING_RATE: AtomicU32 = AtomicU32::new(44100);
 static BIT_DEPTH: AtomicU32 = AtomicU32::new(16);
 static QUEUE: OnceCell<SafePlayerState> = OnceCell::new();
+static LOAD_HANDLE: Mutex<Option<AbortHandle>> = Mutex::new(None);
+/// Background task adding the rest of a playlist to the queue.
+static PLAYLIST_LOADER: Mutex<Option<AbortHandle>> = Mutex::new(None);
+static PLAYLIST_LOADING: AtomicBool = AtomicBool::new(false);
+/// The quality fallback last reported, so it is only reported once.
+static QUALITY_FALLBACK: Mutex<Option<(AudioQuality, AudioQuality)>> = Mutex::new(None);
+/// Whether the queue is kept going with suggested tracks once it runs out.
+static RADIO: AtomicBool = AtomicBool::new(false);
+/// Set when the queue ran out of tracks that can be streamed before it ended.
+static NOTHING_PLAYABLE: AtomicBool = AtomicBool::new(false);
+/// Set after a seek so the clock loop sends the next position without waiting.
+static POSITION_FLUSH: AtomicBool = AtomicBool::new(false);
+/// Set once the next url was prefetched for the playing track, cleared when a
+/// new one starts.
+static PREFETCH_STARTED: AtomicBool = AtomicBool::new(false);
+/// When the url of the loaded track stops working, in seconds since the epoch.
+static STREAM_EXPIRES_AT: AtomicI64 = AtomicI64::new(i64::MAX);
+/// Seconds a jump moves when the action doesn't say.
+static JUMP_SECONDS: AtomicU64 = AtomicU64::new(10);
+static NORMALIZATION: Mutex<NormalizationMode> = Mutex::new(NormalizationMode::Off);
+/// Whether ReplayGain tags from the stream reach rgvolume.
+static REPLAYGAIN_TAGS: AtomicBool = AtomicBool::new(false);
+/// The running sleep timer and when it ends.
+static SLEEP_TIMER: Mutex<Option<(AbortHandle, Instant)>> = Mutex::new(None);
+/// Volume from before the sleep timer started fading out, to put back.
+static SLEEP_FADE_VOLUME: Mutex<Option<f64>> = Mutex::new(None);
+/// Position to pick up from after the pipeline was released for being idle.
+static IDLE_POSITION: Mutex<Option<ClockTime>> = Mutex::new(None);
 static USER_AGENTS: &[&str] = &[
     "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/114.0.0.0 Safari/537.36",
     "Mozilla/5.0 (Macintosh; Intel Mac OS X 13_4) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/114.0.0.0 Safari/537.36"
@@ -141,6 +305,19 @@ pub async fn init(
     password: Option<&str>,
     quit_when_done: bool,
 ) -> Result<()> {
+    let missing = missing_elements();
+    if !missing.is_empty() {
+        let elements = missing
+            .iter()
+            .map(|(element, package)| format!("{element} (install {package})"))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        return Err(Error::GStreamer {
+            message: format!("missing required gstreamer elements: {elements}"),
+        });
+    }
+
     let state = Arc::new(RwLock::new(PlayerState::new(username, password).await));
     let version = gstreamer::version();
     debug!(?version);
@@ -148,12 +325,71 @@ pub async fn init(
     QUEUE.set(state).expect("error setting player state");
     QUIT_WHEN_DONE.store(quit_when_done, Ordering::Relaxed);
 
+    // Must be known before the audio filter is first built.
+    let level_meter = db::get_level_meter().await;
+    if level_meter && gst::ElementFactory::find("level").is_none() {
+        warn!("level meter disabled, the level element (gst-plugins-good) is not installed");
+    } else {
+        LEVEL_METER.store(level_meter, Ordering::Relaxed);
+    }
+
+    set_preamp(db::get_preamp().await)?;
+    set_volume(db::get_volume().await);
+
+    let (skip_silence, threshold) = db::get_skip_silence().await;
+    if let Err(error) = set_skip_silence(skip_silence, threshold) {
+        warn!("{error}");
+    }
+
+    if db::get_mono().await {
+        set_mono(true)?;
+    }
+
+    if let Err(error) = set_normalization(db::get_normalization().await) {
+        warn!("{error}");
+    }
+
+    JUMP_SECONDS.store(
+        db::get_jump_seconds().await.max(1) as u64,
+        Ordering::Relaxed,
+    );
+
     Ok(())
 }
 #[instrument]
-/// Play the player.
+/// Required gstreamer elements missing from the registry, each with the
+/// package that provides it.
+pub fn missing_elements() -> Vec<(&'static str, &'static str)> {
+    gst::init().expect("error initializing gstreamer");
+
+    REQUIRED_ELEMENTS
+        .iter()
+        .filter(|(element, _)| gst::ElementFactory::find(element).is_none())
+        .copied()
+        .collect()
+}
+#[instrument]
+/// Play the player. If the pipeline was released for being idle, or the
+/// track url expired while paused, the current track is loaded again and
+/// playback continues where it left off.
 pub async fn play() -> Result<()> {
-    set_player_state(gst::State::Playing).await?;
+    let idle_position = IDLE_POSITION.lock().unwrap().take();
+
+    let reload_position = match idle_position {
+        Some(position) => Some(position),
+        None if is_paused() && stream_expired() => {
+            debug!("track url expired while paused");
+            Some(position().unwrap_or_default())
+        }
+        None => None,
+    };
+
+    if let Some(position) = reload_position {
+        reload_current_track(position, GstState::Playing).await?;
+    } else {
+        set_player_state(gst::State::Playing).await?;
+    }
+
     Ok(())
 }
 #[instrument]
@@ -175,8 +411,26 @@ pub async fn stop() -> Result<()> {
     Ok(())
 }
 #[instrument]
+/// Loads a new uri into the playbin. The same pipeline is reused for every
+/// track, so it is taken down to `Null` first to release the previous
+/// source and decoders before the uri is swapped.
+async fn load_uri(uri: &str) -> Result<()> {
+    debug!(from = ?current_state(), "pipeline: resetting to null");
+    PLAYBIN.set_state(GstState::Null)?;
+
+    IDLE_POSITION.lock().unwrap().take();
+    IS_LIVE.store(false, Ordering::Relaxed);
+    IS_BUFFERING.store(false, Ordering::Relaxed);
+
+    debug!("pipeline: loading new uri");
+    PLAYBIN.set_property("uri", uri);
+
+    Ok(())
+}
+#[instrument]
 /// Sets the player to a specific state.
 pub async fn set_player_state(state: gst::State) -> Result<()> {
+    debug!(from = ?current_state(), to = ?state, "pipeline: changing state");
     let ret = PLAYBIN.set_state(state)?;
 
     match ret {
@@ -202,6 +456,13 @@ pub async fn set_player_state(state: gst::State) -> Result<()> {
 
     Ok(())
 }
+async fn broadcast_stopped(reason: StopReason) -> Result<()> {
+    BROADCAST_CHANNELS
+        .tx
+        .broadcast(Notification::Stopped { reason })
+        .await?;
+    Ok(())
+}
 async fn broadcast_track_list(list: TrackListValue) -> Result<()> {
     BROADCAST_CHANNELS
         .tx
@@ -214,17 +475,113 @@ async fn broadcast_track_list(list: TrackListValue) -> Result<()> {
 pub async fn play_pause() -> Result<()> {
     let mut state = QUEUE.get().unwrap().write().await;
 
+    // Playing may reload the track, which needs the queue.
     if is_playing() {
         state.set_target_status(GstState::Paused);
+        drop(state);
         pause().await?;
-    } else if is_paused() || is_ready() {
+    } else if is_paused() || is_ready() || is_idle() {
         state.set_target_status(GstState::Playing);
+        drop(state);
         play().await?;
     }
 
     Ok(())
 }
 #[instrument]
+/// Did the player start without being able to log in?
+pub async fn is_offline() -> bool {
+    QUEUE.get().unwrap().read().await.is_offline()
+}
+#[instrument]
+/// Is the level meter part of the pipeline?
+pub fn level_meter_enabled() -> bool {
+    LEVEL_METER.load(Ordering::Relaxed)
+}
+#[instrument]
+/// Was the pipeline released for being idle?
+pub fn is_idle() -> bool {
+    IDLE_POSITION.lock().unwrap().is_some()
+}
+#[instrument]
+/// Release the pipeline, and with it the audio device, remembering the
+/// position so the next play command can pick up from it.
+async fn idle_out() -> Result<()> {
+    let position = position().unwrap_or_default();
+
+    let state = QUEUE.get().unwrap().read().await;
+    state.persist().await;
+    drop(state);
+
+    debug!("idle timeout reached, releasing the pipeline");
+    PLAYBIN.set_state(GstState::Null)?;
+    *IDLE_POSITION.lock().unwrap() = Some(position);
+
+    broadcast_stopped(StopReason::Idle).await
+}
+/// Keep track of when the url of the loaded track stops working.
+pub fn set_stream_expiry(expires_at: i64) {
+    STREAM_EXPIRES_AT.store(expires_at, Ordering::Relaxed);
+}
+/// Has the url of the loaded track expired, or is about to?
+fn stream_expired() -> bool {
+    client::is_past_expiry(STREAM_EXPIRES_AT.load(Ordering::Relaxed))
+}
+#[instrument]
+/// Load the current track again with a fresh url and continue from
+/// `position`, ending up in `target`. Used when the pipeline was released
+/// for being idle, or the url expired while paused.
+async fn reload_current_track(position: ClockTime, target: GstState) -> Result<()> {
+    let mut state = QUEUE.get().unwrap().write().await;
+    let current_position = state.current_track_position();
+    let track_url = state.skip_track(current_position).await?;
+    state.set_target_status(target);
+    apply_normalization(state.current_track().as_ref());
+    drop(state);
+
+    if let Some(url) = track_url {
+        debug!("reloading the current track");
+        load_uri(&url).await?;
+        set_player_state(GstState::Paused).await?;
+        wait_for_paused().await?;
+
+        // Straight to the pipeline, `seek` would check the url again.
+        PLAYBIN.seek_simple(SeekFlags::FLUSH | SeekFlags::TRICKMODE_KEY_UNITS, position)?;
+        POSITION_FLUSH.store(true, Ordering::Relaxed);
+
+        BROADCAST_CHANNELS
+            .tx
+            .broadcast(Notification::Position { clock: position })
+            .await?;
+
+        if target == GstState::Playing {
+            set_player_state(GstState::Playing).await?;
+        }
+
+        Ok(())
+    } else {
+        Err(Error::TrackURL)
+    }
+}
+/// Wait for a newly loaded track to pause. Fails when it doesn't within
+/// `PAUSE_TIMEOUT`, e.g. because its url was refused.
+async fn wait_for_paused() -> Result<()> {
+    let paused = async {
+        let mut interval = tokio::time::interval(Duration::from_millis(100));
+
+        while !is_paused() {
+            debug!("wait for paused state");
+            interval.tick().await;
+        }
+    };
+
+    tokio::time::timeout(PAUSE_TIMEOUT, paused)
+        .await
+        .map_err(|_| Error::Stream {
+            message: "the track didn't load in time".to_string(),
+        })
+}
+#[instrument]
 /// Is the player paused?
 pub fn is_paused() -> bool {
     PLAYBIN.current_state() == gst::State::Paused
@@ -257,6 +614,12 @@ pub fn duration() -> Option<ClockTime> {
 #[instrument]
 /// Seek to a specified time in the current track.
 pub async fn seek(time: ClockTime, flags: Option<SeekFlags>) -> Result<()> {
+    // A paused stream whose url expired would 403 as soon as it reads again.
+    if is_paused() && stream_expired() {
+        debug!("track url expired while paused");
+        return reload_current_track(time, GstState::Paused).await;
+    }
+
     let flags = if let Some(flags) = flags {
         flags
     } else {
@@ -264,9 +627,202 @@ pub async fn seek(time: ClockTime, flags: Option<SeekFlags>) -> Result<()> {
     };
 
     PLAYBIN.seek_simple(flags, time)?;
+    POSITION_FLUSH.store(true, Ordering::Relaxed);
+
+    // Don't wait for the next clock tick, the progress bar should jump right away.
+    BROADCAST_CHANNELS
+        .tx
+        .broadcast(Notification::Position { clock: time })
+        .await?;
+
+    Ok(())
+}
+#[instrument]
+/// Seek to `seconds` into the current track, clamped to its duration.
+/// Ignored when nothing is loaded.
+pub async fn seek_to(seconds: u64) -> Result<()> {
+    if matches!(current_state(), GstState::Null | GstState::Ready) {
+        debug!("nothing loaded, ignoring seek");
+        return Ok(());
+    }
+
+    let duration = if let Some(duration) = PLAYBIN.query_duration::<ClockTime>() {
+        duration
+    } else if let Some(track) = current_track().await {
+        ClockTime::from_seconds(track.duration_seconds as u64)
+    } else {
+        return Ok(());
+    };
+
+    // Clamped before converting, `from_seconds` overflows on huge values.
+    let seconds = seconds.min(duration.seconds());
+
+    seek(ClockTime::from_seconds(seconds).min(duration), None).await
+}
+#[instrument]
+/// Set the output volume, clamped to 0.0 - 1.0.
+pub fn set_volume(level: f64) -> f64 {
+    let level = level.clamp(0.0, 1.0);
+    PLAYBIN.set_property("volume", level);
+
+    level
+}
+#[instrument]
+/// Current output volume, from 0.0 to 1.0.
+pub fn volume() -> f64 {
+    PLAYBIN.property::<f64>("volume")
+}
+#[instrument]
+/// Set the pre-amp gain in dB, clamped to +/- `PREAMP_RANGE`.
+pub fn set_preamp(db: f64) -> Result<()> {
+    let db = db.clamp(-PREAMP_RANGE, PREAMP_RANGE);
+
+    if let Some(preamp) = AUDIO_FILTER.by_name("preamp") {
+        preamp.set_property("volume", 10_f64.powf(db / 20.0));
+
+        update_limiter();
+        warn_on_combined_gain();
+
+        Ok(())
+    } else {
+        Err(Error::GStreamer {
+            message: "pre-amp element is missing from the audio filter".to_string(),
+        })
+    }
+}
+#[instrument]
+/// Current pre-amp gain in dB.
+pub fn preamp() -> f64 {
+    if let Some(preamp) = AUDIO_FILTER.by_name("preamp") {
+        20.0 * preamp.property::<f64>("volume").log10()
+    } else {
+        0.0
+    }
+}
+#[instrument]
+/// Sum every channel to mono, or go back to the source's own channels.
+/// Changing the caps renegotiates the running stream, so this takes effect
+/// right away and stays in place across track changes.
+pub fn set_mono(enabled: bool) -> Result<()> {
+    if let Some(mono) = AUDIO_FILTER.by_name("mono") {
+        let caps = if enabled {
+            gst::Caps::from_str(MONO_CAPS).map_err(|error| Error::GStreamer {
+                message: error.to_string(),
+            })?
+        } else {
+            gst::Caps::new_any()
+        };
+
+        mono.set_property("caps", &caps);
+
+        Ok(())
+    } else {
+        Err(Error::GStreamer {
+            message: "mono element is missing from the audio filter".to_string(),
+        })
+    }
+}
+#[instrument]
+/// Is the output summed to mono?
+pub fn is_mono() -> bool {
+    AUDIO_FILTER
+        .by_name("mono")
+        .map(|mono| mono.property::<gst::Caps>("caps").is_fixed())
+        .unwrap_or(false)
+}
+#[instrument]
+/// Skip silence longer than `MINIMUM_SILENCE` that is below the threshold, in dB.
+pub fn set_skip_silence(enabled: bool, threshold: i32) -> Result<()> {
+    if let Some(silence) = AUDIO_FILTER.by_name("silence") {
+        silence.set_property("remove", enabled);
+        silence.set_property("threshold", threshold.clamp(-70, 70));
+        silence.set_property("minimum-silence-time", MINIMUM_SILENCE.nseconds());
+
+        Ok(())
+    } else if enabled {
+        Err(Error::GStreamer {
+            message: "skipping silence requires the removesilence element (gst-plugins-bad)"
+                .to_string(),
+        })
+    } else {
+        Ok(())
+    }
+}
+#[instrument]
+/// Set how ReplayGain is applied, from the next track on.
+pub fn set_normalization(mode: NormalizationMode) -> Result<()> {
+    if mode != NormalizationMode::Off && AUDIO_FILTER.by_name("replaygain").is_none() {
+        return Err(Error::GStreamer {
+            message:
+                "normalization requires the rgvolume and rglimiter elements (gst-plugins-good)"
+                    .to_string(),
+        });
+    }
+
+    *NORMALIZATION.lock().unwrap() = mode;
+
     Ok(())
 }
 #[instrument]
+pub fn normalization() -> NormalizationMode {
+    *NORMALIZATION.lock().unwrap()
+}
+/// Set up rgvolume for the track about to play. In track mode the gain
+/// Qobuz reports is used when there is one, otherwise the gain tags in the
+/// stream are.
+fn apply_normalization(track: Option<&Track>) {
+    let replaygain = match AUDIO_FILTER.by_name("replaygain") {
+        Some(replaygain) => replaygain,
+        None => return,
+    };
+
+    let mode = normalization();
+    let gain = track.and_then(|t| t.replaygain_gain);
+
+    let (use_tags, fallback_gain) = match (mode, gain) {
+        (NormalizationMode::Off, _) => (false, 0.0),
+        (NormalizationMode::Track, Some(gain)) => (false, gain),
+        (NormalizationMode::Track, None) => (true, 0.0),
+        (NormalizationMode::Album, gain) => (true, gain.unwrap_or(0.0)),
+    };
+
+    REPLAYGAIN_TAGS.store(use_tags, Ordering::Relaxed);
+    replaygain.set_property("album-mode", mode == NormalizationMode::Album);
+    replaygain.set_property("fallback-gain", fallback_gain);
+
+    update_limiter();
+    warn_on_combined_gain();
+}
+/// The limiter holds back peaks whenever something can push the signal
+/// past full scale: ReplayGain, or a pre-amp above 0 dB.
+fn update_limiter() {
+    if let Some(limiter) = AUDIO_FILTER.by_name("rglimiter") {
+        limiter.set_property(
+            "enabled",
+            normalization() != NormalizationMode::Off || preamp() > 0.0,
+        );
+    }
+}
+/// Warn when the pre-amp, the volume and the ReplayGain known ahead of the
+/// stream add up to more than 0 dB, which distorts loud tracks.
+fn warn_on_combined_gain() {
+    let replaygain = match AUDIO_FILTER.by_name("replaygain") {
+        Some(replaygain) if normalization() != NormalizationMode::Off => {
+            replaygain.property::<f64>("fallback-gain")
+        }
+        _ => 0.0,
+    };
+    let volume = 20.0 * volume().max(f64::EPSILON).log10();
+    let combined = preamp() + volume + replaygain;
+
+    if combined > 0.0 {
+        warn!(
+            "combined gain of {combined:.1} dB (pre-amp {:.1}, volume {volume:.1}, ReplayGain {replaygain:.1}) may distort loud tracks",
+            preamp()
+        );
+    }
+}
+#[instrument]
 /// Load the previous player state and seek to the last known position.
 pub async fn resume(autoplay: bool) -> Result<()> {
     let mut state = QUEUE.get().unwrap().write().await;
@@ -287,20 +843,45 @@ pub async fn resume(autoplay: bool) -> Result<()> {
         }
 
         if let Some(track) = state.current_track() {
-            if let Some(url) = track.track_url {
-                PLAYBIN.set_property("uri", url);
+            if let Some(mut url) = track.track_url.clone() {
+                // A saved url may have expired since, which the pipeline
+                // would only notice once it fails to load.
+                match client::url_expires_at(&url) {
+                    Some(expires_at) if !client::is_past_expiry(expires_at) => {
+                        set_stream_expiry(expires_at);
+                    }
+                    _ => {
+                        debug!("saved track url has expired, fetching a new one");
+                        let position = state.current_track_position();
+                        url = state.play_position(position).await?.ok_or(Error::Resume)?;
+                    }
+                }
 
-                ready().await?;
-                pause().await?;
+                // A saved track that won't load gives way to the next playable one.
+                loop {
+                    apply_normalization(state.current_track().as_ref());
+                    load_uri(&url).await?;
+                    pause().await?;
+
+                    match wait_for_paused().await {
+                        Ok(()) => break,
+                        Err(error) => {
+                            warn!("unable to resume the saved track, trying the next one: {error}");
+                            let next = state.current_track_position() + 1;
+                            url = state.skip_track(next).await?.ok_or(Error::Resume)?;
+                        }
+                    }
+                }
 
-                let mut interval = tokio::time::interval(Duration::from_millis(100));
+                let same_track = state.current_track().map(|t| t.id) == Some(track.id);
 
-                while !is_paused() {
-                    debug!("wait for paused state");
-                    interval.tick().await;
+                if same_track && db::get_resume_position().await {
+                    seek(last_position, None).await?;
                 }
 
-                seek(last_position, None).await?;
+                if autoplay {
+                    play().await?;
+                }
 
                 return Ok(());
             } else {
@@ -314,80 +895,275 @@ pub async fn resume(autoplay: bool) -> Result<()> {
     Ok(())
 }
 #[instrument]
-/// Retreive controls for the player.
-pub fn controls() -> Controls {
-    CONTROLS.clone()
-}
-#[instrument]
-/// Jump forward in the currently playing track +10 seconds.
-pub async fn jump_forward() -> Result<()> {
-    if let (Some(current_position), Some(duration)) = (
-        PLAYBIN.query_position::<ClockTime>(),
-        PLAYBIN.query_duration::<ClockTime>(),
-    ) {
-        let ten_seconds = ClockTime::from_seconds(10);
-        let next_position = current_position + ten_seconds;
+/// Stop playback after `minutes`, fading the volume out over the last
+/// `SLEEP_FADE`. Replaces a timer that is already running.
+pub async fn set_sleep_timer(minutes: u32) -> Result<()> {
+    let duration = Duration::from_secs(minutes as u64 * 60);
+    let ends_at = Instant::now() + duration;
 
-        if next_position < duration {
-            seek(next_position, None).await?;
-        } else {
-            seek(duration, None).await?;
-        }
+    clear_sleep_timer();
+
+    // Held until the handle is stored, so a timer that ends right away
+    // can't clear itself before it is recorded.
+    {
+        let mut timer = SLEEP_TIMER.lock().unwrap();
+
+        let handle = tokio::spawn(async move {
+            let fade = duration.min(SLEEP_FADE);
+            tokio::time::sleep(duration - fade).await;
+
+            let level = volume();
+            *SLEEP_FADE_VOLUME.lock().unwrap() = Some(level);
+
+            let steps = fade.as_secs().max(1);
+            for step in 1..=steps {
+                set_volume(level * (1.0 - step as f64 / steps as f64));
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+
+            SLEEP_TIMER.lock().unwrap().take();
+
+            if let Err(error) = sleep_timer_ended().await {
+                error!("failed to stop for the sleep timer: {error}");
+            }
+        });
+
+        *timer = Some((handle.abort_handle(), ends_at));
     }
 
-    Ok(())
+    broadcast_sleep_timer().await
 }
 #[instrument]
-/// Jump forward in the currently playing track -10 seconds.
-pub async fn jump_backward() -> Result<()> {
-    if let Some(current_position) = PLAYBIN.query_position::<ClockTime>() {
-        if current_position.seconds() < 10 {
-            seek(ClockTime::default(), None).await?;
-        } else {
-            let ten_seconds = ClockTime::from_seconds(10);
-            let seek_position = current_position - ten_seconds;
+/// Stop the sleep timer, putting the volume back if it was fading out.
+pub async fn cancel_sleep_timer() -> Result<()> {
+    clear_sleep_timer();
+    broadcast_sleep_timer().await
+}
+#[instrument]
+/// Time left on the sleep timer, if one is running.
+pub fn sleep_timer_remaining() -> Option<Duration> {
+    SLEEP_TIMER
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|(_, ends_at)| ends_at.saturating_duration_since(Instant::now()))
+}
+fn clear_sleep_timer() {
+    if let Some((handle, _)) = SLEEP_TIMER.lock().unwrap().take() {
+        handle.abort();
+    }
 
-            seek(seek_position, None).await?;
-        }
+    if let Some(level) = SLEEP_FADE_VOLUME.lock().unwrap().take() {
+        set_volume(level);
+    }
+}
+async fn sleep_timer_ended() -> Result<()> {
+    stop().await?;
+
+    if let Some(level) = SLEEP_FADE_VOLUME.lock().unwrap().take() {
+        set_volume(level);
     }
 
+    broadcast_stopped(StopReason::SleepTimer).await?;
+    broadcast_sleep_timer().await
+}
+async fn broadcast_sleep_timer() -> Result<()> {
+    BROADCAST_CHANNELS
+        .tx
+        .broadcast(Notification::SleepTimer {
+            seconds: sleep_timer_remaining().map(|r| r.as_secs()),
+        })
+        .await?;
     Ok(())
 }
 #[instrument]
-/// Skip to a specific track in the playlist.
-pub async fn skip(new_position: u32) -> Result<()> {
-    let mut state = QUEUE.get().unwrap().write().await;
-    let current_position = state.current_track_position();
-    let total_tracks = state.track_list().total();
-
-    // Typical previous skip functionality where if,
-    // the track is greater than 1 second into playing,
-    // then it goes to the beginning. If triggered again
-    // within a second after playing, it will skip to the previous track.
-    // Ignore if going from the last track to the first (EOS).
-    if new_position < current_position && total_tracks != current_position && new_position != 1 {
-        if let Some(current_player_position) = position() {
-            if current_player_position.seconds() > 1 {
-                debug!("current track position >1s, seeking to start of track");
-
-                let zero_clock = ClockTime::default();
-
-                seek(zero_clock, None).await?;
-
-                return Ok(());
-            }
-        }
+/// Abort the track, album or playlist currently being fetched or buffered
+/// and return the player to its last settled state.
+pub async fn cancel_load() -> Result<()> {
+    if let Some(handle) = LOAD_HANDLE.lock().unwrap().take() {
+        debug!("aborting load");
+        handle.abort();
     }
 
-    ready().await?;
+    IS_BUFFERING.store(false, Ordering::Relaxed);
+    PLAYBIN.abort_state();
 
-    if let Some(next_track_to_play) = state.skip_track(new_position).await {
-        let list = state.track_list();
-        let target_status = state.target_status();
-
-        drop(state);
+    let state = current_state();
+    QUEUE.get().unwrap().write().await.set_target_status(state);
+
+    BROADCAST_CHANNELS
+        .tx
+        .broadcast(Notification::Loading {
+            is_loading: false,
+            target_state: state,
+        })
+        .await?;
+
+    Ok(())
+}
+#[instrument]
+/// Retreive controls for the player.
+pub fn controls() -> Controls {
+    CONTROLS.clone()
+}
+/// Tell listeners when the account got a lower quality than it asked for,
+/// once for each quality asked for.
+pub fn notify_quality_fallback(stream: &StreamUrl) {
+    if !stream.is_downgraded() {
+        return;
+    }
+
+    let fallback = Some((stream.requested.clone(), stream.granted.clone()));
+    let mut last = QUALITY_FALLBACK.lock().unwrap();
+
+    if *last == fallback {
+        return;
+    }
+
+    *last = fallback;
+
+    let notification = Notification::QualityFallback {
+        requested: stream.requested.clone(),
+        granted: stream.granted.clone(),
+    };
+
+    if let Err(error) = BROADCAST_CHANNELS.tx.try_broadcast(notification) {
+        debug!("unable to send quality fallback: {error}");
+    }
+}
+/// Tell listeners a track was passed over because it can't be streamed.
+pub fn notify_track_skipped(track: &Track) {
+    let notification = Notification::TrackSkipped {
+        track_id: track.id,
+        title: track.title.clone(),
+    };
+
+    if let Err(error) = BROADCAST_CHANNELS.tx.try_broadcast(notification) {
+        debug!("unable to send skipped track: {error}");
+    }
+}
+#[instrument]
+/// Stream at this quality from the next track on, and keep it as the default.
+pub async fn set_quality(quality: AudioQuality) {
+    QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .service()
+        .set_default_quality(quality.clone());
+
+    *QUALITY_FALLBACK.lock().unwrap() = None;
+    db::set_default_quality(quality).await;
+}
+/// The quality tracks are streamed at.
+pub async fn default_quality() -> AudioQuality {
+    QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .service()
+        .default_quality()
+}
+/// Seconds a jump moves by default.
+pub fn jump_seconds() -> u64 {
+    JUMP_SECONDS.load(Ordering::Relaxed)
+}
+#[instrument]
+/// Jump forward in the currently playing track, by `jump_seconds` unless told otherwise.
+pub async fn jump_forward(seconds: Option<u64>) -> Result<()> {
+    if let (Some(current_position), Some(duration)) = (
+        PLAYBIN.query_position::<ClockTime>(),
+        PLAYBIN.query_duration::<ClockTime>(),
+    ) {
+        // No step needs to be longer than the track, which also keeps
+        // `from_seconds` and the addition from overflowing.
+        let step = seconds.unwrap_or_else(jump_seconds).min(duration.seconds());
+        let next_position = current_position + ClockTime::from_seconds(step);
+
+        if next_position < duration {
+            seek(next_position, None).await?;
+        } else {
+            seek(duration, None).await?;
+        }
+    }
+
+    Ok(())
+}
+#[instrument]
+/// Jump backward in the currently playing track, by `jump_seconds` unless told otherwise.
+pub async fn jump_backward(seconds: Option<u64>) -> Result<()> {
+    if let Some(current_position) = PLAYBIN.query_position::<ClockTime>() {
+        let step = seconds.unwrap_or_else(jump_seconds);
+
+        if current_position.seconds() < step {
+            seek(ClockTime::default(), None).await?;
+        } else {
+            let seek_position = current_position - ClockTime::from_seconds(step);
+
+            seek(seek_position, None).await?;
+        }
+    }
+
+    Ok(())
+}
+#[instrument]
+/// Skip to a specific track in the playlist. When the user `picked` the
+/// track, one that can't be streamed is an error instead of being passed over.
+pub async fn skip(new_position: u32, picked: bool) -> Result<()> {
+    wait_for_track(new_position).await;
+
+    let mut state = QUEUE.get().unwrap().write().await;
+    let current_position = state.current_track_position();
+    let total_tracks = state.track_list().total();
+
+    // Typical previous skip functionality where if,
+    // the track is greater than 1 second into playing,
+    // then it goes to the beginning. If triggered again
+    // within a second after playing, it will skip to the previous track.
+    // Ignore if going from the last track to the first (EOS).
+    if new_position < current_position && total_tracks != current_position && new_position != 1 {
+        if let Some(current_player_position) = position() {
+            if current_player_position.seconds() > 1 {
+                debug!("current track position >1s, seeking to start of track");
+
+                let zero_clock = ClockTime::default();
+
+                drop(state);
+                seek(zero_clock, None).await?;
+
+                return Ok(());
+            }
+        }
+    }
+
+    let next_track = if picked {
+        state.play_position(new_position).await
+    } else {
+        state.skip_track(new_position).await
+    };
+
+    let next_track = match next_track {
+        Ok(next_track) => next_track,
+        Err(error) => {
+            let list = state.track_list();
+            drop(state);
+
+            broadcast_track_list(list).await?;
+            return Err(error.into());
+        }
+    };
+
+    if let Some(next_track_to_play) = next_track {
+        let list = state.track_list();
+        let target_status = state.target_status();
+        apply_normalization(state.current_track().as_ref());
+
+        drop(state);
 
         broadcast_track_list(list).await?;
+        POSITION_FLUSH.store(true, Ordering::Relaxed);
         BROADCAST_CHANNELS
             .tx
             .broadcast(Notification::Position {
@@ -397,28 +1173,196 @@ pub async fn skip(new_position: u32) -> Result<()> {
 
         debug!("skipping to next track");
 
-        PLAYBIN.set_property("uri", next_track_to_play);
+        load_uri(&next_track_to_play).await?;
         set_player_state(target_status).await?;
+    } else if state
+        .track_list()
+        .find_track_by_index(new_position)
+        .is_some()
+    {
+        let list = state.track_list();
+        drop(state);
+
+        debug!("no playable tracks left from {new_position}");
+        broadcast_track_list(list).await?;
+        stop().await?;
+        broadcast_stopped(StopReason::NothingPlayable).await?;
     }
 
     Ok(())
 }
 #[instrument]
-/// Plays a single track.
-pub async fn play_track(track_id: i32) -> Result<()> {
-    ready().await?;
+/// Plays a single track, optionally at a quality other than the default.
+pub async fn play_track(track_id: i32, quality: Option<AudioQuality>) -> Result<()> {
+    let mut state = QUEUE.get().unwrap().write().await;
+    let track_url = state.play_track(track_id, quality).await;
+
+    start_queue(state, track_url).await
+}
+#[instrument]
+/// Plays a track radio that never runs out: more suggestions are added
+/// whenever the last track in the queue starts.
+pub async fn start_radio(track_id: i32) -> Result<()> {
+    play_track_radio(track_id).await?;
+    set_radio(true).await
+}
+#[instrument]
+/// Keep the queue going with suggested tracks once it runs out, or not.
+pub async fn set_radio(enabled: bool) -> Result<()> {
+    if RADIO.swap(enabled, Ordering::Relaxed) != enabled {
+        BROADCAST_CHANNELS
+            .tx
+            .broadcast(Notification::Radio { enabled })
+            .await?;
+    }
+
+    if enabled {
+        extend_radio().await?;
+    }
+
+    Ok(())
+}
+/// Is the radio keeping the queue going?
+pub fn is_radio() -> bool {
+    RADIO.load(Ordering::Relaxed)
+}
+#[instrument]
+/// Once the last track in the queue is playing, add tracks suggested from it
+/// so the radio carries on.
+async fn extend_radio() -> Result<()> {
+    if !is_radio() {
+        return Ok(());
+    }
+
+    let (service, seed) = {
+        let state = QUEUE.get().unwrap().read().await;
+
+        if upcoming_position(&state).is_some() {
+            return Ok(());
+        }
+
+        match state.current_track() {
+            Some(track) => (state.service(), track.id),
+            None => return Ok(()),
+        }
+    };
+
+    let suggestions = service
+        .track_suggestions(seed as i32, RADIO_BATCH)
+        .await
+        .unwrap_or_default();
 
     let mut state = QUEUE.get().unwrap().write().await;
 
-    if let Some(track_url) = state.play_track(track_id).await {
-        let list = state.track_list();
-        broadcast_track_list(list).await?;
+    // Stopped, or moved to another track, while the suggestions loaded.
+    if !is_radio() || state.current_track().map(|t| t.id) != Some(seed) {
+        return Ok(());
+    }
 
-        drop(state);
+    let added = state.append_tracks(suggestions);
+    debug!("radio added {added} tracks seeded from {seed}");
 
-        PLAYBIN.set_property("uri", Some(track_url.as_str()));
+    if added > 0 {
+        broadcast_track_list(state.track_list()).await?;
+    }
 
-        play().await?;
+    Ok(())
+}
+#[instrument]
+/// Plays a track followed by tracks suggested from it.
+pub async fn play_track_radio(track_id: i32) -> Result<()> {
+    let mut state = QUEUE.get().unwrap().write().await;
+    let track_url = state.play_track_radio(track_id).await;
+
+    start_queue(state, track_url).await
+}
+#[instrument]
+/// Add a track to the end of the queue.
+pub async fn add_to_queue(track_id: i32) -> Result<()> {
+    let track = fetch_queue_track(track_id).await?;
+
+    let mut state = QUEUE.get().unwrap().write().await;
+    state.add_to_queue(track);
+
+    broadcast_track_list(state.track_list()).await
+}
+#[instrument]
+/// Queue a track to play right after the current one.
+pub async fn play_next(track_id: i32) -> Result<()> {
+    let track = fetch_queue_track(track_id).await?;
+
+    let mut state = QUEUE.get().unwrap().write().await;
+    state.play_next(track);
+
+    broadcast_track_list(state.track_list()).await
+}
+/// Look up a track to queue, without holding the queue while waiting on the service.
+async fn fetch_queue_track(track_id: i32) -> Result<Track> {
+    let service = QUEUE.get().unwrap().read().await.service();
+
+    service.track(track_id).await.ok_or_else(|| Error::Queue {
+        message: format!("track {track_id} was not found"),
+    })
+}
+#[instrument]
+/// Take a track out of the queue. The playing track stays.
+pub async fn remove_from_queue(position: u32) -> Result<()> {
+    let mut state = QUEUE.get().unwrap().write().await;
+
+    if state.remove_from_queue(position).is_none() {
+        return Err(Error::Queue {
+            message: format!("no track to remove at position {position}"),
+        });
+    }
+
+    broadcast_track_list(state.track_list()).await
+}
+#[instrument]
+/// Move a track to another place in the queue. When a playlist is playing
+/// the new order is saved to it as well.
+pub async fn move_track(from: u32, to: u32) -> Result<()> {
+    let mut state = QUEUE.get().unwrap().write().await;
+
+    let moved = match state.move_in_queue(from, to) {
+        Some(track) => track,
+        None => {
+            return Err(Error::Queue {
+                message: format!("can't move the track at {from} to {to}"),
+            });
+        }
+    };
+
+    let list = state.track_list();
+    let service = state.service();
+    drop(state);
+
+    broadcast_track_list(list.clone()).await?;
+
+    let playlist_id = match list.get_playlist() {
+        Some(playlist) => playlist.id,
+        None => return Ok(()),
+    };
+    let insert_before = moved
+        .playlist_track_id
+        .and_then(|id| list.playlist_insert_before(id).map(|before| (id, before)));
+
+    match insert_before {
+        Some((playlist_track_id, insert_before)) => {
+            if service
+                .move_playlist_track(playlist_id, playlist_track_id, insert_before)
+                .await
+            {
+                QUEUE
+                    .get()
+                    .unwrap()
+                    .write()
+                    .await
+                    .set_playlist_order(playlist_id, list.queue);
+            } else {
+                debug!("the new order of playlist {playlist_id} wasn't saved");
+            }
+        }
+        None => debug!("the queue no longer matches playlist {playlist_id}, not saving the order"),
     }
 
     Ok(())
@@ -426,95 +1370,512 @@ pub async fn play_track(track_id: i32) -> Result<()> {
 #[instrument]
 /// Plays a full album.
 pub async fn play_album(album_id: String) -> Result<()> {
-    ready().await?;
+    let mut state = QUEUE.get().unwrap().write().await;
+    let track_url = state.play_album(album_id).await;
 
+    start_queue(state, track_url).await
+}
+#[instrument]
+/// Plays an album, starting at one of its tracks.
+pub async fn play_album_from(album_id: String, track_id: i32) -> Result<()> {
     let mut state = QUEUE.get().unwrap().write().await;
+    let track_url = state.play_album_from(album_id, track_id).await;
 
-    if let Some(track_url) = state.play_album(album_id).await {
-        let list = state.track_list();
-        broadcast_track_list(list).await?;
+    start_queue(state, track_url).await
+}
+/// Loads and plays the track a new queue was started at. When nothing in
+/// the queue can be played, playback stops instead.
+async fn start_queue(
+    state: RwLockWriteGuard<'_, PlayerState>,
+    track_url: std::result::Result<Option<String>, StreamError>,
+) -> Result<()> {
+    let track_url = track_url?;
+    let list = state.track_list();
 
-        drop(state);
+    match track_url {
+        Some(track_url) => {
+            apply_normalization(state.current_track().as_ref());
+            drop(state);
 
-        PLAYBIN.set_property("uri", Some(track_url));
+            broadcast_track_list(list).await?;
+            load_uri(&track_url).await?;
 
-        play().await?;
-    }
+            play().await
+        }
+        None => {
+            drop(state);
 
-    Ok(())
+            debug!("nothing in the new queue can be played");
+            broadcast_track_list(list).await?;
+            stop().await?;
+
+            broadcast_stopped(StopReason::NothingPlayable).await
+        }
+    }
 }
 #[instrument]
-/// Plays all tracks in a playlist.
+/// Plays all tracks in a playlist. Playback starts after the first page of
+/// tracks, the rest are added to the queue as they arrive.
 pub async fn play_playlist(playlist_id: i64) -> Result<()> {
-    ready().await?;
+    if let Some(handle) = PLAYLIST_LOADER.lock().unwrap().take() {
+        handle.abort();
+    }
+    PLAYLIST_LOADING.store(false, Ordering::Relaxed);
+
+    let page_size = db::get_playlist_page_size().await.max(0) as u32;
 
     let mut state = QUEUE.get().unwrap().write().await;
-    if let Some(track_url) = state.play_playlist(playlist_id).await {
-        let list = state.track_list();
-        broadcast_track_list(list).await?;
+    let track_url = state.play_playlist(playlist_id, page_size).await;
+    let total = state.playlist().map(|p| p.tracks_count).unwrap_or(0);
 
-        drop(state);
+    // The rest of the playlist waits for the queue until it has started.
+    if matches!(track_url, Ok(Some(_))) && page_size > 0 && total > page_size {
+        PLAYLIST_LOADING.store(true, Ordering::Relaxed);
 
-        PLAYBIN.set_property("uri", Some(track_url.as_str()));
+        let handle =
+            tokio::spawn(
+                async move { load_playlist_remainder(playlist_id, page_size, total).await },
+            );
 
-        play().await?;
+        *PLAYLIST_LOADER.lock().unwrap() = Some(handle.abort_handle());
     }
 
-    Ok(())
+    start_queue(state, track_url).await
 }
 #[instrument]
-/// Play an item from Qobuz web uri
-pub async fn play_uri(uri: String) -> Result<()> {
-    match client::parse_url(uri.as_str()) {
-        Ok(url) => match url {
-            UrlType::Album { id } => {
-                play_album(id).await?;
-            }
-            UrlType::Playlist { id } => {
-                play_playlist(id).await?;
-            }
-            UrlType::Track { id } => {
-                play_track(id).await?;
+/// Fetches the tracks of a playlist from `offset` on, a page at a time,
+/// appending each page to the queue.
+async fn load_playlist_remainder(playlist_id: i64, mut offset: u32, total: u32) {
+    let service = QUEUE.get().unwrap().read().await.service();
+
+    while offset < total {
+        let page = service
+            .playlist_page(playlist_id, offset, PLAYLIST_PAGE_LIMIT)
+            .await;
+
+        let tracks = match page {
+            Some(page) if !page.tracks.is_empty() => page.tracks,
+            _ => {
+                warn!("stopped loading playlist {playlist_id} at track {offset} of {total}");
+                break;
             }
-        },
-        Err(err) => {
-            return Err(Error::FailedToPlay {
-                message: format!("Failed to play item. {err}"),
-            })
+        };
+
+        offset += PLAYLIST_PAGE_LIMIT;
+
+        let mut state = QUEUE.get().unwrap().write().await;
+
+        if !state.append_playlist_tracks(playlist_id, tracks.into_values().collect()) {
+            debug!("playlist replaced, stop loading");
+            break;
+        }
+
+        let list = state.track_list();
+        drop(state);
+
+        if let Err(error) = broadcast_track_list(list).await {
+            error!("{error}");
         }
     }
 
-    Ok(())
+    PLAYLIST_LOADING.store(false, Ordering::Relaxed);
+}
+#[instrument]
+/// Waits for the rest of the playlist to load, until the queue reaches
+/// `position` or there is nothing left to load.
+async fn wait_for_track(position: u32) {
+    let mut interval = tokio::time::interval(Duration::from_millis(100));
+
+    while PLAYLIST_LOADING.load(Ordering::Relaxed)
+        && QUEUE.get().unwrap().read().await.last_position() < position
+    {
+        debug!("waiting for track {position} to load");
+        interval.tick().await;
+    }
+}
+#[instrument]
+/// Play an item from Qobuz web uri
+pub async fn play_uri(uri: String) -> Result<()> {
+    match client::parse_url(uri.as_str()) {
+        Ok(url) => play_url(url).await,
+        Err(err) => Err(Error::FailedToPlay {
+            message: format!("Failed to play item. {err}"),
+        }),
+    }
+}
+#[instrument]
+/// Play the album, playlist or track a Qobuz link points at.
+async fn play_url(url: UrlType) -> Result<()> {
+    match url {
+        UrlType::Album { id } => play_album(id).await,
+        UrlType::Playlist { id } => play_playlist(id).await,
+        UrlType::Track { id } => play_track(id, None).await,
+    }
 }
 #[instrument]
 /// In response to the about-to-finish signal,
 /// prepare the next track by downloading the stream url.
 async fn prep_next_track() -> Result<()> {
+    // Covers tracks too short to have reached the prefetch.
+    extend_radio().await?;
+
+    let next_position = QUEUE.get().unwrap().read().await.current_track_position() + 1;
+    wait_for_track(next_position).await;
+
     let mut state = QUEUE.get().unwrap().write().await;
 
+    if let Some(next_position) = upcoming_position(&state) {
+        if let Some(next_track_url) = state.skip_track(next_position).await? {
+            drop(state);
+
+            // Swapping the uri from `about-to-finish` keeps the pipeline
+            // running, which is what makes the transition gapless.
+            debug!("pipeline: queueing next uri");
+            PLAYBIN.set_property("uri", next_track_url);
+        } else {
+            let list = state.track_list();
+            drop(state);
+
+            debug!("no playable tracks left");
+            NOTHING_PLAYABLE.store(true, Ordering::Relaxed);
+            broadcast_track_list(list).await?;
+        }
+    } else {
+        debug!("no more tracks left");
+    }
+
+    Ok(())
+}
+/// Position of the track that plays once the current one ends, taking the
+/// repeat mode into account.
+fn upcoming_position(state: &PlayerState) -> Option<u32> {
     let total_tracks = state.track_list().total();
     let current_position = state.current_track_position();
+    let last_track = total_tracks == current_position
+        || (current_position >= state.last_position() && !PLAYLIST_LOADING.load(Ordering::Relaxed));
+
+    match state.repeat() {
+        RepeatMode::One => Some(current_position),
+        RepeatMode::All if last_track => Some(state.first_position()),
+        RepeatMode::Off if last_track => None,
+        _ => Some(current_position + 1),
+    }
+}
+#[instrument]
+/// Fetch the stream url of the upcoming track early, so moving on to it
+/// doesn't wait on the API. A url that comes back also means the active
+/// secret still works.
+async fn prefetch_next_track_url() {
+    let (service, track_id) = {
+        let state = QUEUE.get().unwrap().read().await;
+
+        let track = match upcoming_position(&state)
+            .and_then(|position| state.track_list().find_track_by_index(position).cloned())
+        {
+            Some(track) => track,
+            None => return,
+        };
+
+        let quality = state.service().default_quality();
+
+        if state.prefetched_url().map_or(false, |p| {
+            p.track_id == track.id && p.quality == quality && !p.stream.is_expired()
+        }) {
+            return;
+        }
 
-    if total_tracks == current_position {
-        debug!("no more tracks left");
-    } else if let Some(next_track_url) = state.skip_track(current_position + 1).await {
-        drop(state);
+        (state.service(), track.id)
+    };
+
+    let quality = service.default_quality();
+
+    match service.track_url(track_id as i32, None).await {
+        Ok(stream) => {
+            debug!("prefetched url for track {track_id}");
+            notify_quality_fallback(&stream);
+
+            QUEUE
+                .get()
+                .unwrap()
+                .write()
+                .await
+                .set_prefetched_url(PrefetchedUrl::new(track_id, quality, stream));
+        }
+        Err(error) => debug!("unable to prefetch url for track {track_id}: {error}"),
+    }
+}
+#[instrument]
+/// Position `Next` moves to. Past the last track that is the first one
+/// when repeating, otherwise a position with no track.
+async fn next_position() -> u32 {
+    let next = QUEUE.get().unwrap().read().await.current_track_position() + 1;
+    wait_for_track(next).await;
+
+    let state = QUEUE.get().unwrap().read().await;
+
+    if next > state.last_position() && state.repeat() != RepeatMode::Off {
+        state.first_position()
+    } else {
+        next
+    }
+}
+#[instrument]
+/// Position `Previous` moves to. Before the first track that is the last
+/// one when repeating.
+async fn previous_position() -> u32 {
+    let state = QUEUE.get().unwrap().read().await;
+    let current = state.current_track_position();
+
+    if current <= state.first_position() && state.repeat() != RepeatMode::Off {
+        state.last_position()
+    } else {
+        current.saturating_sub(1)
+    }
+}
+#[instrument]
+/// Whether the track is one of the user's favorites. False when unknown.
+pub async fn is_favorite_track(track_id: u32) -> bool {
+    load_favorite_tracks().await.is_some()
+        && QUEUE
+            .get()
+            .unwrap()
+            .read()
+            .await
+            .is_favorite_track(track_id)
+            .unwrap_or(false)
+}
+/// Fetch the favorite tracks the first time they are needed. None when they
+/// can't be fetched, e.g. signed out. The queue isn't locked during the request.
+async fn load_favorite_tracks() -> Option<()> {
+    let service = {
+        let state = QUEUE.get().unwrap().read().await;
+
+        if state.has_favorite_tracks() {
+            return Some(());
+        }
+
+        state.service()
+    };
+
+    let track_ids = service.favorite_tracks().await?;
+    QUEUE
+        .get()
+        .unwrap()
+        .write()
+        .await
+        .set_favorite_tracks(track_ids);
+
+    Some(())
+}
+/// Flips the favorite status of a track and returns the new status.
+async fn toggle_favorite_track(track_id: u32) -> Option<bool> {
+    load_favorite_tracks().await?;
+
+    let (service, favorite) = {
+        let state = QUEUE.get().unwrap().read().await;
+        (state.service(), !state.is_favorite_track(track_id)?)
+    };
+
+    if !service.set_favorite_track(track_id, favorite).await {
+        return None;
+    }
+
+    QUEUE
+        .get()
+        .unwrap()
+        .write()
+        .await
+        .set_favorite_track(track_id, favorite);
+
+    Some(favorite)
+}
+#[instrument]
+/// Add the current track to the favorites, or remove it if it already is one.
+pub async fn toggle_favorite_current() -> Result<()> {
+    let track = QUEUE.get().unwrap().read().await.current_track();
+
+    if let Some(track) = track {
+        let notification = match toggle_favorite_track(track.id).await {
+            Some(favorited) => Notification::FavoriteChanged {
+                track_id: track.id,
+                favorited,
+            },
+            None => Notification::Error {
+                error: Error::Favorite,
+            },
+        };
+
+        BROADCAST_CHANNELS.tx.broadcast(notification).await?;
+    }
+
+    Ok(())
+}
+#[instrument]
+/// Get a notification channel receiver. The channel stays open for as long as
+/// the player runs, so listeners can stop once their receiver reports it closed.
+pub fn notify_receiver() -> BroadcastReceiver {
+    BROADCAST_CHANNELS.rx.clone()
+}
+#[instrument]
+/// Get a receiver for the level meter readings, which don't go through
+/// `notify_receiver`.
+pub fn level_receiver() -> BroadcastReceiver {
+    LEVEL_CHANNELS.rx.clone()
+}
+
+#[instrument]
+/// Returns the current track list loaded in the player.
+pub async fn current_tracklist() -> TrackListValue {
+    QUEUE.get().unwrap().read().await.track_list()
+}
+
+#[instrument]
+/// The next `count` tracks to play, in queue order. Fewer when the queue runs out.
+pub async fn queue_peek(count: usize) -> Vec<Track> {
+    QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .unplayed_tracks()
+        .into_iter()
+        .take(count)
+        .cloned()
+        .collect()
+}
+
+#[instrument]
+/// The last `count` tracks played, most recent first. Fewer when not as many were played.
+pub async fn queue_prev(count: usize) -> Vec<Track> {
+    QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .played_tracks()
+        .into_iter()
+        .rev()
+        .take(count)
+        .cloned()
+        .collect()
+}
+
+#[instrument]
+/// Writes the current track list to a file in the given format.
+pub async fn export_queue(path: &str, format: ExportFormat) -> Result<()> {
+    let mut output = Vec::new();
+    let library = db::get_library_path().await;
+    current_tracklist()
+        .await
+        .export(format, Some(&library), &mut output)?;
+
+    tokio::fs::write(path, output)
+        .await
+        .map_err(|error| Error::Export {
+            message: error.to_string(),
+        })
+}
+
+#[instrument]
+/// Save the current track, or the whole album it is from, to the library.
+pub async fn download(album: bool) -> Result<()> {
+    let (service, track) = {
+        let state = QUEUE.get().unwrap().read().await;
+        (state.service(), state.current_track())
+    };
+
+    let track = track.ok_or_else(|| Error::Download {
+        message: "nothing is playing".to_string(),
+    })?;
+
+    let library = db::get_library_path().await;
+
+    if album {
+        let album_id =
+            track
+                .album
+                .as_ref()
+                .map(|a| a.id.clone())
+                .ok_or_else(|| Error::Download {
+                    message: format!("{} is not part of an album", track.title),
+                })?;
+
+        let album = service
+            .album(&album_id)
+            .await
+            .ok_or_else(|| Error::Download {
+                message: format!("failed to fetch album {album_id}"),
+            })?;
+
+        download::download_album(service, &album, &library).await
+    } else {
+        download::download_track(service, &track, track.album.as_ref(), &library).await?;
+
+        Ok(())
+    }
+}
+
+#[instrument(skip(writer))]
+/// Writes a playlist to `writer` in the given format.
+pub async fn export_playlist<W: Write>(
+    playlist_id: i64,
+    format: ExportFormat,
+    writer: &mut W,
+) -> Result<()> {
+    let playlist = QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .fetch_playlist(playlist_id)
+        .await
+        .ok_or_else(|| Error::Export {
+            message: format!("playlist {playlist_id} not found"),
+        })?;
+
+    let mut list = TrackListValue::new(Some(playlist.tracks.clone()));
+    list.set_playlist(playlist);
+    list.set_list_type(TrackListType::Playlist);
+
+    let library = db::get_library_path().await;
+    list.export(format, Some(&library), writer)?;
+
+    Ok(())
+}
 
-        PLAYBIN.set_property("uri", next_track_url);
+#[instrument(skip(writer))]
+/// Writes the queue saved by the last session to `writer` in the given format.
+pub async fn export_saved_queue<W: Write>(format: ExportFormat, writer: &mut W) -> Result<()> {
+    let mut state = QUEUE.get().unwrap().write().await;
+
+    if state.load_last_state().await.is_none() {
+        return Err(Error::Export {
+            message: "there is no saved queue".to_string(),
+        });
     }
 
+    let library = db::get_library_path().await;
+    state.track_list().export(format, Some(&library), writer)?;
+
     Ok(())
 }
-#[instrument]
-/// Get a notification channel receiver
-pub fn notify_receiver() -> BroadcastReceiver {
-    BROADCAST_CHANNELS.rx.clone()
-}
 
 #[instrument]
-/// Returns the current track list loaded in the player.
-pub async fn current_tracklist() -> TrackListValue {
-    QUEUE.get().unwrap().read().await.track_list()
+/// Writes a snapshot of the player state to the log and broadcasts it.
+pub async fn dump_state() -> Result<()> {
+    let snapshot = QUEUE.get().unwrap().read().await.snapshot();
+
+    match serde_json::to_string(&snapshot) {
+        Ok(json) => info!("player state: {json}"),
+        Err(error) => warn!("unable to serialize state snapshot: {error}"),
+    }
+
+    BROADCAST_CHANNELS
+        .tx
+        .broadcast(Notification::StateDump { snapshot })
+        .await?;
+
+    Ok(())
 }
 
 #[instrument]
@@ -523,12 +1884,31 @@ pub async fn current_track() -> Option<Track> {
     QUEUE.get().unwrap().read().await.current_track()
 }
 
+#[instrument]
+/// What happens when the queue or the current track runs out.
+pub async fn repeat_mode() -> RepeatMode {
+    QUEUE.get().unwrap().read().await.repeat()
+}
+
 #[instrument]
 /// Returns true if the player is currently buffering data.
 pub fn is_buffering() -> bool {
     IS_BUFFERING.load(Ordering::Relaxed)
 }
 
+#[instrument]
+/// Whether something is being fetched, buffered or started, so `cancel_load`
+/// has anything to cancel.
+pub fn is_loading() -> bool {
+    LOAD_HANDLE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map_or(false, |handle| !handle.is_finished())
+        || is_buffering()
+        || PLAYBIN.pending_state() != gst::State::VoidPending
+}
+
 #[instrument]
 /// Search the service.
 pub async fn search(query: &str) -> SearchResults {
@@ -562,6 +1942,49 @@ pub async fn artist_albums(artist_id: i32) -> Vec<Album> {
     }
 }
 
+#[instrument]
+#[cached(size = 1, option = true)]
+/// Fetch the lyrics of a track, empty when it has none. Only the last one is
+/// kept, so reopening the lyrics of the current track doesn't hit the network
+/// again. A failed request isn't kept.
+pub async fn track_lyrics(track_id: i32) -> Option<String> {
+    QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .fetch_track_lyrics(track_id)
+        .await
+}
+
+#[instrument]
+#[cached(size = 10, time = 600)]
+/// Fetch artists similar to a specific artist.
+pub async fn similar_artists(artist_id: i32) -> Vec<Artist> {
+    QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .fetch_similar_artists(artist_id)
+        .await
+        .unwrap_or_default()
+}
+
+#[instrument]
+#[cached(size = 10, time = 600, option = true)]
+/// Fetch the playlists featuring a specific artist. Failed requests aren't
+/// cached, so they are tried again next time.
+pub async fn artist_playlists(artist_id: i32) -> Option<Vec<Playlist>> {
+    QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .fetch_artist_playlists(artist_id)
+        .await
+}
+
 #[instrument]
 #[cached(size = 10, time = 600)]
 /// Fetch the tracks for a specific playlist.
@@ -580,6 +2003,19 @@ pub async fn playlist_tracks(playlist_id: i64) -> Vec<Track> {
     }
 }
 
+#[instrument]
+#[cached(size = 50, time = 600)]
+/// Fetch the full details of a track.
+pub async fn track(track_id: i32) -> Option<Track> {
+    QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .fetch_track(track_id)
+        .await
+}
+
 #[instrument]
 #[cached(size = 1, time = 600)]
 /// Fetch the current user's list of playlists.
@@ -598,22 +2034,167 @@ pub async fn user_playlists() -> Vec<Playlist> {
     }
 }
 
+#[instrument]
+/// Create a playlist for the current user. The cached list of the user's
+/// playlists is dropped so the new one shows up on the next fetch.
+pub async fn create_playlist(
+    name: String,
+    public: bool,
+    description: Option<String>,
+) -> Option<Playlist> {
+    let playlist = QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .create_playlist(&name, public, description)
+        .await?;
+
+    USER_PLAYLISTS.lock().await.cache_clear();
+
+    Some(playlist)
+}
+
+#[instrument]
+/// Add a track to one of the user's playlists. `Some(false)` means Qobuz
+/// refused it because the track is already in the playlist.
+pub async fn add_track_to_playlist(playlist_id: u32, track_id: u32) -> Option<bool> {
+    let added = QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .add_track_to_playlist(playlist_id, track_id)
+        .await?;
+
+    if added {
+        USER_PLAYLISTS.lock().await.cache_clear();
+    }
+
+    Some(added)
+}
+
+#[instrument]
+/// Follow a public playlist into the user's playlists, or stop following it.
+pub async fn set_playlist_followed(playlist_id: u32, followed: bool) -> bool {
+    let changed = QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .set_playlist_followed(playlist_id, followed)
+        .await;
+
+    if changed {
+        USER_PLAYLISTS.lock().await.cache_clear();
+    }
+
+    changed
+}
+
+#[instrument]
+#[cached(size = 1, time = 600)]
+/// Fetch the albums and tracks the current user has purchased.
+pub async fn user_purchases() -> Purchases {
+    if let Some(purchases) = QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .fetch_user_purchases()
+        .await
+    {
+        purchases
+    } else {
+        Purchases::default()
+    }
+}
+
+#[instrument]
+/// Who is logged in and what their subscription allows.
+pub async fn account() -> Option<Account> {
+    let service = QUEUE.get().unwrap().read().await.service();
+
+    service.account().await
+}
+#[instrument]
+/// Fetch the user's favorite albums, tracks and artists.
+pub async fn favorites() -> Favorites {
+    QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .fetch_favorites()
+        .await
+        .unwrap_or_default()
+}
+
+#[instrument]
+#[cached(size = 1, time = 600, option = true)]
+/// Fetch the latest editorial articles. A failed request isn't cached, so it
+/// is tried again next time.
+pub async fn editorial_content() -> Option<Vec<Article>> {
+    QUEUE
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .fetch_editorial_content()
+        .await
+}
+
 /// Inserts the most recent position into the state at a set interval.
 #[instrument]
-pub async fn clock_loop() {
+pub async fn clock_loop(interval_millis: u64) {
     debug!("starting clock loop");
 
-    let mut interval = tokio::time::interval(Duration::from_millis(REFRESH_RESOLUTION));
+    let (min, max) = POSITION_INTERVAL_RANGE;
+    let mut interval =
+        tokio::time::interval(Duration::from_millis(interval_millis.clamp(min, max)));
     let mut last_position = ClockTime::default();
+    let mut last_sent: Option<(ClockTime, Instant)> = None;
 
     loop {
         interval.tick().await;
 
         if current_state() == GstState::Playing {
             if let Some(position) = position() {
-                if position.seconds() != last_position.seconds() {
+                if position != last_position {
+                    // Save the position regularly so a crash or power loss resumes close by.
+                    if position.seconds() != last_position.seconds()
+                        && position.seconds() % SAVE_POSITION_INTERVAL == 0
+                    {
+                        tokio::spawn(async { QUEUE.get().unwrap().read().await.persist().await });
+                    }
+
+                    // A seek can jump straight past the mark, so anything after it counts.
+                    if position.seconds() >= PREFETCH_AFTER_SECONDS
+                        && !PREFETCH_STARTED.swap(true, Ordering::Relaxed)
+                    {
+                        tokio::spawn(async {
+                            if let Err(error) = extend_radio().await {
+                                debug!("unable to extend the radio: {error}");
+                            }
+
+                            prefetch_next_track_url().await;
+                        });
+                    }
+
                     last_position = position;
 
+                    let due = POSITION_FLUSH.swap(false, Ordering::Relaxed)
+                        || last_sent.map_or(true, |(sent, at)| {
+                            sent.seconds() != position.seconds()
+                                || at.elapsed() >= POSITION_THROTTLE
+                        });
+
+                    if !due {
+                        continue;
+                    }
+
+                    last_sent = Some((position, Instant::now()));
+
                     BROADCAST_CHANNELS
                         .tx
                         .broadcast(Notification::Position { clock: position })
@@ -625,6 +2206,122 @@ pub async fn clock_loop() {
     }
 }
 
+/// Tells listeners whenever the client has to log in again because its user
+/// token expired, and how it went. Stops when the client is dropped.
+pub fn watch_reauthentication(client: &client::api::Client) {
+    let mut reauthentications = client.reauthentications();
+
+    tokio::spawn(async move {
+        loop {
+            let notification = match reauthentications.recv().await {
+                Ok(Reauthentication::Started) => Notification::Reauthenticating,
+                Ok(Reauthentication::Finished { success }) => {
+                    Notification::Reauthenticated { success }
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+
+            BROADCAST_CHANNELS
+                .tx
+                .broadcast(notification)
+                .await
+                .expect("failed to send notification");
+        }
+    });
+}
+
+/// Checks the service still accepts the session and, if it turned the token
+/// down, logs in again with a fresh client. Returns `Some(success)` when a
+/// re-login was attempted. The queue isn't locked while talking to the service.
+async fn refresh_session() -> Option<bool> {
+    let (service, (username, password)) = {
+        let state = QUEUE.get().unwrap().read().await;
+        (state.service(), state.credentials())
+    };
+
+    if !service.session_rejected().await {
+        return None;
+    }
+
+    debug!("session is no longer valid, logging in again");
+    db::clear_user_token().await;
+
+    match qobuz::make_client(username.as_deref(), password.as_deref()).await {
+        Ok(client) => {
+            watch_reauthentication(&client);
+            QUEUE
+                .get()
+                .unwrap()
+                .write()
+                .await
+                .set_service(Arc::new(client));
+            Some(true)
+        }
+        Err(error) => {
+            error!("failed to log in again: {error}");
+            Some(false)
+        }
+    }
+}
+
+/// Periodically re-validates the service session, logging in again if the
+/// token is no longer accepted. Disabled when the interval is zero.
+#[instrument]
+pub async fn session_loop(interval_hours: u64) {
+    debug!("starting session loop");
+
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_hours * 60 * 60));
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        if let Some(success) = refresh_session().await {
+            BROADCAST_CHANNELS
+                .tx
+                .broadcast(Notification::Reauthenticated { success })
+                .await
+                .expect("failed to send notification");
+        }
+    }
+}
+
+/// Releases the pipeline once the player has been paused or stopped for
+/// longer than the timeout, so other applications can use the audio device.
+#[instrument]
+pub async fn idle_loop(timeout_minutes: u64) {
+    debug!("starting idle loop");
+
+    let timeout = Duration::from_secs(timeout_minutes * 60);
+    let mut interval = tokio::time::interval(IDLE_CHECK_INTERVAL);
+    let mut idle_since: Option<std::time::Instant> = None;
+
+    loop {
+        interval.tick().await;
+
+        let target_status = QUEUE.get().unwrap().read().await.target_status();
+
+        // Buffering pauses the pipeline too, but the player still means to play.
+        let idle = (is_paused() || is_ready()) && target_status != GstState::Playing && !is_idle();
+
+        if !idle {
+            idle_since = None;
+            continue;
+        }
+
+        let since = *idle_since.get_or_insert_with(std::time::Instant::now);
+
+        if since.elapsed() >= timeout {
+            idle_since = None;
+
+            if let Err(error) = idle_out().await {
+                error!("failed to release idle pipeline: {error}");
+            }
+        }
+    }
+}
+
 async fn quit() -> Result<()> {
     debug!("stopping player");
 
@@ -663,13 +2360,38 @@ pub async fn player_loop() -> Result<()> {
     let mut actions = action_rx.stream();
     let mut quitter = QUEUE.get().unwrap().read().await.quitter();
 
-    let clock_handle = tokio::spawn(async { clock_loop().await });
+    let position_interval = db::get_position_interval().await as u64;
+    let clock_handle = tokio::spawn(async move { clock_loop(position_interval).await });
+
+    let relogin_interval = db::get_relogin_interval().await;
+    let session_handle = if relogin_interval > 0 {
+        Some(tokio::spawn(async move {
+            session_loop(relogin_interval as u64).await
+        }))
+    } else {
+        None
+    };
+
+    let idle_timeout = db::get_idle_timeout().await;
+    let idle_handle = if idle_timeout > 0 {
+        Some(tokio::spawn(
+            async move { idle_loop(idle_timeout as u64).await },
+        ))
+    } else {
+        None
+    };
 
     loop {
         select! {
             Ok(should_quit)= quitter.recv() => {
                 if should_quit {
                     clock_handle.abort();
+                    if let Some(handle) = &session_handle {
+                        handle.abort();
+                    }
+                    if let Some(handle) = &idle_handle {
+                        handle.abort();
+                    }
                     quit().await?;
                     break;
                 }
@@ -680,7 +2402,22 @@ pub async fn player_loop() -> Result<()> {
                 }
             }
             Some(action) = actions.next() => {
-                tokio::spawn(async { handle_action(action).await.expect("error handling action") });
+                let is_load = action.is_load();
+                let handle = tokio::spawn(async {
+                    if let Err(error) = handle_action(action).await {
+                        error!("failed to handle action: {error}");
+
+                        BROADCAST_CHANNELS
+                            .tx
+                            .broadcast(Notification::Error { error })
+                            .await
+                            .expect("failed to send notification");
+                    }
+                });
+
+                if is_load {
+                    *LOAD_HANDLE.lock().unwrap() = Some(handle.abort_handle());
+                }
             }
             Some(msg) = messages.next() => {
                 if msg.type_() == MessageType::Buffering {
@@ -702,33 +2439,45 @@ pub async fn player_loop() -> Result<()> {
     Ok(())
 }
 
-async fn handle_action(action: Action) -> Result<()> {
+/// Carry out an action from any of the controls.
+pub async fn handle_action(action: Action) -> Result<()> {
     match action {
-        Action::JumpBackward => jump_backward().await?,
-        Action::JumpForward => jump_forward().await?,
-        Action::Next => {
-            let state = QUEUE.get().unwrap().read().await;
+        Action::JumpBackward { seconds } => jump_backward(seconds).await?,
+        Action::CancelLoad => cancel_load().await?,
+        Action::ToggleFavoriteCurrent => toggle_favorite_current().await?,
+        Action::RetryLogin => {
+            let success = QUEUE.get().unwrap().write().await.retry_login().await;
 
-            let current_position = state.current_track_position();
-            drop(state);
-            skip(current_position + 1).await?;
+            BROADCAST_CHANNELS
+                .tx
+                .broadcast(Notification::Reauthenticated { success })
+                .await?;
+        }
+        Action::JumpForward { seconds } => jump_forward(seconds).await?,
+        Action::Seek { time } => seek(time, None).await?,
+        Action::SeekTo { seconds } => seek_to(seconds).await?,
+        Action::Next => {
+            skip(next_position().await, false).await?;
         }
         Action::Pause => pause().await?,
         Action::Play => play().await?,
         Action::PlayPause => play_pause().await?,
         Action::Previous => {
-            let state = QUEUE.get().unwrap().read().await;
-
-            let current_position = state.current_track_position();
-            drop(state);
-            skip(current_position - 1).await?;
+            skip(previous_position().await, false).await?;
+        }
+        Action::Stop => {
+            set_radio(false).await?;
+            stop().await?;
+            broadcast_stopped(StopReason::UserStop).await?;
         }
-        Action::Stop => stop().await?,
         Action::PlayAlbum { album_id } => {
             play_album(album_id).await?;
         }
-        Action::PlayTrack { track_id } => {
-            play_track(track_id).await?;
+        Action::PlayAlbumFrom { album_id, track_id } => {
+            play_album_from(album_id, track_id).await?;
+        }
+        Action::PlayTrack { track_id, quality } => {
+            play_track(track_id, quality).await?;
         }
         Action::PlayUri { uri } => {
             play_uri(uri).await?;
@@ -736,16 +2485,80 @@ async fn handle_action(action: Action) -> Result<()> {
         Action::PlayPlaylist { playlist_id } => {
             play_playlist(playlist_id).await?;
         }
-        Action::Quit => QUEUE.get().unwrap().read().await.quit(),
+        Action::PlayTrackRadio { track_id } => {
+            play_track_radio(track_id).await?;
+        }
+        Action::StartRadio { track_id } => start_radio(track_id).await?,
+        Action::SetRadio { enabled } => set_radio(enabled).await?,
+        Action::AddToQueue { track_id } => add_to_queue(track_id).await?,
+        Action::PlayNext { track_id } => play_next(track_id).await?,
+        Action::RemoveFromQueue { position } => remove_from_queue(position).await?,
+        Action::MoveTrack { from, to } => move_track(from, to).await?,
+        Action::ExportQueue { path, format } => {
+            let format = format.unwrap_or_else(|| ExportFormat::from_path(&path));
+            export_queue(&path, format).await?;
+        }
+        Action::Quit => {
+            RADIO.store(false, Ordering::Relaxed);
+            QUEUE.get().unwrap().read().await.quit();
+        }
         Action::SkipTo { num } => {
-            skip(num).await?;
+            skip(num, true).await?;
         }
         Action::Search { query } => {
             search(&query).await;
         }
+        Action::SetSkipSilence { enabled, threshold } => {
+            set_skip_silence(enabled, threshold)?;
+            db::set_skip_silence(enabled, threshold).await;
+        }
+        Action::SetRepeat { mode } => {
+            QUEUE.get().unwrap().write().await.set_repeat(mode);
+
+            BROADCAST_CHANNELS
+                .tx
+                .broadcast(Notification::Repeat { mode })
+                .await?;
+        }
+        Action::SetQuality { quality } => set_quality(quality).await,
+        Action::SetNormalization { mode } => {
+            set_normalization(mode)?;
+            db::set_normalization(mode).await;
+
+            let track = QUEUE.get().unwrap().read().await.current_track();
+            apply_normalization(track.as_ref());
+        }
+        Action::SetMono { enabled } => {
+            set_mono(enabled)?;
+            db::set_mono(enabled).await;
+
+            BROADCAST_CHANNELS
+                .tx
+                .broadcast(Notification::Mono { enabled })
+                .await?;
+        }
+        Action::SetVolume { level } => {
+            let level = set_volume(level);
+            db::set_volume(level).await;
+
+            BROADCAST_CHANNELS
+                .tx
+                .broadcast(Notification::Volume { level })
+                .await?;
+        }
+        Action::SetPreamp { db: gain } => {
+            set_preamp(gain)?;
+            db::set_preamp(preamp()).await;
+        }
         Action::FetchArtistAlbums { artist_id: _ } => {}
         Action::FetchPlaylistTracks { playlist_id: _ } => {}
         Action::FetchUserPlaylists => {}
+        Action::QueuePeek { count: _ } => {}
+        Action::QueuePrevious { count: _ } => {}
+        Action::DumpState => dump_state().await?,
+        Action::SetSleepTimer { minutes } => set_sleep_timer(minutes).await?,
+        Action::CancelSleepTimer => cancel_sleep_timer().await?,
+        Action::Download { album } => download(album).await?,
     }
 
     Ok(())
@@ -755,6 +2568,13 @@ async fn handle_message(msg: Message) -> Result<()> {
     match msg.view() {
         MessageView::Eos(_) => {
             debug!("END OF STREAM");
+            let reason = if NOTHING_PLAYABLE.swap(false, Ordering::Relaxed) {
+                StopReason::NothingPlayable
+            } else {
+                StopReason::EndOfQueue
+            };
+            broadcast_stopped(reason).await?;
+
             if QUIT_WHEN_DONE.load(Ordering::Relaxed) {
                 QUEUE.get().unwrap().read().await.quit();
             } else {
@@ -762,12 +2582,21 @@ async fn handle_message(msg: Message) -> Result<()> {
                 q.set_target_status(GstState::Paused);
                 drop(q);
 
-                skip(1).await?;
+                skip(1, false).await?;
             }
         }
         MessageView::StreamStart(_) => {
+            PREFETCH_STARTED.store(false, Ordering::Relaxed);
+
+            let state = QUEUE.get().unwrap().read().await;
+            // A gapless transition queues the next track while the last one
+            // is still playing, its gain only applies once it starts.
+            apply_normalization(state.current_track().as_ref());
+
             if is_playing() {
-                let list = QUEUE.get().unwrap().read().await.track_list();
+                let list = state.track_list();
+                drop(state);
+
                 broadcast_track_list(list).await?;
             }
         }
@@ -889,6 +2718,34 @@ async fn handle_message(msg: Message) -> Result<()> {
                     .await?;
             }
         }
+        MessageView::Element(element) => {
+            if let Some(structure) = element.structure() {
+                if structure.name() == "removesilence" && structure.has_field("silence_finished") {
+                    debug!("silence skipped");
+                    BROADCAST_CHANNELS
+                        .tx
+                        .broadcast(Notification::SilenceSkipped)
+                        .await?;
+                } else if structure.name() == "level" {
+                    let channels = |field: &str| -> Vec<f64> {
+                        structure
+                            .get::<gst::glib::ValueArray>(field)
+                            .map(|values| {
+                                values.iter().filter_map(|v| v.get::<f64>().ok()).collect()
+                            })
+                            .unwrap_or_default()
+                    };
+
+                    LEVEL_CHANNELS
+                        .tx
+                        .broadcast(Notification::Level {
+                            peak: channels("peak"),
+                            rms: channels("rms"),
+                        })
+                        .await?;
+                }
+            }
+        }
         MessageView::ClockLost(_) => {
             debug!("clock lost, restarting playback");
             pause().await?;
@@ -900,9 +2757,17 @@ async fn handle_message(msg: Message) -> Result<()> {
                 .broadcast(Notification::Error { error: err.into() })
                 .await?;
 
-            ready().await?;
-            pause().await?;
-            play().await?;
+            let restarted = async {
+                ready().await?;
+                pause().await?;
+                play().await
+            }
+            .await;
+
+            if let Err(error) = restarted {
+                broadcast_stopped(StopReason::Error).await?;
+                return Err(error);
+            }
 
             debug!(
                 "Error from {:?}: {} ({:?})",
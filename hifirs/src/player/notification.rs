@@ -1,7 +1,14 @@
 use gstreamer::{ClockTime, State};
+use hifirs_qobuz_api::client::AudioQuality;
 use serde::{Deserialize, Serialize, Serializer};
 
-use crate::{player, player::queue::TrackListValue};
+use crate::{
+    player,
+    player::{
+        controls::RepeatMode,
+        queue::{controls::StateSnapshot, TrackListValue},
+    },
+};
 
 pub type BroadcastReceiver = async_broadcast::Receiver<Notification>;
 pub type BroadcastSender = async_broadcast::Sender<Notification>;
@@ -13,6 +20,20 @@ where
     clock.seconds().serialize(s)
 }
 
+/// Why playback came to a stop.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum StopReason {
+    EndOfQueue,
+    UserStop,
+    Error,
+    SleepTimer,
+    /// Paused or stopped for longer than the idle timeout.
+    Idle,
+    /// None of the tracks left in the queue can be streamed.
+    NothingPlayable,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum Notification {
@@ -43,4 +64,59 @@ pub enum Notification {
     Error {
         error: player::error::Error,
     },
+    /// The user token expired and the client is logging in again.
+    Reauthenticating,
+    Reauthenticated {
+        success: bool,
+    },
+    SilenceSkipped,
+    /// The track couldn't be streamed, so playback moved past it.
+    TrackSkipped {
+        track_id: u32,
+        title: String,
+    },
+    FavoriteChanged {
+        track_id: u32,
+        favorited: bool,
+    },
+    /// Per channel peak and RMS levels, in dB. Sent through `player::level_receiver`.
+    Level {
+        peak: Vec<f64>,
+        rms: Vec<f64>,
+    },
+    Stopped {
+        reason: StopReason,
+    },
+    StateDump {
+        snapshot: StateSnapshot,
+    },
+    Mono {
+        enabled: bool,
+    },
+    /// Whether the queue is kept going with suggested tracks once it runs out.
+    Radio {
+        enabled: bool,
+    },
+    Repeat {
+        mode: RepeatMode,
+    },
+    Volume {
+        level: f64,
+    },
+    /// Bytes of a track saved so far. Complete once `written` reaches `total`.
+    DownloadProgress {
+        track_id: u32,
+        title: String,
+        written: u64,
+        total: Option<u64>,
+    },
+    /// Seconds left on the sleep timer, `None` once it is cancelled or ends.
+    SleepTimer {
+        seconds: Option<u64>,
+    },
+    /// The account isn't allowed the requested quality, so a lower one is played.
+    QualityFallback {
+        requested: AudioQuality,
+        granted: AudioQuality,
+    },
 }
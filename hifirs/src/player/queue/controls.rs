@@ -1,18 +1,61 @@
 use crate::{
     player,
-    player::queue::{TrackListType, TrackListValue},
+    player::{
+        controls::RepeatMode,
+        notify_quality_fallback,
+        queue::{TrackListType, TrackListValue},
+    },
     qobuz,
-    service::{Album, MusicService, Playlist, SearchResults, Track, TrackStatus},
+    service::{
+        Album, Article, Artist, Favorites, MusicService, Playlist, Purchases, SearchResults,
+        StreamError, StreamUrl, Track, TrackStatus,
+    },
     sql::db,
 };
 use futures::executor;
 use gstreamer::{ClockTime, State as GstState};
-use std::{collections::BTreeMap, sync::Arc};
+use hifirs_qobuz_api::client::{
+    api::{self, Client as QobuzClient},
+    AudioQuality,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 use tokio::sync::{
     broadcast::{Receiver as BroadcastReceiver, Sender as BroadcastSender},
     RwLock,
 };
 
+/// Most tracks queued by a track radio, the seed included.
+const TRACK_RADIO_LIMIT: usize = 50;
+
+/// A stream url fetched before it was needed.
+#[derive(Debug, Clone)]
+pub struct PrefetchedUrl {
+    pub track_id: u32,
+    pub quality: AudioQuality,
+    pub stream: StreamUrl,
+}
+
+impl PrefetchedUrl {
+    pub fn new(track_id: u32, quality: AudioQuality, stream: StreamUrl) -> Self {
+        Self {
+            track_id,
+            quality,
+            stream,
+        }
+    }
+
+    /// Qobuz stream urls stop working after a while, so an expired one is
+    /// fetched again.
+    fn is_usable(&self, track_id: u32, quality: &AudioQuality) -> bool {
+        self.track_id == track_id && &self.quality == quality && !self.stream.is_expired()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PlayerState {
     service: Arc<dyn MusicService>,
@@ -22,6 +65,12 @@ pub struct PlayerState {
     resume: bool,
     target_status: GstState,
     quit_sender: BroadcastSender<bool>,
+    username: Option<String>,
+    password: Option<String>,
+    offline: bool,
+    favorite_tracks: Option<HashSet<u32>>,
+    repeat: RepeatMode,
+    prefetched_url: Option<PrefetchedUrl>,
 }
 
 pub type SafePlayerState = Arc<RwLock<PlayerState>>;
@@ -68,77 +117,316 @@ impl From<PlayerState> for SavedState {
     }
 }
 
-impl PlayerState {
-    pub async fn play_album(&mut self, album_id: String) -> Option<String> {
-        if let Some(album) = self.service.album(album_id.as_str()).await {
-            let mut tracklist = TrackListValue::new(Some(album.tracks.clone()));
-            tracklist.set_album(album);
-            tracklist.set_list_type(TrackListType::Album);
-            tracklist.set_track_status(1, TrackStatus::Playing);
-
-            self.replace_list(tracklist.clone());
+/// A snapshot of the player for troubleshooting. Credentials are only
+/// reported as present or missing, never their values.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StateSnapshot {
+    pub status: GstState,
+    pub target_status: GstState,
+    pub list_type: TrackListType,
+    pub current_track_position: u32,
+    pub queue: Vec<QueueEntry>,
+    pub quality: AudioQuality,
+    pub offline: bool,
+    pub has_app_id: bool,
+    pub has_active_secret: bool,
+}
 
-            if let Some(mut entry) = tracklist.queue.first_entry() {
-                let first_track = entry.get_mut();
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueEntry {
+    pub position: u32,
+    pub track_id: u32,
+    pub title: String,
+    pub status: TrackStatus,
+}
 
-                self.attach_track_url(first_track).await;
-                self.set_current_track(first_track.clone());
-                self.set_target_status(GstState::Playing);
+impl PlayerState {
+    /// Play an album from its first playable track.
+    pub async fn play_album(&mut self, album_id: String) -> Result<Option<String>, StreamError> {
+        self.load_album(album_id).await?;
 
-                first_track.track_url.clone()
-            } else {
-                None
+        let first = self.first_position();
+        self.skip_track(first).await
+    }
+    /// Play an album starting at one of its tracks, or from the first
+    /// track when it isn't on the album.
+    pub async fn play_album_from(
+        &mut self,
+        album_id: String,
+        track_id: i32,
+    ) -> Result<Option<String>, StreamError> {
+        self.load_album(album_id).await?;
+
+        match self.tracklist.track_index(track_id as u32) {
+            Some(position) => self.play_position(position).await,
+            None => {
+                let first = self.first_position();
+                self.skip_track(first).await
             }
-        } else {
-            None
         }
     }
-    pub async fn play_track(&mut self, track_id: i32) -> Option<String> {
-        if let Some(mut track) = self.service.track(track_id).await {
-            track.status = TrackStatus::Playing;
-            track.number = 1;
+    /// Replace the queue with an album, ready to play.
+    async fn load_album(&mut self, album_id: String) -> Result<(), StreamError> {
+        let album = self
+            .service
+            .album(album_id.as_str())
+            .await
+            .ok_or_else(|| not_loaded(format!("album {album_id}")))?;
+
+        let mut tracklist = TrackListValue::new(Some(album.tracks.clone()));
+        tracklist.set_album(album);
+        tracklist.set_list_type(TrackListType::Album);
+
+        self.replace_list(tracklist);
+        self.set_target_status(GstState::Playing);
+
+        Ok(())
+    }
+    /// Play a single track. At the default quality a track that can't be
+    /// streamed is passed over like any other, asking for a quality makes
+    /// it an error.
+    pub async fn play_track(
+        &mut self,
+        track_id: i32,
+        quality: Option<AudioQuality>,
+    ) -> Result<Option<String>, StreamError> {
+        let mut track = self
+            .service
+            .track(track_id)
+            .await
+            .ok_or_else(|| not_loaded(format!("track {track_id}")))?;
+
+        track.number = 1;
+        let position = track.position;
+
+        let mut queue = BTreeMap::new();
+        queue.insert(position, track);
+
+        let mut tracklist = TrackListValue::new(Some(queue));
+        tracklist.set_list_type(TrackListType::Track);
+
+        self.replace_list(tracklist);
+        self.set_target_status(GstState::Playing);
+
+        match quality {
+            Some(quality) => self.play_at_quality(position, quality).await,
+            None => self.skip_track(position).await,
+        }
+    }
+    /// Queue the track followed by suggestions seeded from it.
+    pub async fn play_track_radio(&mut self, track_id: i32) -> Result<Option<String>, StreamError> {
+        let seed = self
+            .service
+            .track(track_id)
+            .await
+            .ok_or_else(|| not_loaded(format!("track {track_id}")))?;
+        let suggestions = self
+            .service
+            .track_suggestions(track_id, TRACK_RADIO_LIMIT - 1)
+            .await
+            .unwrap_or_default();
+
+        let mut queue = BTreeMap::new();
+        let mut seen = HashSet::new();
+
+        for mut track in std::iter::once(seed).chain(suggestions) {
+            if !seen.insert(track.id) {
+                continue;
+            }
 
-            let mut queue = BTreeMap::new();
-            queue.entry(track.position).or_insert_with(|| track.clone());
+            let position = queue.len() as u32 + 1;
+            track.number = position;
+            track.position = position;
 
-            let mut tracklist = TrackListValue::new(Some(queue));
-            tracklist.set_list_type(TrackListType::Track);
+            queue.insert(position, track);
+        }
 
-            self.replace_list(tracklist.clone());
+        let mut tracklist = TrackListValue::new(Some(queue));
+        tracklist.set_list_type(TrackListType::Track);
 
-            self.attach_track_url(&mut track).await;
-            self.set_current_track(track.clone());
-            self.set_target_status(GstState::Playing);
+        self.replace_list(tracklist);
+        self.set_target_status(GstState::Playing);
 
-            track.track_url.clone()
+        let first = self.first_position();
+        self.skip_track(first).await
+    }
+    /// Play a playlist once its first `page_size` tracks are loaded, or all of
+    /// them when `page_size` is zero. The rest are added with
+    /// `append_playlist_tracks`.
+    pub async fn play_playlist(
+        &mut self,
+        playlist_id: i64,
+        page_size: u32,
+    ) -> Result<Option<String>, StreamError> {
+        let playlist = if page_size > 0 {
+            self.service.playlist_page(playlist_id, 0, page_size).await
         } else {
-            None
+            self.service.playlist(playlist_id).await
+        };
+        let playlist = playlist.ok_or_else(|| not_loaded(format!("playlist {playlist_id}")))?;
+
+        let mut tracklist = TrackListValue::new(Some(playlist.tracks.clone()));
+        tracklist.set_playlist(playlist);
+        tracklist.set_list_type(TrackListType::Playlist);
+
+        self.replace_list(tracklist);
+        self.set_target_status(GstState::Playing);
+
+        let first = self.first_position();
+        self.skip_track(first).await
+    }
+
+    /// Adds a page of tracks to the end of the playlist being played. Returns
+    /// false when something else has replaced the playlist in the meantime.
+    pub fn append_playlist_tracks(&mut self, playlist_id: i64, tracks: Vec<Track>) -> bool {
+        let current = self.tracklist.get_playlist().map(|p| p.id as i64);
+
+        if current != Some(playlist_id) {
+            return false;
         }
+
+        let mut position = self.last_position();
+
+        for mut track in tracks {
+            position += 1;
+            track.position = position;
+
+            if let Some(playlist) = &mut self.tracklist.playlist {
+                playlist.tracks.insert(position, track.clone());
+            }
+
+            self.tracklist.queue.insert(position, track);
+        }
+
+        true
     }
-    pub async fn play_playlist(&mut self, playlist_id: i64) -> Option<String> {
-        if let Some(playlist) = self.service.playlist(playlist_id).await {
-            let mut tracklist = TrackListValue::new(Some(playlist.tracks.clone()));
 
-            tracklist.set_playlist(playlist);
-            tracklist.set_list_type(TrackListType::Playlist);
-            tracklist.set_track_status(1, TrackStatus::Playing);
+    /// Add a track to the end of the queue.
+    pub fn add_to_queue(&mut self, track: Track) {
+        let position = self.last_position() + 1;
 
-            self.replace_list(tracklist.clone());
+        self.insert_track(position, track);
+    }
 
-            if let Some(mut entry) = tracklist.queue.first_entry() {
-                let first_track = entry.get_mut();
+    /// Queue a track right after the current one.
+    pub fn play_next(&mut self, track: Track) {
+        let position = self.current_track_position() + 1;
 
-                self.attach_track_url(first_track).await;
-                self.set_current_track(first_track.clone());
-                self.set_target_status(GstState::Playing);
+        self.insert_track(position, track);
+    }
 
-                first_track.track_url.clone()
-            } else {
-                None
+    fn insert_track(&mut self, position: u32, mut track: Track) {
+        track.status = TrackStatus::Unplayed;
+
+        if self.tracklist.list_type == TrackListType::Unknown {
+            self.tracklist.set_list_type(TrackListType::Track);
+        }
+
+        self.tracklist.insert(position, track);
+        self.sync_current_track();
+    }
+
+    /// Add tracks to the end of the queue, leaving out any already in it.
+    /// Returns how many were added.
+    pub fn append_tracks(&mut self, tracks: Vec<Track>) -> usize {
+        let mut queued = self
+            .tracklist
+            .queue
+            .values()
+            .map(|t| t.id)
+            .collect::<HashSet<u32>>();
+        let mut added = 0;
+
+        for mut track in tracks {
+            if !queued.insert(track.id) {
+                continue;
             }
-        } else {
-            None
+
+            track.status = TrackStatus::Unplayed;
+
+            let position = self.last_position() + 1;
+            self.tracklist.insert(position, track);
+            added += 1;
         }
+
+        if added > 0 {
+            if self.tracklist.list_type == TrackListType::Unknown {
+                self.tracklist.set_list_type(TrackListType::Track);
+            }
+
+            self.sync_current_track();
+        }
+
+        added
+    }
+
+    /// Take the track at `position` out of the queue. The playing track can't be removed.
+    pub fn remove_from_queue(&mut self, position: u32) -> Option<Track> {
+        let playing = self
+            .tracklist
+            .find_track_by_index(position)
+            .map_or(false, |t| t.status == TrackStatus::Playing);
+
+        if playing {
+            return None;
+        }
+
+        let removed = self.tracklist.remove(position);
+        self.sync_current_track();
+
+        removed
+    }
+
+    /// Move the track at `from` to `to`. Returns the moved track, or None
+    /// when either place is outside the queue.
+    pub fn move_in_queue(&mut self, from: u32, to: u32) -> Option<Track> {
+        if !self.tracklist.move_track(from, to) {
+            return None;
+        }
+
+        self.sync_current_track();
+
+        self.tracklist.find_track_by_index(to).cloned()
+    }
+
+    /// Keep the current track's place in step with an edited queue.
+    fn sync_current_track(&mut self) {
+        let playing = self
+            .tracklist
+            .queue
+            .values()
+            .find(|t| t.status == TrackStatus::Playing)
+            .map(|t| (t.position, t.number));
+
+        if let (Some(current), Some((position, number))) = (&mut self.current_track, playing) {
+            current.position = position;
+            current.number = number;
+        }
+    }
+
+    pub fn set_repeat(&mut self, mode: RepeatMode) {
+        self.repeat = mode;
+    }
+
+    pub fn repeat(&self) -> RepeatMode {
+        self.repeat
+    }
+
+    /// Position of the first track in the queue, or zero when it is empty.
+    pub fn first_position(&self) -> u32 {
+        self.tracklist.queue.keys().next().copied().unwrap_or(0)
+    }
+
+    /// Position of the last track in the queue, or zero when it is empty.
+    pub fn last_position(&self) -> u32 {
+        self.tracklist.queue.keys().last().copied().unwrap_or(0)
+    }
+
+    /// The service, for requests that shouldn't hold the player state.
+    pub fn service(&self) -> Arc<dyn MusicService> {
+        self.service.clone()
     }
 
     pub fn set_status(&mut self, status: GstState) {
@@ -214,40 +502,145 @@ impl PlayerState {
         self.target_status = target;
     }
 
-    /// Attach a `TrackURL` to the given track.
-    pub async fn attach_track_url(&mut self, track: &mut Track) {
-        debug!("fetching track url");
-        if let Some(track_url) = self.service.track_url(track.id as i32).await {
-            debug!("attaching url information to track");
-            track.track_url = Some(track_url);
+    /// Keep a url fetched ahead of time for `skip_track` to use.
+    pub fn set_prefetched_url(&mut self, prefetched: PrefetchedUrl) {
+        self.prefetched_url = Some(prefetched);
+    }
+
+    pub fn prefetched_url(&self) -> Option<&PrefetchedUrl> {
+        self.prefetched_url.as_ref()
+    }
+
+    /// Move playback to the track at `index`, or the first playable one after
+    /// it. Tracks Qobuz refuses to stream are marked unplayable and skipped.
+    /// Ok(None) when nothing from `index` on can be played.
+    pub async fn skip_track(&mut self, index: u32) -> Result<Option<String>, StreamError> {
+        self.start_from(index, true).await
+    }
+
+    /// Move playback to the track at `index`, which the user picked. A track
+    /// that can't be streamed is an error instead of being passed over.
+    pub async fn play_position(&mut self, index: u32) -> Result<Option<String>, StreamError> {
+        self.start_from(index, false).await
+    }
+
+    /// Errors that may go away, like a dropped connection, are returned
+    /// without touching the track's status so it can be tried again.
+    async fn start_from(
+        &mut self,
+        index: u32,
+        pass_over: bool,
+    ) -> Result<Option<String>, StreamError> {
+        let mut candidate = self.tracklist.next_playable(index);
+
+        while let Some(position) = candidate {
+            let track = match self.tracklist.find_track_by_index(position) {
+                Some(track) => track.clone(),
+                None => return Ok(None),
+            };
+
+            match self.stream_for(&track).await {
+                Ok(stream) => {
+                    player::set_stream_expiry(stream.expires_at);
+                    self.mark_playing(position, &stream.url);
+
+                    return Ok(Some(stream.url));
+                }
+                Err(StreamError::Refused) => {
+                    debug!("track {} can't be streamed", track.id);
+                    self.tracklist
+                        .set_track_status(position, TrackStatus::Unplayable);
+
+                    if !pass_over {
+                        return Err(StreamError::Refused);
+                    }
+
+                    player::notify_track_skipped(&track);
+                    candidate = self.tracklist.next_playable(position + 1);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Start the track at `position` at the given quality. A track that can't
+    /// be streamed is an error.
+    async fn play_at_quality(
+        &mut self,
+        position: u32,
+        quality: AudioQuality,
+    ) -> Result<Option<String>, StreamError> {
+        let track_id = match self.tracklist.find_track_by_index(position) {
+            Some(track) => track.id,
+            None => return Ok(None),
+        };
+
+        match self.service.track_url(track_id as i32, Some(quality)).await {
+            Ok(stream) => {
+                notify_quality_fallback(&stream);
+                player::set_stream_expiry(stream.expires_at);
+                self.mark_playing(position, &stream.url);
+
+                Ok(Some(stream.url))
+            }
+            Err(StreamError::Refused) => {
+                self.tracklist
+                    .set_track_status(position, TrackStatus::Unplayable);
+
+                Err(StreamError::Refused)
+            }
+            Err(error) => Err(error),
         }
     }
 
-    pub async fn skip_track(&mut self, index: u32) -> Option<String> {
-        let mut track_url = None;
+    /// The prefetched url when it was fetched for this track, otherwise a new one.
+    async fn stream_for(&mut self, track: &Track) -> Result<StreamUrl, StreamError> {
+        let prefetched = self
+            .prefetched_url
+            .take()
+            .filter(|p| p.is_usable(track.id, &self.service.default_quality()))
+            .map(|p| p.stream);
+
+        match prefetched {
+            Some(stream) => {
+                debug!("using prefetched track url");
+                Ok(stream)
+            }
+            None => self
+                .service
+                .track_url(track.id as i32, None)
+                .await
+                .map(|stream| {
+                    notify_quality_fallback(&stream);
+                    stream
+                }),
+        }
+    }
 
+    /// Tracks before `position` are played and those after it are up next.
+    /// Unplayable tracks stay that way.
+    fn mark_playing(&mut self, position: u32, url: &str) {
         for t in self.tracklist.queue.values_mut() {
-            match t.position.cmp(&index) {
+            if t.status == TrackStatus::Unplayable {
+                continue;
+            }
+
+            match t.position.cmp(&position) {
                 std::cmp::Ordering::Less => {
                     t.status = TrackStatus::Played;
                 }
                 std::cmp::Ordering::Equal => {
-                    if let Some(url) = self.service.track_url(t.id as i32).await {
-                        t.status = TrackStatus::Playing;
-                        t.track_url = Some(url.clone());
-                        track_url = Some(url);
-                        self.current_track = Some(t.clone());
-                    } else {
-                        t.status = TrackStatus::Unplayable;
-                    }
+                    t.status = TrackStatus::Playing;
+                    t.track_url = Some(url.to_string());
+                    self.current_track = Some(t.clone());
                 }
                 std::cmp::Ordering::Greater => {
                     t.status = TrackStatus::Unplayed;
                 }
             }
         }
-
-        track_url
     }
 
     pub async fn search_all(&self, query: &str) -> Option<SearchResults> {
@@ -261,6 +654,22 @@ impl PlayerState {
         }
     }
 
+    pub async fn fetch_artist_playlists(&self, artist_id: i32) -> Option<Vec<Playlist>> {
+        self.service.artist_playlists(artist_id).await
+    }
+
+    pub async fn fetch_track_lyrics(&self, track_id: i32) -> Option<String> {
+        self.service.track_lyrics(track_id).await
+    }
+
+    pub async fn fetch_similar_artists(&self, artist_id: i32) -> Option<Vec<Artist>> {
+        self.service.similar_artists(artist_id).await
+    }
+
+    pub async fn fetch_playlist(&self, playlist_id: i64) -> Option<Playlist> {
+        self.service.playlist(playlist_id).await
+    }
+
     pub async fn fetch_playlist_tracks(&self, playlist_id: i64) -> Option<Vec<Track>> {
         match self.service.playlist(playlist_id).await {
             Some(results) => Some(results.tracks.values().cloned().collect::<Vec<Track>>()),
@@ -268,10 +677,82 @@ impl PlayerState {
         }
     }
 
+    pub async fn fetch_track(&self, track_id: i32) -> Option<Track> {
+        self.service.track(track_id).await
+    }
+
     pub async fn fetch_user_playlists(&self) -> Option<Vec<Playlist>> {
         self.service.user_playlists().await
     }
 
+    pub async fn create_playlist(
+        &self,
+        name: &str,
+        public: bool,
+        description: Option<String>,
+    ) -> Option<Playlist> {
+        self.service
+            .create_playlist(name, public, description)
+            .await
+    }
+
+    pub async fn set_playlist_followed(&self, playlist_id: u32, followed: bool) -> bool {
+        self.service
+            .set_playlist_followed(playlist_id, followed)
+            .await
+    }
+
+    /// Record the order a playlist was saved in.
+    pub fn set_playlist_order(&mut self, playlist_id: u32, tracks: BTreeMap<u32, Track>) {
+        self.tracklist.set_playlist_order(playlist_id, tracks);
+    }
+
+    pub async fn add_track_to_playlist(&self, playlist_id: u32, track_id: u32) -> Option<bool> {
+        self.service
+            .add_track_to_playlist(playlist_id, track_id)
+            .await
+    }
+
+    pub async fn fetch_user_purchases(&self) -> Option<Purchases> {
+        self.service.user_purchases().await
+    }
+
+    pub async fn fetch_favorites(&self) -> Option<Favorites> {
+        self.service.favorites().await
+    }
+
+    pub async fn fetch_editorial_content(&self) -> Option<Vec<Article>> {
+        self.service.editorial_content().await
+    }
+
+    /// Whether the track is a favorite. None until the favorites are loaded
+    /// with `set_favorite_tracks`.
+    pub fn is_favorite_track(&self, track_id: u32) -> Option<bool> {
+        self.favorite_tracks
+            .as_ref()
+            .map(|favorites| favorites.contains(&track_id))
+    }
+
+    pub fn has_favorite_tracks(&self) -> bool {
+        self.favorite_tracks.is_some()
+    }
+
+    /// The favorites are fetched once, then kept up to date locally.
+    pub fn set_favorite_tracks(&mut self, track_ids: Vec<u32>) {
+        self.favorite_tracks = Some(track_ids.into_iter().collect());
+    }
+
+    /// Record a change the service has accepted.
+    pub fn set_favorite_track(&mut self, track_id: u32, favorite: bool) {
+        if let Some(favorites) = &mut self.favorite_tracks {
+            if favorite {
+                favorites.insert(track_id);
+            } else {
+                favorites.remove(&track_id);
+            }
+        }
+    }
+
     pub fn quitter(&self) -> BroadcastReceiver<bool> {
         self.quit_sender.subscribe()
     }
@@ -291,12 +772,91 @@ impl PlayerState {
         self.resume = false;
     }
 
+    /// The username and password the service was logged in with.
+    pub fn credentials(&self) -> (Option<String>, Option<String>) {
+        (self.username.clone(), self.password.clone())
+    }
+
+    pub fn set_service(&mut self, service: Arc<dyn MusicService>) {
+        self.service = service;
+    }
+
+    /// Log in, giving up after the configured timeout so an unreachable
+    /// service can't hang startup.
+    async fn login(username: Option<&str>, password: Option<&str>) -> Option<QobuzClient> {
+        let timeout = Duration::from_secs(db::get_login_timeout().await.max(1) as u64);
+
+        match tokio::time::timeout(timeout, qobuz::make_client(username, password)).await {
+            Ok(Ok(client)) => {
+                player::watch_reauthentication(&client);
+                Some(client)
+            }
+            Ok(Err(error)) => {
+                error!("failed to log in: {error}");
+                None
+            }
+            Err(_) => {
+                error!("login timed out after {}s", timeout.as_secs());
+                None
+            }
+        }
+    }
+
+    /// Try to log in again after starting offline. Returns true once signed in.
+    pub async fn retry_login(&mut self) -> bool {
+        if let Some(client) = Self::login(self.username.as_deref(), self.password.as_deref()).await
+        {
+            self.service = Arc::new(client);
+            self.offline = false;
+        }
+
+        !self.offline
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    pub fn snapshot(&self) -> StateSnapshot {
+        let queue = self
+            .tracklist
+            .queue
+            .values()
+            .map(|t| QueueEntry {
+                position: t.position,
+                track_id: t.id,
+                title: t.title.trim().to_string(),
+                status: t.status.clone(),
+            })
+            .collect();
+
+        StateSnapshot {
+            status: self.status,
+            target_status: self.target_status,
+            list_type: self.list_type(),
+            current_track_position: self.current_track_position(),
+            queue,
+            quality: self.service.default_quality(),
+            offline: self.offline,
+            has_app_id: self.service.has_app_id(),
+            has_active_secret: self.service.has_active_secret(),
+        }
+    }
+
     pub async fn new(username: Option<&str>, password: Option<&str>) -> Self {
-        let client = Arc::new(
-            qobuz::make_client(username, password)
-                .await
-                .expect("error making client"),
-        );
+        let (client, offline) = match Self::login(username, password).await {
+            Some(client) => (client, false),
+            None => {
+                warn!("starting in offline mode");
+                (
+                    api::new(None, None, None, None, qobuz::cookies().await, None)
+                        .await
+                        .expect("error making client"),
+                    true,
+                )
+            }
+        };
+        let client = Arc::new(client);
 
         let tracklist = TrackListValue::new(None);
         let (quit_sender, _) = tokio::sync::broadcast::channel::<bool>(1);
@@ -309,6 +869,12 @@ impl PlayerState {
             target_status: gstreamer::State::Null,
             resume: false,
             quit_sender,
+            username: username.map(|u| u.to_string()),
+            password: password.map(|p| p.to_string()),
+            offline,
+            favorite_tracks: None,
+            repeat: RepeatMode::default(),
+            prefetched_url: None,
         }
     }
 
@@ -319,6 +885,26 @@ impl PlayerState {
         }
     }
 
+    /// Skips to the saved track and returns the position to resume from. If the
+    /// track is no longer available, the next playable track starts from the beginning.
+    async fn restore_track(&mut self, last_state: &SavedState) -> Option<ClockTime> {
+        let index = last_state.playback_track_index as u32;
+
+        self.skip_track(index).await.ok().flatten()?;
+
+        if self.current_track_position() == index {
+            Some(ClockTime::from_mseconds(
+                last_state.playback_position as u64,
+            ))
+        } else {
+            debug!(
+                "saved track unavailable, resuming from track {}",
+                self.current_track_position()
+            );
+            Some(ClockTime::default())
+        }
+    }
+
     pub async fn load_last_state(&mut self) -> Option<ClockTime> {
         if let Some(last_state) = db::get_last_state().await {
             let entity_type: TrackListType = last_state.playback_entity_type.as_str().into();
@@ -330,12 +916,7 @@ impl PlayerState {
                         self.tracklist.set_list_type(TrackListType::Album);
                         self.tracklist.set_album(album);
 
-                        self.skip_track(last_state.playback_track_index as u32)
-                            .await;
-
-                        let position =
-                            ClockTime::from_mseconds(last_state.playback_position as u64);
-                        return Some(position);
+                        return self.restore_track(&last_state).await;
                     }
                 }
                 TrackListType::Playlist => {
@@ -353,12 +934,7 @@ impl PlayerState {
                         self.tracklist.set_list_type(TrackListType::Playlist);
                         self.tracklist.set_playlist(playlist);
 
-                        self.skip_track(last_state.playback_track_index as u32)
-                            .await;
-
-                        let position =
-                            ClockTime::from_mseconds(last_state.playback_position as u64);
-                        return Some(position);
+                        return self.restore_track(&last_state).await;
                     }
                 }
                 TrackListType::Track => {
@@ -379,12 +955,7 @@ impl PlayerState {
                         self.replace_list(tracklist);
                         self.tracklist.set_list_type(TrackListType::Track);
 
-                        self.skip_track(last_state.playback_track_index as u32)
-                            .await;
-
-                        let position =
-                            ClockTime::from_mseconds(last_state.playback_position as u64);
-                        return Some(position);
+                        return self.restore_track(&last_state).await;
                     }
                 }
                 TrackListType::Unknown => unreachable!(),
@@ -394,3 +965,210 @@ impl PlayerState {
         None
     }
 }
+
+fn not_loaded(what: String) -> StreamError {
+    StreamError::Unavailable {
+        message: format!("{what} could not be loaded"),
+    }
+}
+
+/// A service with one playlist, refusing to stream the tracks in `refused`.
+#[cfg(test)]
+#[derive(Debug)]
+struct PlaylistService {
+    playlist: Playlist,
+    refused: Vec<u32>,
+}
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl MusicService for PlaylistService {
+    async fn login(&self, _username: &str, _password: &str) {}
+    async fn account(&self) -> Option<crate::service::Account> {
+        None
+    }
+    async fn album(&self, _album_id: &str) -> Option<Album> {
+        None
+    }
+    async fn track(&self, _track_id: i32) -> Option<Track> {
+        None
+    }
+    async fn track_suggestions(&self, _track_id: i32, _limit: usize) -> Option<Vec<Track>> {
+        None
+    }
+    async fn track_lyrics(&self, _track_id: i32) -> Option<String> {
+        None
+    }
+    async fn artist(&self, _artist_id: i32) -> Option<Artist> {
+        None
+    }
+    async fn playlist(&self, _playlist_id: i64) -> Option<Playlist> {
+        Some(self.playlist.clone())
+    }
+    async fn playlist_page(
+        &self,
+        _playlist_id: i64,
+        _offset: u32,
+        _limit: u32,
+    ) -> Option<Playlist> {
+        Some(self.playlist.clone())
+    }
+    async fn search(&self, _query: &str) -> Option<SearchResults> {
+        None
+    }
+    async fn track_url(
+        &self,
+        track_id: i32,
+        _quality: Option<AudioQuality>,
+    ) -> Result<StreamUrl, StreamError> {
+        if self.refused.contains(&(track_id as u32)) {
+            return Err(StreamError::Refused);
+        }
+
+        Ok(StreamUrl {
+            url: format!("https://example.com/{track_id}"),
+            requested: AudioQuality::Mp3,
+            granted: AudioQuality::Mp3,
+            expires_at: i64::MAX,
+        })
+    }
+    async fn download_track(
+        &self,
+        _track_id: i32,
+        _path: &std::path::Path,
+        _on_progress: Box<dyn FnMut(u64, Option<u64>) + Send>,
+    ) -> bool {
+        false
+    }
+    async fn user_playlists(&self) -> Option<Vec<Playlist>> {
+        None
+    }
+    async fn create_playlist(
+        &self,
+        _name: &str,
+        _public: bool,
+        _description: Option<String>,
+    ) -> Option<Playlist> {
+        None
+    }
+    async fn add_track_to_playlist(&self, _playlist_id: u32, _track_id: u32) -> Option<bool> {
+        None
+    }
+    async fn set_playlist_followed(&self, _playlist_id: u32, _followed: bool) -> bool {
+        false
+    }
+    async fn move_playlist_track(
+        &self,
+        _playlist_id: u32,
+        _playlist_track_id: u64,
+        _insert_before: u32,
+    ) -> bool {
+        false
+    }
+    async fn artist_playlists(&self, _artist_id: i32) -> Option<Vec<Playlist>> {
+        None
+    }
+    async fn similar_artists(&self, _artist_id: i32) -> Option<Vec<Artist>> {
+        None
+    }
+    async fn user_purchases(&self) -> Option<Purchases> {
+        None
+    }
+    async fn favorites(&self) -> Option<Favorites> {
+        None
+    }
+    async fn editorial_content(&self) -> Option<Vec<Article>> {
+        None
+    }
+    async fn favorite_tracks(&self) -> Option<Vec<u32>> {
+        None
+    }
+    async fn set_favorite_track(&self, _track_id: u32, _favorite: bool) -> bool {
+        false
+    }
+    async fn session_rejected(&self) -> bool {
+        false
+    }
+    fn default_quality(&self) -> AudioQuality {
+        AudioQuality::Mp3
+    }
+    fn set_default_quality(&self, _quality: AudioQuality) {}
+    fn has_app_id(&self) -> bool {
+        true
+    }
+    fn has_active_secret(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+fn playlist_state(track_ids: &[u32], refused: Vec<u32>) -> PlayerState {
+    let tracks = track_ids
+        .iter()
+        .enumerate()
+        .map(|(index, id)| {
+            let position = index as u32 + 1;
+            let track = Track {
+                id: *id,
+                number: position,
+                position,
+                ..Default::default()
+            };
+
+            (position, track)
+        })
+        .collect::<BTreeMap<u32, Track>>();
+
+    let service = PlaylistService {
+        playlist: Playlist {
+            id: 7,
+            tracks_count: tracks.len() as u32,
+            tracks,
+            ..Default::default()
+        },
+        refused,
+    };
+    let (quit_sender, _) = tokio::sync::broadcast::channel::<bool>(1);
+
+    PlayerState {
+        service: Arc::new(service),
+        current_track: None,
+        tracklist: TrackListValue::new(None),
+        status: GstState::Null,
+        resume: false,
+        target_status: GstState::Null,
+        quit_sender,
+        username: None,
+        password: None,
+        offline: false,
+        favorite_tracks: None,
+        repeat: RepeatMode::default(),
+        prefetched_url: None,
+    }
+}
+
+#[tokio::test]
+async fn playlists_start_at_the_first_playable_track() {
+    let mut state = playlist_state(&[10, 20, 30], vec![10]);
+
+    assert_eq!(
+        state.play_playlist(7, 0).await,
+        Ok(Some("https://example.com/20".to_string()))
+    );
+    assert_eq!(state.current_track().map(|t| t.id), Some(20));
+
+    let list = state.track_list();
+    assert_eq!(
+        list.find_track_by_index(1).map(|t| t.status.clone()),
+        Some(TrackStatus::Unplayable)
+    );
+    assert_eq!(
+        list.find_track_by_index(2).map(|t| t.status.clone()),
+        Some(TrackStatus::Playing)
+    );
+
+    // Nothing to play is not an error, the queue is loaded but stays stopped.
+    let mut state = playlist_state(&[10, 20], vec![10, 20]);
+    assert_eq!(state.play_playlist(7, 0).await, Ok(None));
+    assert!(state.current_track().is_none());
+}
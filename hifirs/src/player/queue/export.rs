@@ -0,0 +1,310 @@
+use crate::{
+    player::{download, queue::TrackListValue},
+    service::Track,
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// File formats a track list can be exported to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+    /// Plain text, one `NN. Artist - Title` line per track.
+    #[default]
+    Text,
+    M3u8,
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    /// Picks the format matching the extension of `path`, falling back to plain text.
+    pub fn from_path(path: &str) -> Self {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+
+        match extension.as_deref() {
+            Some("m3u8") | Some("m3u") => ExportFormat::M3u8,
+            Some("json") => ExportFormat::Json,
+            Some("csv") => ExportFormat::Csv,
+            _ => ExportFormat::Text,
+        }
+    }
+}
+
+const CSV_HEADER: &str = "position,track_id,title,artist,album,release_year,duration_seconds,isrc,bit_depth,sampling_rate,explicit,available,url";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonExport<'a> {
+    title: Option<String>,
+    list_type: String,
+    tracks: Vec<&'a Track>,
+}
+
+/// Link that opens the track in Qobuz.
+pub fn share_url(track: &Track) -> String {
+    format!("https://open.qobuz.com/track/{}", track.id)
+}
+
+impl TrackListValue {
+    /// Writes the track list to `writer` in the given format. M3U8 entries
+    /// point at the downloaded file when the track is in `library`.
+    pub fn export<W: Write>(
+        &self,
+        format: ExportFormat,
+        library: Option<&Path>,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        match format {
+            ExportFormat::Text => writeln!(writer, "{}", self.to_text()),
+            ExportFormat::M3u8 => self.write_m3u8(library, writer),
+            ExportFormat::Json => self.write_json(writer),
+            ExportFormat::Csv => self.write_csv(writer),
+        }
+    }
+
+    fn artist_name(&self, track: &Track) -> String {
+        if let Some(artist) = &track.artist {
+            artist.name.trim().to_string()
+        } else if let Some(album) = &self.album {
+            album.artist.name.trim().to_string()
+        } else {
+            "Unknown Artist".to_string()
+        }
+    }
+
+    /// Extended M3U. Tracks that can't be streamed are kept as comments so
+    /// the list still shows what is missing.
+    fn write_m3u8<W: Write>(&self, library: Option<&Path>, writer: &mut W) -> std::io::Result<()> {
+        writeln!(writer, "#EXTM3U")?;
+
+        if let Some(title) = self.title() {
+            writeln!(writer, "#PLAYLIST:{title}")?;
+        }
+
+        for track in self.queue.values() {
+            let info = format!(
+                "#EXTINF:{},{} - {}",
+                track.duration_seconds,
+                self.artist_name(track),
+                track.title.trim()
+            );
+
+            if let Some(path) = library.and_then(|l| self.downloaded_path(l, track)) {
+                writeln!(writer, "{info}")?;
+                writeln!(writer, "{}", path.display())?;
+            } else if track.available {
+                writeln!(writer, "{info}")?;
+                writeln!(writer, "{}", share_url(track))?;
+            } else {
+                writeln!(writer, "# unavailable: {info}")?;
+                writeln!(writer, "# {}", share_url(track))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The file a track was downloaded to, if it is in the library.
+    fn downloaded_path(&self, library: &Path, track: &Track) -> Option<PathBuf> {
+        ["flac", "mp3"]
+            .iter()
+            .map(|extension| download::track_path(library, track, self.album.as_ref(), extension))
+            .find(|path| path.exists())
+    }
+
+    fn write_json<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let export = JsonExport {
+            title: self.title(),
+            list_type: self.list_type.to_string(),
+            tracks: self.queue.values().collect(),
+        };
+
+        serde_json::to_writer_pretty(&mut *writer, &export)?;
+        writeln!(writer)
+    }
+
+    fn write_csv<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(writer, "{CSV_HEADER}")?;
+
+        for track in self.queue.values() {
+            let (album, release_year) =
+                if let Some(album) = track.album.as_ref().or(self.album.as_ref()) {
+                    (
+                        album.title.trim().to_string(),
+                        album.release_year.to_string(),
+                    )
+                } else {
+                    (String::new(), String::new())
+                };
+
+            let fields = [
+                track.position.to_string(),
+                track.id.to_string(),
+                track.title.trim().to_string(),
+                self.artist_name(track),
+                album,
+                release_year,
+                track.duration_seconds.to_string(),
+                track.isrc.clone().unwrap_or_default(),
+                track.bit_depth.to_string(),
+                track.sampling_rate.to_string(),
+                track.explicit.to_string(),
+                track.available.to_string(),
+                share_url(track),
+            ];
+
+            let row = fields
+                .iter()
+                .map(|field| csv_field(field))
+                .collect::<Vec<String>>()
+                .join(",");
+
+            writeln!(writer, "{row}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Quotes a field when it contains a separator, quote or line break.
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+use crate::{
+    player::queue::TrackListType,
+    service::{Artist, Playlist},
+};
+#[cfg(test)]
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+fn track(id: u32, position: u32, title: &str, available: bool) -> Track {
+    Track {
+        id,
+        number: position,
+        title: title.to_string(),
+        artist: Some(Artist {
+            id: 1,
+            name: "Artist".to_string(),
+            albums: None,
+        }),
+        available,
+        position,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+fn list() -> TrackListValue {
+    let mut queue = BTreeMap::new();
+    queue.insert(1, track(10, 1, "First, Song", true));
+    queue.insert(2, track(20, 2, "Second \"Song\"", false));
+
+    let mut list = TrackListValue::new(Some(queue.clone()));
+    list.set_playlist(Playlist {
+        title: "Mix".to_string(),
+        tracks_count: 2,
+        id: 5,
+        tracks: queue,
+        ..Default::default()
+    });
+    list.set_list_type(TrackListType::Playlist);
+
+    list
+}
+
+#[cfg(test)]
+fn export(format: ExportFormat) -> String {
+    let mut output = Vec::new();
+    list()
+        .export(format, None, &mut output)
+        .expect("failed to export");
+
+    String::from_utf8(output).expect("export is not utf-8")
+}
+
+#[test]
+fn m3u8_comments_unavailable_tracks() {
+    let lines = export(ExportFormat::M3u8)
+        .lines()
+        .map(|l| l.to_string())
+        .collect::<Vec<String>>();
+
+    assert_eq!(
+        lines,
+        vec![
+            "#EXTM3U",
+            "#PLAYLIST:Mix",
+            "#EXTINF:200,Artist - First, Song",
+            "https://open.qobuz.com/track/10",
+            "# unavailable: #EXTINF:200,Artist - Second \"Song\"",
+            "# https://open.qobuz.com/track/20",
+        ]
+    );
+}
+
+#[test]
+fn m3u8_points_at_downloaded_files() {
+    let library = PathBuf::from(crate::TEST_TEMP_PATH).join("m3u8_library");
+    let list = list();
+    let track = list.find_track_by_index(1).expect("missing track");
+    let path = download::track_path(&library, track, None, "flac");
+
+    std::fs::create_dir_all(path.parent().unwrap()).expect("failed to create library");
+    std::fs::write(&path, b"").expect("failed to create download");
+
+    let mut output = Vec::new();
+    list.export(ExportFormat::M3u8, Some(&library), &mut output)
+        .expect("failed to export");
+
+    let output = String::from_utf8(output).expect("export is not utf-8");
+    assert!(output.contains(&format!("\n{}\n", path.display())));
+
+    std::fs::remove_dir_all(&library).ok();
+}
+
+#[test]
+fn json_includes_track_metadata() {
+    let json: serde_json::Value =
+        serde_json::from_str(&export(ExportFormat::Json)).expect("invalid json");
+
+    assert_eq!(json["title"], "Mix");
+    assert_eq!(json["listType"], "playlist");
+    assert_eq!(json["tracks"].as_array().map(|t| t.len()), Some(2));
+    assert_eq!(json["tracks"][0]["id"], 10);
+    assert_eq!(json["tracks"][1]["available"], false);
+}
+
+#[test]
+fn csv_has_a_header_and_quoted_rows() {
+    let csv = export(ExportFormat::Csv);
+    let lines = csv.lines().collect::<Vec<&str>>();
+
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], CSV_HEADER);
+    assert!(lines[1].starts_with("1,10,\"First, Song\",Artist,"));
+    assert!(lines[2].starts_with("2,20,\"Second \"\"Song\"\"\",Artist,"));
+    assert!(lines[2].ends_with(",false,https://open.qobuz.com/track/20"));
+}
+
+#[test]
+fn format_follows_file_extension() {
+    assert_eq!(ExportFormat::from_path("a.M3U8"), ExportFormat::M3u8);
+    assert_eq!(ExportFormat::from_path("a.json"), ExportFormat::Json);
+    assert_eq!(ExportFormat::from_path("a.csv"), ExportFormat::Csv);
+    assert_eq!(ExportFormat::from_path("a.txt"), ExportFormat::Text);
+}
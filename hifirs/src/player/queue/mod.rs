@@ -1,6 +1,7 @@
 pub mod controls;
+pub mod export;
 
-use crate::service::{Album, Playlist, Track, TrackStatus};
+use crate::service::{self, Album, Playlist, Track, TrackStatus};
 use serde::{Deserialize, Serialize, Serializer};
 use std::{collections::BTreeMap, fmt::Display};
 
@@ -71,8 +72,8 @@ impl TrackListValue {
     }
 
     pub fn total(&self) -> u32 {
-        if let Some(album) = &self.album {
-            album.total_tracks
+        if self.album.is_some() {
+            self.queue.len() as u32
         } else if let Some(list) = &self.playlist {
             list.tracks_count
         } else {
@@ -134,6 +135,129 @@ impl TrackListValue {
         }
     }
 
+    /// Put a track at `position`, moving it and every track after it down one place.
+    #[instrument(skip(self, track), fields(track_id = track.id))]
+    pub fn insert(&mut self, position: u32, track: Track) {
+        let tail = self.queue.split_off(&position);
+
+        self.place(position, track);
+
+        for (position, track) in tail {
+            self.place(position + 1, track);
+        }
+
+        if let Some(playlist) = &mut self.playlist {
+            playlist.tracks_count += 1;
+        }
+    }
+
+    /// Take the track at `position` out, moving every track after it up one place.
+    #[instrument(skip(self))]
+    pub fn remove(&mut self, position: u32) -> Option<Track> {
+        let removed = self.queue.remove(&position)?;
+        let tail = self.queue.split_off(&position);
+
+        for (position, track) in tail {
+            self.place(position - 1, track);
+        }
+
+        if let Some(playlist) = &mut self.playlist {
+            playlist.tracks_count = playlist.tracks_count.saturating_sub(1);
+        }
+
+        Some(removed)
+    }
+
+    /// Move the track at `from` to `to`, shifting the tracks in between.
+    /// False when either place is outside the list.
+    #[instrument(skip(self))]
+    pub fn move_track(&mut self, from: u32, to: u32) -> bool {
+        if from == to || !self.queue.contains_key(&from) || !self.queue.contains_key(&to) {
+            return false;
+        }
+
+        if let Some(track) = self.remove(from) {
+            self.insert(to, track);
+        }
+
+        true
+    }
+
+    /// Where a track just moved in the queue goes in the playlist as it was
+    /// loaded, counting from zero: before the track that now follows it, or
+    /// at the end. None unless the queue is still exactly that playlist apart
+    /// from the move, so an edited queue never overwrites the playlist's order.
+    #[instrument(skip(self))]
+    pub fn playlist_insert_before(&self, playlist_track_id: u64) -> Option<u32> {
+        let playlist = self
+            .playlist
+            .as_ref()
+            .filter(|_| self.list_type == TrackListType::Playlist)?;
+
+        let loaded = playlist
+            .tracks
+            .values()
+            .map(|t| t.playlist_track_id)
+            .collect::<Option<Vec<_>>>()?;
+        let queued = self
+            .queue
+            .values()
+            .map(|t| t.playlist_track_id)
+            .collect::<Option<Vec<_>>>()?;
+
+        let others = |ids: &[u64]| {
+            ids.iter()
+                .filter(|id| **id != playlist_track_id)
+                .copied()
+                .collect::<Vec<_>>()
+        };
+
+        // Every page loaded, nothing added or removed, only this track moved.
+        if loaded.len() != playlist.tracks_count as usize
+            || loaded.len() != queued.len()
+            || !loaded.contains(&playlist_track_id)
+            || others(&loaded) != others(&queued)
+        {
+            return None;
+        }
+
+        let moved = queued.iter().position(|id| *id == playlist_track_id)?;
+
+        match queued.get(moved + 1) {
+            Some(next) => loaded.iter().position(|id| id == next).map(|i| i as u32),
+            None => Some(loaded.len() as u32),
+        }
+    }
+
+    /// Take `tracks` as the order of the playlist once it has been saved,
+    /// unless another playlist has replaced it.
+    #[instrument(skip(self, tracks))]
+    pub fn set_playlist_order(&mut self, playlist_id: u32, tracks: BTreeMap<u32, Track>) {
+        if let Some(playlist) = self.playlist.as_mut().filter(|p| p.id == playlist_id) {
+            playlist.tracks = tracks;
+        }
+    }
+
+    /// Lists of single tracks are numbered by their place in the queue.
+    fn place(&mut self, position: u32, mut track: Track) {
+        track.position = position;
+
+        if self.list_type == TrackListType::Track {
+            track.number = position;
+        }
+
+        self.queue.insert(position, track);
+    }
+
+    /// Position of the first track from `position` on that can be played.
+    #[instrument(skip(self))]
+    pub fn next_playable(&self, position: u32) -> Option<u32> {
+        self.queue
+            .range(position..)
+            .find(|(_, t)| t.available && t.status != TrackStatus::Unplayable)
+            .map(|(position, _)| *position)
+    }
+
     #[instrument(skip(self))]
     pub fn unplayed_tracks(&self) -> Vec<&Track> {
         self.queue
@@ -173,6 +297,79 @@ impl TrackListValue {
         None
     }
 
+    /// Position of the first track on each disc, by disc number, for an
+    /// album spanning more than one.
+    pub fn disc_starts(&self) -> BTreeMap<u32, u32> {
+        if self.list_type == TrackListType::Album {
+            service::disc_starts(self.queue.values())
+        } else {
+            BTreeMap::new()
+        }
+    }
+
+    /// Length of the whole queue, in seconds.
+    pub fn duration_seconds(&self) -> u64 {
+        self.queue.values().map(|t| t.duration_seconds as u64).sum()
+    }
+
+    /// Seconds left to play, `elapsed` seconds into the playing track. The
+    /// whole queue when nothing is playing.
+    pub fn remaining_seconds(&self, elapsed: u64) -> u64 {
+        let playing = self
+            .queue
+            .values()
+            .find(|t| t.status == TrackStatus::Playing)
+            .map(|t| t.position);
+
+        match playing {
+            Some(position) => self
+                .queue
+                .range(position..)
+                .map(|(_, t)| t.duration_seconds as u64)
+                .sum::<u64>()
+                .saturating_sub(elapsed),
+            None => self.duration_seconds(),
+        }
+    }
+
+    /// Title of the album or playlist the list was created from.
+    pub fn title(&self) -> Option<String> {
+        if let Some(album) = &self.album {
+            Some(album.title.trim().to_string())
+        } else {
+            self.playlist.as_ref().map(|p| p.title.trim().to_string())
+        }
+    }
+
+    /// Plain text tracklist, one `NN. Artist - Title` line per track.
+    pub fn to_text(&self) -> String {
+        let mut lines = Vec::new();
+
+        if let Some(title) = self.title() {
+            lines.push(title);
+            lines.push(String::new());
+        }
+
+        for (index, track) in self.queue.values().enumerate() {
+            let artist = if let Some(artist) = &track.artist {
+                artist.name.trim().to_string()
+            } else if let Some(album) = &self.album {
+                album.artist.name.trim().to_string()
+            } else {
+                "Unknown Artist".to_string()
+            };
+
+            lines.push(format!(
+                "{:02}. {} - {}",
+                index + 1,
+                artist,
+                track.title.trim()
+            ));
+        }
+
+        lines.join("\n")
+    }
+
     pub fn cursive_list(&self) -> Vec<(String, i32)> {
         self.queue
             .values()
@@ -180,3 +377,165 @@ impl TrackListValue {
             .collect::<Vec<(String, i32)>>()
     }
 }
+
+#[cfg(test)]
+fn track(id: u32, position: u32) -> Track {
+    Track {
+        id,
+        number: position,
+        title: format!("Track {id}"),
+        position,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+fn ids(list: &TrackListValue) -> Vec<(u32, u32)> {
+    list.queue
+        .iter()
+        .map(|(position, track)| (*position, track.id))
+        .collect()
+}
+
+#[test]
+fn inserting_and_removing_keeps_positions_in_order() {
+    let mut queue = BTreeMap::new();
+    queue.insert(1, track(10, 1));
+    queue.insert(2, track(20, 2));
+
+    let mut list = TrackListValue::new(Some(queue));
+    list.set_list_type(TrackListType::Track);
+
+    list.insert(2, track(30, 0));
+    assert_eq!(ids(&list), vec![(1, 10), (2, 30), (3, 20)]);
+    assert_eq!(list.find_track_by_index(3).map(|t| t.number), Some(3));
+
+    assert_eq!(list.remove(1).map(|t| t.id), Some(10));
+    assert_eq!(ids(&list), vec![(1, 30), (2, 20)]);
+    assert_eq!(list.total(), 2);
+    assert!(list.remove(5).is_none());
+}
+
+#[test]
+fn moving_a_track_shifts_the_ones_in_between() {
+    let mut queue = BTreeMap::new();
+    queue.insert(1, track(10, 1));
+    queue.insert(2, track(20, 2));
+    queue.insert(3, track(30, 3));
+
+    let mut list = TrackListValue::new(Some(queue));
+
+    assert!(list.move_track(1, 3));
+    assert_eq!(ids(&list), vec![(1, 20), (2, 30), (3, 10)]);
+
+    assert!(list.move_track(3, 2));
+    assert_eq!(ids(&list), vec![(1, 20), (2, 10), (3, 30)]);
+    assert_eq!(list.find_track_by_index(2).map(|t| t.position), Some(2));
+
+    assert!(!list.move_track(1, 4));
+    assert!(!list.move_track(2, 2));
+    assert_eq!(list.total(), 3);
+}
+
+#[test]
+fn playlist_order_is_only_saved_for_an_unedited_playlist() {
+    let mut queue = BTreeMap::new();
+    for (position, id) in [(1, 10), (2, 20), (3, 30), (4, 40)] {
+        let mut t = track(id, position);
+        t.playlist_track_id = Some(id as u64 * 100);
+        queue.insert(position, t);
+    }
+
+    let mut list = TrackListValue::new(Some(queue.clone()));
+    list.set_playlist(Playlist {
+        id: 7,
+        tracks_count: 4,
+        tracks: queue,
+        ..Default::default()
+    });
+
+    // Down: 10 ends up before 40, which was fourth.
+    assert!(list.move_track(1, 3));
+    assert_eq!(list.playlist_insert_before(1000), Some(3));
+    assert!(list.move_track(3, 1));
+
+    // Up: 40 ends up before 20, which was second. Last goes at the end.
+    assert!(list.move_track(4, 2));
+    assert_eq!(list.playlist_insert_before(4000), Some(1));
+    assert!(list.move_track(2, 4));
+    assert!(list.move_track(1, 4));
+    assert_eq!(list.playlist_insert_before(1000), Some(4));
+    assert!(list.move_track(4, 1));
+
+    // Once something else is in the queue the order is no longer saved.
+    list.insert(2, track(50, 0));
+    assert!(list.move_track(1, 3));
+    assert_eq!(list.playlist_insert_before(1000), None);
+}
+
+#[test]
+fn remaining_time_counts_from_the_playing_track() {
+    let mut queue = BTreeMap::new();
+    queue.insert(1, track(10, 1));
+    queue.insert(2, track(20, 2));
+    queue.insert(3, track(30, 3));
+
+    let mut list = TrackListValue::new(Some(queue));
+
+    assert_eq!(list.duration_seconds(), 600);
+    assert_eq!(list.remaining_seconds(50), 600);
+
+    list.set_track_status(1, TrackStatus::Played);
+    list.set_track_status(2, TrackStatus::Playing);
+
+    assert_eq!(list.remaining_seconds(50), 350);
+    assert_eq!(list.remaining_seconds(500), 0);
+}
+
+#[test]
+fn unstreamable_album_tracks_are_passed_over() {
+    let mut unstreamable = track(20, 2);
+    unstreamable.available = false;
+    unstreamable.status = TrackStatus::Unplayable;
+
+    let mut queue = BTreeMap::new();
+    queue.insert(1, track(10, 1));
+    queue.insert(2, unstreamable);
+    queue.insert(3, track(30, 3));
+    queue.insert(4, track(40, 4));
+
+    let mut list = TrackListValue::new(Some(queue));
+    list.set_list_type(TrackListType::Album);
+
+    assert_eq!(list.next_playable(1), Some(1));
+    assert_eq!(list.next_playable(2), Some(3));
+
+    // A url that couldn't be fetched marks the track too.
+    list.set_track_status(3, TrackStatus::Unplayable);
+    assert_eq!(list.next_playable(2), Some(4));
+
+    list.set_track_status(4, TrackStatus::Unplayable);
+    assert_eq!(list.next_playable(2), None);
+    assert_eq!(list.unplayed_tracks().len(), 1);
+}
+
+#[test]
+fn disc_starts_only_for_multi_disc_albums() {
+    let mut queue = BTreeMap::new();
+    queue.insert(1, track(10, 1));
+    queue.insert(2, track(20, 2));
+
+    let mut second_disc = track(30, 3);
+    second_disc.media_number = 2;
+    second_disc.number = 1;
+    queue.insert(3, second_disc);
+
+    let mut list = TrackListValue::new(Some(queue));
+    assert!(list.disc_starts().is_empty());
+
+    list.set_list_type(TrackListType::Album);
+    assert_eq!(list.disc_starts(), BTreeMap::from([(1, 1), (2, 3)]));
+
+    list.remove(3);
+    assert!(list.disc_starts().is_empty());
+}
@@ -1,33 +1,65 @@
-use hifirs_qobuz_api::client::album::Album as QobuzAlbum;
+use chrono::Datelike;
+use hifirs_qobuz_api::client::{album::Album as QobuzAlbum, Image};
 use std::{collections::BTreeMap, str::FromStr};
 
 use crate::service::{Album, Track};
 
+/// The largest cover image available, falling back from large to small to
+/// thumbnail. Qobuz sometimes sends empty strings for sizes it doesn't have.
+pub fn cover_art(image: &Image) -> Option<String> {
+    [
+        Some(&image.large),
+        Some(&image.small),
+        image.thumbnail.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|url| !url.is_empty())
+    .cloned()
+}
+
+/// The year from the original release date, or the download or streaming
+/// release dates when that one is missing. Zero when none of them parse.
+fn release_year(album: &QobuzAlbum) -> u32 {
+    let year = [
+        &album.release_date_original,
+        &album.release_date_download,
+        &album.release_date_stream,
+    ]
+    .into_iter()
+    .find_map(|date| chrono::NaiveDate::from_str(date).ok())
+    .map(|date| date.year() as u32);
+
+    match year {
+        Some(year) => year,
+        None => {
+            warn!("album {} has no valid release date", album.id);
+            0
+        }
+    }
+}
+
 impl From<QobuzAlbum> for Album {
     fn from(value: QobuzAlbum) -> Self {
-        let year = chrono::NaiveDate::from_str(&value.release_date_original)
-            .expect("failed to parse date")
-            .format("%Y");
+        let release_year = release_year(&value);
 
         let tracks = if let Some(tracks) = value.tracks {
-            let mut position = 1_u32;
-
-            tracks
+            let mut tracks = tracks
                 .items
                 .into_iter()
-                .filter_map(|t| {
-                    if t.streamable {
-                        let mut track: Track = t.into();
+                .filter(|t| t.streamable)
+                .map(Track::from)
+                .collect::<Vec<Track>>();
 
-                        let next_position = position;
-                        track.position = next_position;
+            // Track numbers start over on every disc of a multi-disc set.
+            tracks.sort_by_key(|t| (t.media_number, t.number));
 
-                        position += 1;
-
-                        Some((next_position, track))
-                    } else {
-                        None
-                    }
+            tracks
+                .into_iter()
+                .zip(1_u32..)
+                .map(|(mut track, position)| {
+                    track.position = position;
+                    (position, track)
                 })
                 .collect::<BTreeMap<u32, Track>>()
         } else {
@@ -39,15 +71,46 @@ impl From<QobuzAlbum> for Album {
             title: value.title,
             artist: value.artist.into(),
             total_tracks: value.tracks_count as u32,
-            release_year: year
-                .to_string()
-                .parse::<u32>()
-                .expect("error converting year"),
+            release_year,
             hires_available: value.hires_streamable,
             explicit: value.parental_warning,
             available: value.streamable,
             tracks,
-            cover_art: value.image.large,
+            cover_art: cover_art(&value.image),
+            upc: Some(value.upc).filter(|upc| !upc.is_empty()),
         }
     }
 }
+
+#[test]
+fn cover_art_falls_back_to_smaller_sizes() {
+    let image = |small: &str, thumbnail: Option<&str>, large: &str| Image {
+        small: small.to_string(),
+        thumbnail: thumbnail.map(|t| t.to_string()),
+        large: large.to_string(),
+        back: None,
+    };
+
+    assert_eq!(
+        cover_art(&image("small.jpg", Some("thumb.jpg"), "large.jpg")),
+        Some("large.jpg".to_string())
+    );
+    assert_eq!(
+        cover_art(&image("small.jpg", Some("thumb.jpg"), "")),
+        Some("small.jpg".to_string())
+    );
+    assert_eq!(
+        cover_art(&image("", Some("thumb.jpg"), "")),
+        Some("thumb.jpg".to_string())
+    );
+    assert_eq!(cover_art(&image("", None, "")), None);
+    assert_eq!(cover_art(&image("", Some(""), "")), None);
+}
+
+#[test]
+fn cover_art_handles_missing_image_fields() {
+    let image: Image =
+        serde_json::from_str(r#"{ "thumbnail": "thumb.jpg" }"#).expect("failed to parse image");
+
+    assert_eq!(cover_art(&image), Some("thumb.jpg".to_string()));
+}
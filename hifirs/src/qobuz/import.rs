@@ -0,0 +1,332 @@
+use crate::qobuz::Result;
+use hifirs_qobuz_api::client::{
+    api::Client as QobuzClient, parse_url, track::Track as QobuzTrack, UrlType,
+};
+
+/// Tracks added to a playlist per request.
+const ADD_TRACKS_CHUNK: usize = 50;
+
+/// One track to look up, read from a line of the imported file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportEntry {
+    /// A Qobuz track link, like the ones written by the exporter.
+    TrackId(i32),
+    Isrc(String),
+    Search {
+        artist: Option<String>,
+        title: String,
+    },
+}
+
+/// Where imported tracks go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportTarget {
+    Existing(i64),
+    New(String),
+}
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// The playlist the tracks were added to. Empty on a dry run.
+    pub playlist_id: Option<i64>,
+    /// Each line that was resolved, with the track it matched.
+    pub matched: Vec<(String, QobuzTrack)>,
+    /// Lines that couldn't be matched to a track.
+    pub unresolved: Vec<String>,
+}
+
+/// Reads the entries of an M3U or newline-delimited text file.
+///
+/// `#EXTINF` titles are used for M3U entries, since the paths that follow
+/// them point to files on someone else's disk. Plain lines can be a Qobuz
+/// track link, an ISRC or `Artist - Title`, optionally numbered like the
+/// text export.
+pub fn parse_entries(contents: &str) -> Vec<(String, ImportEntry)> {
+    let mut entries = Vec::new();
+    let mut pending: Option<(String, ImportEntry)> = None;
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            if let Some(entry) = pending.take() {
+                entries.push(entry);
+            }
+
+            let title = info.split_once(',').map(|(_, t)| t).unwrap_or(info);
+            pending = Some((line.to_string(), search_entry(title)));
+
+            continue;
+        }
+
+        if line.starts_with('#') {
+            continue;
+        }
+
+        match parse_url(line) {
+            Ok(UrlType::Track { id }) => {
+                // A track link is more precise than the title it follows.
+                pending.take();
+                entries.push((line.to_string(), ImportEntry::TrackId(id)));
+            }
+            _ => {
+                if let Some(entry) = pending.take() {
+                    entries.push(entry);
+                } else {
+                    entries.push((line.to_string(), parse_line(line)));
+                }
+            }
+        }
+    }
+
+    if let Some(entry) = pending {
+        entries.push(entry);
+    }
+
+    entries
+}
+
+fn parse_line(line: &str) -> ImportEntry {
+    if is_isrc(line) {
+        ImportEntry::Isrc(line.to_uppercase())
+    } else {
+        search_entry(strip_numbering(line))
+    }
+}
+
+fn search_entry(text: &str) -> ImportEntry {
+    if let Some((artist, title)) = text.split_once(" - ") {
+        ImportEntry::Search {
+            artist: Some(artist.trim().to_string()),
+            title: title.trim().to_string(),
+        }
+    } else {
+        ImportEntry::Search {
+            artist: None,
+            title: text.trim().to_string(),
+        }
+    }
+}
+
+/// Drops a leading `12. ` track number.
+fn strip_numbering(line: &str) -> &str {
+    if let Some((number, rest)) = line.split_once(". ") {
+        if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) {
+            return rest;
+        }
+    }
+
+    line
+}
+
+/// Country code, registrant and year-plus-designation, e.g. `USRC17607839`.
+fn is_isrc(text: &str) -> bool {
+    let chars = text.chars().collect::<Vec<char>>();
+
+    chars.len() == 12
+        && chars[..2].iter().all(|c| c.is_ascii_alphabetic())
+        && chars[2..5].iter().all(|c| c.is_ascii_alphanumeric())
+        && chars[5..].iter().all(|c| c.is_ascii_digit())
+}
+
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Higher is better. Streamable tracks win ties, since the rest can't be played.
+/// None unless the titles match at least partially, a matching artist alone
+/// isn't enough. Blank titles match nothing.
+fn score(track: &QobuzTrack, artist: Option<&str>, title: &str) -> Option<u32> {
+    let track_title = normalize(&track.title);
+    let title = normalize(title);
+
+    if title.is_empty() || track_title.is_empty() {
+        return None;
+    }
+
+    let mut score = if track_title == title {
+        4
+    } else if track_title.contains(&title) || title.contains(&track_title) {
+        2
+    } else {
+        return None;
+    };
+
+    if let (Some(artist), Some(performer)) = (artist, &track.performer) {
+        let artist = normalize(artist);
+        let performer = normalize(&performer.name);
+
+        if performer == artist {
+            score += 4;
+        } else if performer.contains(&artist) || artist.contains(&performer) {
+            score += 2;
+        }
+    }
+
+    if track.streamable {
+        score += 1;
+    }
+
+    Some(score)
+}
+
+/// Finds the track best matching the entry, if there is a reasonable one.
+pub async fn resolve(client: &QobuzClient, entry: &ImportEntry) -> Option<QobuzTrack> {
+    match entry {
+        ImportEntry::TrackId(id) => client.track(*id).await.ok(),
+        ImportEntry::Isrc(isrc) => {
+            let results = client.search_all(isrc.clone(), 10, 0).await.ok()?;
+
+            results.tracks.items.into_iter().find(|t| {
+                t.isrc
+                    .as_ref()
+                    .map(|i| i.eq_ignore_ascii_case(isrc))
+                    .unwrap_or(false)
+            })
+        }
+        ImportEntry::Search { artist, title } => {
+            if normalize(title).is_empty() {
+                return None;
+            }
+
+            let query = match artist {
+                Some(artist) => format!("{artist} {title}"),
+                None => title.clone(),
+            };
+
+            let results = client.search_all(query, 20, 0).await.ok()?;
+
+            results
+                .tracks
+                .items
+                .into_iter()
+                .filter_map(|t| score(&t, artist.as_deref(), title).map(|score| (score, t)))
+                .max_by_key(|(score, _)| *score)
+                .map(|(_, t)| t)
+        }
+    }
+}
+
+/// Matches every entry in `contents` and, unless it's a dry run, adds the
+/// matches to the target playlist.
+pub async fn import_playlist(
+    client: &QobuzClient,
+    contents: &str,
+    target: ImportTarget,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+
+    for (line, entry) in parse_entries(contents) {
+        match resolve(client, &entry).await {
+            Some(track) => report.matched.push((line, track)),
+            None => report.unresolved.push(line),
+        }
+    }
+
+    if dry_run || report.matched.is_empty() {
+        return Ok(report);
+    }
+
+    let playlist_id = match target {
+        ImportTarget::Existing(id) => id,
+        ImportTarget::New(name) => client.create_playlist(name, false, None, None).await?.id,
+    };
+
+    let track_ids = report
+        .matched
+        .iter()
+        .map(|(_, t)| t.id.to_string())
+        .collect::<Vec<String>>();
+
+    for chunk in track_ids.chunks(ADD_TRACKS_CHUNK) {
+        client
+            .playlist_add_track(playlist_id.to_string(), chunk.to_vec())
+            .await?;
+    }
+
+    report.playlist_id = Some(playlist_id);
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hifirs_qobuz_api::client::track::Performer;
+
+    #[test]
+    fn reads_m3u_titles_and_links() {
+        let m3u = "#EXTM3U\n#PLAYLIST:Mix\n#EXTINF:200,Artist - First\nhttps://open.qobuz.com/track/10\n#EXTINF:180,Other - Second\n/music/second.flac\n";
+
+        let entries = parse_entries(m3u)
+            .into_iter()
+            .map(|(_, e)| e)
+            .collect::<Vec<ImportEntry>>();
+
+        assert_eq!(
+            entries,
+            vec![
+                ImportEntry::TrackId(10),
+                ImportEntry::Search {
+                    artist: Some("Other".to_string()),
+                    title: "Second".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_text_lines() {
+        let text = "01. Artist - Title\nusrc17607839\nJust A Title\n";
+
+        let entries = parse_entries(text)
+            .into_iter()
+            .map(|(_, e)| e)
+            .collect::<Vec<ImportEntry>>();
+
+        assert_eq!(
+            entries,
+            vec![
+                ImportEntry::Search {
+                    artist: Some("Artist".to_string()),
+                    title: "Title".to_string()
+                },
+                ImportEntry::Isrc("USRC17607839".to_string()),
+                ImportEntry::Search {
+                    artist: None,
+                    title: "Just A Title".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_need_a_title() {
+        let track = QobuzTrack {
+            title: "Paranoid Android".to_string(),
+            performer: Some(Performer {
+                id: 1,
+                name: "Radiohead".to_string(),
+            }),
+            streamable: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            score(&track, Some("Radiohead"), "Paranoid Android"),
+            Some(9)
+        );
+        assert_eq!(score(&track, Some("Radiohead"), "Paranoid"), Some(7));
+        assert_eq!(score(&track, Some("Radiohead"), "Karma Police"), None);
+        assert_eq!(score(&track, Some("Radiohead"), " !? "), None);
+    }
+}
@@ -1,20 +1,31 @@
 use crate::{
-    service::{Album, Artist, MusicService, Playlist, SearchResults, Track},
+    service::{
+        Account, Album, Article, ArticleLink, Artist, Favorites, MusicService, Playlist, Purchases,
+        SearchResults, StreamError, StreamUrl, Track,
+    },
     sql::db::{self},
 };
 use async_trait::async_trait;
+use clap::ValueEnum;
 use hifirs_qobuz_api::client::{
     api::{self, Client as QobuzClient},
+    cookies::Cookies,
+    favorite::Favorites as QobuzFavorites,
+    purchase::Purchases as QobuzPurchases,
     search_results::SearchAllResults,
-    AudioQuality,
+    AudioQuality, UrlType,
 };
+use hifirs_qobuz_api::Error;
+use std::{path::Path, str::FromStr};
 
-pub type Result<T, E = hifirs_qobuz_api::Error> = std::result::Result<T, E>;
+pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 pub mod album;
 pub mod artist;
+pub mod import;
 pub mod playlist;
 pub mod track;
+pub mod user;
 
 #[async_trait]
 impl MusicService for QobuzClient {
@@ -22,6 +33,20 @@ impl MusicService for QobuzClient {
         self.login(username, password).await;
     }
 
+    /// Known from logging in, or asked for once.
+    async fn account(&self) -> Option<Account> {
+        match self.user() {
+            Some(user) => Some(user.into()),
+            None => match self.me().await {
+                Ok(user) => Some(user.into()),
+                Err(error) => {
+                    debug!("unable to fetch the account: {error}");
+                    None
+                }
+            },
+        }
+    }
+
     async fn album(&self, album_id: &str) -> Option<Album> {
         match self.album(album_id).await {
             Ok(album) => Some(album.into()),
@@ -36,6 +61,13 @@ impl MusicService for QobuzClient {
         }
     }
 
+    async fn track_suggestions(&self, track_id: i32, limit: usize) -> Option<Vec<Track>> {
+        match self.track_suggestions(track_id, limit).await {
+            Ok(tracks) => Some(tracks.into_iter().map(|t| t.into()).collect()),
+            Err(_) => None,
+        }
+    }
+
     async fn artist(&self, artist_id: i32) -> Option<Artist> {
         match self.artist(artist_id, None).await {
             Ok(track) => Some(track.into()),
@@ -50,17 +82,84 @@ impl MusicService for QobuzClient {
         }
     }
 
+    async fn playlist_page(&self, playlist_id: i64, offset: u32, limit: u32) -> Option<Playlist> {
+        match self.playlist_page(playlist_id, offset, limit).await {
+            Ok(playlist) => Some(playlist.into()),
+            Err(_) => None,
+        }
+    }
+
     async fn search(&self, query: &str) -> Option<SearchResults> {
-        match self.search_all(query.to_string(), 100).await {
+        match self.search_all(query.to_string(), 100, 0).await {
             Ok(results) => Some(results.into()),
             Err(_) => None,
         }
     }
 
-    async fn track_url(&self, track_id: i32) -> Option<String> {
-        match self.track_url(track_id, None, None).await {
-            Ok(track_url) => Some(track_url.url),
-            Err(_) => None,
+    /// Steps down a quality at a time when Qobuz refuses a url, so the track
+    /// still plays. Other errors, like a dropped connection, end the attempt
+    /// rather than quietly lowering the quality.
+    async fn track_url(
+        &self,
+        track_id: i32,
+        quality: Option<AudioQuality>,
+    ) -> Result<StreamUrl, StreamError> {
+        let requested = quality.unwrap_or_else(|| self.quality());
+        let mut attempt = Some(requested.clone());
+
+        while let Some(quality) = attempt {
+            match self.track_url(track_id, Some(quality.clone()), None).await {
+                Ok(track_url) if track_url.sample => {
+                    debug!("only a sample of track {track_id} is available");
+                    return Err(StreamError::Refused);
+                }
+                Ok(track_url) => {
+                    let granted = if track_url.restricted_for_user() {
+                        AudioQuality::from(track_url.format_id as i64)
+                    } else {
+                        quality
+                    };
+
+                    return Ok(StreamUrl {
+                        expires_at: track_url.expires_at(),
+                        url: track_url.url,
+                        requested,
+                        granted,
+                    });
+                }
+                Err(Error::Refused { message }) => {
+                    debug!("no url for track {track_id} at quality {quality}: {message}");
+                    attempt = quality.lower();
+                }
+                Err(error) => {
+                    warn!("failed to get a url for track {track_id}: {error}");
+                    return Err(StreamError::Unavailable {
+                        message: error.to_string(),
+                    });
+                }
+            }
+        }
+
+        Err(StreamError::Refused)
+    }
+
+    async fn download_track(
+        &self,
+        track_id: i32,
+        path: &Path,
+        mut on_progress: Box<dyn FnMut(u64, Option<u64>) + Send>,
+    ) -> bool {
+        match self
+            .download_track(track_id, None, path, |progress| {
+                on_progress(progress.written, progress.total)
+            })
+            .await
+        {
+            Ok(_) => true,
+            Err(error) => {
+                warn!("failed to download track {track_id}: {error}");
+                false
+            }
         }
     }
 
@@ -76,15 +175,244 @@ impl MusicService for QobuzClient {
             Err(_) => None,
         }
     }
+
+    async fn create_playlist(
+        &self,
+        name: &str,
+        public: bool,
+        description: Option<String>,
+    ) -> Option<Playlist> {
+        match self
+            .create_playlist(name.to_string(), public, description, None)
+            .await
+        {
+            Ok(playlist) => Some(playlist.into()),
+            Err(error) => {
+                warn!("failed to create playlist {name}: {error}");
+                None
+            }
+        }
+    }
+
+    async fn add_track_to_playlist(&self, playlist_id: u32, track_id: u32) -> Option<bool> {
+        match self
+            .playlist_add_track(playlist_id.to_string(), vec![track_id.to_string()])
+            .await
+        {
+            Ok(_) => Some(true),
+            Err(Error::Api { message } | Error::Refused { message })
+                if is_duplicate_rejection(&message) =>
+            {
+                Some(false)
+            }
+            Err(error) => {
+                warn!("failed to add track {track_id} to playlist {playlist_id}: {error}");
+                None
+            }
+        }
+    }
+
+    async fn artist_playlists(&self, artist_id: i32) -> Option<Vec<Playlist>> {
+        match self.artist_playlists(artist_id).await {
+            Ok(playlists) => Some(
+                playlists
+                    .items
+                    .into_iter()
+                    .map(|p| p.into())
+                    .collect::<Vec<Playlist>>(),
+            ),
+            Err(_) => None,
+        }
+    }
+
+    async fn track_lyrics(&self, track_id: i32) -> Option<String> {
+        match self.track_lyrics(track_id).await {
+            Ok(lyrics) if !lyrics.is_empty() => lyrics.text,
+            Ok(_) => Some(String::new()),
+            Err(error) => {
+                warn!("failed to fetch lyrics for track {track_id}: {error}");
+                None
+            }
+        }
+    }
+
+    async fn similar_artists(&self, artist_id: i32) -> Option<Vec<Artist>> {
+        match self.similar_artists(artist_id, None).await {
+            Ok(results) => Some(
+                results
+                    .artists
+                    .items
+                    .into_iter()
+                    .map(|a| a.into())
+                    .collect::<Vec<Artist>>(),
+            ),
+            Err(_) => None,
+        }
+    }
+
+    async fn user_purchases(&self) -> Option<Purchases> {
+        match self.user_purchases().await {
+            Ok(purchases) => Some(purchases.into()),
+            Err(_) => None,
+        }
+    }
+
+    async fn favorites(&self) -> Option<Favorites> {
+        match self.favorites(None, None, None).await {
+            Ok(favorites) => Some(favorites.into()),
+            Err(_) => None,
+        }
+    }
+
+    async fn editorial_content(&self) -> Option<Vec<Article>> {
+        match self.editorial_content().await {
+            Ok(result) => Some(
+                result
+                    .articles
+                    .items
+                    .into_iter()
+                    .map(|a| {
+                        let link = match a.linked_entity() {
+                            Some(UrlType::Album { id }) => Some(ArticleLink::Album(id)),
+                            Some(UrlType::Playlist { id }) => Some(ArticleLink::Playlist(id)),
+                            _ => None,
+                        };
+
+                        Article {
+                            title: a.title,
+                            description: a.description,
+                            link,
+                        }
+                    })
+                    .collect::<Vec<Article>>(),
+            ),
+            Err(_) => None,
+        }
+    }
+
+    async fn favorite_tracks(&self) -> Option<Vec<u32>> {
+        match self.favorite_ids().await {
+            Ok(ids) => Some(ids.tracks.into_iter().map(|id| id as u32).collect()),
+            Err(_) => None,
+        }
+    }
+
+    async fn set_favorite_track(&self, track_id: u32, favorite: bool) -> bool {
+        let result = if favorite {
+            self.add_favorite_track(track_id as i32).await
+        } else {
+            self.remove_favorite_track(track_id as i32).await
+        };
+
+        result.is_ok()
+    }
+
+    async fn set_playlist_followed(&self, playlist_id: u32, followed: bool) -> bool {
+        let playlist_id = playlist_id.to_string();
+
+        let result = if followed {
+            self.subscribe_playlist(playlist_id).await
+        } else {
+            self.unsubscribe_playlist(playlist_id).await
+        };
+
+        result.is_ok()
+    }
+
+    async fn move_playlist_track(
+        &self,
+        playlist_id: u32,
+        playlist_track_id: u64,
+        insert_before: u32,
+    ) -> bool {
+        match self
+            .playlist_track_position(
+                insert_before as usize,
+                playlist_id.to_string(),
+                playlist_track_id.to_string(),
+            )
+            .await
+        {
+            Ok(_) => true,
+            Err(error) => {
+                warn!("failed to move track in playlist {playlist_id}: {error}");
+                false
+            }
+        }
+    }
+
+    async fn session_rejected(&self) -> bool {
+        matches!(self.test_token().await, Err(Error::Authorization))
+    }
+
+    fn default_quality(&self) -> AudioQuality {
+        self.quality()
+    }
+
+    fn set_default_quality(&self, quality: AudioQuality) {
+        self.set_default_quality(quality);
+    }
+
+    fn has_app_id(&self) -> bool {
+        self.get_app_id().is_some()
+    }
+
+    fn has_active_secret(&self) -> bool {
+        self.get_active_secret().is_some()
+    }
+}
+
+/// How the api client handles cookies, see `Cookies` for the tradeoffs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CookieMode {
+    /// Keep cookies in memory until the player exits.
+    #[default]
+    Session,
+    /// Never store or send cookies.
+    Disabled,
+    /// Save cookies to disk so they survive restarts.
+    Persist,
+}
+
+impl CookieMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CookieMode::Session => "session",
+            CookieMode::Disabled => "disabled",
+            CookieMode::Persist => "persist",
+        }
+    }
+}
+
+impl From<&str> for CookieMode {
+    fn from(value: &str) -> Self {
+        match value {
+            "disabled" => CookieMode::Disabled,
+            "persist" => CookieMode::Persist,
+            _ => CookieMode::Session,
+        }
+    }
+}
+
+/// The cookie handling saved in the config.
+pub async fn cookies() -> Cookies {
+    match db::get_cookie_mode().await {
+        CookieMode::Session => Cookies::Session,
+        CookieMode::Disabled => Cookies::Disabled,
+        CookieMode::Persist => Cookies::Persisted(db::cookie_jar_path()),
+    }
 }
 
 pub async fn make_client(username: Option<&str>, password: Option<&str>) -> Result<QobuzClient> {
-    let mut client = api::new(None, None, None, None).await?;
+    let mut client = api::new(None, None, None, None, cookies().await, None).await?;
 
     setup_client(&mut client, username, password).await
 }
 
-/// Setup app_id, secret and user credentials for authentication
+/// Setup app_id, secret and user credentials for authentication.
+///
+/// The app_id and secret are scraped from the web player only when none are
+/// cached or the API rejects the cached ones, since that takes several requests.
 pub async fn setup_client(
     client: &mut QobuzClient,
     username: Option<&str>,
@@ -93,76 +421,102 @@ pub async fn setup_client(
     info!("setting up the api client");
 
     if let Some(config) = db::get_config().await {
-        let mut refresh_config = false;
+        let mut refreshed = false;
 
         if let Some(quality) = config.default_quality {
-            info!("using default quality from cache: {}", quality);
-            let quality: AudioQuality = quality.into();
-            client.set_default_quality(quality);
+            match AudioQuality::from_str(&quality.to_string()) {
+                Ok(quality) => {
+                    info!("using default quality from cache: {}", quality);
+                    client.set_default_quality(quality);
+                }
+                Err(error) => warn!("ignoring cached default quality. {error}"),
+            }
         }
 
-        if let Some(app_id) = config.app_id {
-            debug!("using app_id from cache");
-            client.set_app_id(app_id);
-        } else {
-            debug!("app_id not found, will have to refresh config");
-            refresh_config = true;
-        }
+        if let (Some(app_id), Some(secret)) = (config.app_id, config.active_secret) {
+            match db::get_secrets_cached_at().await {
+                Some(cached_at) => debug!(
+                    "using app_id and active secret cached {}h ago",
+                    (chrono::Utc::now().timestamp() - cached_at) / 3600
+                ),
+                None => debug!("using app_id and active secret from cache"),
+            }
 
-        if let Some(secret) = config.active_secret {
-            debug!("using active secret from cache");
+            client.set_app_id(app_id);
             client.set_active_secret(secret);
         } else {
-            debug!("active_secret not found, will have to refresh config");
-            refresh_config = true;
+            debug!("app_id or active_secret not found, will have to refresh config");
+            refresh_app_id(client).await?;
+            refreshed = true;
         }
 
+        let credentials = match (username, password) {
+            (Some(u), Some(p)) => Some((u.to_string(), p.to_string())),
+            _ => config.username.zip(config.password),
+        };
+
         if let Some(token) = config.user_token {
             info!("using token from cache");
             client.set_token(token);
 
-            if refresh_config {
-                client.refresh().await?;
-                client.test_secrets().await?;
+            if let Some((username, password)) = &credentials {
+                client.set_credentials(username, password);
+            }
 
-                if let Some(id) = client.get_app_id() {
-                    db::set_app_id(id).await;
-                }
+            // Renewing the token keeps long-lived installs signed in without
+            // the password. Keep the cached token if the refresh is refused.
+            let mut session = client.refresh_session().await;
 
-                if let Some(secret) = client.get_active_secret() {
-                    db::set_active_secret(secret).await;
-                }
+            if let (Err(Error::AppID), false) = (&session, refreshed) {
+                warn!("cached app_id was rejected, refreshing config");
+                refresh_app_id(client).await?;
+                refreshed = true;
+                session = client.refresh_session().await;
             }
-        } else {
-            let (username, password): (Option<String>, Option<String>) =
-                if let (Some(u), Some(p)) = (username, password) {
-                    (Some(u.to_string()), Some(p.to_string()))
-                } else if let (Some(u), Some(p)) = (config.username, config.password) {
-                    (Some(u), Some(p))
-                } else {
-                    (None, None)
-                };
-
-            if let (Some(username), Some(password)) = (username, password) {
-                info!("setting auth using username and password from cache");
-                if refresh_config {
-                    client.refresh().await?;
-
-                    if let Some(id) = client.get_app_id() {
-                        db::set_app_id(id).await;
+
+            match session {
+                Ok(()) => {
+                    if let Some(token) = client.get_token() {
+                        db::set_user_token(token).await;
                     }
                 }
+                Err(error) => warn!("unable to refresh session: {error}"),
+            }
+        } else if let Some((username, password)) = credentials {
+            info!("setting auth using username and password from cache");
 
-                client.login(&username, &password).await?;
-                client.test_secrets().await?;
+            let mut login = client.login(&username, &password).await;
 
-                if let Some(token) = client.get_token() {
-                    db::set_user_token(token).await;
-                }
+            if let (Err(Error::AppID), false) = (&login, refreshed) {
+                warn!("cached app_id was rejected, refreshing config");
+                refresh_app_id(client).await?;
+                refreshed = true;
+                login = client.login(&username, &password).await;
+            }
 
-                if let Some(secret) = client.get_active_secret() {
-                    db::set_active_secret(secret).await;
-                }
+            login?;
+
+            if let Some(token) = client.get_token() {
+                db::set_user_token(token).await;
+            }
+        }
+
+        if client.signed_in()
+            && (refreshed
+                || matches!(
+                    client.verify_active_secret().await,
+                    Err(Error::ActiveSecret)
+                ))
+        {
+            if !refreshed {
+                warn!("cached active secret was rejected, refreshing config");
+                refresh_app_id(client).await?;
+            }
+
+            client.test_secrets().await?;
+
+            if let Some(secret) = client.get_active_secret() {
+                db::set_active_secret(secret).await;
             }
         }
     }
@@ -170,6 +524,75 @@ pub async fn setup_client(
     Ok(client.clone())
 }
 
+/// Scrape a new app_id and secrets from the web player. The cached ones are
+/// only forgotten once that worked, so a failed refresh leaves them in place.
+/// A working secret still has to be picked with `test_secrets`.
+async fn refresh_app_id(client: &mut QobuzClient) -> Result<()> {
+    let mut fresh = client.clone();
+    fresh.invalidate_cached_secrets();
+    fresh.refresh().await?;
+
+    *client = fresh;
+    db::clear_secrets().await;
+
+    if let Some(id) = client.get_app_id() {
+        db::set_app_id(id).await;
+    }
+
+    Ok(())
+}
+
+/// Tracks are added with `no_duplicate` set, so Qobuz refuses to add one
+/// that is already in the playlist.
+fn is_duplicate_rejection(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("duplicate") || message.contains("already")
+}
+
+impl From<QobuzFavorites> for Favorites {
+    fn from(f: QobuzFavorites) -> Self {
+        Self {
+            albums: f
+                .albums
+                .items
+                .into_iter()
+                .map(|a| a.into())
+                .collect::<Vec<Album>>(),
+            tracks: f
+                .tracks
+                .items
+                .into_iter()
+                .map(|t| t.into())
+                .collect::<Vec<Track>>(),
+            artists: f
+                .artists
+                .items
+                .into_iter()
+                .map(|a| a.into())
+                .collect::<Vec<Artist>>(),
+        }
+    }
+}
+
+impl From<QobuzPurchases> for Purchases {
+    fn from(p: QobuzPurchases) -> Self {
+        Self {
+            albums: p
+                .albums
+                .items
+                .into_iter()
+                .map(|a| a.into())
+                .collect::<Vec<Album>>(),
+            tracks: p
+                .tracks
+                .items
+                .into_iter()
+                .map(|t| t.into())
+                .collect::<Vec<Track>>(),
+        }
+    }
+}
+
 impl From<SearchAllResults> for SearchResults {
     fn from(s: SearchAllResults) -> Self {
         Self {
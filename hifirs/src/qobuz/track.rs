@@ -1,4 +1,7 @@
-use crate::service::{Album, Artist, Track, TrackStatus};
+use crate::{
+    qobuz::album::cover_art,
+    service::{Album, Artist, Credit, Track, TrackStatus},
+};
 use hifirs_qobuz_api::client::track::Track as QobuzTrack;
 
 impl From<QobuzTrack> for Track {
@@ -20,7 +23,15 @@ impl From<QobuzTrack> for Track {
             value.album.as_ref().map(|a| a.artist.clone().into())
         };
 
-        let cover_art = value.album.as_ref().map(|a| a.image.large.clone());
+        let cover_art = value.album.as_ref().and_then(|a| cover_art(&a.image));
+
+        let genre = value
+            .album
+            .as_ref()
+            .map(|a| a.genre.name.clone())
+            .filter(|name| !name.is_empty());
+
+        let credits = value.performers.as_deref().map(credits).unwrap_or_default();
 
         let status = if value.streamable {
             TrackStatus::Unplayed
@@ -45,6 +56,99 @@ impl From<QobuzTrack> for Track {
             position: value.position.unwrap_or(value.track_number as usize) as u32,
             cover_art,
             media_number: value.media_number as u32,
+            isrc: value.isrc,
+            replaygain_gain: Some(value.audio_info.replaygain_track_gain).filter(|g| *g != 0.0),
+            playlist_track_id: value.playlist_track_id.map(|id| id as u64),
+            genre,
+            composer: value.composer.map(|c| c.name),
+            credits,
         }
     }
 }
+
+/// Qobuz lists performers as "Name, Role, Role - Name, Role".
+fn credits(performers: &str) -> Vec<Credit> {
+    performers
+        .split(" - ")
+        .filter_map(|performer| {
+            let mut parts = performer.split(',').map(str::trim);
+            let name = parts.next().filter(|name| !name.is_empty())?;
+
+            Some(Credit {
+                name: name.to_string(),
+                roles: parts
+                    .filter(|role| !role.is_empty())
+                    .map(String::from)
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn performers_become_credits() {
+    let parsed = credits("Miles Davis, MainArtist, Trumpet - Teo Macero, Producer -  ");
+
+    assert_eq!(
+        parsed,
+        vec![
+            Credit {
+                name: "Miles Davis".to_string(),
+                roles: vec!["MainArtist".to_string(), "Trumpet".to_string()],
+            },
+            Credit {
+                name: "Teo Macero".to_string(),
+                roles: vec!["Producer".to_string()],
+            },
+        ]
+    );
+    assert_eq!(parsed[1].to_string(), "Teo Macero (Producer)");
+}
+
+#[test]
+fn track_isrc_is_optional() {
+    let payload = |isrc: &str| {
+        format!(
+            r#"{{
+                "audio_info": {{ "replaygain_track_gain": -7.5, "replaygain_track_peak": 0.98 }},
+                "displayable": true,
+                "downloadable": true,
+                "duration": 466,
+                "hires": true,
+                "hires_streamable": true,
+                "id": 155999429,
+                {isrc}
+                "maximum_bit_depth": 24,
+                "maximum_channel_count": 2,
+                "maximum_sampling_rate": 96.0,
+                "media_number": 1,
+                "parental_warning": false,
+                "previewable": true,
+                "purchasable": true,
+                "sampleable": true,
+                "streamable": true,
+                "title": "Acknowledgement",
+                "track_number": 1
+            }}"#
+        )
+    };
+
+    let with_isrc: QobuzTrack =
+        serde_json::from_str(&payload(r#""isrc": "USMC16246949","#)).expect("invalid payload");
+    let track: Track = with_isrc.into();
+    assert_eq!(track.isrc, Some("USMC16246949".to_string()));
+
+    let without_isrc: QobuzTrack = serde_json::from_str(&payload("")).expect("invalid payload");
+    let track: Track = without_isrc.into();
+    assert_eq!(track.isrc, None);
+    assert_eq!(track.composer, None);
+    assert!(track.credits.is_empty());
+
+    let with_composer: QobuzTrack = serde_json::from_str(&payload(
+        r#""composer": { "id": 1, "name": "John Coltrane" }, "performers": "John Coltrane, Composer","#,
+    ))
+    .expect("invalid payload");
+    let track: Track = with_composer.into();
+    assert_eq!(track.composer, Some("John Coltrane".to_string()));
+    assert_eq!(track.credits.len(), 1);
+}
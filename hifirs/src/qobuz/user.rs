@@ -0,0 +1,13 @@
+use crate::service::Account;
+use hifirs_qobuz_api::client::user::UserInfo;
+
+impl From<UserInfo> for Account {
+    fn from(value: UserInfo) -> Self {
+        Self {
+            name: value.name().to_string(),
+            subscription: value.subscription_type().map(|s| s.to_string()),
+            max_quality: value.max_quality(),
+            email: value.email,
+        }
+    }
+}
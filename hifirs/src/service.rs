@@ -1,23 +1,128 @@
-use crate::{cursive::CursiveFormat, player::queue::TrackListType};
+use crate::{
+    cursive::{append_badges, fixed_width, CursiveFormat},
+    player::queue::TrackListType,
+};
 use async_trait::async_trait;
 use cursive::{
     theme::{Effect, Style},
     utils::markup::StyledString,
 };
 use gstreamer::ClockTime;
+use hifirs_qobuz_api::client::{self, AudioQuality};
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeMap, fmt::Debug};
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    fmt::{Debug, Display},
+    path::Path,
+};
+
+const TITLE_WIDTH: usize = 40;
+const ARTIST_WIDTH: usize = 24;
+const ALBUM_WIDTH: usize = 24;
 
 #[async_trait]
 pub trait MusicService: Send + Sync + Debug {
     async fn login(&self, username: &str, password: &str);
+    async fn account(&self) -> Option<Account>;
     async fn album(&self, album_id: &str) -> Option<Album>;
     async fn track(&self, track_id: i32) -> Option<Track>;
+    async fn track_suggestions(&self, track_id: i32, limit: usize) -> Option<Vec<Track>>;
+    /// Empty when the track has no lyrics, None when they couldn't be fetched.
+    async fn track_lyrics(&self, track_id: i32) -> Option<String>;
     async fn artist(&self, artist_id: i32) -> Option<Artist>;
     async fn playlist(&self, playlist_id: i64) -> Option<Playlist>;
+    async fn playlist_page(&self, playlist_id: i64, offset: u32, limit: u32) -> Option<Playlist>;
     async fn search(&self, query: &str) -> Option<SearchResults>;
-    async fn track_url(&self, track_id: i32) -> Option<String>;
+    async fn track_url(
+        &self,
+        track_id: i32,
+        quality: Option<AudioQuality>,
+    ) -> Result<StreamUrl, StreamError>;
+    async fn download_track(
+        &self,
+        track_id: i32,
+        path: &Path,
+        on_progress: Box<dyn FnMut(u64, Option<u64>) + Send>,
+    ) -> bool;
     async fn user_playlists(&self) -> Option<Vec<Playlist>>;
+    async fn create_playlist(
+        &self,
+        name: &str,
+        public: bool,
+        description: Option<String>,
+    ) -> Option<Playlist>;
+    async fn add_track_to_playlist(&self, playlist_id: u32, track_id: u32) -> Option<bool>;
+    async fn set_playlist_followed(&self, playlist_id: u32, followed: bool) -> bool;
+    async fn move_playlist_track(
+        &self,
+        playlist_id: u32,
+        playlist_track_id: u64,
+        insert_before: u32,
+    ) -> bool;
+    async fn artist_playlists(&self, artist_id: i32) -> Option<Vec<Playlist>>;
+    async fn similar_artists(&self, artist_id: i32) -> Option<Vec<Artist>>;
+    async fn user_purchases(&self) -> Option<Purchases>;
+    async fn favorites(&self) -> Option<Favorites>;
+    async fn editorial_content(&self) -> Option<Vec<Article>>;
+    async fn favorite_tracks(&self) -> Option<Vec<u32>>;
+    async fn set_favorite_track(&self, track_id: u32, favorite: bool) -> bool;
+    /// True only when the service turned the session's token down. Errors
+    /// that say nothing about the token, like a timeout, aren't a rejection.
+    async fn session_rejected(&self) -> bool;
+    fn default_quality(&self) -> AudioQuality;
+    fn set_default_quality(&self, quality: AudioQuality);
+    fn has_app_id(&self) -> bool;
+    fn has_active_secret(&self) -> bool;
+}
+
+/// Who is logged in, and what their subscription allows.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Account {
+    pub name: String,
+    pub email: Option<String>,
+    pub subscription: Option<String>,
+    pub max_quality: AudioQuality,
+}
+
+/// Why a track has no stream url.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamError {
+    /// The service won't stream the track, e.g. it is restricted in this region.
+    Refused,
+    /// Anything else, like a dropped connection. Trying again later may work.
+    Unavailable { message: String },
+}
+
+impl Display for StreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamError::Refused => f.write_str("the track can't be streamed"),
+            StreamError::Unavailable { message } => f.write_str(message),
+        }
+    }
+}
+
+/// Where to stream a track from, and at what quality.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamUrl {
+    pub url: String,
+    pub requested: AudioQuality,
+    /// Lower than `requested` when the account isn't allowed it.
+    pub granted: AudioQuality,
+    /// When the url stops working, in seconds since the epoch.
+    pub expires_at: i64,
+}
+
+impl StreamUrl {
+    pub fn is_downgraded(&self) -> bool {
+        self.granted != self.requested
+    }
+
+    pub fn is_expired(&self) -> bool {
+        client::is_past_expiry(self.expires_at)
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
@@ -49,6 +154,96 @@ pub struct Track {
     pub cover_art: Option<String>,
     pub position: u32,
     pub media_number: u32,
+    pub isrc: Option<String>,
+    /// ReplayGain track gain in dB, when the service provides one.
+    #[serde(default)]
+    pub replaygain_gain: Option<f64>,
+    /// The entry in the playlist the track was loaded from, used to reorder it.
+    #[serde(default)]
+    pub playlist_track_id: Option<u64>,
+    #[serde(default)]
+    pub genre: Option<String>,
+    #[serde(default)]
+    pub composer: Option<String>,
+    /// Everyone credited on the track, main artist included.
+    #[serde(default)]
+    pub credits: Vec<Credit>,
+}
+
+/// A streamable track on the first disc, for tests to fill in.
+#[cfg(test)]
+impl Default for Track {
+    fn default() -> Self {
+        Self {
+            id: 1,
+            number: 1,
+            title: "Track".to_string(),
+            album: None,
+            artist: None,
+            duration_seconds: 200,
+            explicit: false,
+            hires_available: false,
+            sampling_rate: 44.1,
+            bit_depth: 16,
+            status: TrackStatus::Unplayed,
+            track_url: None,
+            available: true,
+            cover_art: None,
+            position: 1,
+            media_number: 1,
+            isrc: None,
+            replaygain_gain: None,
+            playlist_track_id: None,
+            genre: None,
+            composer: None,
+            credits: Vec::new(),
+        }
+    }
+}
+
+/// Someone who worked on a track, with what they did.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Credit {
+    pub name: String,
+    pub roles: Vec<String>,
+}
+
+impl Display for Credit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.roles.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{} ({})", self.name, self.roles.join(", "))
+        }
+    }
+}
+
+impl Track {
+    /// Appends aligned title, artist, album and year columns to `item`.
+    fn append_columns(&self, item: &mut StyledString, style: Style) {
+        let artist = self
+            .artist
+            .as_ref()
+            .map(|a| a.name.trim())
+            .unwrap_or_default();
+        let (album, year) = if let Some(album) = &self.album {
+            (album.title.trim(), album.release_year.to_string())
+        } else {
+            ("", String::new())
+        };
+
+        item.append_styled(fixed_width(self.title.trim(), TITLE_WIDTH), style);
+        item.append_plain(" ");
+        item.append_styled(
+            fixed_width(artist, ARTIST_WIDTH),
+            style.combine(Effect::Dim),
+        );
+        item.append_plain(" ");
+        item.append_styled(fixed_width(album, ALBUM_WIDTH), style.combine(Effect::Dim));
+        item.append_plain(" ");
+        item.append_styled(fixed_width(&year, 4), style.combine(Effect::Dim));
+        item.append_plain(" ");
+    }
 }
 
 impl CursiveFormat for Track {
@@ -59,28 +254,17 @@ impl CursiveFormat for Track {
             style = style.combine(Effect::Dim).combine(Effect::Strikethrough);
         }
 
-        let mut title = StyledString::styled(self.title.trim(), style.combine(Effect::Bold));
-
-        if let Some(artist) = &self.artist {
-            title.append_styled(" by ", style);
-            title.append_styled(&artist.name, style);
-        }
+        let mut title = StyledString::new();
+        self.append_columns(&mut title, style);
 
         let duration = ClockTime::from_seconds(self.duration_seconds as u64)
             .to_string()
             .as_str()[2..7]
             .to_string();
-        title.append_plain(" ");
         title.append_styled(duration, style.combine(Effect::Dim));
         title.append_plain(" ");
 
-        if self.explicit {
-            title.append_styled("e", style.combine(Effect::Dim));
-        }
-
-        if self.hires_available {
-            title.append_styled("*", style.combine(Effect::Dim));
-        }
+        append_badges(&mut title, self.explicit, self.hires_available, style);
 
         title
     }
@@ -102,8 +286,17 @@ impl CursiveFormat for Track {
         };
 
         let mut item = StyledString::styled(format!("{:02} ", num), style);
-        item.append_styled(self.title.trim(), style.combine(Effect::Simple));
-        item.append_plain(" ");
+
+        match list_type {
+            // Every track on an album shares the artist, album and year.
+            TrackListType::Album | TrackListType::Track => {
+                item.append_styled(self.title.trim(), style.combine(Effect::Simple));
+                item.append_plain(" ");
+            }
+            TrackListType::Playlist | TrackListType::Unknown => {
+                self.append_columns(&mut item, style);
+            }
+        }
 
         let duration = ClockTime::from_seconds(self.duration_seconds as u64)
             .to_string()
@@ -111,6 +304,9 @@ impl CursiveFormat for Track {
             .to_string();
 
         item.append_styled(duration, style.combine(Effect::Dim));
+        item.append_plain(" ");
+
+        append_badges(&mut item, self.explicit, self.hires_available, style);
 
         item
     }
@@ -128,7 +324,33 @@ pub struct Album {
     pub total_tracks: u32,
     pub tracks: BTreeMap<u32, Track>,
     pub available: bool,
-    pub cover_art: String,
+    pub cover_art: Option<String>,
+    pub upc: Option<String>,
+}
+
+impl Album {
+    /// Position of the first track on each disc, by disc number. Empty when
+    /// the album is a single disc.
+    pub fn disc_starts(&self) -> BTreeMap<u32, u32> {
+        disc_starts(self.tracks.values())
+    }
+}
+
+/// Position of the first track on each disc, by disc number, when the tracks
+/// span more than one disc.
+pub fn disc_starts<'a>(tracks: impl Iterator<Item = &'a Track>) -> BTreeMap<u32, u32> {
+    let mut starts = BTreeMap::new();
+
+    for track in tracks {
+        let start = starts.entry(track.media_number).or_insert(track.position);
+        *start = (*start).min(track.position);
+    }
+
+    if starts.len() > 1 {
+        starts
+    } else {
+        BTreeMap::new()
+    }
 }
 
 impl CursiveFormat for Album {
@@ -148,13 +370,7 @@ impl CursiveFormat for Album {
         title.append_styled(self.release_year.to_string(), style.combine(Effect::Dim));
         title.append_plain(" ");
 
-        if self.explicit {
-            title.append_styled("e", style.combine(Effect::Dim));
-        }
-
-        if self.hires_available {
-            title.append_styled("*", style.combine(Effect::Dim));
-        }
+        append_badges(&mut title, self.explicit, self.hires_available, style);
 
         title
     }
@@ -169,6 +385,33 @@ pub struct SearchResults {
     pub playlists: Vec<Playlist>,
 }
 
+/// Something playable an editorial article points at.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ArticleLink {
+    Album(String),
+    Playlist(i64),
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Article {
+    pub title: String,
+    pub description: Option<String>,
+    pub link: Option<ArticleLink>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Purchases {
+    pub albums: Vec<Album>,
+    pub tracks: Vec<Track>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Favorites {
+    pub albums: Vec<Album>,
+    pub tracks: Vec<Track>,
+    pub artists: Vec<Artist>,
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Artist {
     pub id: u32,
@@ -192,3 +435,116 @@ impl CursiveFormat for Artist {
         StyledString::plain(self.name.clone())
     }
 }
+
+/// Client side ordering for search results. Relevance keeps the order the
+/// service returned.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortOrder {
+    #[default]
+    Relevance,
+    ReleaseYear,
+    Duration,
+    Title,
+}
+
+impl SortOrder {
+    pub const ALL: [SortOrder; 4] = [
+        SortOrder::Relevance,
+        SortOrder::ReleaseYear,
+        SortOrder::Duration,
+        SortOrder::Title,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Relevance => "relevance",
+            SortOrder::ReleaseYear => "release year",
+            SortOrder::Duration => "duration",
+            SortOrder::Title => "title",
+        }
+    }
+}
+
+impl From<&str> for SortOrder {
+    fn from(value: &str) -> Self {
+        SortOrder::ALL
+            .into_iter()
+            .find(|order| order.as_str() == value)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SortKey {
+    Number(u32),
+    Text(String),
+}
+
+pub trait Sortable {
+    /// The value to sort by, or None when the item doesn't have one.
+    fn sort_key(&self, order: SortOrder) -> Option<SortKey>;
+}
+
+/// Sorts in place, newest first for release years and ascending otherwise.
+/// Items missing the sort key keep their relative order at the end.
+pub fn sort_results<T: Sortable>(items: &mut [T], order: SortOrder) {
+    if order == SortOrder::Relevance {
+        return;
+    }
+
+    items.sort_by(|a, b| match (a.sort_key(order), b.sort_key(order)) {
+        (Some(a), Some(b)) if order == SortOrder::ReleaseYear => b.cmp(&a),
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+}
+
+fn non_zero(value: u32) -> Option<SortKey> {
+    Some(value).filter(|v| *v > 0).map(SortKey::Number)
+}
+
+fn text(value: &str) -> Option<SortKey> {
+    Some(SortKey::Text(value.trim().to_lowercase()))
+}
+
+impl Sortable for Album {
+    fn sort_key(&self, order: SortOrder) -> Option<SortKey> {
+        match order {
+            SortOrder::ReleaseYear => non_zero(self.release_year),
+            SortOrder::Title => text(&self.title),
+            SortOrder::Relevance | SortOrder::Duration => None,
+        }
+    }
+}
+
+impl Sortable for Track {
+    fn sort_key(&self, order: SortOrder) -> Option<SortKey> {
+        match order {
+            SortOrder::ReleaseYear => self.album.as_ref().and_then(|a| non_zero(a.release_year)),
+            SortOrder::Duration => non_zero(self.duration_seconds),
+            SortOrder::Title => text(&self.title),
+            SortOrder::Relevance => None,
+        }
+    }
+}
+
+impl Sortable for Artist {
+    fn sort_key(&self, order: SortOrder) -> Option<SortKey> {
+        match order {
+            SortOrder::Title => text(&self.name),
+            _ => None,
+        }
+    }
+}
+
+impl Sortable for Playlist {
+    fn sort_key(&self, order: SortOrder) -> Option<SortKey> {
+        match order {
+            SortOrder::Duration => non_zero(self.duration_seconds),
+            SortOrder::Title => text(&self.title),
+            SortOrder::Relevance | SortOrder::ReleaseYear => None,
+        }
+    }
+}
@@ -1,27 +1,61 @@
 use hifirs_qobuz_api::client::{ApiConfig, AudioQuality};
 use once_cell::sync::OnceCell;
 use sqlx::{sqlite::SqliteConnectOptions, Pool, Sqlite, SqlitePool};
-use std::path::PathBuf;
+use std::{net::SocketAddr, path::PathBuf};
 
 use crate::{
     acquire, get_one,
-    player::queue::controls::{PlayerState, SavedState},
+    player::{
+        controls::NormalizationMode,
+        queue::controls::{PlayerState, SavedState},
+    },
+    qobuz::CookieMode,
     query,
+    service::SortOrder,
 };
 
 static POOL: OnceCell<Pool<Sqlite>> = OnceCell::new();
 
+/// Directory holding the database and other saved data.
+fn data_dir() -> PathBuf {
+    let mut dir = dirs::data_local_dir().unwrap();
+    dir.push("hifi-rs");
+
+    if !dir.exists() {
+        std::fs::create_dir_all(dir.clone()).expect("failed to create database directory");
+    }
+
+    dir
+}
+
+/// File the api client saves cookies to when they are persisted.
+pub fn cookie_jar_path() -> PathBuf {
+    let mut path = data_dir();
+    path.push("cookies.txt");
+
+    path
+}
+
+/// Where downloaded tracks go unless another folder is configured.
+fn default_library_path() -> PathBuf {
+    match dirs::audio_dir() {
+        Some(mut dir) => {
+            dir.push("hifi-rs");
+            dir
+        }
+        None => {
+            let mut dir = data_dir();
+            dir.push("library");
+            dir
+        }
+    }
+}
+
 pub async fn init() {
     let database_url = if let Ok(url) = std::env::var("DATABASE_URL") {
         PathBuf::from(url.replace("sqlite://", ""))
     } else {
-        let mut url = dirs::data_local_dir().unwrap();
-        url.push("hifi-rs");
-
-        if !url.exists() {
-            std::fs::create_dir_all(url.clone()).expect("failed to create database directory");
-        }
-
+        let mut url = data_dir();
         url.push("data.db");
 
         url
@@ -86,6 +120,12 @@ pub async fn set_password(password: String) {
 }
 
 pub async fn set_user_token(token: String) {
+    #[cfg(feature = "keyring")]
+    if super::secrets::set_token(&token).await {
+        clear_stored_user_token().await;
+        return;
+    }
+
     if let Ok(mut conn) = acquire!() {
         query!(
             r#"
@@ -99,6 +139,22 @@ pub async fn set_user_token(token: String) {
     }
 }
 
+pub async fn clear_user_token() {
+    #[cfg(feature = "keyring")]
+    super::secrets::delete_token().await;
+
+    clear_stored_user_token().await;
+}
+
+async fn clear_stored_user_token() {
+    if let Ok(mut conn) = acquire!() {
+        sqlx::query!("UPDATE config SET user_token=NULL WHERE ROWID = 1")
+            .execute(&mut *conn)
+            .await
+            .expect("database failure");
+    }
+}
+
 pub async fn set_app_id(id: String) {
     if let Ok(mut conn) = acquire!() {
         query!(
@@ -118,7 +174,7 @@ pub async fn set_active_secret(secret: String) {
         query!(
             r#"
             UPDATE config
-            SET active_secret=?1
+            SET active_secret=?1, secrets_cached_at=strftime('%s', 'now')
             WHERE ROWID = 1
             "#,
             conn,
@@ -127,6 +183,31 @@ pub async fn set_active_secret(secret: String) {
     }
 }
 
+/// When the app_id and active secret were last found, in seconds since the epoch.
+pub async fn get_secrets_cached_at() -> Option<i64> {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT secrets_cached_at FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.secrets_cached_at;
+        }
+    }
+
+    None
+}
+
+pub async fn clear_secrets() {
+    if let Ok(mut conn) = acquire!() {
+        sqlx::query!(
+            "UPDATE config SET app_id=NULL, active_secret=NULL, secrets_cached_at=NULL WHERE ROWID = 1"
+        )
+        .execute(&mut *conn)
+        .await
+        .expect("database failure");
+    }
+}
+
 pub async fn set_default_quality(quality: AudioQuality) {
     if let Ok(mut conn) = acquire!() {
         let quality_id = quality as i32;
@@ -143,6 +224,680 @@ pub async fn set_default_quality(quality: AudioQuality) {
     }
 }
 
+pub async fn set_preamp(preamp: f64) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET preamp=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            preamp
+        );
+    }
+}
+
+pub async fn get_preamp() -> f64 {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT preamp FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.preamp;
+        }
+    }
+
+    0.0
+}
+
+pub async fn set_volume(volume: f64) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET volume=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            volume
+        );
+    }
+}
+
+pub async fn get_volume() -> f64 {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT volume FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.volume;
+        }
+    }
+
+    1.0
+}
+
+pub async fn set_relogin_interval(hours: i64) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET relogin_interval=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            hours
+        );
+    }
+}
+
+pub async fn get_relogin_interval() -> i64 {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT relogin_interval FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.relogin_interval;
+        }
+    }
+
+    0
+}
+
+pub async fn set_idle_timeout(minutes: i64) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET idle_timeout=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            minutes
+        );
+    }
+}
+
+pub async fn get_idle_timeout() -> i64 {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT idle_timeout FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.idle_timeout;
+        }
+    }
+
+    0
+}
+
+pub async fn set_position_interval(millis: i64) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET position_interval=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            millis
+        );
+    }
+}
+
+pub async fn get_position_interval() -> i64 {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT position_interval FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.position_interval;
+        }
+    }
+
+    crate::REFRESH_RESOLUTION as i64
+}
+
+pub async fn set_playlist_page_size(size: i64) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET playlist_page_size=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            size
+        );
+    }
+}
+
+pub async fn get_playlist_page_size() -> i64 {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT playlist_page_size FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.playlist_page_size;
+        }
+    }
+
+    50
+}
+
+pub async fn set_mono(enabled: bool) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET mono=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            enabled
+        );
+    }
+}
+
+pub async fn get_mono() -> bool {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT mono FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.mono != 0;
+        }
+    }
+
+    false
+}
+
+pub async fn set_normalization(mode: NormalizationMode) {
+    if let Ok(mut conn) = acquire!() {
+        let mode = mode.as_str();
+
+        query!(
+            r#"
+            UPDATE config
+            SET normalization=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            mode
+        );
+    }
+}
+
+pub async fn get_normalization() -> NormalizationMode {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT normalization FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.normalization.as_str().into();
+        }
+    }
+
+    NormalizationMode::default()
+}
+
+pub async fn set_skip_silence(enabled: bool, threshold: i32) {
+    if let Ok(mut conn) = acquire!() {
+        sqlx::query!(
+            r#"
+            UPDATE config
+            SET skip_silence=?1, skip_silence_threshold=?2
+            WHERE ROWID = 1
+            "#,
+            enabled,
+            threshold
+        )
+        .execute(&mut *conn)
+        .await
+        .expect("database failure");
+    }
+}
+
+pub async fn get_skip_silence() -> (bool, i32) {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) =
+            sqlx::query!("SELECT skip_silence, skip_silence_threshold FROM config WHERE ROWID = 1;")
+                .fetch_one(&mut *conn)
+                .await
+        {
+            return (row.skip_silence != 0, row.skip_silence_threshold as i32);
+        }
+    }
+
+    (false, -60)
+}
+
+/// The screen the TUI opens on, by its index.
+pub async fn set_default_screen(screen_id: i64) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET default_screen=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            screen_id
+        );
+    }
+}
+
+pub async fn get_default_screen() -> i64 {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT default_screen FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.default_screen;
+        }
+    }
+
+    0
+}
+
+pub async fn set_resume_position(enabled: bool) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET resume_position=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            enabled
+        );
+    }
+}
+
+pub async fn get_resume_position() -> bool {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT resume_position FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.resume_position != 0;
+        }
+    }
+
+    true
+}
+
+pub async fn set_autoplay(enabled: bool) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET autoplay=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            enabled
+        );
+    }
+}
+
+pub async fn get_autoplay() -> bool {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT autoplay FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.autoplay != 0;
+        }
+    }
+
+    false
+}
+
+pub async fn set_marquee(enabled: bool) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET marquee=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            enabled
+        );
+    }
+}
+
+pub async fn get_marquee() -> bool {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT marquee FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.marquee != 0;
+        }
+    }
+
+    false
+}
+
+pub async fn set_level_meter(enabled: bool) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET level_meter=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            enabled
+        );
+    }
+}
+
+pub async fn get_level_meter() -> bool {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT level_meter FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.level_meter != 0;
+        }
+    }
+
+    false
+}
+
+pub async fn set_login_timeout(seconds: i64) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET login_timeout=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            seconds
+        );
+    }
+}
+
+pub async fn get_login_timeout() -> i64 {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT login_timeout FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.login_timeout;
+        }
+    }
+
+    15
+}
+
+pub async fn set_jump_seconds(seconds: i64) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET jump_seconds=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            seconds
+        );
+    }
+}
+
+pub async fn get_jump_seconds() -> i64 {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT jump_seconds FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.jump_seconds;
+        }
+    }
+
+    10
+}
+
+pub async fn set_library_path(path: String) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET library_path=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            path
+        );
+    }
+}
+
+pub async fn get_library_path() -> PathBuf {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT library_path FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            if let Some(path) = row.library_path {
+                return PathBuf::from(path);
+            }
+        }
+    }
+
+    default_library_path()
+}
+
+/// Where the REST control API listens. None keeps it off.
+pub async fn set_http_address(address: Option<String>) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET http_address=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            address
+        );
+    }
+}
+
+pub async fn get_http_address() -> Option<SocketAddr> {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT http_address FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.http_address.and_then(|address| address.parse().ok());
+        }
+    }
+
+    None
+}
+
+/// The Discord application presence is shown as. None keeps it off.
+pub async fn set_discord_app_id(app_id: Option<String>) {
+    if let Ok(mut conn) = acquire!() {
+        query!(
+            r#"
+            UPDATE config
+            SET discord_app_id=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            app_id
+        );
+    }
+}
+
+pub async fn get_discord_app_id() -> Option<String> {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT discord_app_id FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.discord_app_id.filter(|id| !id.is_empty());
+        }
+    }
+
+    None
+}
+
+pub async fn set_cookie_mode(mode: CookieMode) {
+    if let Ok(mut conn) = acquire!() {
+        let mode = mode.as_str();
+
+        query!(
+            r#"
+            UPDATE config
+            SET cookies=?1
+            WHERE ROWID = 1
+            "#,
+            conn,
+            mode
+        );
+    }
+}
+
+pub async fn get_cookie_mode() -> CookieMode {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!("SELECT cookies FROM config WHERE ROWID = 1;")
+            .fetch_one(&mut *conn)
+            .await
+        {
+            return row.cookies.as_str().into();
+        }
+    }
+
+    CookieMode::default()
+}
+
+pub async fn set_search_sort(result_type: &str, order: SortOrder) {
+    if let Ok(mut conn) = acquire!() {
+        let order = order.as_str();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO search_sort (result_type, sort_order) VALUES (?1, ?2)
+            ON CONFLICT(result_type) DO UPDATE SET sort_order=?2;
+            "#,
+            result_type,
+            order
+        )
+        .execute(&mut *conn)
+        .await
+        .expect("database failure");
+    }
+}
+
+pub async fn get_search_sort(result_type: &str) -> SortOrder {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(row) = sqlx::query!(
+            "SELECT sort_order FROM search_sort WHERE result_type = ?1;",
+            result_type
+        )
+        .fetch_one(&mut *conn)
+        .await
+        {
+            return row.sort_order.as_str().into();
+        }
+    }
+
+    SortOrder::default()
+}
+
+pub async fn set_keybinding(action: &str, key: &str) {
+    if let Ok(mut conn) = acquire!() {
+        sqlx::query!(
+            r#"
+            INSERT INTO keybindings (action, key) VALUES (?1, ?2)
+            ON CONFLICT(action) DO UPDATE SET key=?2;
+            "#,
+            action,
+            key
+        )
+        .execute(&mut *conn)
+        .await
+        .expect("database failure");
+    }
+}
+
+pub async fn clear_keybinding(action: &str) {
+    if let Ok(mut conn) = acquire!() {
+        sqlx::query!("DELETE FROM keybindings WHERE action = ?1;", action)
+            .execute(&mut *conn)
+            .await
+            .expect("database failure");
+    }
+}
+
+/// Remember a search, keeping only the most recent `limit`.
+pub async fn add_search_term(term: &str, limit: i64) {
+    if let Ok(mut conn) = acquire!() {
+        let searched_at = chrono::Utc::now().timestamp_millis();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO search_history (term, searched_at) VALUES (?1, ?2)
+            ON CONFLICT(term) DO UPDATE SET term=?1, searched_at=?2;
+            "#,
+            term,
+            searched_at
+        )
+        .execute(&mut *conn)
+        .await
+        .expect("database failure");
+
+        sqlx::query!(
+            r#"
+            DELETE FROM search_history WHERE term NOT IN (
+                SELECT term FROM search_history ORDER BY searched_at DESC LIMIT ?1
+            );
+            "#,
+            limit
+        )
+        .execute(&mut *conn)
+        .await
+        .expect("database failure");
+    }
+}
+
+/// Recent searches, newest first.
+pub async fn get_search_history(limit: i64) -> Vec<String> {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(rows) = sqlx::query!(
+            "SELECT term FROM search_history ORDER BY searched_at DESC LIMIT ?1;",
+            limit
+        )
+        .fetch_all(&mut *conn)
+        .await
+        {
+            return rows.into_iter().map(|r| r.term).collect();
+        }
+    }
+
+    Vec::new()
+}
+
+/// Keys chosen in place of the defaults, as (action, key) pairs.
+pub async fn get_keybindings() -> Vec<(String, String)> {
+    if let Ok(mut conn) = acquire!() {
+        if let Ok(rows) = sqlx::query!("SELECT action, key FROM keybindings;")
+            .fetch_all(&mut *conn)
+            .await
+        {
+            return rows.into_iter().map(|r| (r.action, r.key)).collect();
+        }
+    }
+
+    Vec::new()
+}
+
 pub async fn create_config() {
     if let Ok(mut conn) = acquire!() {
         let rowid = 1;
@@ -160,12 +915,27 @@ pub async fn get_config() -> Option<ApiConfig> {
     if let Ok(mut conn) = acquire!() {
         if let Ok(conf) = get_one!(
             r#"
-            SELECT * FROM config
+            SELECT username, password, default_quality, user_token, app_id, active_secret
+            FROM config
             WHERE ROWID = 1;
             "#,
             ApiConfig,
             conn
         ) {
+            #[cfg(feature = "keyring")]
+            let conf = {
+                let mut conf = conf;
+
+                if let Some(token) = super::secrets::get_token().await {
+                    conf.user_token = Some(token);
+                } else if let Some(token) = conf.user_token.clone() {
+                    debug!("moving user token from config into the keyring");
+                    set_user_token(token).await;
+                }
+
+                conf
+            };
+
             Some(conf)
         } else {
             None
@@ -191,6 +961,17 @@ pub async fn persist_state(state: PlayerState) {
         .execute(&mut *conn)
         .await
         .expect("database failure");
+
+        // Only the most recent states are ever read back.
+        sqlx::query!(
+            r#"
+            DELETE FROM player_state
+            WHERE rowid NOT IN (SELECT rowid FROM player_state ORDER BY rowid DESC LIMIT 10);
+            "#
+        )
+        .execute(&mut *conn)
+        .await
+        .expect("database failure");
     }
 }
 
@@ -1,5 +1,7 @@
 #[macro_use]
 pub mod db;
+#[cfg(feature = "keyring")]
+mod secrets;
 
 #[macro_export]
 macro_rules! acquire {
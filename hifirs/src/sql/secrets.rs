@@ -0,0 +1,68 @@
+use keyring::Entry;
+
+const SERVICE: &str = "hifi-rs";
+const USER: &str = "user_token";
+
+fn entry() -> Option<Entry> {
+    match Entry::new(SERVICE, USER) {
+        Ok(entry) => Some(entry),
+        Err(error) => {
+            warn!("keyring unavailable: {error}");
+            None
+        }
+    }
+}
+
+// Keyring backends block on IPC, e.g. D-Bus for the Secret Service, so the
+// calls run off the async runtime.
+async fn blocking<T, F>(call: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    match tokio::task::spawn_blocking(call).await {
+        Ok(result) => Some(result),
+        Err(error) => {
+            warn!("keyring call failed: {error}");
+            None
+        }
+    }
+}
+
+pub async fn get_token() -> Option<String> {
+    blocking(|| entry().and_then(|entry| entry.get_password().ok()))
+        .await
+        .flatten()
+}
+
+/// Returns false when the keyring could not store the token.
+pub async fn set_token(token: &str) -> bool {
+    let token = token.to_string();
+
+    blocking(move || {
+        if let Some(entry) = entry() {
+            match entry.set_password(&token) {
+                Ok(()) => true,
+                Err(error) => {
+                    warn!("failed to store token in keyring: {error}");
+                    false
+                }
+            }
+        } else {
+            false
+        }
+    })
+    .await
+    .unwrap_or(false)
+}
+
+pub async fn delete_token() {
+    blocking(|| {
+        if let Some(entry) = entry() {
+            if let Err(error) = entry.delete_password() {
+                debug!("no token removed from keyring: {error}");
+            }
+        }
+    })
+    .await;
+}
@@ -6,9 +6,10 @@ use axum::{
     routing::get,
     Router,
 };
-use futures::{SinkExt, StreamExt};
+use futures::{stream::SplitSink, SinkExt, StreamExt};
 use include_dir::{include_dir, Dir};
 use mime_guess::{mime::HTML, MimeGuess};
+use serde::Serialize;
 use serde_json::{json, Value};
 use std::{net::SocketAddr, path::PathBuf, str::FromStr};
 use tokio::select;
@@ -30,11 +31,9 @@ pub async fn init(binding_interface: SocketAddr) {
     let graceful = server.with_graceful_shutdown(async {
         let mut broadcast_receiver = player::notify_receiver();
 
-        loop {
-            if let Some(message) = broadcast_receiver.next().await {
-                if message == Notification::Quit {
-                    break;
-                }
+        while let Some(message) = broadcast_receiver.next().await {
+            if message == Notification::Quit {
+                break;
             }
         }
     });
@@ -86,61 +85,97 @@ async fn static_handler(req: Request<Body>) -> impl IntoResponse {
     }
 }
 
-async fn ws_handler(ws: WebSocketUpgrade) -> impl IntoResponse {
-    ws.on_upgrade(handle_connection)
-}
+/// Notifications that bring a new client up to date with the player.
+async fn snapshot() -> Vec<Notification> {
+    let mut notifications = vec![Notification::CurrentTrackList {
+        list: player::current_tracklist().await,
+    }];
 
-async fn handle_connection(socket: WebSocket) {
-    debug!("new websocket connection");
-    let (mut sender, mut receiver) = socket.split();
-    let (rt_sender, rt_receiver) = flume::bounded::<Value>(1);
+    if let Some(position) = player::position() {
+        notifications.push(Notification::Position { clock: position });
+    }
 
-    let mut send_task = tokio::spawn(async move {
-        debug!("spawning send task");
-        let mut broadcast_receiver = player::notify_receiver();
+    notifications.push(Notification::Status {
+        status: player::current_state(),
+    });
+    notifications.push(Notification::Volume {
+        level: player::volume(),
+    });
 
-        if let Ok(ct) = serde_json::to_string(&Notification::CurrentTrackList {
-            list: player::current_tracklist().await,
-        }) {
-            sender.send(Message::Text(ct)).await.expect("error");
+    notifications
+}
+
+/// Send a value to the client as JSON. False once the client is gone.
+async fn send_json<T: Serialize>(sender: &mut SplitSink<WebSocket, Message>, value: &T) -> bool {
+    let json = match serde_json::to_string(value) {
+        Ok(json) => json,
+        Err(error) => {
+            debug!(?error);
+            return true;
         }
+    };
 
-        if let Some(position) = player::position() {
-            if let Ok(p) = serde_json::to_string(&Notification::Position { clock: position }) {
-                sender.send(Message::Text(p)).await.expect("error");
-            }
+    match sender.send(Message::Text(json)).await {
+        Ok(()) => true,
+        Err(error) => {
+            debug!(?error);
+            false
         }
+    }
+}
+
+/// Send a snapshot of the player, then every notification and response as
+/// it happens. Each client has its own receiver, so a slow one only misses
+/// its own oldest notifications. Returns once the client is gone.
+pub async fn stream_notifications(
+    sender: &mut SplitSink<WebSocket, Message>,
+    responses: flume::Receiver<Value>,
+) {
+    let mut broadcast_receiver = player::notify_receiver();
 
-        if let Ok(s) = serde_json::to_string(&Notification::Status {
-            status: player::current_state(),
-        }) {
-            sender.send(Message::Text(s)).await.expect("error");
+    for notification in snapshot().await {
+        if !send_json(sender, &notification).await {
+            return;
         }
+    }
 
-        let mut rt_stream = rt_receiver.stream();
-
-        loop {
-            select! {
-                Some(message) = broadcast_receiver.next() => {
-                    let json = serde_json::to_string(&message).expect("error making json");
-                    match sender.send(Message::Text(json)).await {
-                        Ok(()) => {}
-                        Err(error) => {
-                            debug!(?error)
-                        }
-                    }
+    let mut responses = responses.into_stream();
+
+    loop {
+        select! {
+            message = broadcast_receiver.next() => {
+                let sent = match message {
+                    Some(message) => send_json(sender, &message).await,
+                    None => false,
+                };
+
+                if !sent {
+                    break;
                 }
-                Some(response) = rt_stream.next() => {
-                    let json = serde_json::to_string(&response).expect("error making json");
-                    match sender.send(Message::Text(json)).await {
-                        Ok(()) => {}
-                        Err(error) => {
-                            debug!(?error)
-                        }
-                    }
+            }
+            Some(response) = responses.next() => {
+                if !send_json(sender, &response).await {
+                    break;
                 }
             }
         }
+    }
+
+    debug!("websocket client gone");
+}
+
+async fn ws_handler(ws: WebSocketUpgrade) -> impl IntoResponse {
+    ws.on_upgrade(handle_connection)
+}
+
+async fn handle_connection(socket: WebSocket) {
+    debug!("new websocket connection");
+    let (mut sender, mut receiver) = socket.split();
+    let (rt_sender, rt_receiver) = flume::bounded::<Value>(1);
+
+    let mut send_task = tokio::spawn(async move {
+        debug!("spawning send task");
+        stream_notifications(&mut sender, rt_receiver).await;
     });
 
     let mut recv_task = tokio::spawn(async move {
@@ -162,18 +197,73 @@ async fn handle_connection(socket: WebSocket) {
                                 Action::Stop => controls.stop().await,
                                 Action::Quit => controls.quit().await,
                                 Action::SkipTo { num } => controls.skip_to(num).await,
-                                Action::JumpForward => controls.jump_forward().await,
-                                Action::JumpBackward => controls.jump_backward().await,
+                                Action::JumpForward { seconds } => {
+                                    controls.jump_forward(seconds).await
+                                }
+                                Action::JumpBackward { seconds } => {
+                                    controls.jump_backward(seconds).await
+                                }
+                                Action::Seek { time } => controls.seek(time).await,
+                                Action::SeekTo { seconds } => controls.seek_to(seconds).await,
+                                Action::SetSkipSilence { enabled, threshold } => {
+                                    controls.set_skip_silence(enabled, threshold).await
+                                }
+                                Action::SetPreamp { db } => controls.set_preamp(db).await,
+                                Action::SetVolume { level } => controls.set_volume(level).await,
+                                Action::SetMono { enabled } => controls.set_mono(enabled).await,
+                                Action::SetNormalization { mode } => {
+                                    controls.set_normalization(mode).await
+                                }
+                                Action::SetQuality { quality } => {
+                                    controls.set_quality(quality).await
+                                }
+                                Action::SetRepeat { mode } => controls.set_repeat(mode).await,
                                 Action::PlayAlbum { album_id } => {
                                     controls.play_album(album_id).await
                                 }
-                                Action::PlayTrack { track_id } => {
-                                    controls.play_track(track_id).await
+                                Action::PlayAlbumFrom { album_id, track_id } => {
+                                    controls.play_album_from(album_id, track_id).await
                                 }
+                                Action::PlayTrack { track_id, quality } => match quality {
+                                    Some(quality) => {
+                                        controls.play_track_with_quality(track_id, quality).await
+                                    }
+                                    None => controls.play_track(track_id).await,
+                                },
                                 Action::PlayUri { uri } => controls.play_uri(uri).await,
                                 Action::PlayPlaylist { playlist_id } => {
                                     controls.play_playlist(playlist_id).await
                                 }
+                                Action::PlayTrackRadio { track_id } => {
+                                    controls.play_track_radio(track_id).await
+                                }
+                                Action::StartRadio { track_id } => {
+                                    controls.start_radio(track_id).await
+                                }
+                                Action::SetRadio { enabled } => controls.set_radio(enabled).await,
+                                Action::AddToQueue { track_id } => {
+                                    controls.add_to_queue(track_id).await
+                                }
+                                Action::PlayNext { track_id } => controls.play_next(track_id).await,
+                                Action::RemoveFromQueue { position } => {
+                                    controls.remove_from_queue(position).await
+                                }
+                                Action::MoveTrack { from, to } => {
+                                    controls.move_track(from, to).await
+                                }
+                                Action::CancelLoad => controls.cancel_load().await,
+                                Action::RetryLogin => controls.retry_login().await,
+                                Action::ToggleFavoriteCurrent => {
+                                    controls.toggle_favorite_current().await
+                                }
+                                // The websocket API never touches the file system.
+                                Action::ExportQueue { path: _, format: _ } => {}
+                                Action::DumpState => controls.dump_state().await,
+                                Action::SetSleepTimer { minutes } => {
+                                    controls.set_sleep_timer(minutes).await
+                                }
+                                Action::CancelSleepTimer => controls.cancel_sleep_timer().await,
+                                Action::Download { album } => controls.download(album).await,
                                 Action::Search { query } => {
                                     let results = player::search(&query).await;
                                     match rt_sender
@@ -222,6 +312,28 @@ async fn handle_connection(socket: WebSocket) {
                                         Err(error) => debug!("error sending response {}", error),
                                     }
                                 }
+                                Action::QueuePeek { count } => {
+                                    let results = player::queue_peek(count).await;
+                                    match rt_sender
+                                        .send_async(json!({ "queuePeek": { "tracks": results } }))
+                                        .await
+                                    {
+                                        Ok(_) => {}
+                                        Err(error) => debug!("error sending response {}", error),
+                                    }
+                                }
+                                Action::QueuePrevious { count } => {
+                                    let results = player::queue_prev(count).await;
+                                    match rt_sender
+                                        .send_async(
+                                            json!({ "queuePrevious": { "tracks": results } }),
+                                        )
+                                        .await
+                                    {
+                                        Ok(_) => {}
+                                        Err(error) => debug!("error sending response {}", error),
+                                    }
+                                }
                             }
                         };
                     }
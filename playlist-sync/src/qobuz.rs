@@ -1,6 +1,7 @@
 use crate::Isrc;
 use hifirs_qobuz_api::client::{
     api::Client,
+    cookies::Cookies,
     playlist::Playlist,
     track::{Track, Tracks},
 };
@@ -13,7 +14,7 @@ pub struct Qobuz<'q> {
 }
 
 pub async fn new<'q>(progress: &'_ ProgressBar) -> Qobuz<'_> {
-    let client = hifirs_qobuz_api::client::api::new(None, None, None, None)
+    let client = hifirs_qobuz_api::client::api::new(None, None, None, None, Cookies::default(), None)
         .await
         .unwrap_or_else(|err| {
             println!("There was a problem creating the api client.");
@@ -47,7 +48,7 @@ impl<'q> Qobuz<'q> {
 
     pub async fn search(&self, query: String) -> Vec<Track> {
         self.progress.set_message(format!("{query} searching"));
-        let results = self.client.search_all(query.clone(), 100).await.unwrap();
+        let results = self.client.search_all(query.clone(), 100, 0).await.unwrap();
 
         if results.tracks.items.is_empty() {
             self.progress.set_message(format!("{query} not found"));
@@ -4,6 +4,7 @@ use crate::client::{
     track::Tracks,
     Composer, Image,
 };
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -44,8 +45,11 @@ pub struct Album {
     pub qobuz_id: i64,
     pub recording_information: Option<String>,
     pub relative_url: Option<String>,
+    #[serde(default)]
     pub release_date_download: String,
+    #[serde(default)]
     pub release_date_original: String,
+    #[serde(default)]
     pub release_date_stream: String,
     pub release_tags: Option<Vec<String>>,
     pub release_type: Option<String>,
@@ -78,19 +82,54 @@ impl Album {
         vec![
             format!("{} {}{}", self.title, hires_icon, parental_icon),
             self.artist.name.clone(),
-            self.release_date_original.as_str()[0..4].to_string(),
+            self.release_date_original
+                .get(0..4)
+                .unwrap_or_default()
+                .to_string(),
         ]
     }
 }
 
+impl From<Album> for Vec<Vec<String>> {
+    fn from(album: Album) -> Self {
+        vec![album.columns()]
+    }
+}
+
 impl From<Box<Album>> for Vec<Vec<String>> {
     fn from(album: Box<Album>) -> Self {
         vec![album.columns()]
     }
 }
 
+/// Curated album lists Qobuz publishes.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum FeaturedType {
+    #[default]
+    NewReleases,
+    BestSellers,
+    PressAwards,
+    EditorPicks,
+    MostStreamed,
+}
+
+impl FeaturedType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FeaturedType::NewReleases => "new-releases",
+            FeaturedType::BestSellers => "best-sellers",
+            FeaturedType::PressAwards => "press-awards",
+            FeaturedType::EditorPicks => "editor-picks",
+            FeaturedType::MostStreamed => "most-streamed",
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AlbumSearchResults {
+    /// Empty for lists that aren't search results, like featured albums.
+    #[serde(default)]
     pub query: String,
     pub albums: Albums,
 }
@@ -111,16 +150,9 @@ pub struct Albums {
 
 impl Albums {
     pub fn sort_by_date(&mut self) {
-        self.items.sort_by(|a, b| {
-            chrono::NaiveDate::parse_from_str(a.release_date_original.as_str(), "%Y-%m-%d")
-                .unwrap()
-                .cmp(
-                    &chrono::NaiveDate::parse_from_str(
-                        b.release_date_original.as_str(),
-                        "%Y-%m-%d",
-                    )
-                    .unwrap(),
-                )
+        // Albums without a valid date sort first.
+        self.items.sort_by_key(|a| {
+            chrono::NaiveDate::parse_from_str(a.release_date_original.as_str(), "%Y-%m-%d").ok()
         });
     }
 }
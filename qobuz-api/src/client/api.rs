@@ -1,10 +1,16 @@
 use crate::{
     client::{
-        album::{Album, AlbumSearchResults},
+        album::{Album, AlbumSearchResults, FeaturedType},
+        article::ArticleListResult,
         artist::{Artist, ArtistSearchResults},
-        playlist::{Playlist, UserPlaylistsResult},
+        cookies::{Cookies, PersistentJar},
+        favorite::{FavoriteIds, FavoriteType, Favorites},
+        playlist::{Playlist, PlaylistSearchResults, Playlists, UserPlaylistsResult},
+        purchase::Purchases,
+        rate_limit::{RateLimiter, DEFAULT_REQUESTS_PER_SECOND},
         search_results::SearchAllResults,
-        track::Track,
+        track::{Lyrics, Track, TrackSearchResults, Tracks},
+        user::UserInfo,
         AudioQuality, TrackURL,
     },
     Error, Result,
@@ -12,12 +18,24 @@ use crate::{
 use base64::{engine::general_purpose, Engine as _};
 use clap::ValueEnum;
 use reqwest::{
-    header::{HeaderMap, HeaderValue},
-    Method, Response, StatusCode,
+    header::{HeaderMap, HeaderValue, RANGE, RETRY_AFTER},
+    Method, RequestBuilder, Response, StatusCode,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::{broadcast, Mutex},
+    task::JoinSet,
+};
 
 const BUNDLE_REGEX: &str =
     r#"<script src="(/resources/\d+\.\d+\.\d+-[a-z0-9]\d{3}/bundle\.js)"></script>"#;
@@ -26,12 +44,44 @@ const APP_REGEX: &str =
 const SEED_REGEX: &str =
     r#"[a-z]\.initialSeed\("(?P<seed>[\w=]+)",window\.utimezone\.(?P<timezone>[a-z]+)\)"#;
 
+/// Most results per category the catalog search returns in one page.
+const MAX_SEARCH_PAGE_SIZE: i32 = 500;
+
+/// Most characters of a non-JSON error body kept in an error message.
+const ERROR_SNIPPET_LENGTH: usize = 200;
+
+/// How many times a failed call is retried before giving up.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+/// Delay before the first retry, doubled for every one after it.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+
 macro_rules! info_regex {
     () => {
         r#"name:"\w+/(?P<timezone>{}([a-z]?))",info:"(?P<info>[\w=]+)",extras:"(?P<extras>[\w=]+)""#
     };
 }
 
+/// Attempts made to finish a download before giving up.
+const DOWNLOAD_ATTEMPTS: u32 = 5;
+
+/// Failed pages in a row before a playlist is returned with the tracks it has.
+const PLAYLIST_PAGE_ATTEMPTS: u32 = 3;
+
+/// Bytes written so far and, once known, the full size of a download.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadProgress {
+    pub written: u64,
+    pub total: Option<u64>,
+}
+
+/// Progress of a login the client started by itself after its user token
+/// expired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reauthentication {
+    Started,
+    Finished { success: bool },
+}
+
 #[derive(Debug, Clone)]
 pub struct Client {
     secrets: HashMap<String, String>,
@@ -39,18 +89,28 @@ pub struct Client {
     app_id: Option<String>,
     base_url: String,
     client: reqwest::Client,
-    default_quality: AudioQuality,
-    user_token: Option<String>,
+    default_quality: Arc<RwLock<AudioQuality>>,
+    user_token: Arc<RwLock<Option<String>>>,
+    /// Who is logged in, once known from logging in or `me`.
+    user: Arc<RwLock<Option<UserInfo>>>,
+    credentials: Option<(String, String)>,
+    reauthentications: broadcast::Sender<Reauthentication>,
     bundle_regex: regex::Regex,
     app_id_regex: regex::Regex,
     seed_regex: regex::Regex,
+    max_retries: u32,
+    limiter: Arc<Mutex<RateLimiter>>,
 }
 
+/// Create a client. `requests_per_second` caps how fast calls are sent,
+/// defaulting to [`DEFAULT_REQUESTS_PER_SECOND`]; zero disables the cap.
 pub async fn new(
     active_secret: Option<String>,
     app_id: Option<String>,
     audio_quality: Option<AudioQuality>,
     user_token: Option<String>,
+    cookies: Cookies,
+    requests_per_second: Option<f64>,
 ) -> Result<Client> {
     let mut headers = HeaderMap::new();
     headers.insert(
@@ -61,11 +121,15 @@ pub async fn new(
             .unwrap(),
         );
 
-    let client = reqwest::Client::builder()
-        .cookie_store(true)
-        .default_headers(headers)
-        .build()
-        .unwrap();
+    let builder = reqwest::Client::builder().default_headers(headers);
+
+    let builder = match cookies {
+        Cookies::Session => builder.cookie_store(true),
+        Cookies::Disabled => builder.cookie_store(false),
+        Cookies::Persisted(path) => builder.cookie_provider(Arc::new(PersistentJar::load(path))),
+    };
+
+    let client = builder.build().unwrap();
 
     let default_quality = if let Some(quality) = audio_quality {
         quality
@@ -77,32 +141,53 @@ pub async fn new(
         client,
         secrets: HashMap::new(),
         active_secret,
-        user_token,
+        user_token: Arc::new(RwLock::new(user_token)),
+        user: Arc::new(RwLock::new(None)),
+        credentials: None,
+        reauthentications: broadcast::channel(2).0,
         app_id,
-        default_quality,
+        default_quality: Arc::new(RwLock::new(default_quality)),
         base_url: "https://www.qobuz.com/api.json/0.2/".to_string(),
         bundle_regex: regex::Regex::new(BUNDLE_REGEX).unwrap(),
         app_id_regex: regex::Regex::new(APP_REGEX).unwrap(),
         seed_regex: regex::Regex::new(SEED_REGEX).unwrap(),
+        max_retries: DEFAULT_MAX_RETRIES,
+        limiter: Arc::new(Mutex::new(RateLimiter::new(
+            requests_per_second.unwrap_or(DEFAULT_REQUESTS_PER_SECOND),
+        ))),
     })
 }
 
 #[non_exhaustive]
 enum Endpoint {
     Album,
+    AlbumFeatured,
     Artist,
+    ArtistSimilar,
+    Articles,
+    FavoriteCreate,
+    FavoriteDelete,
+    FavoriteIds,
+    Favorites,
     Login,
     Track,
+    TrackLyrics,
     UserPlaylist,
     SearchArtists,
     SearchAlbums,
+    SearchPlaylists,
+    SearchTracks,
     TrackURL,
+    User,
     Playlist,
     PlaylistCreate,
     PlaylistDelete,
     PlaylistAddTracks,
     PlaylistDeleteTracks,
     PlaylistUpdatePosition,
+    PlaylistSubscribe,
+    PlaylistUnsubscribe,
+    Purchases,
     Search,
 }
 
@@ -110,7 +195,14 @@ impl Endpoint {
     fn as_str(&self) -> &str {
         match self {
             Endpoint::Album => "album/get",
+            Endpoint::AlbumFeatured => "album/getFeatured",
             Endpoint::Artist => "artist/get",
+            Endpoint::ArtistSimilar => "artist/getSimilarArtists",
+            Endpoint::Articles => "article/listLastArticles",
+            Endpoint::FavoriteCreate => "favorite/create",
+            Endpoint::FavoriteDelete => "favorite/delete",
+            Endpoint::FavoriteIds => "favorite/getUserFavoriteIds",
+            Endpoint::Favorites => "favorite/getUserFavorites",
             Endpoint::Login => "user/login",
             Endpoint::Playlist => "playlist/get",
             Endpoint::PlaylistCreate => "playlist/create",
@@ -118,11 +210,18 @@ impl Endpoint {
             Endpoint::PlaylistAddTracks => "playlist/addTracks",
             Endpoint::PlaylistDeleteTracks => "playlist/deleteTracks",
             Endpoint::PlaylistUpdatePosition => "playlist/updateTracksPosition",
+            Endpoint::PlaylistSubscribe => "playlist/subscribe",
+            Endpoint::PlaylistUnsubscribe => "playlist/unsubscribe",
+            Endpoint::Purchases => "purchase/getUserPurchases",
             Endpoint::Search => "catalog/search",
             Endpoint::SearchAlbums => "album/search",
             Endpoint::SearchArtists => "artist/search",
+            Endpoint::SearchPlaylists => "playlist/search",
+            Endpoint::SearchTracks => "track/search",
             Endpoint::Track => "track/get",
+            Endpoint::TrackLyrics => "track/getLyrics",
             Endpoint::TrackURL => "track/getFileUrl",
+            Endpoint::User => "user/get",
             Endpoint::UserPlaylist => "playlist/getUserPlaylists",
         }
     }
@@ -137,9 +236,7 @@ macro_rules! get {
                     message: error.to_string(),
                 }),
             },
-            Err(error) => Err(Error::Api {
-                message: error.to_string(),
-            }),
+            Err(error) => Err(error),
         }
     };
 }
@@ -153,24 +250,31 @@ macro_rules! post {
                     message: error.to_string(),
                 }),
             },
-            Err(error) => Err(Error::Api {
-                message: error.to_string(),
-            }),
+            Err(error) => Err(error),
         }
     };
 }
 
 impl Client {
     pub fn quality(&self) -> AudioQuality {
-        self.default_quality.clone()
+        self.default_quality.read().unwrap().clone()
     }
 
     pub fn signed_in(&self) -> bool {
-        self.user_token.is_some()
+        self.get_token().is_some()
     }
 
-    /// Login a user
+    /// Login a user. The credentials are kept so the client can log in again
+    /// by itself if the user token expires.
     pub async fn login(&mut self, username: &str, password: &str) -> Result<()> {
+        self.request_token(username, password).await?;
+        self.credentials = Some((username.to_string(), password.to_string()));
+
+        Ok(())
+    }
+
+    // Exchange the credentials for a new user token
+    async fn request_token(&self, username: &str, password: &str) -> Result<()> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::Login.as_str());
 
         if let Some(app_id) = &self.app_id {
@@ -179,22 +283,20 @@ impl Client {
                 username, app_id
             );
 
-            let params = vec![
+            let params = Some(vec![
                 ("email", username),
                 ("password", password),
                 ("app_id", app_id.as_str()),
-            ];
+            ]);
 
-            match self.make_get_call(endpoint, Some(params)).await {
+            match self.send_get_call(&endpoint, &params).await {
                 Ok(response) => {
-                    let json: Value = serde_json::from_str(response.as_str()).unwrap();
                     info!("Successfully logged in");
-                    debug!("{}", json);
-                    let mut token = json["user_auth_token"].to_string();
-                    token = token[1..token.len() - 1].to_string();
-
-                    self.user_token = Some(token);
-                    Ok(())
+                    self.set_token_from_login(response.as_str())
+                }
+                Err(Error::AppID) => {
+                    error!("qobuz rejected the app_id");
+                    Err(Error::AppID)
                 }
                 Err(err) => {
                     error!("error logging into qobuz: {}", err);
@@ -206,6 +308,98 @@ impl Client {
         }
     }
 
+    // Log in again with the stored credentials after the user token was
+    // rejected, telling subscribers to `reauthentications` as it goes.
+    async fn reauthenticate(&self) -> Result<()> {
+        let (username, password) = self.credentials.as_ref().ok_or(Error::Login)?;
+
+        info!("user token was rejected, logging in again");
+        let _ = self.reauthentications.send(Reauthentication::Started);
+
+        let result = self.request_token(username, password).await;
+
+        let _ = self.reauthentications.send(Reauthentication::Finished {
+            success: result.is_ok(),
+        });
+
+        result
+    }
+
+    /// Notified every time the client logs in again because the user token
+    /// expired.
+    pub fn reauthentications(&self) -> broadcast::Receiver<Reauthentication> {
+        self.reauthentications.subscribe()
+    }
+
+    /// Keep credentials for logging in again when the user token expires,
+    /// for clients that were given a cached token instead of calling `login`.
+    pub fn set_credentials(&mut self, username: &str, password: &str) {
+        self.credentials = Some((username.to_string(), password.to_string()));
+    }
+
+    /// Renew the session using the current user token instead of a password.
+    /// The login endpoint accepts an existing token and answers with a fresh one.
+    pub async fn refresh_session(&mut self) -> Result<()> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::Login.as_str());
+
+        if let (Some(token), Some(app_id)) = (self.get_token(), self.app_id.clone()) {
+            info!("refreshing session with the stored user token");
+
+            let params = Some(vec![
+                ("user_auth_token", token.as_str()),
+                ("app_id", app_id.as_str()),
+            ]);
+
+            match self.send_get_call(&endpoint, &params).await {
+                Ok(response) => self.set_token_from_login(response.as_str()),
+                Err(Error::AppID) => {
+                    error!("qobuz rejected the app_id");
+                    Err(Error::AppID)
+                }
+                Err(err) => {
+                    error!("error refreshing qobuz session: {}", err);
+                    Err(Error::Login)
+                }
+            }
+        } else {
+            Err(Error::Authorization)
+        }
+    }
+
+    // Pull the user token out of a login response
+    fn set_token_from_login(&self, response: &str) -> Result<()> {
+        let json: Value = serde_json::from_str(response).map_err(|_| Error::Login)?;
+        debug!("{}", json);
+
+        if let Some(token) = json["user_auth_token"].as_str() {
+            *self.user_token.write().unwrap() = Some(token.to_string());
+
+            // The login answers with the user too, which saves asking again.
+            if let Ok(user) = serde_json::from_value::<UserInfo>(json["user"].clone()) {
+                *self.user.write().unwrap() = Some(user);
+            }
+
+            Ok(())
+        } else {
+            Err(Error::Login)
+        }
+    }
+
+    /// Retrieve the logged in user and their subscription, and keep it for `user`.
+    pub async fn me(&self) -> Result<UserInfo> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::User.as_str());
+
+        let user: UserInfo = get!(self, endpoint, None)?;
+        *self.user.write().unwrap() = Some(user.clone());
+
+        Ok(user)
+    }
+
+    /// The logged in user, when known without asking the API.
+    pub fn user(&self) -> Option<UserInfo> {
+        self.user.read().unwrap().clone()
+    }
+
     /// Retrieve a list of the user's playlists
     pub async fn user_playlists(&self) -> Result<UserPlaylistsResult> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::UserPlaylist.as_str());
@@ -214,6 +408,141 @@ impl Client {
         get!(self, endpoint, Some(params))
     }
 
+    /// Retrieve the ids of the user's favorite albums, tracks and artists
+    pub async fn favorite_ids(&self) -> Result<FavoriteIds> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::FavoriteIds.as_str());
+
+        get!(self, endpoint, None)
+    }
+
+    /// Retrieve the user's favorite tracks, albums and artists, or only one
+    /// type of them. Pages of `limit` items are fetched, starting at
+    /// `offset`, until the whole list is in.
+    pub async fn favorites(
+        &self,
+        item: Option<FavoriteType>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> Result<Favorites> {
+        let types = match item {
+            Some(item) => vec![item],
+            None => vec![
+                FavoriteType::Track,
+                FavoriteType::Album,
+                FavoriteType::Artist,
+            ],
+        };
+
+        let limit = limit.unwrap_or(500).max(1);
+        let start = offset.unwrap_or(0).max(0);
+
+        let mut favorites = Favorites::default();
+
+        for item in types {
+            loop {
+                let (fetched, _) = favorites.counts(item);
+                let page = self
+                    .favorites_page(item, limit, start + fetched as i32)
+                    .await?;
+                let (page_items, total) = page.counts(item);
+
+                favorites.append(item, page);
+
+                if page_items == 0 || (start as i64 + fetched as i64 + page_items as i64) >= total {
+                    break;
+                }
+
+                debug!("fetching more favorite {}", item.list_type());
+            }
+        }
+
+        Ok(favorites)
+    }
+
+    async fn favorites_page(
+        &self,
+        item: FavoriteType,
+        limit: i32,
+        offset: i32,
+    ) -> Result<Favorites> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::Favorites.as_str());
+        let limit = limit.to_string();
+        let offset = offset.to_string();
+        let params = vec![
+            ("type", item.list_type()),
+            ("limit", limit.as_str()),
+            ("offset", offset.as_str()),
+        ];
+
+        get!(self, endpoint, Some(params))
+    }
+
+    /// Add a track, album or artist to the user's favorites
+    pub async fn add_favorite(&self, item: FavoriteType, id: &str) -> Result<SuccessfulResponse> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::FavoriteCreate.as_str());
+
+        let mut form_data = HashMap::new();
+        form_data.insert(item.ids_field(), id);
+
+        post!(self, endpoint, form_data)
+    }
+
+    /// Remove a track, album or artist from the user's favorites
+    pub async fn remove_favorite(
+        &self,
+        item: FavoriteType,
+        id: &str,
+    ) -> Result<SuccessfulResponse> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::FavoriteDelete.as_str());
+
+        let mut form_data = HashMap::new();
+        form_data.insert(item.ids_field(), id);
+
+        post!(self, endpoint, form_data)
+    }
+
+    /// Add a track to the user's favorites
+    pub async fn add_favorite_track(&self, track_id: i32) -> Result<SuccessfulResponse> {
+        self.add_favorite(FavoriteType::Track, &track_id.to_string())
+            .await
+    }
+
+    /// Remove a track from the user's favorites
+    pub async fn remove_favorite_track(&self, track_id: i32) -> Result<SuccessfulResponse> {
+        self.remove_favorite(FavoriteType::Track, &track_id.to_string())
+            .await
+    }
+
+    /// Retrieve the latest editorial articles
+    pub async fn editorial_content(&self) -> Result<ArticleListResult> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::Articles.as_str());
+        let params = vec![("limit", "50"), ("offset", "0")];
+
+        get!(self, endpoint, Some(params))
+    }
+
+    /// Retrieve the albums and tracks the user has purchased
+    pub async fn user_purchases(&self) -> Result<Purchases> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::Purchases.as_str());
+        let params = vec![("limit", "500"), ("offset", "0")];
+
+        get!(self, endpoint, Some(params))
+    }
+
+    /// Make a cheap authenticated call to check the user token is still accepted.
+    pub async fn test_token(&self) -> Result<()> {
+        if self.get_token().is_none() {
+            return Err(Error::Authorization);
+        }
+
+        let endpoint = format!("{}{}", self.base_url, Endpoint::UserPlaylist.as_str());
+        let params = vec![("limit", "1"), ("offset", "0")];
+
+        self.make_get_call(endpoint, Some(params)).await?;
+
+        Ok(())
+    }
+
     /// Retrieve a playlist
     pub async fn playlist(&self, playlist_id: i64) -> Result<Playlist> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::Playlist.as_str());
@@ -241,6 +570,27 @@ impl Client {
         }
     }
 
+    /// Retrieve a playlist with only `limit` of its tracks, starting at `offset`.
+    pub async fn playlist_page(
+        &self,
+        playlist_id: i64,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Playlist> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::Playlist.as_str());
+        let id_string = playlist_id.to_string();
+        let offset_string = offset.to_string();
+        let limit_string = limit.to_string();
+        let params = vec![
+            ("limit", limit_string.as_str()),
+            ("extra", "tracks"),
+            ("playlist_id", id_string.as_str()),
+            ("offset", offset_string.as_str()),
+        ];
+
+        get!(self, endpoint, Some(params))
+    }
+
     async fn playlist_items<'p>(
         &self,
         playlist: &'p mut Playlist,
@@ -252,6 +602,8 @@ impl Client {
         if let Some(mut tracks) = playlist.tracks.clone() {
             all_tracks.append(&mut tracks.items);
 
+            let mut failures = 0;
+
             while all_tracks.len() < total_tracks {
                 let id = playlist.id.to_string();
                 let limit_string = (total_tracks - all_tracks.len()).to_string();
@@ -268,12 +620,37 @@ impl Client {
 
                 match &playlist {
                     Ok(playlist) => {
+                        let new_tracks = playlist
+                            .tracks
+                            .as_ref()
+                            .map(|t| t.items.clone())
+                            .unwrap_or_default();
+
+                        // A page with nothing new would be asked for again forever.
+                        if new_tracks.is_empty() {
+                            warn!(
+                                "playlist {id} returned no tracks at offset {offset_string}, keeping {} of {total_tracks}",
+                                all_tracks.len()
+                            );
+                            break;
+                        }
+
                         debug!("appending tracks to playlist");
-                        if let Some(new_tracks) = &playlist.tracks {
-                            all_tracks.append(&mut new_tracks.clone().items);
+                        all_tracks.extend(new_tracks);
+                        failures = 0;
+                    }
+                    Err(error) => {
+                        error!("{}", error.to_string());
+                        failures += 1;
+
+                        if failures >= PLAYLIST_PAGE_ATTEMPTS {
+                            warn!(
+                                "giving up on playlist {id} after {failures} failed pages, keeping {} of {total_tracks}",
+                                all_tracks.len()
+                            );
+                            break;
                         }
                     }
-                    Err(error) => error!("{}", error.to_string()),
                 }
             }
 
@@ -330,6 +707,30 @@ impl Client {
         post!(self, endpoint, form_data)
     }
 
+    /// Follow a public playlist, adding it to the user's playlists
+    pub async fn subscribe_playlist(&self, playlist_id: String) -> Result<SuccessfulResponse> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::PlaylistSubscribe.as_str());
+
+        let mut form_data = HashMap::new();
+        form_data.insert("playlist_id", playlist_id.as_str());
+
+        post!(self, endpoint, form_data)
+    }
+
+    /// Stop following a playlist, taking it out of the user's playlists
+    pub async fn unsubscribe_playlist(&self, playlist_id: String) -> Result<SuccessfulResponse> {
+        let endpoint = format!(
+            "{}{}",
+            self.base_url,
+            Endpoint::PlaylistUnsubscribe.as_str()
+        );
+
+        let mut form_data = HashMap::new();
+        form_data.insert("playlist_id", playlist_id.as_str());
+
+        post!(self, endpoint, form_data)
+    }
+
     /// Add new track to playlist
     pub async fn playlist_add_track(
         &self,
@@ -401,6 +802,60 @@ impl Client {
         get!(self, endpoint, Some(params))
     }
 
+    /// Retrieve the lyrics of a track. Tracks without lyrics return an empty
+    /// [`Lyrics`] rather than an error.
+    pub async fn track_lyrics(&self, track_id: i32) -> Result<Lyrics> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::TrackLyrics.as_str());
+        let track_id_string = track_id.to_string();
+        let params = vec![("track_id", track_id_string.as_str())];
+
+        let lyrics: Result<Lyrics> = get!(self, endpoint, Some(params));
+
+        match lyrics {
+            Err(Error::DeserializeJSON { message }) => {
+                debug!("no lyrics: {message}");
+                Ok(Lyrics {
+                    track_id: track_id as i64,
+                    text: None,
+                })
+            }
+            lyrics => lyrics,
+        }
+    }
+
+    /// Tracks to play after the given one. Qobuz doesn't expose track
+    /// similarity, so these are the performer's most popular tracks, as
+    /// ranked by the catalog search, without the seed track or duplicates.
+    pub async fn track_suggestions(&self, track_id: i32, limit: usize) -> Result<Vec<Track>> {
+        let track = self.track(track_id).await?;
+
+        let performer = if let Some(performer) = track.performer {
+            performer
+        } else {
+            return Ok(Vec::new());
+        };
+
+        let results = self.search_all(performer.name.clone(), 100, 0).await?;
+
+        let mut seen = vec![track_id];
+        let mut suggestions = Vec::new();
+
+        for t in results.tracks.items {
+            if suggestions.len() >= limit {
+                break;
+            }
+
+            let same_performer = t.performer.as_ref().map(|p| p.id) == Some(performer.id);
+
+            if same_performer && !seen.contains(&t.id) {
+                seen.push(t.id);
+                suggestions.push(t);
+            }
+        }
+
+        Ok(suggestions)
+    }
+
     /// Retrieve url information for a track's audio file
     pub async fn track_url(
         &self,
@@ -447,18 +902,233 @@ impl Client {
         get!(self, endpoint, Some(params))
     }
 
-    pub async fn search_all(&self, query: String, limit: i32) -> Result<SearchAllResults> {
+    /// Download a track's audio file to `path`. The file is written next to it with a
+    /// `.part` suffix and only moved into place once complete. An interrupted
+    /// download, including a partial file left by an earlier run, is resumed from the
+    /// last byte written with a fresh signed url, since the previous one may have expired.
+    pub async fn download_track<F>(
+        &self,
+        track_id: i32,
+        quality: Option<AudioQuality>,
+        path: &Path,
+        mut on_progress: F,
+    ) -> Result<DownloadProgress>
+    where
+        F: FnMut(DownloadProgress),
+    {
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&partial)
+            .await?;
+
+        let mut progress = DownloadProgress {
+            written: file.metadata().await?.len(),
+            total: None,
+        };
+
+        let mut attempt = 1;
+
+        loop {
+            let track_url = self.track_url(track_id, quality.clone(), None).await?;
+
+            match self
+                .download_range(&track_url.url, &mut file, &mut progress, &mut on_progress)
+                .await
+            {
+                Ok(()) => break,
+                Err(error) if attempt < DOWNLOAD_ATTEMPTS => {
+                    warn!(
+                        "download of track {track_id} interrupted at {} bytes, resuming: {error}",
+                        progress.written
+                    );
+                    attempt += 1;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        file.flush().await?;
+        drop(file);
+
+        match progress.total {
+            Some(total) if total != progress.written => {
+                // Resuming from a file of the wrong size would only repeat the error.
+                tokio::fs::remove_file(&partial).await?;
+
+                Err(Error::Download {
+                    message: format!("expected {total} bytes but wrote {}", progress.written),
+                })
+            }
+            _ => {
+                tokio::fs::rename(&partial, path).await?;
+                Ok(progress)
+            }
+        }
+    }
+
+    // Fetch the rest of a file, starting after the bytes already written
+    async fn download_range<F>(
+        &self,
+        url: &str,
+        file: &mut File,
+        progress: &mut DownloadProgress,
+        on_progress: &mut F,
+    ) -> Result<()>
+    where
+        F: FnMut(DownloadProgress),
+    {
+        let mut request = self.client.get(url);
+
+        if progress.written > 0 {
+            request = request.header(RANGE, format!("bytes={}-", progress.written));
+        }
+
+        let mut response = request.send().await?;
+
+        match response.status() {
+            StatusCode::PARTIAL_CONTENT => {}
+            StatusCode::OK => {
+                // The server ignored the range, so start the file over.
+                if progress.written > 0 {
+                    debug!("range not honored, restarting download");
+                    file.set_len(0).await?;
+                    progress.written = 0;
+                }
+            }
+            // Nothing past the last byte written, the file is already complete.
+            StatusCode::RANGE_NOT_SATISFIABLE if progress.written > 0 => {
+                progress.total = Some(progress.written);
+                return Ok(());
+            }
+            status => {
+                return Err(Error::Api {
+                    message: status.to_string(),
+                })
+            }
+        }
+
+        if progress.total.is_none() {
+            progress.total = response
+                .content_length()
+                .map(|length| length + progress.written);
+        }
+
+        on_progress(*progress);
+
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).await?;
+            progress.written += chunk.len() as u64;
+
+            on_progress(*progress);
+        }
+
+        file.flush().await?;
+
+        Ok(())
+    }
+
+    pub async fn search_all(
+        &self,
+        query: String,
+        limit: i32,
+        offset: i32,
+    ) -> Result<SearchAllResults> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::Search.as_str());
-        let limit = limit.to_string();
-        let params = vec![("query", query.as_str()), ("limit", &limit)];
+        let limit = limit.min(MAX_SEARCH_PAGE_SIZE).to_string();
+        let offset = offset.to_string();
+        let params = vec![
+            ("query", query.as_str()),
+            ("limit", &limit),
+            ("offset", &offset),
+        ];
 
         get!(self, endpoint, Some(params))
     }
 
+    /// Search the catalog, fetching page after page until every category has
+    /// all of its results or `max` results per category have been fetched.
+    pub async fn search_all_paged(&self, query: String, max: i32) -> Result<SearchAllResults> {
+        let page_size = max.min(MAX_SEARCH_PAGE_SIZE);
+        let mut results = self.search_all(query.clone(), page_size, 0).await?;
+        let mut offset = page_size;
+
+        while offset < max && !results.is_complete() {
+            let limit = page_size.min(max - offset);
+            let page = self.search_all(query.clone(), limit, offset).await?;
+
+            if page.item_count() == 0 {
+                break;
+            }
+
+            results.append(page);
+            offset += limit;
+        }
+
+        Ok(results)
+    }
+
     // Retrieve information about an album
     pub async fn album(&self, album_id: &str) -> Result<Album> {
         let endpoint = format!("{}{}", self.base_url, Endpoint::Album.as_str());
-        let params = vec![("album_id", album_id)];
+        let params = vec![("album_id", album_id), ("limit", "500"), ("offset", "0")];
+
+        let mut album: Album = get!(self, endpoint.clone(), Some(params))?;
+        self.album_items(&mut album, endpoint).await;
+
+        Ok(album)
+    }
+
+    // Page through the tracks of albums too large to be returned in one response (box sets).
+    async fn album_items(&self, album: &mut Album, endpoint: String) {
+        let total_tracks = album.tracks_count as usize;
+
+        if let Some(tracks) = album.tracks.take() {
+            let album_id = album.id.clone();
+
+            let tracks = paginate_tracks(tracks, total_tracks, |offset, limit| {
+                let endpoint = endpoint.clone();
+                let album_id = album_id.clone();
+
+                async move {
+                    let offset = offset.to_string();
+                    let limit = limit.to_string();
+                    let params = vec![
+                        ("album_id", album_id.as_str()),
+                        ("limit", limit.as_str()),
+                        ("offset", offset.as_str()),
+                    ];
+
+                    let page: Result<Album> = get!(self, endpoint, Some(params));
+                    page.map(|a| a.tracks)
+                }
+            })
+            .await;
+
+            album.tracks = Some(tracks);
+        }
+    }
+
+    /// Retrieve one of the curated album lists, new releases when no type is given.
+    pub async fn featured_albums(
+        &self,
+        featured_type: Option<FeaturedType>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> Result<AlbumSearchResults> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::AlbumFeatured.as_str());
+        let featured_type = featured_type.unwrap_or_default();
+        let limit = limit.unwrap_or(100).to_string();
+        let offset = offset.unwrap_or(0).to_string();
+        let params = vec![
+            ("type", featured_type.as_str()),
+            ("limit", limit.as_str()),
+            ("offset", offset.as_str()),
+        ];
 
         get!(self, endpoint, Some(params))
     }
@@ -506,6 +1176,54 @@ impl Client {
         }
     }
 
+    /// Retrieve the playlists featuring an artist
+    pub async fn artist_playlists(&self, artist_id: i32) -> Result<Playlists> {
+        if let Some(app_id) = &self.app_id {
+            let endpoint = format!("{}{}", self.base_url, Endpoint::Artist.as_str());
+            let artistid_string = artist_id.to_string();
+
+            let params = vec![
+                ("artist_id", artistid_string.as_str()),
+                ("app_id", app_id),
+                ("limit", "100"),
+                ("offset", "0"),
+                ("extra", "playlists"),
+            ];
+
+            let artist: Artist = get!(self, endpoint, Some(params))?;
+
+            Ok(artist.playlists.unwrap_or_default())
+        } else {
+            Err(Error::AppID)
+        }
+    }
+
+    /// Retrieve artists similar to the given one. Empty when Qobuz knows of none.
+    pub async fn similar_artists(
+        &self,
+        artist_id: i32,
+        limit: Option<i32>,
+    ) -> Result<ArtistSearchResults> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::ArtistSimilar.as_str());
+        let artist_id = artist_id.to_string();
+        let limit = limit.unwrap_or(25).to_string();
+        let params = vec![
+            ("artist_id", artist_id.as_str()),
+            ("limit", limit.as_str()),
+            ("offset", "0"),
+        ];
+
+        let results: Result<ArtistSearchResults> = get!(self, endpoint, Some(params));
+
+        match results {
+            Err(Error::DeserializeJSON { message }) => {
+                debug!("no similar artists: {message}");
+                Ok(ArtistSearchResults::default())
+            }
+            results => results,
+        }
+    }
+
     // Search the database for artists
     pub async fn search_artists(
         &self,
@@ -523,9 +1241,47 @@ impl Client {
         get!(self, endpoint, Some(params))
     }
 
+    /// Search for tracks only, which ranks them better than `search_all`.
+    pub async fn search_tracks(
+        &self,
+        query: String,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> Result<TrackSearchResults> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::SearchTracks.as_str());
+        let limit = limit.unwrap_or(100).to_string();
+        let offset = offset.unwrap_or(0).to_string();
+        let params = vec![
+            ("query", query.as_str()),
+            ("limit", limit.as_str()),
+            ("offset", offset.as_str()),
+        ];
+
+        get!(self, endpoint, Some(params))
+    }
+
+    /// Search for playlists only, paged with `limit` and `offset`.
+    pub async fn search_playlists(
+        &self,
+        query: String,
+        limit: Option<i32>,
+        offset: Option<i32>,
+    ) -> Result<PlaylistSearchResults> {
+        let endpoint = format!("{}{}", self.base_url, Endpoint::SearchPlaylists.as_str());
+        let limit = limit.unwrap_or(100).to_string();
+        let offset = offset.unwrap_or(0).to_string();
+        let params = vec![
+            ("query", query.as_str()),
+            ("limit", limit.as_str()),
+            ("offset", offset.as_str()),
+        ];
+
+        get!(self, endpoint, Some(params))
+    }
+
     // Set a user access token for authentication
     pub fn set_token(&mut self, token: String) {
-        self.user_token = Some(token);
+        *self.user_token.write().unwrap() = Some(token);
     }
 
     // Set an app_id for authentication
@@ -538,12 +1294,13 @@ impl Client {
         self.active_secret = Some(active_secret);
     }
 
-    pub fn set_default_quality(&mut self, quality: AudioQuality) {
-        self.default_quality = quality;
+    /// Shared with clones of the client, so it can be changed while playing.
+    pub fn set_default_quality(&self, quality: AudioQuality) {
+        *self.default_quality.write().unwrap() = quality;
     }
 
     pub fn get_token(&self) -> Option<String> {
-        self.user_token.clone()
+        self.user_token.read().unwrap().clone()
     }
 
     pub fn get_active_secret(&self) -> Option<String> {
@@ -564,7 +1321,7 @@ impl Client {
             error!("no app_id");
         }
 
-        if let Some(token) = &self.user_token {
+        if let Some(token) = self.get_token() {
             info!("adding token to request headers: {}", token);
             headers.insert(
                 "X-User-Auth-Token",
@@ -575,55 +1332,156 @@ impl Client {
         headers
     }
 
-    // Make a GET call to the API with the provided parameters
+    // Make a GET call to the API with the provided parameters, logging in
+    // again and retrying once if the user token was rejected
     async fn make_get_call(
         &self,
         endpoint: String,
         params: Option<Vec<(&str, &str)>>,
+    ) -> Result<String> {
+        match self.send_get_call(&endpoint, &params).await {
+            Err(Error::Authorization) if self.credentials.is_some() => {
+                self.reauthenticate().await?;
+                self.send_get_call(&endpoint, &params).await
+            }
+            response => response,
+        }
+    }
+
+    async fn send_get_call(
+        &self,
+        endpoint: &str,
+        params: &Option<Vec<(&str, &str)>>,
     ) -> Result<String> {
         let headers = self.client_headers();
 
         debug!("calling {} endpoint, with params {params:?}", endpoint);
-        let request = self.client.request(Method::GET, endpoint).headers(headers);
-
-        if let Some(p) = params {
-            let response = request.query(&p).send().await?;
-            self.handle_response(response).await
-        } else {
-            let response = request.send().await?;
-            self.handle_response(response).await
-        }
+        self.send_with_retry(true, || {
+            let request = self
+                .client
+                .request(Method::GET, endpoint)
+                .headers(headers.clone());
+
+            if let Some(p) = params {
+                request.query(p)
+            } else {
+                request
+            }
+        })
+        .await
     }
 
-    // Make a POST call to the API with form data
+    // Make a POST call to the API with form data, logging in again and
+    // retrying once if the user token was rejected
     async fn make_post_call(
         &self,
         endpoint: String,
         params: HashMap<&str, &str>,
     ) -> Result<String> {
+        match self.send_post_call(&endpoint, &params).await {
+            Err(Error::Authorization) if self.credentials.is_some() => {
+                self.reauthenticate().await?;
+                self.send_post_call(&endpoint, &params).await
+            }
+            response => response,
+        }
+    }
+
+    async fn send_post_call(&self, endpoint: &str, params: &HashMap<&str, &str>) -> Result<String> {
         let headers = self.client_headers();
 
         debug!("calling {} endpoint, with params {params:?}", endpoint);
-        let response = self
-            .client
-            .request(Method::POST, endpoint)
-            .headers(headers)
-            .form(&params)
-            .send()
-            .await?;
+        self.send_with_retry(false, || {
+            self.client
+                .request(Method::POST, endpoint)
+                .headers(headers.clone())
+                .form(params)
+        })
+        .await
+    }
+
+    // Send a request, retrying server errors, rate limiting and dropped
+    // connections with exponential backoff. Other failures return immediately.
+    // A request that isn't `idempotent` may have been applied by the time it
+    // fails, so it is only retried when it never reached the server or was
+    // turned away by the rate limit.
+    async fn send_with_retry<F>(&self, idempotent: bool, request: F) -> Result<String>
+    where
+        F: Fn() -> RequestBuilder,
+    {
+        let mut attempt = 0;
+
+        loop {
+            self.wait_for_rate_limit().await;
+
+            let response = request().send().await;
+
+            if let Ok(response) = &response {
+                if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                    if let Some(delay) = retry_after(response) {
+                        debug!("rate limited, pausing requests for {delay:?}");
+                        self.limiter.lock().await.pause(Instant::now() + delay);
+                    }
+                }
+            }
+
+            match response {
+                Ok(response)
+                    if attempt < self.max_retries
+                        && is_retryable(response.status())
+                        && (idempotent || response.status() == StatusCode::TOO_MANY_REQUESTS) =>
+                {
+                    debug!("retrying after {}", response.status());
+                }
+                Ok(response) => return self.handle_response(response).await,
+                Err(error)
+                    if attempt < self.max_retries
+                        && (error.is_connect()
+                            || (idempotent && (error.is_timeout() || error.is_request()))) =>
+                {
+                    debug!("retrying after {error}");
+                }
+                Err(error) => return Err(error.into()),
+            }
+
+            tokio::time::sleep(retry_delay(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    // Wait until the rate limiter lets another request through.
+    async fn wait_for_rate_limit(&self) {
+        loop {
+            let wait = self.limiter.lock().await.acquire(Instant::now());
+
+            match wait {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => break,
+            }
+        }
+    }
+
+    /// How many times failed calls are retried.
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
 
-        self.handle_response(response).await
+    /// Set how many times failed calls are retried. Zero disables retries.
+    pub fn set_max_retries(&mut self, retries: u32) {
+        self.max_retries = retries;
     }
 
     // Handle a response retrieved from the api
     async fn handle_response(&self, response: Response) -> Result<String> {
-        if response.status() == StatusCode::OK {
+        let status = response.status();
+
+        if status == StatusCode::OK {
             let res = response.text().await.unwrap();
             Ok(res)
         } else {
-            Err(Error::Api {
-                message: response.status().to_string(),
-            })
+            let body = response.text().await.unwrap_or_default();
+
+            Err(response_error(status, &body))
         }
     }
 
@@ -703,30 +1561,208 @@ impl Client {
         }
     }
 
-    // Check the retrieved secrets to see which one works.
+    /// Check the active secret is still accepted, with a single track url request.
+    /// Only a rejected signature is `Error::ActiveSecret`, anything else that
+    /// goes wrong with the request is returned as is.
+    pub async fn verify_active_secret(&self) -> Result<()> {
+        if self.active_secret.is_none() {
+            return Err(Error::ActiveSecret);
+        }
+
+        self.track_url(64868955, Some(AudioQuality::Mp3), None)
+            .await
+            .map(|_| ())
+    }
+
+    /// Forget the app_id and secrets, so they have to be fetched again with
+    /// `refresh` and `test_secrets`. Use when they may have been rotated.
+    pub fn invalidate_cached_secrets(&mut self) {
+        self.app_id = None;
+        self.active_secret = None;
+        self.secrets.clear();
+    }
+
+    /// Find a working secret among the ones found by `refresh`. They are
+    /// tried all at once and the first to be accepted wins.
     pub async fn test_secrets(&mut self) -> Result<()> {
-        let secrets = self.secrets.clone();
+        let secrets = self.secrets.clone().into_iter().collect::<Vec<_>>();
         debug!("testing secrets: {secrets:?}");
 
-        for (timezone, secret) in secrets.iter() {
-            let response = self
-                .track_url(64868955, Some(AudioQuality::Mp3), Some(secret.to_string()))
-                .await;
+        let client = self.clone();
+        let working = first_working_secret(secrets, move |secret| {
+            let client = client.clone();
 
-            if response.is_ok() {
-                debug!("found good secret: {}\t{}", timezone, secret);
-                let secret_string = secret.to_string();
+            async move {
+                client
+                    .track_url(64868955, Some(AudioQuality::Mp3), Some(secret))
+                    .await
+                    .is_ok()
+            }
+        })
+        .await;
 
-                self.set_active_secret(secret_string);
+        if let Some((timezone, secret)) = working {
+            debug!("found good secret: {}\t{}", timezone, secret);
+            self.set_active_secret(secret);
 
-                return Ok(());
+            Ok(())
+        } else {
+            Err(Error::ActiveSecret)
+        }
+    }
+}
+
+// Run `test` for every (timezone, secret) pair concurrently and return the
+// first pair that passes. The remaining tests are cancelled.
+async fn first_working_secret<F, Fut>(
+    secrets: Vec<(String, String)>,
+    test: F,
+) -> Option<(String, String)>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = bool> + Send + 'static,
+{
+    let mut tests = JoinSet::new();
+
+    for (timezone, secret) in secrets {
+        let works = test(secret.clone());
+        tests.spawn(async move { (works.await, timezone, secret) });
+    }
+
+    while let Some(result) = tests.join_next().await {
+        if let Ok((true, timezone, secret)) = result {
+            tests.abort_all();
+            return Some((timezone, secret));
+        }
+    }
+
+    None
+}
+
+// Request the missing tracks page by page until `total` is reached or a page comes back empty.
+async fn paginate_tracks<F, Fut>(mut tracks: Tracks, total: usize, mut fetch: F) -> Tracks
+where
+    F: FnMut(usize, usize) -> Fut,
+    Fut: Future<Output = Result<Option<Tracks>>>,
+{
+    while tracks.items.len() < total {
+        let offset = tracks.items.len();
+
+        match fetch(offset, total - offset).await {
+            Ok(Some(mut page)) if !page.items.is_empty() => {
+                debug!("appending tracks to album");
+                tracks.items.append(&mut page.items);
+            }
+            Ok(_) => break,
+            Err(error) => {
+                error!("{}", error.to_string());
+                break;
             }
         }
+    }
+
+    tracks
+}
+
+// What Qobuz sends back with a failed call.
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    code: Option<i64>,
+    message: Option<String>,
+}
+
+// The status, followed by the message Qobuz gave for it or, when the body
+// isn't one of its JSON errors, the start of the body.
+fn error_message(status: StatusCode, body: &str) -> String {
+    if let Ok(ErrorBody {
+        code,
+        message: Some(message),
+    }) = serde_json::from_str::<ErrorBody>(body)
+    {
+        return match code {
+            Some(code) => format!("{status}: {message} (code {code})"),
+            None => format!("{status}: {message}"),
+        };
+    }
+
+    let body = body.trim();
 
-        Err(Error::ActiveSecret)
+    if body.is_empty() {
+        status.to_string()
+    } else if body.chars().count() > ERROR_SNIPPET_LENGTH {
+        let snippet = body.chars().take(ERROR_SNIPPET_LENGTH).collect::<String>();
+        format!("{status}: {snippet}...")
+    } else {
+        format!("{status}: {body}")
     }
 }
 
+// Sort a failed response by the message Qobuz gave. The credential errors
+// carry no message, so the status and body are logged before they are dropped.
+fn response_error(status: StatusCode, body: &str) -> Error {
+    let message = error_message(status, body);
+    let reason = serde_json::from_str::<ErrorBody>(body)
+        .ok()
+        .and_then(|error| error.message)
+        .unwrap_or_default()
+        .to_lowercase();
+
+    if status == StatusCode::UNAUTHORIZED || reason.contains("user_auth_token") {
+        warn!("user token rejected: {message}");
+        Error::Authorization
+    } else if reason.contains("app_id") {
+        warn!("app id rejected: {message}");
+        Error::AppID
+    } else if reason.contains("signature") {
+        warn!("request signature rejected: {message}");
+        Error::ActiveSecret
+    } else if is_refusal(status) {
+        Error::Refused { message }
+    } else {
+        Error::Api { message }
+    }
+}
+
+fn is_refusal(status: StatusCode) -> bool {
+    status == StatusCode::BAD_REQUEST
+        || status == StatusCode::FORBIDDEN
+        || status == StatusCode::NOT_FOUND
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::INTERNAL_SERVER_ERROR
+        || status == StatusCode::BAD_GATEWAY
+        || status == StatusCode::SERVICE_UNAVAILABLE
+        || status == StatusCode::GATEWAY_TIMEOUT
+}
+
+// Qobuz sends the delay in seconds. HTTP dates aren't supported.
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+// The base delay doubled for each attempt, plus up to half of that again so
+// clients that failed together don't retry together.
+fn retry_delay(attempt: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY * 2u32.pow(attempt.min(6));
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    let jitter = nanos as u64 % (delay.as_millis() as u64 / 2 + 1);
+
+    delay + Duration::from_millis(jitter)
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct SuccessfulResponse {
     status: String,
@@ -736,6 +1772,8 @@ pub struct SuccessfulResponse {
 pub enum OutputFormat {
     Json,
     Tsv,
+    Csv,
+    Yaml,
 }
 
 #[tokio::test]
@@ -743,7 +1781,7 @@ async fn can_use_methods() {
     //pretty_env_logger::init();
     use insta::assert_yaml_snapshot;
 
-    let mut client = new(None, None, None, None)
+    let mut client = new(None, None, None, None, Cookies::default(), None)
         .await
         .expect("failed to create client");
 
@@ -829,3 +1867,156 @@ async fn can_use_methods() {
     //     "deleting the newly created playlist"
     // );
 }
+
+#[tokio::test]
+async fn album_items_pages_through_tracks() {
+    let track = |id: i32| Track {
+        id,
+        ..Default::default()
+    };
+
+    let first_page = Tracks {
+        items: vec![track(1), track(2)],
+        ..Default::default()
+    };
+
+    let mut pages = vec![
+        Tracks {
+            items: vec![track(3), track(4)],
+            ..Default::default()
+        },
+        Tracks {
+            items: vec![track(5)],
+            ..Default::default()
+        },
+    ]
+    .into_iter();
+
+    let tracks = paginate_tracks(first_page, 5, |_, _| {
+        let page = pages.next();
+        async move { Ok(page) }
+    })
+    .await;
+
+    assert_eq!(
+        tracks.items.iter().map(|t| t.id).collect::<Vec<i32>>(),
+        vec![1, 2, 3, 4, 5]
+    );
+
+    // An empty page ends pagination instead of looping forever.
+    let tracks = paginate_tracks(
+        Tracks {
+            items: vec![track(1)],
+            ..Default::default()
+        },
+        5,
+        |_, _| async { Ok(Some(Tracks::default())) },
+    )
+    .await;
+
+    assert_eq!(tracks.items.len(), 1);
+}
+
+#[test]
+fn retries_only_transient_failures() {
+    assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+    assert!(is_retryable(StatusCode::BAD_GATEWAY));
+    assert!(!is_retryable(StatusCode::UNAUTHORIZED));
+    assert!(!is_retryable(StatusCode::NOT_FOUND));
+    assert!(is_refusal(StatusCode::FORBIDDEN));
+    assert!(!is_refusal(StatusCode::TOO_MANY_REQUESTS));
+    assert!(!is_refusal(StatusCode::SERVICE_UNAVAILABLE));
+
+    for attempt in 0..3 {
+        let base = RETRY_BASE_DELAY * 2u32.pow(attempt);
+        let delay = retry_delay(attempt);
+
+        assert!(delay >= base && delay <= base + base / 2);
+    }
+}
+
+#[tokio::test]
+async fn test_secrets_finds_working_secret_in_any_order() {
+    let secrets = || {
+        (0..3)
+            .map(|i| (format!("tz{i}"), format!("secret{i}")))
+            .collect::<Vec<(String, String)>>()
+    };
+
+    for working in 0..3u64 {
+        let found = first_working_secret(secrets(), move |secret| async move {
+            // Later secrets answer first, so order doesn't decide the winner.
+            let index = secret.trim_start_matches("secret").parse::<u64>().unwrap();
+            tokio::time::sleep(Duration::from_millis((3 - index) * 10)).await;
+
+            secret == format!("secret{working}")
+        })
+        .await;
+
+        assert_eq!(
+            found,
+            Some((format!("tz{working}"), format!("secret{working}")))
+        );
+    }
+
+    let found = first_working_secret(secrets(), |_| async { false }).await;
+    assert_eq!(found, None);
+}
+
+#[test]
+fn error_message_includes_body() {
+    assert_eq!(
+        error_message(
+            StatusCode::BAD_REQUEST,
+            r#"{"status":"error","code":400,"message":"Invalid Request Signature parameter (request_sig)"}"#
+        ),
+        "400 Bad Request: Invalid Request Signature parameter (request_sig) (code 400)"
+    );
+
+    assert_eq!(
+        error_message(StatusCode::BAD_GATEWAY, "<html>bad gateway</html>"),
+        "502 Bad Gateway: <html>bad gateway</html>"
+    );
+
+    let long = "x".repeat(ERROR_SNIPPET_LENGTH * 2);
+    assert_eq!(
+        error_message(StatusCode::INTERNAL_SERVER_ERROR, &long),
+        format!(
+            "500 Internal Server Error: {}...",
+            "x".repeat(ERROR_SNIPPET_LENGTH)
+        )
+    );
+
+    assert_eq!(error_message(StatusCode::NOT_FOUND, ""), "404 Not Found");
+}
+
+#[test]
+fn response_errors_follow_the_qobuz_message() {
+    assert!(matches!(
+        response_error(
+            StatusCode::BAD_REQUEST,
+            r#"{"status":"error","code":400,"message":"Invalid Request Signature parameter (request_sig)"}"#
+        ),
+        Error::ActiveSecret
+    ));
+    assert!(matches!(
+        response_error(
+            StatusCode::BAD_REQUEST,
+            r#"{"status":"error","code":400,"message":"Invalid or missing app_id parameter (app_id)"}"#
+        ),
+        Error::AppID
+    ));
+    assert!(matches!(
+        response_error(StatusCode::UNAUTHORIZED, ""),
+        Error::Authorization
+    ));
+
+    // Words in other parts of the body, like a track title, don't count.
+    assert!(matches!(
+        response_error(
+            StatusCode::NOT_FOUND,
+            r#"{"status":"error","code":404,"message":"No result matching given argument","title":"Signature"}"#
+        ),
+        Error::Refused { .. }
+    ));
+}
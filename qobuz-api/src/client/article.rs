@@ -0,0 +1,85 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::client::UrlType;
+
+const LINK_REGEX: &str = r#"qobuz\.com/(?:[a-z]{2}-[a-z]{2}/)?(?P<kind>album|playlist)/(?:[^/\s"?]+/)?(?P<id>[A-Za-z0-9]+)"#;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArticleListResult {
+    pub articles: Articles,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Articles {
+    pub offset: i64,
+    pub limit: i64,
+    pub total: i64,
+    pub items: Vec<Article>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Article {
+    pub id: i64,
+    pub title: String,
+    #[serde(rename = "abstract", default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
+impl Article {
+    /// The first album or playlist the article links to, looking at the
+    /// article url before its body.
+    pub fn linked_entity(&self) -> Option<UrlType> {
+        let regex = Regex::new(LINK_REGEX).expect("failed to compile link regex");
+
+        [self.url.as_deref(), self.content.as_deref()]
+            .into_iter()
+            .flatten()
+            .flat_map(|text| regex.captures_iter(text).collect::<Vec<_>>())
+            .find_map(|captures| {
+                let id = captures.name("id")?.as_str();
+
+                match captures.name("kind")?.as_str() {
+                    "album" => Some(UrlType::Album { id: id.to_string() }),
+                    "playlist" => id.parse::<i64>().ok().map(|id| UrlType::Playlist { id }),
+                    _ => None,
+                }
+            })
+    }
+}
+
+#[test]
+fn finds_linked_album_or_playlist() {
+    let article = |url: Option<&str>, content: Option<&str>| Article {
+        url: url.map(|u| u.to_string()),
+        content: content.map(|c| c.to_string()),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        article(
+            Some("https://www.qobuz.com/us-en/album/some-record/0093624876745"),
+            None
+        )
+        .linked_entity(),
+        Some(UrlType::Album {
+            id: "0093624876745".to_string()
+        })
+    );
+    assert_eq!(
+        article(
+            Some("https://www.qobuz.com/us-en/magazine/story/123"),
+            Some(r#"<a href="https://play.qobuz.com/playlist/4567">listen</a>"#)
+        )
+        .linked_entity(),
+        Some(UrlType::Playlist { id: 4567 })
+    );
+    assert_eq!(
+        article(Some("https://www.qobuz.com/us-en/magazine/story/123"), None).linked_entity(),
+        None
+    );
+}
@@ -1,9 +1,11 @@
 use serde::{Deserialize, Serialize};
 
-use crate::client::{album::Albums, Image};
+use crate::client::{album::Albums, playlist::Playlists, Image};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ArtistSearchResults {
+    /// Empty for lists that aren't search results, like similar artists.
+    #[serde(default)]
     pub query: String,
     pub artists: Artists,
 }
@@ -40,6 +42,7 @@ pub struct Artist {
     pub albums_count: i64,
     pub slug: String,
     pub albums: Option<Albums>,
+    pub playlists: Option<Playlists>,
 }
 
 impl Artist {
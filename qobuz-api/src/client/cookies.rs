@@ -0,0 +1,147 @@
+use reqwest::{
+    cookie::{CookieStore, Jar},
+    header::HeaderValue,
+    Url,
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// How the HTTP client handles cookies.
+///
+/// Qobuz authenticates with the user token, so cookies are not needed to stay
+/// signed in. Keeping them for the session matches a browser and is the safest
+/// default. Disabling them helps when a proxy or auth gateway misbehaves with
+/// cookies, or when debugging requests. Persisting them lets session cookies
+/// survive restarts, at the cost of leaving them on disk in plain text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Cookies {
+    /// Kept in memory for the life of the client.
+    #[default]
+    Session,
+    /// Never stored or sent.
+    Disabled,
+    /// Kept in memory and saved to the file, so they survive restarts.
+    Persisted(PathBuf),
+}
+
+/// A cookie jar that writes every cookie it receives to a file and loads
+/// them back when created.
+#[derive(Debug)]
+pub struct PersistentJar {
+    jar: Jar,
+    path: PathBuf,
+    /// Saved cookies, each with the origin that set it.
+    saved: Mutex<Vec<(String, String)>>,
+    /// Number of times the cookies changed.
+    version: AtomicU64,
+    /// The version last written to the file. Held while writing so writes
+    /// finishing out of order never replace newer cookies with older ones.
+    written: Arc<Mutex<u64>>,
+}
+
+impl PersistentJar {
+    pub fn load(path: PathBuf) -> Self {
+        let jar = Jar::default();
+        let mut saved = Vec::new();
+
+        if let Ok(contents) = std::fs::read_to_string(&path) {
+            for line in contents.lines() {
+                if let Some((url, cookie)) = line.split_once('\t') {
+                    if let Ok(parsed) = Url::parse(url) {
+                        jar.add_cookie_str(cookie, &parsed);
+                        saved.push((url.to_string(), cookie.to_string()));
+                    }
+                }
+            }
+
+            debug!("loaded {} cookies from {}", saved.len(), path.display());
+        }
+
+        Self {
+            jar,
+            path,
+            saved: Mutex::new(saved),
+            version: AtomicU64::new(0),
+            written: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    fn save(&self, saved: &[(String, String)]) {
+        let contents = saved
+            .iter()
+            .map(|(url, cookie)| format!("{url}\t{cookie}"))
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let version = self.version.fetch_add(1, Ordering::SeqCst) + 1;
+        let path = self.path.clone();
+        let written = self.written.clone();
+
+        let write = move || {
+            let mut written = written.lock().unwrap();
+
+            if *written > version {
+                return;
+            }
+
+            match write_file(&path, &contents) {
+                Ok(()) => *written = version,
+                Err(error) => warn!("unable to save cookies to {}: {error}", path.display()),
+            }
+        };
+
+        // Cookies are set from inside requests, keep the file IO off the runtime.
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn_blocking(write);
+            }
+            Err(_) => write(),
+        }
+    }
+}
+
+// Write to a temporary file first, so an interrupted write never leaves the
+// saved cookies half written.
+fn write_file(path: &Path, contents: &str) -> std::io::Result<()> {
+    let temp = path.with_extension("tmp");
+
+    std::fs::write(&temp, contents)?;
+    std::fs::rename(&temp, path)
+}
+
+fn cookie_name(cookie: &str) -> &str {
+    cookie
+        .split_once('=')
+        .map(|(name, _)| name.trim())
+        .unwrap_or(cookie)
+}
+
+impl CookieStore for PersistentJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let headers = cookie_headers.collect::<Vec<&HeaderValue>>();
+
+        self.jar.set_cookies(&mut headers.iter().copied(), url);
+
+        let origin = url.origin().ascii_serialization();
+        let mut saved = self.saved.lock().unwrap();
+
+        for header in headers {
+            if let Ok(cookie) = header.to_str() {
+                // A newer cookie with the same name replaces the saved one.
+                saved.retain(|(u, c)| !(*u == origin && cookie_name(c) == cookie_name(cookie)));
+                saved.push((origin.clone(), cookie.to_string()));
+            }
+        }
+
+        self.save(&saved);
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        self.jar.cookies(url)
+    }
+}
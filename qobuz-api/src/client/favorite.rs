@@ -0,0 +1,82 @@
+use crate::client::{album::Albums, artist::Artists, track::Tracks};
+use serde::{Deserialize, Serialize};
+
+/// Ids of everything the user has marked as a favorite.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FavoriteIds {
+    #[serde(default)]
+    pub albums: Vec<String>,
+    #[serde(default)]
+    pub tracks: Vec<i64>,
+    #[serde(default)]
+    pub artists: Vec<i64>,
+}
+
+/// Kinds of items that can be marked as a favorite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FavoriteType {
+    Track,
+    Album,
+    Artist,
+}
+
+impl FavoriteType {
+    /// Form field the favorite endpoints expect the ids in.
+    pub fn ids_field(&self) -> &'static str {
+        match self {
+            FavoriteType::Track => "track_ids",
+            FavoriteType::Album => "album_ids",
+            FavoriteType::Artist => "artist_ids",
+        }
+    }
+
+    /// Value of the `type` filter when listing favorites.
+    pub fn list_type(&self) -> &'static str {
+        match self {
+            FavoriteType::Track => "tracks",
+            FavoriteType::Album => "albums",
+            FavoriteType::Artist => "artists",
+        }
+    }
+}
+
+/// The user's favorites. Collections that weren't requested are empty.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Favorites {
+    #[serde(default)]
+    pub albums: Albums,
+    #[serde(default)]
+    pub tracks: Tracks,
+    #[serde(default)]
+    pub artists: Artists,
+}
+
+impl Favorites {
+    /// Items fetched and total available for the given type.
+    pub fn counts(&self, item: FavoriteType) -> (usize, i64) {
+        match item {
+            FavoriteType::Track => (self.tracks.items.len(), self.tracks.total),
+            FavoriteType::Album => (self.albums.items.len(), self.albums.total),
+            FavoriteType::Artist => (self.artists.items.len(), self.artists.total),
+        }
+    }
+
+    /// Adds the items of the given type from `page`.
+    pub fn append(&mut self, item: FavoriteType, page: Favorites) {
+        match item {
+            FavoriteType::Track => {
+                self.tracks.total = page.tracks.total;
+                self.tracks.items.extend(page.tracks.items);
+            }
+            FavoriteType::Album => {
+                self.albums.total = page.albums.total;
+                self.albums.items.extend(page.albums.items);
+            }
+            FavoriteType::Artist => {
+                self.artists.total = page.artists.total;
+                self.artists.items.extend(page.artists.items);
+            }
+        }
+    }
+}
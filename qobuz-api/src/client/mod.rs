@@ -1,14 +1,30 @@
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use snafu::prelude::*;
-use std::fmt::Display;
+use std::{
+    fmt::Display,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 pub mod album;
 pub mod api;
+pub mod article;
 pub mod artist;
+pub mod cookies;
+pub mod favorite;
 pub mod playlist;
+pub mod purchase;
+pub mod rate_limit;
 pub mod search_results;
 pub mod track;
+pub mod user;
+
+/// How long a stream url is trusted for when it doesn't say when it expires.
+const TRACK_URL_LIFETIME: i64 = 10 * 60;
+/// Seconds before its expiry a stream url is already treated as expired, so
+/// it isn't used right as it stops working.
+const TRACK_URL_EXPIRY_MARGIN: i64 = 60;
 
 #[derive(Default, Debug)]
 pub struct ApiConfig {
@@ -24,15 +40,20 @@ pub struct ApiConfig {
 pub struct Composer {
     pub id: i64,
     pub name: String,
+    /// Missing on the composer of a track.
+    #[serde(default)]
     pub slug: String,
+    #[serde(default)]
     pub albums_count: i64,
     pub image: Option<Image>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Image {
+    #[serde(default)]
     pub small: String,
     pub thumbnail: Option<String>,
+    #[serde(default)]
     pub large: String,
     pub back: Option<String>,
 }
@@ -46,6 +67,41 @@ pub struct TrackURL {
     pub mime_type: String,
     pub sampling_rate: f64,
     pub bit_depth: i32,
+    /// Why the url is for something other than what was asked for, like a
+    /// lower quality or a sample.
+    #[serde(default)]
+    pub restrictions: Vec<TrackURLRestriction>,
+    /// A short preview instead of the track, which can't be streamed.
+    #[serde(default)]
+    pub sample: bool,
+    /// When the url was fetched, in seconds since the epoch.
+    #[serde(skip, default = "unix_now")]
+    pub issued_at: i64,
+}
+
+impl TrackURL {
+    /// When the url stops working, in seconds since the epoch. Qobuz signs
+    /// this into the url as `etsp`; without it the url is given a
+    /// conservative lifetime from when it was fetched.
+    pub fn expires_at(&self) -> i64 {
+        url_expires_at(&self.url).unwrap_or(self.issued_at + TRACK_URL_LIFETIME)
+    }
+
+    /// Whether the url has expired, or is about to.
+    pub fn is_expired(&self) -> bool {
+        is_past_expiry(self.expires_at())
+    }
+
+    /// Whether the account isn't allowed the quality that was asked for, as
+    /// opposed to the track not being available in it.
+    pub fn restricted_for_user(&self) -> bool {
+        self.restrictions.iter().any(|r| r.code == "UserUneligible")
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackURLRestriction {
+    pub code: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -54,6 +110,7 @@ pub struct User {
     pub login: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum UrlType {
     Album { id: String },
     Playlist { id: i64 },
@@ -75,7 +132,7 @@ pub enum UrlTypeError {
 pub type ParseUrlResult<T, E = UrlTypeError> = std::result::Result<T, E>;
 
 /// The audio quality as defined by the Qobuz API.
-#[derive(Default, Clone, Debug, Serialize, Deserialize, ValueEnum)]
+#[derive(Default, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
 pub enum AudioQuality {
     #[default]
     Mp3 = 5,
@@ -84,6 +141,18 @@ pub enum AudioQuality {
     HIFI192 = 27,
 }
 
+impl AudioQuality {
+    /// The next quality down, `None` below MP3.
+    pub fn lower(&self) -> Option<AudioQuality> {
+        match self {
+            Self::HIFI192 => Some(Self::HIFI96),
+            Self::HIFI96 => Some(Self::CD),
+            Self::CD => Some(Self::Mp3),
+            Self::Mp3 => None,
+        }
+    }
+}
+
 impl From<i64> for AudioQuality {
     fn from(quality_id: i64) -> Self {
         match quality_id {
@@ -96,64 +165,245 @@ impl From<i64> for AudioQuality {
     }
 }
 
+#[derive(Snafu, Debug, PartialEq, Eq)]
+pub enum AudioQualityError {
+    #[snafu(display(
+        "'{input}' is not a valid quality. Use one of: mp3 (5), cd (6), hifi96 (7), hifi192 (27)."
+    ))]
+    InvalidQuality { input: String },
+}
+
+impl FromStr for AudioQuality {
+    type Err = AudioQualityError;
+
+    /// Accepts the canonical names, the Qobuz format ids and a few friendly
+    /// aliases, ignoring case. A bare "hires" means the best hi-res quality.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s
+            .trim()
+            .to_lowercase()
+            .replace(['-', '_', ' ', '/', '.'], "");
+
+        match normalized.as_str() {
+            "mp3" | "320" | "5" => Ok(Self::Mp3),
+            "cd" | "lossless" | "flac" | "1644" | "16441" | "6" => Ok(Self::CD),
+            "hifi96" | "hires96" | "2496" | "7" => Ok(Self::HIFI96),
+            "hifi192" | "hires192" | "hires" | "max" | "24192" | "27" => Ok(Self::HIFI192),
+            _ => Err(AudioQualityError::InvalidQuality {
+                input: s.to_string(),
+            }),
+        }
+    }
+}
+
 impl Display for AudioQuality {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!("{}", self.clone() as u32))
     }
 }
 
+/// Reads an album, track or playlist from a Qobuz link. Takes
+/// `play.qobuz.com`/`open.qobuz.com` links, store links from `www.qobuz.com`,
+/// `qobuz://` and `qobuzapp://` links, and `album:<id>`, `track:<id>`
+/// or `playlist:<id>`.
 pub fn parse_url(string_url: &str) -> ParseUrlResult<UrlType> {
-    if let Ok(url) = url::Url::parse(string_url) {
-        if let (Some(host), Some(mut path)) = (url.host_str(), url.path_segments()) {
-            if host == "play.qobuz.com" || host == "open.qobuz.com" {
-                debug!("got a qobuz url");
-
-                match path.next() {
-                    Some("album") => {
-                        debug!("this is an album");
-                        let id = path.next().unwrap().to_string();
-
-                        Ok(UrlType::Album { id })
-                    }
-                    Some("playlist") => {
-                        debug!("this is a playlist");
-                        let id = path
-                            .next()
-                            .unwrap()
-                            .parse::<i64>()
-                            .expect("failed to convert id");
-
-                        Ok(UrlType::Playlist { id })
-                    }
-                    Some("track") => {
-                        debug!("this is a track");
-                        let id = path
-                            .next()
-                            .unwrap()
-                            .parse::<i32>()
-                            .expect("failed to convert id");
-
-                        Ok(UrlType::Track { id })
-                    }
-                    None => {
-                        debug!("no path, cannot use path");
-                        Err(UrlTypeError::InvalidPath)
-                    }
-                    _ => Err(UrlTypeError::Unknown),
-                }
-            } else {
-                Err(UrlTypeError::WrongDomain)
+    let url = url::Url::parse(string_url.trim()).map_err(|_| UrlTypeError::InvalidUrl)?;
+    let segments = url
+        .path_segments()
+        .map(|s| s.filter(|s| !s.is_empty()).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let (kind, id) = match (url.scheme(), url.host_str()) {
+        ("http" | "https", Some("play.qobuz.com" | "open.qobuz.com")) => {
+            debug!("got a qobuz url");
+
+            match segments.as_slice() {
+                [kind, id, ..] => (*kind, *id),
+                _ => return Err(UrlTypeError::InvalidPath),
+            }
+        }
+        // e.g. https://www.qobuz.com/us-en/album/<name>/<id>
+        ("http" | "https", Some("www.qobuz.com" | "qobuz.com")) => {
+            debug!("got a qobuz store url");
+
+            let kind = segments
+                .iter()
+                .find(|s| matches!(**s, "album" | "track" | "playlist" | "playlists"))
+                .ok_or(UrlTypeError::InvalidPath)?;
+
+            match (*kind, segments.last()) {
+                ("playlists", Some(id)) => ("playlist", *id),
+                (kind, Some(id)) if kind != *id => (kind, *id),
+                _ => return Err(UrlTypeError::InvalidPath),
             }
-        } else {
-            Err(UrlTypeError::InvalidUrl)
         }
-    } else {
-        Err(UrlTypeError::InvalidUrl)
+        ("http" | "https", _) => return Err(UrlTypeError::WrongDomain),
+        ("qobuz" | "qobuzapp", Some(kind)) => match segments.first() {
+            Some(id) => (kind, *id),
+            None => return Err(UrlTypeError::InvalidPath),
+        },
+        (kind @ ("album" | "track" | "playlist"), None) => (kind, url.path()),
+        _ => return Err(UrlTypeError::InvalidUrl),
+    };
+
+    match kind {
+        "album" if !id.is_empty() => Ok(UrlType::Album { id: id.to_string() }),
+        "playlist" => id
+            .parse::<i64>()
+            .map(|id| UrlType::Playlist { id })
+            .map_err(|_| UrlTypeError::InvalidPath),
+        "track" => id
+            .parse::<i32>()
+            .map(|id| UrlType::Track { id })
+            .map_err(|_| UrlTypeError::InvalidPath),
+        "album" => Err(UrlTypeError::InvalidPath),
+        _ => Err(UrlTypeError::Unknown),
     }
 }
 
+/// The `etsp` Qobuz signs into a stream url, the time it stops working in
+/// seconds since the epoch.
+pub fn url_expires_at(url: &str) -> Option<i64> {
+    url::Url::parse(url).ok().and_then(|url| {
+        url.query_pairs()
+            .find(|(key, _)| key == "etsp")
+            .and_then(|(_, value)| value.parse::<i64>().ok())
+    })
+}
+
+/// Whether a url expiring at `expires_at`, in seconds since the epoch, has
+/// expired or is about to.
+pub fn is_past_expiry(expires_at: i64) -> bool {
+    unix_now() + TRACK_URL_EXPIRY_MARGIN >= expires_at
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
 pub fn capitalize(s: &mut str) {
     if let Some(r) = s.get_mut(0..1) {
         r.make_ascii_uppercase();
     }
 }
+
+#[test]
+fn audio_quality_accepts_aliases() {
+    let cases = [
+        ("mp3", AudioQuality::Mp3),
+        ("5", AudioQuality::Mp3),
+        ("cd", AudioQuality::CD),
+        ("lossless", AudioQuality::CD),
+        ("6", AudioQuality::CD),
+        ("hifi96", AudioQuality::HIFI96),
+        ("hi-res-96", AudioQuality::HIFI96),
+        ("7", AudioQuality::HIFI96),
+        ("hifi192", AudioQuality::HIFI192),
+        ("hires", AudioQuality::HIFI192),
+        ("24/192", AudioQuality::HIFI192),
+        ("27", AudioQuality::HIFI192),
+    ];
+
+    for (input, expected) in cases {
+        assert_eq!(
+            AudioQuality::from_str(input).map(|q| q as u32),
+            Ok(expected as u32),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn audio_quality_ignores_case() {
+    for input in ["CD", "Hi-Res", "HIFI192", " Mp3 "] {
+        assert!(AudioQuality::from_str(input).is_ok(), "{input}");
+    }
+}
+
+#[test]
+fn audio_quality_rejects_invalid_input() {
+    for input in ["", "vinyl", "8", "hifi48"] {
+        let error = AudioQuality::from_str(input).unwrap_err();
+
+        assert!(error.to_string().contains("hifi192"), "{input}");
+    }
+}
+
+#[test]
+fn qobuz_links_are_parsed() {
+    let cases = [
+        (
+            "https://play.qobuz.com/album/lzqw5n2ft5pmb",
+            Some(UrlType::Album {
+                id: "lzqw5n2ft5pmb".to_string(),
+            }),
+        ),
+        (
+            "https://open.qobuz.com/track/52151402",
+            Some(UrlType::Track { id: 52151402 }),
+        ),
+        (
+            "https://www.qobuz.com/us-en/album/kid-a-radiohead/0634904032463",
+            Some(UrlType::Album {
+                id: "0634904032463".to_string(),
+            }),
+        ),
+        (
+            "https://www.qobuz.com/us-en/playlists/jazz-essentials/1141084",
+            Some(UrlType::Playlist { id: 1141084 }),
+        ),
+        (
+            "qobuzapp://playlist/1141084",
+            Some(UrlType::Playlist { id: 1141084 }),
+        ),
+        (
+            "qobuz://track/52151402",
+            Some(UrlType::Track { id: 52151402 }),
+        ),
+        ("track:52151402", Some(UrlType::Track { id: 52151402 })),
+        (
+            " album:0634904032463 ",
+            Some(UrlType::Album {
+                id: "0634904032463".to_string(),
+            }),
+        ),
+        ("https://play.qobuz.com/track/not-a-number", None),
+        ("https://play.qobuz.com/album", None),
+        ("https://example.com/album/123", None),
+        ("Radiohead - Airbag", None),
+    ];
+
+    for (input, expected) in cases {
+        assert_eq!(parse_url(input).ok(), expected, "{input}");
+    }
+}
+
+#[test]
+fn track_url_expiry_comes_from_the_url() {
+    let track_url = |url: &str, issued_at: i64| TrackURL {
+        url: url.to_string(),
+        issued_at,
+        ..Default::default()
+    };
+
+    let now = unix_now();
+
+    let signed = track_url(
+        "https://streaming.qobuz.com/file?uid=1&etsp=1700000000&hmac=x",
+        now,
+    );
+    assert_eq!(signed.expires_at(), 1700000000);
+    assert!(signed.is_expired());
+
+    let unsigned = track_url("https://streaming.qobuz.com/file?uid=1", now);
+    assert_eq!(unsigned.expires_at(), now + TRACK_URL_LIFETIME);
+    assert!(!unsigned.is_expired());
+
+    let soon = track_url(
+        &format!("https://streaming.qobuz.com/file?etsp={}", now + 30),
+        now,
+    );
+    assert!(soon.is_expired());
+}
@@ -82,6 +82,18 @@ pub struct Playlists {
     pub items: Vec<Playlist>,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlaylistSearchResults {
+    pub query: String,
+    pub playlists: Playlists,
+}
+
+impl From<PlaylistSearchResults> for Vec<Vec<String>> {
+    fn from(results: PlaylistSearchResults) -> Self {
+        results.playlists.into()
+    }
+}
+
 impl From<Playlists> for Vec<Vec<String>> {
     fn from(playlists: Playlists) -> Self {
         playlists
@@ -110,6 +122,12 @@ impl From<&Playlist> for Vec<String> {
     }
 }
 
+impl From<Playlist> for Vec<Vec<String>> {
+    fn from(playlist: Playlist) -> Self {
+        vec![playlist.into()]
+    }
+}
+
 impl From<Box<Playlist>> for Vec<Vec<String>> {
     fn from(playlist: Box<Playlist>) -> Self {
         vec![playlist.into()]
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+use crate::client::{album::Albums, track::Tracks};
+
+/// Albums and individual tracks bought by the user. Either list is
+/// left out of the response when the account has none.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Purchases {
+    #[serde(default)]
+    pub albums: Albums,
+    #[serde(default)]
+    pub tracks: Tracks,
+}
@@ -0,0 +1,95 @@
+use std::time::{Duration, Instant};
+
+/// Requests per second allowed when the caller doesn't choose a rate.
+///
+/// Well under what the web player sends while browsing, so scrolling through
+/// search results won't get the account throttled.
+pub const DEFAULT_REQUESTS_PER_SECOND: f64 = 5.0;
+
+/// A token bucket shared by every request a client makes.
+///
+/// The bucket holds up to one second's worth of requests, so short bursts go
+/// out immediately and sustained traffic is spread out to the configured rate.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+    paused_until: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// A rate of zero or less disables limiting, though pauses still apply.
+    pub fn new(requests_per_second: f64) -> Self {
+        let capacity = requests_per_second.max(1.0);
+
+        Self {
+            rate: requests_per_second,
+            capacity,
+            tokens: capacity,
+            updated: Instant::now(),
+            paused_until: None,
+        }
+    }
+
+    /// Takes a token if one is available. Otherwise returns how long to wait
+    /// before trying again.
+    pub fn acquire(&mut self, now: Instant) -> Option<Duration> {
+        if let Some(until) = self.paused_until {
+            if until > now {
+                return Some(until - now);
+            }
+
+            self.paused_until = None;
+        }
+
+        if self.rate <= 0.0 {
+            return None;
+        }
+
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+
+    /// Holds every request until `until`, e.g. when the server asks clients
+    /// to back off.
+    pub fn pause(&mut self, until: Instant) {
+        if self.paused_until.map(|p| p < until).unwrap_or(true) {
+            self.paused_until = Some(until);
+        }
+    }
+}
+
+#[test]
+fn limits_to_rate_after_burst() {
+    let start = Instant::now();
+    let mut limiter = RateLimiter::new(2.0);
+
+    assert_eq!(limiter.acquire(start), None);
+    assert_eq!(limiter.acquire(start), None);
+    assert_eq!(limiter.acquire(start), Some(Duration::from_millis(500)));
+    assert_eq!(limiter.acquire(start + Duration::from_millis(500)), None);
+}
+
+#[test]
+fn pause_holds_requests() {
+    let start = Instant::now();
+    let mut limiter = RateLimiter::new(0.0);
+
+    limiter.pause(start + Duration::from_secs(3));
+
+    assert_eq!(
+        limiter.acquire(start + Duration::from_secs(1)),
+        Some(Duration::from_secs(2))
+    );
+    assert_eq!(limiter.acquire(start + Duration::from_secs(3)), None);
+}
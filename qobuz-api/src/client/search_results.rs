@@ -13,6 +13,54 @@ pub struct SearchAllResults {
     pub playlists: Playlists,
 }
 
+impl SearchAllResults {
+    /// Results fetched so far, across every category.
+    pub fn item_count(&self) -> usize {
+        self.albums.items.len()
+            + self.tracks.items.len()
+            + self.artists.items.len()
+            + self.playlists.items.len()
+    }
+
+    /// Whether every category holds all of the results Qobuz has for it.
+    pub fn is_complete(&self) -> bool {
+        self.albums.items.len() as i64 >= self.albums.total
+            && self.tracks.items.len() as i64 >= self.tracks.total
+            && self.artists.items.len() as i64 >= self.artists.total
+            && self.playlists.items.len() as i64 >= self.playlists.total
+    }
+
+    /// Adds the results of a later page.
+    pub fn append(&mut self, page: SearchAllResults) {
+        self.albums.total = page.albums.total;
+        self.albums.items.extend(page.albums.items);
+        self.tracks.total = page.tracks.total;
+        self.tracks.items.extend(page.tracks.items);
+        self.artists.total = page.artists.total;
+        self.artists.items.extend(page.artists.items);
+        self.playlists.total = page.playlists.total;
+        self.playlists.items.extend(page.playlists.items);
+    }
+}
+
+impl From<SearchAllResults> for Vec<Vec<String>> {
+    fn from(results: SearchAllResults) -> Self {
+        let mut rows: Vec<Vec<String>> = results.albums.into();
+
+        rows.extend(results.tracks.items.iter().map(|track| track.columns()));
+        rows.extend(results.artists.items.iter().map(|artist| artist.columns()));
+        rows.extend(
+            results
+                .playlists
+                .items
+                .iter()
+                .map(|playlist| playlist.into()),
+        );
+
+        rows
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Analytics {
@@ -1,4 +1,4 @@
-use crate::client::album::Album;
+use crate::client::{album::Album, Composer};
 use gstreamer::ClockTime;
 use serde::{Deserialize, Serialize};
 
@@ -14,6 +14,7 @@ pub struct Tracks {
 pub struct Track {
     pub album: Option<Album>,
     pub audio_info: AudioInfo,
+    pub composer: Option<Composer>,
     pub copyright: Option<String>,
     pub displayable: bool,
     pub downloadable: bool,
@@ -29,6 +30,8 @@ pub struct Track {
     pub parental_warning: bool,
     pub performer: Option<Performer>,
     pub performers: Option<String>,
+    /// Identifies the entry within a playlist, only set on playlist tracks.
+    pub playlist_track_id: Option<i64>,
     pub position: Option<usize>,
     pub previewable: bool,
     pub purchasable: bool,
@@ -66,6 +69,34 @@ impl Track {
     }
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackSearchResults {
+    pub query: String,
+    pub tracks: Tracks,
+}
+
+impl From<TrackSearchResults> for Vec<Vec<String>> {
+    fn from(results: TrackSearchResults) -> Self {
+        results.tracks.into()
+    }
+}
+
+impl From<Track> for Vec<Vec<String>> {
+    fn from(track: Track) -> Self {
+        vec![track.columns()]
+    }
+}
+
+impl From<Tracks> for Vec<Vec<String>> {
+    fn from(tracks: Tracks) -> Self {
+        tracks
+            .items
+            .into_iter()
+            .map(|track| track.columns())
+            .collect::<Vec<Vec<String>>>()
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AudioInfo {
     pub replaygain_track_gain: f64,
@@ -77,3 +108,21 @@ pub struct Performer {
     pub id: i64,
     pub name: String,
 }
+
+/// Lyrics for a track. Most tracks have none, in which case `text` is empty.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lyrics {
+    #[serde(default)]
+    pub track_id: i64,
+    #[serde(default, alias = "lyrics")]
+    pub text: Option<String>,
+}
+
+impl Lyrics {
+    pub fn is_empty(&self) -> bool {
+        self.text
+            .as_ref()
+            .map(|t| t.trim().is_empty())
+            .unwrap_or(true)
+    }
+}
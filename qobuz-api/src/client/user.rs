@@ -0,0 +1,113 @@
+use crate::client::AudioQuality;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// The logged in user and what their subscription allows.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserInfo {
+    pub id: i64,
+    pub login: String,
+    pub display_name: Option<String>,
+    pub email: Option<String>,
+    pub country_code: Option<String>,
+    pub credential: Option<Credential>,
+    pub subscription: Option<Subscription>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Credential {
+    pub id: Option<i64>,
+    pub label: Option<String>,
+    pub description: Option<String>,
+    pub parameters: Option<CredentialParameters>,
+}
+
+/// What the subscription may stream. Missing for accounts without one.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CredentialParameters {
+    #[serde(default)]
+    pub lossy_streaming: bool,
+    #[serde(default)]
+    pub lossless_streaming: bool,
+    #[serde(default)]
+    pub hires_streaming: bool,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub offer: Option<String>,
+    pub periodicity: Option<String>,
+    pub end_date: Option<String>,
+}
+
+impl UserInfo {
+    /// The name to show for the user, the login when there's no display name.
+    pub fn name(&self) -> &str {
+        self.display_name
+            .as_deref()
+            .filter(|name| !name.is_empty())
+            .unwrap_or(&self.login)
+    }
+
+    /// The subscription as Qobuz describes it, like "Studio" or "Sublime".
+    pub fn subscription_type(&self) -> Option<&str> {
+        self.credential
+            .as_ref()
+            .and_then(|c| c.description.as_deref().or(c.label.as_deref()))
+            .or_else(|| self.subscription.as_ref()?.offer.as_deref())
+            .filter(|name| !name.is_empty())
+    }
+
+    /// The best quality the account may stream.
+    pub fn max_quality(&self) -> AudioQuality {
+        let parameters = self.credential.as_ref().and_then(|c| c.parameters.as_ref());
+
+        match parameters {
+            Some(p) if p.hires_streaming => AudioQuality::HIFI192,
+            Some(p) if p.lossless_streaming => AudioQuality::CD,
+            _ => AudioQuality::Mp3,
+        }
+    }
+
+    pub fn columns(&self) -> Vec<String> {
+        vec![
+            self.name().to_string(),
+            self.email.clone().unwrap_or_default(),
+            self.subscription_type().unwrap_or_default().to_string(),
+            self.max_quality()
+                .to_possible_value()
+                .map(|v| v.get_name().to_string())
+                .unwrap_or_default(),
+        ]
+    }
+}
+
+impl From<UserInfo> for Vec<Vec<String>> {
+    fn from(user: UserInfo) -> Self {
+        vec![user.columns()]
+    }
+}
+
+#[test]
+fn max_quality_follows_the_subscription() {
+    let user = |json: &str| serde_json::from_str::<UserInfo>(json).expect("invalid payload");
+
+    let studio = user(
+        r#"{
+            "id": 1,
+            "login": "listener",
+            "display_name": "",
+            "credential": {
+                "description": "Studio",
+                "parameters": { "lossy_streaming": true, "lossless_streaming": true, "hires_streaming": true }
+            }
+        }"#,
+    );
+    assert_eq!(studio.max_quality(), AudioQuality::HIFI192);
+    assert_eq!(studio.name(), "listener");
+    assert_eq!(studio.subscription_type(), Some("Studio"));
+
+    let free = user(r#"{ "id": 2, "login": "guest", "credential": { "parameters": null } }"#);
+    assert_eq!(free.max_quality(), AudioQuality::Mp3);
+    assert_eq!(free.subscription_type(), None);
+}
@@ -33,8 +33,22 @@ pub enum Error {
     Create,
     #[snafu(display("{message}"))]
     Api { message: String },
+    /// Qobuz understood the request and turned it down, e.g. a quality the
+    /// track isn't available in or content restricted for the account.
+    #[snafu(display("{message}"))]
+    Refused { message: String },
     #[snafu(display("Failed to deserialize json: {message}"))]
     DeserializeJSON { message: String },
+    #[snafu(display("Download failed: {message}"))]
+    Download { message: String },
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Download {
+            message: error.to_string(),
+        }
+    }
 }
 
 impl From<reqwest::Error> for Error {